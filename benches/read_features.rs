@@ -0,0 +1,61 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use noodles_fpkm::{
+    features::read_features,
+    gtf::{read_features_mmap, read_features_parallel},
+};
+
+const GENE_COUNT: usize = 20000;
+const EXONS_PER_GENE: usize = 8;
+
+fn write_annotations() -> io::Result<PathBuf> {
+    let dst = env::temp_dir().join("noodles-fpkm-read-features.gtf");
+    let mut writer = BufWriter::new(File::create(&dst)?);
+
+    for i in 0..GENE_COUNT {
+        let gene_start = i * 10000 + 1;
+
+        for j in 0..EXONS_PER_GENE {
+            let start = gene_start + j * 1000;
+            let end = start + 499;
+
+            writeln!(
+                writer,
+                "chr1\tHAVANA\texon\t{}\t{}\t.\t+\t.\tgene_id \"G{}\"; transcript_id \"T{}\"; exon_number {}; level 2;",
+                start, end, i, i, j + 1
+            )?;
+        }
+    }
+
+    Ok(dst)
+}
+
+fn bench_read_features(c: &mut Criterion) {
+    let src = write_annotations().unwrap();
+
+    let mut group = c.benchmark_group("read_features");
+    group.sample_size(10);
+
+    group.bench_function("reader", |b| {
+        b.iter(|| read_features(&src, "exon", "gene_id").unwrap())
+    });
+
+    group.bench_function("mmap", |b| {
+        b.iter(|| read_features_mmap(&src, "exon", "gene_id").unwrap())
+    });
+
+    group.bench_function("parallel", |b| {
+        b.iter(|| read_features_parallel(&src, "exon", "gene_id").unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_read_features);
+criterion_main!(benches);
@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+};
+
+use csv::StringRecord;
+
+use crate::{feature_id::FeatureId, Expressions};
+
+static CUFFLINKS_ID_HEADER: &str = "tracking_id";
+static STRINGTIE_ID_HEADER: &str = "Gene ID";
+static FPKM_HEADER: &str = "FPKM";
+
+const COMMENT_PREFIX: u8 = b'#';
+static SUMMARY_ROW_PREFIX: &str = "__";
+
+// The ID, effective length, and TPM headers of salmon (`quant.sf`) and
+// kallisto (`abundance.tsv`) transcript abundance files.
+static SALMON_HEADERS: [&str; 3] = ["Name", "EffectiveLength", "TPM"];
+static KALLISTO_HEADERS: [&str; 3] = ["target_id", "eff_length", "tpm"];
+
+/// The abundance of a transcript.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TranscriptAbundance {
+    /// The effective length of the transcript.
+    pub effective_length: f64,
+    /// The transcripts per million of the transcript.
+    pub tpm: f64,
+}
+
+pub type TranscriptAbundances = HashMap<FeatureId, TranscriptAbundance>;
+
+/// Reads the FPKMs of a Cufflinks or StringTie gene abundance file.
+///
+/// The format is detected by the header: Cufflinks `genes.fpkm_tracking`
+/// files are keyed by the `tracking_id` column, and StringTie gene abundance
+/// files (`-A`) are keyed by the `Gene ID` column. In both, the values are
+/// read from the `FPKM` column.
+///
+/// A gene can be reported at multiple loci. Its FPKMs are summed.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::abundances::read_abundances;
+///
+/// let data = "\
+/// Gene ID\tGene Name\tReference\tStrand\tStart\tEnd\tCoverage\tFPKM\tTPM
+/// AAAS\tAAAS\tchr12\t-\t53307456\t53324864\t12.5\t3.25\t5.5
+/// RPL37AP1\t-\tchr8\t+\t44892713\t44892766\t180.0\t27.5\t46.5
+/// ";
+///
+/// let fpkms = read_abundances(data.as_bytes()).unwrap();
+///
+/// assert_eq!(fpkms.len(), 2);
+/// assert_eq!(fpkms["AAAS"], 3.25);
+/// assert_eq!(fpkms["RPL37AP1"], 27.5);
+/// ```
+pub fn read_abundances<R>(reader: R) -> io::Result<Expressions>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .from_reader(reader);
+
+    let headers = rdr.headers()?.clone();
+
+    let id_index = find_column(&headers, CUFFLINKS_ID_HEADER)
+        .or_else(|| find_column(&headers, STRINGTIE_ID_HEADER))
+        .ok_or_else(|| {
+            invalid_data(format!(
+                "missing column: {} (Cufflinks) or {} (StringTie)",
+                CUFFLINKS_ID_HEADER, STRINGTIE_ID_HEADER
+            ))
+        })?;
+
+    let fpkm_index = find_column(&headers, FPKM_HEADER)
+        .ok_or_else(|| invalid_data(format!("missing column: {}", FPKM_HEADER)))?;
+
+    let mut fpkms = Expressions::new();
+
+    for result in rdr.records() {
+        let record = result?;
+
+        let id = record
+            .get(id_index)
+            .ok_or_else(|| invalid_data(format!("invalid record: {:?}", record)))?;
+
+        let fpkm = parse_value(&record, fpkm_index)
+            .ok_or_else(|| invalid_data(format!("invalid FPKM: {:?}", record)))?;
+
+        *fpkms.entry(id.into()).or_insert(0.0) += fpkm;
+    }
+
+    Ok(fpkms)
+}
+
+/// Reads the effective lengths and TPMs of a salmon or kallisto transcript
+/// abundance file.
+///
+/// The format is detected by the header: salmon `quant.sf` files have the
+/// columns `Name`, `EffectiveLength`, and `TPM`, and kallisto `abundance.tsv`
+/// files have the columns `target_id`, `eff_length`, and `tpm`.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::abundances::read_transcript_abundances;
+///
+/// let data = "\
+/// Name\tLength\tEffectiveLength\tTPM\tNumReads
+/// ENST00000456328.2\t1657\t1482.000\t2.5\t6.000
+/// ENST00000450305.2\t632\t457.000\t0.0\t0.000
+/// ";
+///
+/// let abundances = read_transcript_abundances(data.as_bytes())?;
+///
+/// assert_eq!(abundances.len(), 2);
+/// assert_eq!(abundances["ENST00000456328.2"].effective_length, 1482.0);
+/// assert_eq!(abundances["ENST00000456328.2"].tpm, 2.5);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_transcript_abundances<R>(reader: R) -> io::Result<TranscriptAbundances>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .from_reader(reader);
+
+    let headers = rdr.headers()?.clone();
+
+    let find_columns = |names: &[&str; 3]| -> Option<Vec<usize>> {
+        names
+            .iter()
+            .map(|name| find_column(&headers, name))
+            .collect()
+    };
+
+    let indices = find_columns(&SALMON_HEADERS)
+        .or_else(|| find_columns(&KALLISTO_HEADERS))
+        .ok_or_else(|| {
+            invalid_data(format!(
+                "missing columns: {} (salmon) or {} (kallisto)",
+                SALMON_HEADERS.join(", "),
+                KALLISTO_HEADERS.join(", ")
+            ))
+        })?;
+
+    let (id_index, effective_length_index, tpm_index) = (indices[0], indices[1], indices[2]);
+
+    let mut abundances = TranscriptAbundances::default();
+
+    for result in rdr.records() {
+        let record = result?;
+
+        let id = record
+            .get(id_index)
+            .ok_or_else(|| invalid_data(format!("invalid record: {:?}", record)))?;
+
+        let effective_length = parse_value(&record, effective_length_index)
+            .ok_or_else(|| invalid_data(format!("invalid effective length: {:?}", record)))?;
+
+        let tpm = parse_value(&record, tpm_index)
+            .ok_or_else(|| invalid_data(format!("invalid TPM: {:?}", record)))?;
+
+        abundances.insert(
+            id.into(),
+            TranscriptAbundance {
+                effective_length,
+                tpm,
+            },
+        );
+    }
+
+    Ok(abundances)
+}
+
+/// Reads the expression values of a single sample, as written by noodles-fpkm.
+///
+/// The input is TSV with no header: a feature ID and its value. Values can be
+/// negative, e.g., after median-centering, but must be finite. Comment lines,
+/// i.e., lines that start with `#`, and summary rows, i.e., feature IDs that
+/// start with `__`, are skipped.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::abundances::read_expressions;
+///
+/// let data = "# normalized\nAAAS\t3.25\nRPL37AP1\t-1.5\n__detected\t2\n";
+/// let expressions = read_expressions(data.as_bytes())?;
+///
+/// assert_eq!(expressions.len(), 2);
+/// assert_eq!(expressions["RPL37AP1"], -1.5);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_expressions<R>(reader: R) -> io::Result<Expressions>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .comment(Some(COMMENT_PREFIX))
+        .from_reader(reader);
+
+    let mut expressions = Expressions::new();
+
+    for result in rdr.records() {
+        let record = result?;
+
+        let id = record
+            .get(0)
+            .ok_or_else(|| invalid_data(format!("invalid record: {:?}", record)))?;
+
+        if id.starts_with(SUMMARY_ROW_PREFIX) {
+            continue;
+        }
+
+        let value = record
+            .get(1)
+            .and_then(|s| s.parse().ok())
+            .filter(|n: &f64| n.is_finite())
+            .ok_or_else(|| invalid_data(format!("invalid value: {:?}", record)))?;
+
+        if expressions.insert(id.into(), value).is_some() {
+            return Err(invalid_data(format!("duplicate feature ID: {}", id)));
+        }
+    }
+
+    Ok(expressions)
+}
+
+fn parse_value(record: &StringRecord, index: usize) -> Option<f64> {
+    record
+        .get(index)
+        .and_then(|s| s.parse().ok())
+        .filter(|n: &f64| n.is_finite() && *n >= 0.0)
+}
+
+fn find_column(headers: &StringRecord, name: &str) -> Option<usize> {
+    headers.iter().position(|h| h == name)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_expressions_with_invalid_input() {
+        assert!(read_expressions(&b"AAAS\tinf\n"[..]).is_err());
+        assert!(read_expressions(&b"AAAS\t1\nAAAS\t2\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_abundances_with_cufflinks_genes() {
+        let data = "\
+tracking_id\tclass_code\tnearest_ref_id\tgene_id\tgene_short_name\ttss_id\tlocus\tlength\tcoverage\tFPKM\tFPKM_conf_lo\tFPKM_conf_hi\tFPKM_status
+AAAS\t-\t-\tAAAS\tAAAS\t-\tchr12:53307455-53324864\t-\t-\t3.25\t2.5\t4\tOK
+RPL37AP1\t-\t-\tRPL37AP1\t-\t-\tchr8:44892712-44892766\t-\t-\t20\t15\t25\tOK
+RPL37AP1\t-\t-\tRPL37AP1\t-\t-\tchr8:44900000-44900054\t-\t-\t7.5\t5\t10\tOK
+";
+
+        let fpkms = read_abundances(data.as_bytes()).unwrap();
+
+        assert_eq!(fpkms.len(), 2);
+        assert_eq!(fpkms["AAAS"], 3.25);
+        assert_eq!(fpkms["RPL37AP1"], 27.5);
+    }
+
+    #[test]
+    fn test_read_abundances_with_unknown_format() {
+        let data = "gene\tFPKM\nAAAS\t3.25\n";
+        assert!(read_abundances(data.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_abundances_with_invalid_fpkm() {
+        let data = "Gene ID\tFPKM\nAAAS\tNaN\n";
+        assert!(read_abundances(data.as_bytes()).is_err());
+
+        let data = "Gene ID\tFPKM\nAAAS\t-1\n";
+        assert!(read_abundances(data.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_transcript_abundances_with_kallisto_abundances() -> io::Result<()> {
+        let data = "\
+target_id\tlength\teff_length\test_counts\ttpm
+ENST00000456328.2\t1657\t1482.5\t6\t2.5
+";
+
+        let abundances = read_transcript_abundances(data.as_bytes())?;
+
+        assert_eq!(
+            abundances["ENST00000456328.2"],
+            TranscriptAbundance {
+                effective_length: 1482.5,
+                tpm: 2.5,
+            }
+        );
+
+        let data = "Name\tLength\tTPM\nENST00000456328.2\t1657\t2.5\n";
+        assert!(read_transcript_abundances(data.as_bytes()).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use crate::{
+    feature_id::{strip_version, FeatureId},
+    lengths::Lengths,
+};
+
+/// A kind of change of a feature between two annotation releases.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChangeKind {
+    /// The feature is only in the new release.
+    Added,
+    /// The feature is only in the old release.
+    Removed,
+    /// The ID of the feature changed, e.g., its version suffix, from the given
+    /// old ID.
+    Renamed(FeatureId),
+    /// The length of the feature changed.
+    Length,
+}
+
+impl ChangeKind {
+    /// Returns the name of the kind of change, as used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Renamed(_) => "renamed",
+            ChangeKind::Length => "length",
+        }
+    }
+}
+
+/// A change of a feature between two annotation releases.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    /// The ID of the feature, in the new release unless it was removed.
+    pub id: FeatureId,
+    /// The kind of change.
+    pub kind: ChangeKind,
+    /// The length of the feature in the old release.
+    pub old_length: Option<u64>,
+    /// The length of the feature in the new release.
+    pub new_length: Option<u64>,
+}
+
+impl Change {
+    /// Returns the relative change of the FPKM of the feature due only to the
+    /// change of its length, e.g., 0.25 for a 25% increase.
+    ///
+    /// FPKM is inversely proportional to length, so for the same count, this
+    /// is the old length divided by the new length, less 1. This is `None`
+    /// unless the feature is in both releases.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::{annotation_diff::{Change, ChangeKind}, feature_id::FeatureId};
+    ///
+    /// let change = Change {
+    ///     id: FeatureId::from("AAAS"),
+    ///     kind: ChangeKind::Length,
+    ///     old_length: Some(2000),
+    ///     new_length: Some(1600),
+    /// };
+    ///
+    /// assert_eq!(change.fpkm_shift(), Some(0.25));
+    /// ```
+    pub fn fpkm_shift(&self) -> Option<f64> {
+        match (self.old_length, self.new_length) {
+            (Some(old_length), Some(new_length)) if new_length > 0 => {
+                Some(old_length as f64 / new_length as f64 - 1.0)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Compares the feature lengths of two annotation releases.
+///
+/// A feature that is only in one release is added or removed, unless its ID
+/// differs from the ID of a feature only in the other release by only a
+/// version suffix, e.g., `ENSG00000094914.12` and `ENSG00000094914.13`, in
+/// which case it is renamed. A feature in both releases whose length differs
+/// is a length change. Unchanged features are skipped, and the changes are
+/// sorted by ID.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     annotation_diff::{diff_lengths, ChangeKind},
+///     feature_id::FeatureId,
+///     lengths::Lengths,
+/// };
+///
+/// let old: Lengths = [
+///     (FeatureId::from("AAAS.1"), 2000),
+///     (FeatureId::from("AC009952.3"), 500),
+///     (FeatureId::from("RPL37AP1"), 1000),
+/// ].iter().cloned().collect();
+///
+/// let new: Lengths = [
+///     (FeatureId::from("AAAS.2"), 2500),
+///     (FeatureId::from("RPL37AP1"), 1000),
+///     (FeatureId::from("XIST"), 3000),
+/// ].iter().cloned().collect();
+///
+/// let changes = diff_lengths(&old, &new);
+///
+/// assert_eq!(changes.len(), 3);
+/// assert_eq!(changes[0].kind, ChangeKind::Renamed(FeatureId::from("AAAS.1")));
+/// assert_eq!(changes[1].kind, ChangeKind::Removed);
+/// assert_eq!(changes[2].kind, ChangeKind::Added);
+/// ```
+pub fn diff_lengths(old: &Lengths, new: &Lengths) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    // Old IDs that are not in the new release, keyed by their unversioned IDs.
+    // Unversioned IDs shared by multiple old IDs are ambiguous and not used
+    // to match renames.
+    let mut unmatched_old_ids: HashMap<&str, Option<&FeatureId>> = HashMap::new();
+
+    for id in old.keys().filter(|id| !new.contains_key(*id)) {
+        unmatched_old_ids
+            .entry(strip_version(id.as_str()))
+            .and_modify(|old_id| *old_id = None)
+            .or_insert(Some(id));
+    }
+
+    let mut renamed_old_ids = Vec::new();
+
+    for (id, &new_length) in new {
+        let (kind, old_length) = match old.get(id) {
+            Some(&old_length) if old_length == new_length => continue,
+            Some(&old_length) => (ChangeKind::Length, Some(old_length)),
+            None => match unmatched_old_ids.get(strip_version(id.as_str())) {
+                Some(Some(old_id)) => {
+                    renamed_old_ids.push(*old_id);
+                    (ChangeKind::Renamed((*old_id).clone()), Some(old[*old_id]))
+                }
+                _ => (ChangeKind::Added, None),
+            },
+        };
+
+        changes.push(Change {
+            id: id.clone(),
+            kind,
+            old_length,
+            new_length: Some(new_length),
+        });
+    }
+
+    for (id, &old_length) in old {
+        if !new.contains_key(id) && !renamed_old_ids.contains(&id) {
+            changes.push(Change {
+                id: id.clone(),
+                kind: ChangeKind::Removed,
+                old_length: Some(old_length),
+                new_length: None,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.id.cmp(&b.id));
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lengths_with_ambiguous_versions() {
+        let old: Lengths = [
+            (FeatureId::from("AAAS.1"), 2000),
+            (FeatureId::from("AAAS.2"), 2000),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let new: Lengths = [(FeatureId::from("AAAS.3"), 2000)]
+            .iter()
+            .cloned()
+            .collect();
+
+        let kinds: Vec<_> = diff_lengths(&old, &new)
+            .into_iter()
+            .map(|change| change.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            [ChangeKind::Removed, ChangeKind::Removed, ChangeKind::Added]
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    cmp,
+    io::{self, BufRead, Read, Seek, SeekFrom, Write},
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression, Crc};
+
+// The maximum size of the uncompressed data of a block. This is less than
+// 64 KiB so that the compressed block, including its header and trailer, fits
+// in 64 KiB even when the data is incompressible.
+const MAX_BLOCK_DATA_SIZE: usize = 0xff00;
+
+const HEADER_SIZE: usize = 18;
+const TRAILER_SIZE: usize = 8;
+
+const MAGIC_NUMBER: [u8; 4] = [0x1f, 0x8b, 0x08, 0x04];
+
+// gzip header fields after the magic number: MTIME (0), XFL (0), OS (255,
+// unknown), XLEN (6), and the BC extra subfield identifier and length (2).
+const HEADER_FIELDS: [u8; 12] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, b'B', b'C', 0x02, 0x00,
+];
+
+// An empty block, which marks the end of a BGZF file.
+const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// A blocked gzip format (BGZF) writer.
+///
+/// BGZF is a series of concatenated gzip members (blocks), so it can be read
+/// by any gzip decompressor. Each block holds at most 64 KiB of data, so a
+/// position in the uncompressed data can be addressed by a virtual position:
+/// the offset of the block in the compressed file (upper 48 bits) and the
+/// offset in the block's uncompressed data (lower 16 bits).
+pub struct Writer<W>
+where
+    W: Write,
+{
+    inner: W,
+    buf: Vec<u8>,
+    position: u64,
+}
+
+impl<W> Writer<W>
+where
+    W: Write,
+{
+    /// Creates a BGZF writer.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(MAX_BLOCK_DATA_SIZE),
+            position: 0,
+        }
+    }
+
+    /// Returns the virtual position of the next byte to be written.
+    pub fn virtual_position(&self) -> u64 {
+        (self.position << 16) | self.buf.len() as u64
+    }
+
+    /// Writes any remaining data and the end-of-file marker and returns the
+    /// inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_block()?;
+        self.inner.write_all(&EOF_BLOCK)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&self.buf)?;
+        let cdata = encoder.finish()?;
+
+        let mut crc = Crc::new();
+        crc.update(&self.buf);
+
+        let block_size = HEADER_SIZE + cdata.len() + TRAILER_SIZE;
+        let bsize = (block_size - 1) as u16;
+
+        self.inner.write_all(&MAGIC_NUMBER)?;
+        self.inner.write_all(&HEADER_FIELDS)?;
+        self.inner.write_all(&bsize.to_le_bytes())?;
+        self.inner.write_all(&cdata)?;
+        self.inner.write_all(&crc.sum().to_le_bytes())?;
+        self.inner
+            .write_all(&(self.buf.len() as u32).to_le_bytes())?;
+
+        self.position += block_size as u64;
+        self.buf.clear();
+
+        Ok(())
+    }
+}
+
+impl<W> Write for Writer<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = cmp::min(MAX_BLOCK_DATA_SIZE - self.buf.len(), buf.len());
+        self.buf.extend_from_slice(&buf[..n]);
+
+        if self.buf.len() >= MAX_BLOCK_DATA_SIZE {
+            self.flush_block()?;
+        }
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_block()?;
+        self.inner.flush()
+    }
+}
+
+/// A blocked gzip format (BGZF) reader.
+///
+/// # Example
+///
+/// ```
+/// use std::io::{BufRead, Cursor, Write};
+///
+/// use noodles_fpkm::bgzf;
+///
+/// let mut writer = bgzf::Writer::new(Vec::new());
+/// writer.write_all(b"noodles\n")?;
+/// let position = writer.virtual_position();
+/// writer.write_all(b"fpkm\n")?;
+/// let data = writer.finish()?;
+///
+/// let mut reader = bgzf::Reader::new(Cursor::new(data));
+/// reader.seek(position)?;
+///
+/// let mut line = String::new();
+/// reader.read_line(&mut line)?;
+/// assert_eq!(line, "fpkm\n");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Reader<R>
+where
+    R: Read,
+{
+    inner: R,
+    block: Vec<u8>,
+    offset: usize,
+}
+
+impl<R> Reader<R>
+where
+    R: Read,
+{
+    /// Creates a BGZF reader.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            block: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    // Reads and decompresses the next block. This returns false at EOF.
+    fn read_block(&mut self) -> io::Result<bool> {
+        self.block.clear();
+        self.offset = 0;
+
+        let mut header = [0; HEADER_SIZE];
+
+        match self.inner.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e),
+        }
+
+        if header[..4] != MAGIC_NUMBER || header[12..14] != *b"BC" {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid BGZF block header",
+            ));
+        }
+
+        let block_size = usize::from(u16::from_le_bytes([header[16], header[17]])) + 1;
+
+        if block_size < HEADER_SIZE + TRAILER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid BGZF block size",
+            ));
+        }
+
+        let mut buf = vec![0; block_size - HEADER_SIZE];
+        self.inner.read_exact(&mut buf)?;
+
+        let cdata = &buf[..buf.len() - TRAILER_SIZE];
+        DeflateDecoder::new(cdata).read_to_end(&mut self.block)?;
+
+        Ok(true)
+    }
+}
+
+impl<R> Reader<R>
+where
+    R: Read + Seek,
+{
+    /// Seeks to a virtual position.
+    pub fn seek(&mut self, virtual_position: u64) -> io::Result<()> {
+        let block_position = virtual_position >> 16;
+        let offset = (virtual_position & 0xffff) as usize;
+
+        self.inner.seek(SeekFrom::Start(block_position))?;
+        self.read_block()?;
+
+        if offset > self.block.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid virtual position",
+            ));
+        }
+
+        self.offset = offset;
+
+        Ok(())
+    }
+}
+
+impl<R> Read for Reader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let src = self.fill_buf()?;
+        let n = cmp::min(src.len(), buf.len());
+        buf[..n].copy_from_slice(&src[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R> BufRead for Reader<R>
+where
+    R: Read,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // Empty blocks, e.g., the EOF marker, are skipped.
+        while self.offset >= self.block.len() {
+            if !self.read_block()? {
+                return Ok(&[]);
+            }
+        }
+
+        Ok(&self.block[self.offset..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.offset = cmp::min(self.offset + amt, self.block.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn test_write_is_gzip_compatible() -> io::Result<()> {
+        let mut writer = Writer::new(Vec::new());
+        writer.write_all(b"noodles\tfpkm\n")?;
+        let data = writer.finish()?;
+
+        assert!(data.ends_with(&EOF_BLOCK));
+
+        let mut buf = String::new();
+        MultiGzDecoder::new(&data[..]).read_to_string(&mut buf)?;
+        assert_eq!(buf, "noodles\tfpkm\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_with_multiple_blocks() -> io::Result<()> {
+        let data: Vec<u8> = (0..3 * MAX_BLOCK_DATA_SIZE)
+            .map(|i| (i % 251) as u8)
+            .collect();
+
+        let mut writer = Writer::new(Vec::new());
+        writer.write_all(&data[..10])?;
+        let position = writer.virtual_position();
+        assert_eq!(position, 10);
+        writer.write_all(&data[10..])?;
+        let compressed = writer.finish()?;
+
+        let mut reader = Reader::new(Cursor::new(&compressed));
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        assert_eq!(buf, data);
+
+        reader.seek(position)?;
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        assert_eq!(buf, &data[10..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_with_invalid_header() {
+        let mut reader = Reader::new(&b"noodles-fpkm, not bgzf"[..]);
+        let mut buf = Vec::new();
+        assert!(reader.read_to_end(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Lines, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::matrix::ExpressionMatrix;
+
+/// A writer of a wide matrix in blocks of columns.
+///
+/// Each block, e.g., a batch of samples, is written to a temporary file in
+/// `dir` as soon as it is calculated, so only one block is held in memory at a
+/// time. [`finish`] then stitches the blocks into one matrix with rows in
+/// lexicographic order, giving features absent from a block a value of 0.
+///
+/// The output is the same as writing the matrix built from all columns at
+/// once.
+///
+/// [`finish`]: #method.finish
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{chunked::ChunkedMatrixWriter, matrix::ExpressionMatrix};
+///
+/// let dir = std::env::temp_dir().join(format!("noodles-fpkm-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir)?;
+///
+/// let mut writer = ChunkedMatrixWriter::new(&dir);
+///
+/// writer.write_chunk(&ExpressionMatrix::new(
+///     vec![String::from("RPL37AP1")],
+///     vec![String::from("sample1")],
+///     arr2(&[[2.5]]),
+/// ))?;
+///
+/// writer.write_chunk(&ExpressionMatrix::new(
+///     vec![String::from("AAAS")],
+///     vec![String::from("sample2")],
+///     arr2(&[[8.0]]),
+/// ))?;
+///
+/// let mut buf = Vec::new();
+/// writer.finish(&mut buf, "tpm")?;
+///
+/// assert_eq!(
+///     String::from_utf8(buf).unwrap(),
+///     "tpm\tsample1\tsample2\nAAAS\t0\t8\nRPL37AP1\t2.5\t0\n"
+/// );
+///
+/// std::fs::remove_dir(&dir)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct ChunkedMatrixWriter {
+    dir: PathBuf,
+    chunks: Vec<(PathBuf, usize)>,
+    column_names: Vec<String>,
+    row_names: BTreeSet<String>,
+}
+
+impl ChunkedMatrixWriter {
+    /// Creates a chunked matrix writer that writes temporary files to `dir`.
+    ///
+    /// The directory must exist.
+    pub fn new<P>(dir: P) -> ChunkedMatrixWriter
+    where
+        P: AsRef<Path>,
+    {
+        ChunkedMatrixWriter {
+            dir: dir.as_ref().to_path_buf(),
+            chunks: Vec::new(),
+            column_names: Vec::new(),
+            row_names: BTreeSet::new(),
+        }
+    }
+
+    /// Writes a block of columns to a temporary file.
+    pub fn write_chunk(&mut self, matrix: &ExpressionMatrix) -> io::Result<()> {
+        let dst = self.dir.join(format!("chunk-{:06}.tsv", self.chunks.len()));
+        let mut writer = File::create(&dst).map(BufWriter::new)?;
+
+        let row_names = matrix.row_names();
+        let mut row_indices: Vec<usize> = (0..row_names.len()).collect();
+        row_indices.sort_by(|&a, &b| row_names[a].cmp(&row_names[b]));
+
+        for i in row_indices {
+            write!(writer, "{}", row_names[i])?;
+
+            for value in matrix.data().row(i) {
+                write!(writer, "\t{}", value)?;
+            }
+
+            writeln!(writer)?;
+        }
+
+        writer.flush()?;
+
+        let column_count = matrix.column_names().len();
+        self.chunks.push((dst, column_count));
+        self.column_names
+            .extend(matrix.column_names().iter().cloned());
+        self.row_names.extend(row_names.iter().cloned());
+
+        Ok(())
+    }
+
+    /// Stitches the blocks into one matrix and removes the temporary files.
+    ///
+    /// `label` is the first field of the header, followed by the column names
+    /// in the order the blocks were written.
+    pub fn finish<W>(self, mut writer: W, label: &str) -> io::Result<()>
+    where
+        W: Write,
+    {
+        write!(writer, "{}", label)?;
+
+        for name in &self.column_names {
+            write!(writer, "\t{}", name)?;
+        }
+
+        writeln!(writer)?;
+
+        let mut readers = Vec::with_capacity(self.chunks.len());
+
+        for (src, column_count) in &self.chunks {
+            let mut lines = File::open(src).map(BufReader::new)?.lines();
+            let line = lines.next().transpose()?;
+            readers.push(ChunkReader {
+                lines,
+                line,
+                column_count: *column_count,
+            });
+        }
+
+        for row_name in &self.row_names {
+            write!(writer, "{}", row_name)?;
+
+            for reader in &mut readers {
+                reader.write_row(&mut writer, row_name)?;
+            }
+
+            writeln!(writer)?;
+        }
+
+        writer.flush()?;
+
+        for (src, _) in &self.chunks {
+            fs::remove_file(src)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct ChunkReader {
+    lines: Lines<BufReader<File>>,
+    line: Option<String>,
+    column_count: usize,
+}
+
+impl ChunkReader {
+    // Writes the values of the row from the current line, if it is the row, or
+    // zeros otherwise.
+    fn write_row<W>(&mut self, mut writer: W, row_name: &str) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let values = self.line.as_ref().and_then(|line| {
+            let (name, values) = line.split_at(line.find('\t').unwrap_or(line.len()));
+
+            if name == row_name {
+                Some(values.to_string())
+            } else {
+                None
+            }
+        });
+
+        match values {
+            Some(values) => {
+                writer.write_all(values.as_bytes())?;
+                self.line = self.lines.next().transpose()?;
+            }
+            None => {
+                for _ in 0..self.column_count {
+                    writer.write_all(b"\t0")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_finish_with_unsorted_rows() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("noodles-fpkm-chunked-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let mut writer = ChunkedMatrixWriter::new(&dir);
+
+        writer.write_chunk(&ExpressionMatrix::new(
+            vec![String::from("b"), String::from("a")],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[1.0, 2.0], [3.0, 4.0]]),
+        ))?;
+
+        writer.write_chunk(&ExpressionMatrix::new(
+            vec![String::from("c"), String::from("a")],
+            vec![String::from("sample3")],
+            arr2(&[[5.0], [6.0]]),
+        ))?;
+
+        let mut buf = Vec::new();
+        writer.finish(&mut buf, "fpkm")?;
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "\
+fpkm\tsample1\tsample2\tsample3
+a\t3\t4\t6
+b\t1\t2\t0
+c\t0\t0\t5
+";
+
+        assert_eq!(actual, expected);
+        assert_eq!(fs::read_dir(&dir)?.count(), 0);
+
+        fs::remove_dir(&dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,8 @@
+//! The command-line interface, built with the `cli` feature.
+
+pub(crate) mod app;
+pub(crate) mod checksums;
+pub(crate) mod quantify;
+pub(crate) mod reports;
+pub(crate) mod tenx;
+pub(crate) mod watch;
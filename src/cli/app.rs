@@ -0,0 +1,911 @@
+use clap::{crate_name, crate_version, App, AppSettings, Arg, SubCommand};
+
+// Appends to the help of an input option that the input can be a URL (see
+// `remote::open`) when built with the `remote` feature.
+#[cfg(feature = "remote")]
+macro_rules! url_help {
+    ($help:expr) => {
+        concat!(
+            $help,
+            "; or an http(s):// or s3:// URL (S3 objects are fetched anonymously from https://{bucket}.s3.amazonaws.com/{key})"
+        )
+    };
+}
+
+#[cfg(not(feature = "remote"))]
+macro_rules! url_help {
+    ($help:expr) => {
+        $help
+    };
+}
+
+pub(crate) fn app() -> App<'static, 'static> {
+    App::new(crate_name!())
+        .version(crate_version!())
+        .args(&global_args())
+        .args(&annotation_args())
+        .args(&counts_args())
+        .args(&length_args())
+        .args(&calculation_args())
+        .args(&matrix_args())
+        .args(&sample_report_args())
+        .args(&matrix_report_args())
+        .args(&output_args())
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(saturation_command())
+        .subcommand(simulate_command())
+        .subcommand(screen_spike_ins_command())
+        .subcommand(annotation_diff_command())
+        .subcommand(serve_command())
+        .subcommand(export_index_command())
+        .subcommand(merge_command())
+        .subcommand(matrix_command())
+        .subcommand(query_command())
+}
+
+// Arguments to configure logging and error reporting.
+fn global_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("verbose")
+            .short("v")
+            .long("verbose")
+            .help("Use verbose logging"),
+        Arg::with_name("error-format")
+            .long("error-format")
+            .value_name("str")
+            .help("Format of errors written to stderr")
+            .env("NOODLES_FPKM_ERROR_FORMAT")
+            .default_value("text")
+            .possible_values(&["text", "json"]),
+    ]
+}
+
+// Arguments to read annotations and match their feature IDs to counts.
+fn annotation_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("annotations")
+            .short("a")
+            .long("annotations")
+            .value_name("file")
+            .help(url_help!("Input annotations file (GTF/GFFv2, GFF3, BED, SAF, refFlat, FASTA, or lengths) or - for stdin; required for fpkm, tpm, and cpm"))
+            .env("NOODLES_FPKM_ANNOTATIONS"),
+        Arg::with_name("feature-type")
+            .short("t")
+            .long("type")
+            .value_name("str")
+            .help("Feature type to count")
+            .env("NOODLES_FPKM_TYPE")
+            .default_value("exon"),
+        Arg::with_name("feature-id")
+            .short("i")
+            .long("id")
+            .visible_alias("group-by")
+            .value_name("str")
+.help("Feature attribute, or grouping expression, to use as the feature identity")
+            .env("NOODLES_FPKM_ID")
+            .default_value("gene_id"),
+        Arg::with_name("id-map")
+            .long("id-map")
+            .value_name("file")
+            .help("Map of counts feature IDs to annotation feature IDs (TSV: from ID, to ID)")
+            .env("NOODLES_FPKM_ID_MAP"),
+        Arg::with_name("unmapped-ids")
+            .long("unmapped-ids")
+            .value_name("file")
+            .help("Output the counts feature IDs that are not in --id-map")
+            .requires("id-map"),
+        Arg::with_name("id-normalize")
+            .long("id-normalize")
+            .value_name("str")
+            .help("Normalization of counts feature IDs that are not in the annotations before matching")
+            .possible_values(&["none", "case", "alnum"])
+            .default_value("none"),
+        Arg::with_name("skip-missing-ids")
+            .long("skip-missing-ids")
+            .help("Skip, rather than fail on, records of --type that lack the --id attribute"),
+        Arg::with_name("mmap")
+            .long("mmap")
+            .help("Memory-map the annotations file (uncompressed only)"),
+    ]
+}
+
+// Arguments to read counts and other per-sample inputs.
+fn counts_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("counts")
+            .help(url_help!("Input feature counts (one or more files, each of one or more samples) or 10x Genomics matrix directory"))
+            .required_unless("watch")
+            .multiple(true)
+            .index(1),
+        Arg::with_name("meta-prefix")
+            .long("meta-prefix")
+            .value_name("str")
+            .help(
+                "Feature ID prefix of metadata rows, which end the counts (empty to read all)",
+            )
+            .env("NOODLES_FPKM_META_PREFIX")
+            .default_value("__"),
+        Arg::with_name("lenient-numbers")
+            .long("lenient-numbers")
+            .help("Accept counts with thousands separators (1,234) or decimal commas (12,0), e.g., from spreadsheets"),
+        Arg::with_name("strandedness")
+            .long("strandedness")
+            .value_name("str")
+            .help("Library strandedness, which selects the column of counts of STAR ReadsPerGene files")
+            .possible_values(&["unstranded", "forward", "reverse"])
+            .default_value("unstranded"),
+        Arg::with_name("abundances")
+            .long("abundances")
+            .value_name("file")
+            .help("Cufflinks or StringTie gene abundances to add as samples (fpkm and tpm only)")
+            .multiple(true)
+            .number_of_values(1),
+        Arg::with_name("transcript-abundances")
+            .long("transcript-abundances")
+            .value_name("file")
+            .help("salmon or kallisto transcript abundances to weight gene effective lengths (fpkm and tpm only; once or once per sample)")
+            .multiple(true)
+            .number_of_values(1)
+            .conflicts_with("antisense"),
+        Arg::with_name("antisense")
+            .long("antisense")
+            .value_name("file")
+            .help(url_help!("Antisense feature counts of the sample, output as a third column"))
+            .conflicts_with("downsample"),
+        Arg::with_name("barcodes")
+            .long("barcodes")
+            .value_name("file")
+            .help("Cell barcode whitelist (10x Genomics input only)"),
+        Arg::with_name("pseudobulk")
+            .long("pseudobulk")
+            .value_name("file")
+            .help("Barcode-group assignments (barcode<TAB>group) to sum counts per group into samples (10x Genomics input only)")
+            .conflicts_with_all(&["log1p", "output"]),
+        Arg::with_name("size-factors")
+            .long("size-factors")
+            .value_name("file")
+            .help("Sample size factors (TSV) to use for --unit scaled"),
+        Arg::with_name("downsample")
+            .long("downsample")
+            .value_name("uint")
+            .help("Downsample each sample or cell to this depth before normalization"),
+        Arg::with_name("seed")
+            .long("seed")
+            .value_name("uint")
+            .help("Seed for the random number generator used to downsample")
+            .default_value("0"),
+        Arg::with_name("checksums")
+            .long("checksums")
+            .value_name("file")
+            .help("Output the SHA-256 checksums of the input files (sha256sum format)"),
+        Arg::with_name("verify-checksums")
+            .long("verify-checksums")
+            .value_name("file")
+            .help("Fail unless every input file matches its SHA-256 checksum in this manifest (sha256sum format)"),
+    ]
+}
+
+// Arguments to calculate feature lengths.
+fn length_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("length-source")
+            .long("length-source")
+            .value_name("str")
+            .help("Intervals used for feature lengths")
+            .env("NOODLES_FPKM_LENGTH_SOURCE")
+            .default_value("feature")
+            .possible_values(&[
+                "feature",
+                "gene-body",
+                "three-prime-utr",
+                "last-exon",
+                "exon-bin",
+            ]),
+        Arg::with_name("upstream-flank")
+            .long("upstream-flank")
+            .value_name("uint")
+            .help("Length of the upstream flank added to each span (gene-body length source only)"),
+        Arg::with_name("downstream-flank")
+            .long("downstream-flank")
+            .value_name("uint")
+            .help("Length of the downstream flank added to each span (gene-body length source only)"),
+        Arg::with_name("max-length")
+            .long("max-length")
+            .value_name("uint")
+            .help("Caps feature lengths at the given length and reports the capped features")
+            .conflicts_with("winsorize-lengths"),
+        Arg::with_name("winsorize-lengths")
+            .long("winsorize-lengths")
+            .value_name("quantile")
+            .help("Caps feature lengths at the given quantile of lengths, in (0, 1], and reports the capped features"),
+        Arg::with_name("fragment-length-distribution")
+            .long("fragment-length-distribution")
+            .value_name("file")
+            .help("Fragment length distribution (TSV: length, weight) used to calculate effective lengths")
+            .conflicts_with("fragment-length-mean"),
+        Arg::with_name("fragment-length-mean")
+            .long("fragment-length-mean")
+            .value_name("float")
+            .help("Mean fragment length used to calculate effective lengths"),
+        Arg::with_name("fragment-length-sd")
+            .long("fragment-length-sd")
+            .value_name("float")
+            .help("Standard deviation of a normal fragment length distribution")
+            .requires("fragment-length-mean"),
+    ]
+}
+
+// Arguments to calculate and check expression values.
+fn calculation_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("unit")
+            .short("u")
+            .long("unit")
+            .value_name("str")
+            .help("Unit of expression")
+            .env("NOODLES_FPKM_UNIT")
+            .default_value("tpm")
+            .possible_values(&["fpkm", "tpm", "cpm", "scaled", "cp10k", "median"]),
+        Arg::with_name("log1p")
+            .long("log1p")
+            .help("Apply ln(1 + x) to expression values (10x Genomics input only)"),
+        Arg::with_name("threads")
+            .long("threads")
+            .value_name("uint")
+            .help("Number of threads to use to read annotations")
+            .env("NOODLES_FPKM_THREADS")
+            .default_value("1"),
+        Arg::with_name("deterministic")
+            .long("deterministic")
+            .help("Use a fixed evaluation order, e.g., to compare outputs bit-for-bit (overrides --threads)"),
+        Arg::with_name("compensated-summation")
+            .long("compensated-summation")
+            .help("Use compensated summation when normalizing tpm and cpm"),
+        Arg::with_name("tpm-sum-tolerance")
+            .long("tpm-sum-tolerance")
+            .value_name("float")
+            .help("Relative tolerance of the deviation of the sum of each sample's tpm or cpm from 1e6")
+            .default_value("1e-6"),
+        Arg::with_name("strict")
+            .long("strict")
+            .help("Fail, rather than warn, when a sanity check fails"),
+        Arg::with_name("self-check")
+            .long("self-check")
+            .help("Check internal invariants after each stage of the pipeline (for debugging)"),
+        Arg::with_name("explain-gene")
+            .long("explain-gene")
+            .value_name("str")
+            .help("Write how the expression values of this feature are calculated to stderr")
+            .conflicts_with("watch"),
+        Arg::with_name("compare-against")
+            .long("compare-against")
+            .value_name("file")
+            .help("Expected output to compare the result to, failing on differences beyond --tolerance")
+            .conflicts_with_all(&["chunk-size", "watch"]),
+        Arg::with_name("tolerance")
+            .long("tolerance")
+            .value_name("float")
+            .help("Tolerance of the differences from --compare-against")
+            .default_value("1e-9"),
+    ]
+}
+
+// Arguments to normalize, transform, and filter the expression matrix.
+fn matrix_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("genome")
+            .long("genome")
+            .value_name("file")
+            .help(
+                "Reference genome (FASTA) to correct GC content and length bias across samples",
+            ),
+        Arg::with_name("reference-distribution")
+            .long("reference-distribution")
+            .value_name("file")
+            .help("Reference distribution of expression values (TSV) to map each sample onto by quantile"),
+        Arg::with_name("inverse-normal")
+            .long("inverse-normal")
+            .help("Applies a rank-based inverse normal transform to each feature across samples"),
+        Arg::with_name("rank-offset")
+            .long("rank-offset")
+            .value_name("float")
+            .help("Rank offset of the inverse normal transform, in [0, 0.5) (default: 0.375, Blom)")
+            .requires("inverse-normal"),
+        Arg::with_name("transform")
+            .long("transform")
+            .value_name("name")
+            .help("Transform to apply to each sample, in the order given: median-center or scale-total=<float>")
+            .multiple(true)
+            .number_of_values(1),
+        Arg::with_name("transform-features")
+            .long("transform-features")
+            .value_name("file")
+            .help("Features, one per line, to total with --transform scale-total (default: all)")
+            .requires("transform"),
+        Arg::with_name("sample-sheet")
+            .long("sample-sheet")
+            .value_name("file")
+            .help("Sample sheet (TSV) with sample and condition columns"),
+        Arg::with_name("batch-center")
+            .long("batch-center")
+            .help("Mean-centers log expression per batch (sample sheet batch column) as a lightweight batch adjustment")
+            .requires("sample-sheet"),
+        Arg::with_name("gene-universe")
+            .long("gene-universe")
+            .value_name("file")
+            .help("Features, one per line, that are the exact rows of the output, in order")
+            .conflicts_with_all(&["chunk-size", "antisense"]),
+        Arg::with_name("gene-metadata")
+            .long("gene-metadata")
+            .value_name("file")
+            .help("TSV of metadata keyed by feature ID whose columns are joined onto the output rows")
+            .conflicts_with_all(&["chunk-size", "antisense"]),
+        Arg::with_name("missing-metadata")
+            .long("missing-metadata")
+            .value_name("str")
+            .help("Handling of features that are not in --gene-metadata")
+            .possible_values(&["na", "drop", "error"])
+            .default_value("na"),
+        Arg::with_name("min-detected-fraction")
+            .long("min-detected-fraction")
+            .value_name("float")
+            .help("Keeps only features with at least --min-value in at least this fraction of samples")
+            .conflicts_with("gene-universe"),
+        Arg::with_name("min-value")
+            .long("min-value")
+            .value_name("float")
+            .help("Minimum expression value for --min-detected-fraction")
+            .default_value("1"),
+    ]
+}
+
+// Arguments to write reports of the counts of each sample.
+fn sample_report_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("qc-report")
+            .long("qc-report")
+            .value_name("file")
+            .help("Output QC metrics of each sample, e.g., mitochondrial and rRNA count fractions"),
+        Arg::with_name("max-mitochondrial-fraction")
+            .long("max-mitochondrial-fraction")
+            .value_name("float")
+            .help("Warn when a sample's mitochondrial count fraction exceeds this")
+            .requires("qc-report"),
+        Arg::with_name("max-rrna-fraction")
+            .long("max-rrna-fraction")
+            .value_name("float")
+            .help("Warn when a sample's rRNA count fraction exceeds this")
+            .requires("qc-report"),
+        Arg::with_name("length-report")
+            .long("length-report")
+            .value_name("file")
+            .help("Output the distribution of feature lengths, overall and per biotype"),
+        Arg::with_name("rarefaction")
+            .long("rarefaction")
+            .value_name("file")
+            .help("Write a rarefaction curve report (detected features by depth)"),
+        Arg::with_name("method-comparison")
+            .long("method-comparison")
+            .value_name("file")
+            .help("Write the values and ranks of each feature under FPKM, TPM, FPKM-UQ, and TMM-CPM")
+            .conflicts_with_all(&["chunk-size", "watch"]),
+        Arg::with_name("raw-counts")
+            .long("raw-counts")
+            .value_name("file")
+            .help("Output the raw count matrix used for normalization, e.g., for DE tools"),
+        Arg::with_name("emit-size-factors")
+            .long("emit-size-factors")
+            .value_name("file")
+            .help("Output sample size factors calculated by the median-of-ratios method"),
+        Arg::with_name("resource-report")
+            .long("resource-report")
+            .value_name("file")
+            .help("Output the wall time, records per second, and peak memory of each stage of the run"),
+    ]
+}
+
+// Arguments to write reports of the expression matrix.
+fn matrix_report_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("group-summary")
+            .long("group-summary")
+            .value_name("file")
+            .help("Output destination for the mean and median of each condition")
+            .requires("sample-sheet"),
+        Arg::with_name("stratify-by")
+            .long("stratify-by")
+            .value_name("column")
+            .help("Sample sheet column to stratify --qc-report and --group-summary by, e.g., tissue")
+            .requires("sample-sheet"),
+        Arg::with_name("control")
+            .long("control")
+            .value_name("condition")
+            .help("Condition of the control samples in the sample sheet")
+            .requires("sample-sheet"),
+        Arg::with_name("fold-changes")
+            .long("fold-changes")
+            .value_name("file")
+            .help("Output destination for log2 fold changes versus the control")
+            .requires("control"),
+        Arg::with_name("fold-change-groups")
+            .long("fold-change-groups")
+            .help("Calculates fold changes of condition means rather than samples")
+            .requires("fold-changes"),
+        Arg::with_name("signatures")
+            .long("signatures")
+            .value_name("file")
+            .help("Gene signatures to score (GMT)")
+            .requires("scores"),
+        Arg::with_name("scores")
+            .long("scores")
+            .value_name("file")
+            .help("Output signature scores")
+            .requires("signatures"),
+        Arg::with_name("detection")
+            .long("detection")
+            .value_name("file")
+            .help("Output a binary detection matrix and the number of detected features"),
+        Arg::with_name("gene-lists")
+            .long("gene-lists")
+            .value_name("dir")
+            .help("Output lists of the features at or above (<sample>.above.txt) and below (<sample>.below.txt) --detection-threshold per sample"),
+        Arg::with_name("detection-threshold")
+            .long("detection-threshold")
+            .value_name("float")
+            .help("Minimum expression value for a feature to be detected")
+            .default_value("1"),
+        Arg::with_name("top")
+            .long("top")
+            .value_name("uint")
+            .help("Number of most highly expressed features to report per sample")
+            .requires("top-report"),
+        Arg::with_name("top-report")
+            .long("top-report")
+            .value_name("file")
+            .help("Output the most highly expressed features of each sample")
+            .requires("top"),
+        Arg::with_name("ercc")
+            .long("ercc")
+            .value_name("file")
+            .help("ERCC spike-in mix table of expected concentrations")
+            .requires("ercc-report"),
+        Arg::with_name("ercc-mix")
+            .long("ercc-mix")
+            .value_name("uint")
+            .help("ERCC spike-in mix used in the samples")
+            .default_value("1")
+            .possible_values(&["1", "2"]),
+        Arg::with_name("ercc-report")
+            .long("ercc-report")
+            .value_name("file")
+            .help("Output the ERCC dose-response fit of each sample")
+            .requires("ercc"),
+        Arg::with_name("reference-sequence-summary")
+            .long("reference-sequence-summary")
+            .value_name("file")
+            .help("Output the fraction of expression from each reference sequence (chromosome)"),
+        Arg::with_name("biotype-summary")
+            .long("biotype-summary")
+            .value_name("file")
+            .help("Output the fraction of expression from each biotype, e.g., protein_coding"),
+        Arg::with_name("sex-report")
+            .long("sex-report")
+            .value_name("file")
+            .help("Output the sex of each sample inferred from marker gene expression"),
+        Arg::with_name("female-genes")
+            .long("female-genes")
+            .value_name("str")
+            .help("Comma-separated female marker gene IDs for --sex-report [default: XIST]")
+            .use_delimiter(true)
+            .multiple(true),
+        Arg::with_name("male-genes")
+            .long("male-genes")
+            .value_name("str")
+            .help("Comma-separated male marker gene IDs for --sex-report [default: RPS4Y1,DDX3Y,KDM5D,UTY,EIF1AY]")
+            .use_delimiter(true)
+            .multiple(true),
+        Arg::with_name("reference-genes")
+            .long("reference-genes")
+            .value_name("file")
+            .help("Output a ranking of candidate reference genes by stability"),
+    ]
+}
+
+// Arguments to write the expression matrix.
+fn output_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("delimiter")
+            .long("delimiter")
+            .value_name("str")
+            .help("Field delimiter of the expression output and matrix reports")
+            .default_value("tab")
+            .possible_values(&["tab", "comma", "semicolon"]),
+        Arg::with_name("decimal-separator")
+            .long("decimal-separator")
+            .value_name("str")
+            .help("Decimal separator of the expression output and matrix reports")
+            .default_value("period")
+            .possible_values(&["period", "comma"]),
+        Arg::with_name("chunk-size")
+            .long("chunk-size")
+            .value_name("int")
+            .help("Calculates and writes samples in blocks of this many samples to limit memory use")
+            .conflicts_with_all(&[
+                "signatures",
+                "reference-genes",
+                "genome",
+                "reference-distribution",
+                "inverse-normal",
+                "transform",
+                "fold-changes",
+                "group-summary",
+                "batch-center",
+                "detection",
+                "top",
+                "ercc",
+                "reference-sequence-summary",
+                "biotype-summary",
+                "sex-report",
+                "indexed-output",
+                "bundle",
+                "min-detected-fraction",
+                "gene-lists",
+            ]),
+        Arg::with_name("indexed-output")
+            .long("indexed-output")
+            .value_name("file")
+            .help("Output the expression matrix as BGZF-compressed TSV indexed by feature ID (<file>.fidx)"),
+        Arg::with_name("bundle")
+            .long("bundle")
+            .value_name("dir")
+            .help("Output raw counts, sample data, feature data, and the expression matrix as TSVs with matching rows and columns")
+            .conflicts_with_all(&["abundances", "transcript-abundances", "antisense"]),
+        Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("dir")
+            .help("Output directory (10x Genomics input only)"),
+        Arg::with_name("watch")
+            .long("watch")
+            .value_name("dir")
+            .help("Watch a directory for new counts files and quantify each as it appears")
+            .conflicts_with("counts")
+            .requires("watch-output"),
+        Arg::with_name("watch-output")
+            .long("watch-output")
+            .value_name("dir")
+            .help("Output directory of per-sample expressions and the running matrix (--watch)")
+            .requires("watch"),
+        Arg::with_name("watch-interval")
+            .long("watch-interval")
+            .value_name("uint")
+            .help("Seconds between checks of the watched directory [default: 5]")
+            .requires("watch"),
+    ]
+}
+
+fn saturation_command() -> App<'static, 'static> {
+    SubCommand::with_name("saturation")
+        .about("Prints the number of detected features at several depths of subsampled counts")
+        .arg(
+            Arg::with_name("depths")
+                .long("depths")
+                .value_name("uint")
+                .help("Depths to subsample to [default: 10 depths evenly spaced up to the largest library size]")
+                .multiple(true)
+                .require_delimiter(true),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("uint")
+                .help("Seed for the random number generator used to subsample")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("counts")
+                .help(url_help!("Input feature counts (one or more files, each of one or more samples)"))
+                .required(true)
+                .multiple(true)
+                .index(1),
+        )
+}
+
+fn simulate_command() -> App<'static, 'static> {
+    SubCommand::with_name("simulate")
+        .about("Prints synthetic counts of the features of an annotations file")
+        .arg(
+            Arg::with_name("annotations")
+                .short("a")
+                .long("annotations")
+                .value_name("file")
+                .help("Input annotations file (GTF/GFFv2)")
+                .env("NOODLES_FPKM_ANNOTATIONS")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("feature-type")
+                .short("t")
+                .long("type")
+                .value_name("str")
+                .help("Feature type to count")
+                .env("NOODLES_FPKM_TYPE")
+                .default_value("exon"),
+        )
+        .arg(
+            Arg::with_name("feature-id")
+                .short("i")
+                .long("id")
+                .value_name("str")
+                .help("Feature attribute to use as the feature identity")
+                .env("NOODLES_FPKM_ID")
+                .default_value("gene_id"),
+        )
+        .arg(
+            Arg::with_name("mean")
+                .long("mean")
+                .value_name("float")
+                .help("Mean count of each feature")
+                .default_value("100"),
+        )
+        .arg(
+            Arg::with_name("dispersion")
+                .long("dispersion")
+                .value_name("float")
+                .help("Negative binomial dispersion of the counts (0 is Poisson)")
+                .default_value("0.1"),
+        )
+        .arg(
+            Arg::with_name("samples")
+                .long("samples")
+                .value_name("uint")
+                .help("Number of samples")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("uint")
+                .help("Seed for the random number generator")
+                .default_value("0"),
+        )
+}
+
+fn screen_spike_ins_command() -> App<'static, 'static> {
+    SubCommand::with_name("screen-spike-ins")
+        .about("Prints spike-in counts from exact k-mer matches of reads, without alignment")
+        .arg(
+            Arg::with_name("spike-ins")
+                .long("spike-ins")
+                .value_name("file")
+                .help("Spike-in sequences (FASTA), e.g., ERCC92.fa")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("kmer-size")
+                .short("k")
+                .long("kmer-size")
+                .value_name("uint")
+                .help("Length of the k-mers matched (at most 32)")
+                .default_value("31"),
+        )
+        .arg(
+            Arg::with_name("reads")
+                .help("Input reads (FASTQ, one file per sample)")
+                .required(true)
+                .multiple(true)
+                .index(1),
+        )
+}
+
+fn annotation_diff_command() -> App<'static, 'static> {
+    SubCommand::with_name("annotation-diff")
+        .about("Prints the changes of feature lengths and IDs between two annotation releases")
+        .arg(
+            Arg::with_name("feature-type")
+                .short("t")
+                .long("type")
+                .value_name("str")
+                .help("Feature type to count")
+                .env("NOODLES_FPKM_TYPE")
+                .default_value("exon"),
+        )
+        .arg(
+            Arg::with_name("feature-id")
+                .short("i")
+                .long("id")
+                .value_name("str")
+                .help("Feature attribute to use as the feature identity")
+                .env("NOODLES_FPKM_ID")
+                .default_value("gene_id"),
+        )
+        .arg(
+            Arg::with_name("shift-threshold")
+                .long("shift-threshold")
+                .value_name("float")
+                .help(
+                    "Relative FPKM change due to a length change above which a feature is flagged",
+                )
+                .default_value("0.1"),
+        )
+        .arg(
+            Arg::with_name("old")
+                .help("Old annotations file (GTF/GFFv2)")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("new")
+                .help("New annotations file (GTF/GFFv2)")
+                .required(true)
+                .index(2),
+        )
+}
+
+fn serve_command() -> App<'static, 'static> {
+    SubCommand::with_name("serve")
+        .about("Parses annotations once and answers quantification requests over HTTP")
+        .arg(
+            Arg::with_name("annotations")
+                .short("a")
+                .long("annotations")
+                .value_name("file")
+                .help("Input annotations file (GTF/GFFv2)")
+                .env("NOODLES_FPKM_ANNOTATIONS")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("feature-type")
+                .short("t")
+                .long("type")
+                .value_name("str")
+                .help("Feature type to count")
+                .env("NOODLES_FPKM_TYPE")
+                .default_value("exon"),
+        )
+        .arg(
+            Arg::with_name("feature-id")
+                .short("i")
+                .long("id")
+                .value_name("str")
+                .help("Feature attribute to use as the feature identity")
+                .env("NOODLES_FPKM_ID")
+                .default_value("gene_id"),
+        )
+        .arg(
+            Arg::with_name("host")
+                .long("host")
+                .value_name("str")
+                .help("Address to listen on")
+                .default_value("127.0.0.1"),
+        )
+        .arg(
+            Arg::with_name("port")
+                .short("p")
+                .long("port")
+                .value_name("uint")
+                .help("Port to listen on")
+                .default_value("8080"),
+        )
+}
+
+fn export_index_command() -> App<'static, 'static> {
+    SubCommand::with_name("export-index")
+        .about(
+            "Writes the features of annotations, with their lengths and strands, as a binary index",
+        )
+        .arg(
+            Arg::with_name("annotations")
+                .short("a")
+                .long("annotations")
+                .value_name("file")
+                .help("Input annotations file (GTF/GFFv2)")
+                .env("NOODLES_FPKM_ANNOTATIONS")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("feature-type")
+                .short("t")
+                .long("type")
+                .value_name("str")
+                .help("Feature type to count")
+                .env("NOODLES_FPKM_TYPE")
+                .default_value("exon"),
+        )
+        .arg(
+            Arg::with_name("feature-id")
+                .short("i")
+                .long("id")
+                .value_name("str")
+                .help("Feature attribute to use as the feature identity")
+                .env("NOODLES_FPKM_ID")
+                .default_value("gene_id"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("Output feature index file")
+                .required(true)
+                .index(1),
+        )
+}
+
+fn merge_command() -> App<'static, 'static> {
+    SubCommand::with_name("merge")
+        .about("Appends samples to an existing expression matrix in place")
+        .arg(
+            Arg::with_name("append")
+                .long("append")
+                .value_name("file")
+                .help("Expression matrix to append to")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("unit")
+                .short("u")
+                .long("unit")
+                .visible_alias("assume-unit")
+                .value_name("str")
+                .help("Unit of inputs without a unit label, e.g., tpm (inferred from the values if not given, except for 10x directories)"),
+        )
+        .arg(
+            Arg::with_name("inputs")
+                .help("Input expression matrices, single-sample outputs, or 10x directories")
+                .required(true)
+                .multiple(true)
+                .index(1),
+        )
+}
+
+fn matrix_command() -> App<'static, 'static> {
+    SubCommand::with_name("matrix")
+        .about("Subsets, reorders, and transposes an expression matrix")
+        .arg(
+            Arg::with_name("rows").long("rows").value_name("file").help(
+                "Keeps only the features listed in the given file (one per line), in its order",
+            ),
+        )
+        .arg(
+            Arg::with_name("columns")
+                .long("columns")
+                .value_name("file")
+                .help(
+                    "Keeps only the samples listed in the given file (one per line), in its order",
+                ),
+        )
+        .arg(
+            Arg::with_name("transpose")
+                .long("transpose")
+                .help("Writes samples as rows and features as columns"),
+        )
+        .arg(
+            Arg::with_name("unit")
+                .short("u")
+                .long("unit")
+                .value_name("str")
+                .help("Unit of a 10x directory input, which has no header, e.g., tpm"),
+        )
+        .arg(
+            Arg::with_name("input")
+                .help("Input expression matrix or 10x directory")
+                .required(true)
+                .index(1),
+        )
+}
+
+fn query_command() -> App<'static, 'static> {
+    SubCommand::with_name("query")
+        .about("Prints the rows of features from an indexed expression matrix")
+        .arg(
+            Arg::with_name("input")
+                .help("Input BGZF-compressed expression matrix (--indexed-output)")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("ids")
+                .help("Feature IDs")
+                .required(true)
+                .multiple(true)
+                .index(2),
+        )
+}
@@ -0,0 +1,120 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+
+use clap::ArgMatches;
+use log::info;
+use noodles_fpkm::checksum::{read_checksum_manifest, sha256_hex};
+
+use crate::{exit_with_error, read_input, write_output, STDIN_SRC};
+
+// The arguments whose values are input files, which are checksummed.
+const INPUT_ARGS: [&str; 15] = [
+    "counts",
+    "annotations",
+    "id-map",
+    "fragment-length-distribution",
+    "genome",
+    "reference-distribution",
+    "sample-sheet",
+    "signatures",
+    "ercc",
+    "size-factors",
+    "abundances",
+    "transcript-abundances",
+    "antisense",
+    "barcodes",
+    "pseudobulk",
+];
+
+// Returns the input files of the run. Standard input, URLs, and directories,
+// e.g., 10x Genomics matrix directories, are not files and are skipped.
+fn input_files<'a>(matches: &'a ArgMatches<'a>) -> Vec<&'a Path> {
+    INPUT_ARGS
+        .iter()
+        .flat_map(|arg| matches.values_of_os(arg).into_iter().flatten())
+        .map(Path::new)
+        .filter(|src| {
+            *src != Path::new(STDIN_SRC) && !src.to_string_lossy().contains("://") && src.is_file()
+        })
+        .collect()
+}
+
+fn calculate_checksums<'a>(srcs: &[&'a Path]) -> io::Result<Vec<(&'a Path, String)>> {
+    srcs.iter()
+        .map(|&src| {
+            let reader = File::open(src)?;
+            sha256_hex(reader).map(|digest| (src, digest))
+        })
+        .collect()
+}
+
+// Returns the messages of inputs that are not in the manifest or whose digests
+// differ. Paths are compared as given and, if they exist, as canonical paths.
+fn verify_checksums(manifest: &[(String, String)], checksums: &[(&Path, String)]) -> Vec<String> {
+    let canonicalize = |src: &Path| fs::canonicalize(src).unwrap_or_else(|_| src.to_path_buf());
+
+    let mut messages = Vec::new();
+
+    for (src, digest) in checksums {
+        let path = canonicalize(src);
+
+        let expected = manifest
+            .iter()
+            .map(|(manifest_src, expected)| (Path::new(manifest_src), expected))
+            .find(|(manifest_src, _)| manifest_src == src || canonicalize(manifest_src) == path)
+            .map(|(_, expected)| expected);
+
+        match expected {
+            Some(expected) if expected == digest => {}
+            Some(expected) => messages.push(format!(
+                "{}: checksum mismatch (expected {}, got {})",
+                src.display(),
+                expected,
+                digest
+            )),
+            None => messages.push(format!("{}: not in the checksum manifest", src.display())),
+        }
+    }
+
+    messages
+}
+
+fn write_checksums<W>(mut writer: W, checksums: &[(&Path, String)]) -> io::Result<()>
+where
+    W: Write,
+{
+    for (src, digest) in checksums {
+        writeln!(writer, "{}  {}", digest, src.display())?;
+    }
+
+    writer.flush()
+}
+
+// Writes (--checksums) and verifies (--verify-checksums) the checksums of the
+// input files.
+pub(crate) fn check_input_checksums(matches: &ArgMatches<'_>) {
+    let checksums_dst = matches.value_of("checksums");
+    let verify_checksums_src = matches.value_of("verify-checksums");
+
+    if checksums_dst.is_some() || verify_checksums_src.is_some() {
+        let checksums = calculate_checksums(&input_files(matches)).unwrap();
+
+        if let Some(src) = verify_checksums_src {
+            let manifest = read_input(src, read_checksum_manifest);
+            let messages = verify_checksums(&manifest, &checksums);
+
+            if !messages.is_empty() {
+                exit_with_error(&messages.join("\n"), clap::ErrorKind::InvalidValue);
+            }
+
+            info!("verified the checksums of {} input files", checksums.len());
+        }
+
+        if let Some(dst) = checksums_dst {
+            write_output(dst, |writer| write_checksums(writer, &checksums));
+        }
+    }
+}
@@ -0,0 +1,2078 @@
+mod matrix;
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env,
+    io::{self, Write},
+    path::Path,
+    rc::Rc,
+    str::FromStr,
+    time::Instant,
+};
+
+use clap::{value_t, ArgMatches};
+use log::{info, warn};
+#[cfg(feature = "mmap")]
+use noodles_fpkm::gtf::read_features_mmap_with_options;
+#[cfg(feature = "parallel")]
+use noodles_fpkm::gtf::read_features_parallel_with_options;
+#[cfg(feature = "remote")]
+use noodles_fpkm::remote;
+use noodles_fpkm::{
+    abundances::{read_abundances, read_transcript_abundances},
+    calculate_antisense_fpkms, calculate_antisense_tpms_with_summation, calculate_fpkms,
+    calculate_tpms_from_fpkms_with_summation, calculate_tpms_with_summation,
+    chunked::ChunkedMatrixWriter,
+    counts::{read_counts_with_options, sum_counts, Counts, ReadCountsOptions, Strandedness},
+    downsampling::downsample_counts,
+    expression_set::{merge_expression_sets, ExpressionSet, Method},
+    feature_id::FeatureId,
+    features::{
+        detect_feature_source_of, merge_intervals_in_place, read_features_from_reader_with_options,
+        read_merged_features_from_reader_with_options, read_merged_features_with_options,
+        FeatureSource, Features, ReadFeaturesOptions,
+    },
+    fragment_lengths::{
+        calculate_effective_lengths, read_fragment_length_distribution, FragmentLengthDistribution,
+    },
+    gene_metadata::{read_gene_metadata, GeneMetadata, MissingMetadata},
+    group_by::GroupBy,
+    gtf::{
+        self, read_aliases, read_annotations, read_last_exons, Aliases, Annotations, Biotypes, Gtf,
+        THREE_PRIME_UTR_TYPE,
+    },
+    ids::{
+        alias_key, find_unmatched_feature_ids, map_feature_ids, match_feature_ids, read_id_map,
+        IdMap, Normalization,
+    },
+    lengths::{
+        calculate_exon_bin_lengths, calculate_flanked_span_lengths,
+        calculate_gene_effective_lengths, calculate_lengths, calculate_unit_lengths, cap_lengths,
+        length_quantile, summarize_lengths, Lengths,
+    },
+    matrix::ExpressionMatrix,
+    method_comparison::{calculate_tmm_factors, compare_methods},
+    mtx::{read_10x, read_names},
+    output_format::OutputFormat,
+    pseudobulk::{aggregate, read_group_assignments},
+    qc::{calculate_count_fraction, check_tpm_sum, is_mitochondrial, is_rrna, TPM_SUM},
+    quantile_mapping::BLOM_OFFSET,
+    regression::compare_matrices,
+    resources::ResourceUsage,
+    sample_sheet::SampleSheet,
+    size_factors::{
+        calculate_scaled_counts, calculate_size_factors, read_size_factors, SizeFactors,
+    },
+    summation::Summation,
+    transform::Transform,
+    unit::Unit,
+    Error, Expressions,
+};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+
+use self::matrix::{
+    filter_matrix, merge_samples, normalize_matrix, transform_matrix, write_matrix_bundle,
+    write_matrix_reports,
+};
+use crate::{
+    build_count_matrix, check_invariants,
+    cli::{
+        checksums::check_input_checksums,
+        reports::{
+            build_rarefaction_depths, sorted_count_values, write_feature_explanation, write_ids,
+            write_length_report, write_qc_metrics, write_rarefaction_curves, write_resource_report,
+            write_sample_explanation, write_size_factors, write_stratified_qc_metrics,
+            FeatureExplanation, MethodComparisonWriter, QcMetrics, ALL_BIOTYPES,
+        },
+        tenx::{filter_barcodes, quantify_10x},
+        watch::run_watch,
+    },
+    exit_with_calculation_error, exit_with_clap_error, exit_with_error, exit_with_io_error,
+    formatted_writer, open_counts, open_sample_sheet, parse_output_format, read_input,
+    read_matrix_or_expressions, read_samples, sample_name, warn_or_exit, write_expressions,
+    write_expressions_in_order, write_indexed_matrix, write_matrix, write_matrix_with_metadata,
+    write_output, write_stranded_expressions, TempDir, STDIN_SRC,
+};
+
+const MAX_UNMATCHED_FEATURE_ID_FRACTION: f64 = 0.5;
+
+const MAX_MISSING_ID_FRACTION: f64 = 0.01;
+
+const UNMATCHED_FEATURE_ID_EXAMPLE_COUNT: usize = 3;
+
+const TRANSCRIPT_ID_KEY: &str = "transcript_id";
+
+// Reads the features of annotations with the feature source that detects
+// their format. GTF/GFFv2 annotations are read with the reader chosen by the
+// arguments.
+//
+// Unless `merge` is set, the intervals of each feature are kept as they are
+// in the annotations, e.g., for exon bins, which are built from the raw,
+// overlapping exons. The memory-mapped, parallel, and remote readers never
+// merge intervals. Annotations from `stdin` or a URL are read as GTF/GFFv2.
+fn open_features(
+    src: &Path,
+    feature_type: &str,
+    feature_id: &str,
+    use_mmap: bool,
+    threads: usize,
+    merge: bool,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features> {
+    if src == Path::new(STDIN_SRC) {
+        let stdin = io::stdin();
+
+        return if merge {
+            read_merged_features_from_reader_with_options(
+                stdin.lock(),
+                feature_type,
+                feature_id,
+                options,
+            )
+        } else {
+            read_features_from_reader_with_options(stdin.lock(), feature_type, feature_id, options)
+        };
+    }
+
+    #[cfg(feature = "remote")]
+    {
+        if let Some(url) = src.to_str().filter(|src| remote::is_url(src)) {
+            return remote::read_features_with_options(url, feature_type, feature_id, options);
+        }
+    }
+
+    let source = detect_feature_source_of(src)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown annotations format"))?;
+
+    info!("reading {} as {} annotations", src.display(), source.name());
+
+    if source.name() == Gtf.name() {
+        if use_mmap {
+            return read_features_mmap_with_options(src, feature_type, feature_id, options);
+        } else if threads > 1 {
+            return read_features_parallel_with_options(src, feature_type, feature_id, options);
+        } else if merge {
+            return read_merged_features_with_options(src, feature_type, feature_id, options);
+        }
+    }
+
+    let mut features = source.read_features_from_path(src, feature_type, feature_id, options)?;
+
+    if merge {
+        for intervals in features.values_mut() {
+            merge_intervals_in_place(intervals);
+        }
+    }
+
+    Ok(features)
+}
+
+#[cfg(not(feature = "mmap"))]
+fn read_features_mmap_with_options(
+    _: &Path,
+    _: &str,
+    _: &str,
+    _: &ReadFeaturesOptions,
+) -> io::Result<Features> {
+    Err(unsupported("mmap"))
+}
+
+#[cfg(not(feature = "parallel"))]
+fn read_features_parallel_with_options(
+    _: &Path,
+    _: &str,
+    _: &str,
+    _: &ReadFeaturesOptions,
+) -> io::Result<Features> {
+    Err(unsupported("parallel"))
+}
+
+#[cfg(not(all(feature = "mmap", feature = "parallel")))]
+#[allow(clippy::io_other_error)]
+fn unsupported(feature: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "{} was built without the '{}' feature",
+            clap::crate_name!(),
+            feature
+        ),
+    )
+}
+
+// Replaces the lengths of features with the lengths of their 3' UTRs or last
+// exons. Features without these intervals keep their full lengths.
+fn restrict_lengths(
+    lengths: &mut Lengths,
+    src: &Path,
+    length_source: &str,
+    feature_id: &str,
+) -> io::Result<()> {
+    let features = match length_source {
+        "three-prime-utr" => gtf::read_features(src, THREE_PRIME_UTR_TYPE, feature_id)?,
+        "last-exon" => read_last_exons(src, feature_id)?,
+        _ => unreachable!(),
+    };
+
+    let restricted_lengths: Vec<_> = calculate_lengths(&features)
+        .into_iter()
+        .filter(|(name, _)| lengths.contains_key(name))
+        .collect();
+
+    info!(
+        "restricted lengths of {} of {} features to {}",
+        restricted_lengths.len(),
+        lengths.len(),
+        length_source
+    );
+
+    lengths.extend(restricted_lengths);
+
+    Ok(())
+}
+
+// Reads the annotations and calculates the feature lengths of the default
+// (quantify) mode.
+pub(crate) struct AnnotationLoader<'a> {
+    src: Option<&'a Path>,
+    feature_type: &'a str,
+    feature_id: &'a str,
+    length_source: &'a str,
+    upstream_flank: u64,
+    downstream_flank: u64,
+    use_mmap: bool,
+    threads: usize,
+    self_check: bool,
+    read_features_options: ReadFeaturesOptions,
+    length_cap: Option<LengthCap>,
+    fragment_length_distribution: Option<FragmentLengthDistribution>,
+    resource_usage: &'a RefCell<ResourceUsage>,
+    annotations: RefCell<Option<Rc<Annotations>>>,
+}
+
+impl<'a> AnnotationLoader<'a> {
+    pub(crate) fn from_matches(
+        matches: &'a ArgMatches<'_>,
+        resource_usage: &'a RefCell<ResourceUsage>,
+    ) -> Self {
+        let src = matches.value_of_os("annotations").map(Path::new);
+        let feature_id = matches.value_of("feature-id").unwrap();
+
+        if let Err(e) = feature_id.parse::<GroupBy>() {
+            exit_with_error(&e.to_string(), clap::ErrorKind::InvalidValue);
+        }
+
+        let length_source = matches.value_of("length-source").unwrap();
+        let strict = matches.is_present("strict");
+
+        // Skipped records are always logged, but with --strict, they can only
+        // be a small fraction of the records.
+        let read_features_options = ReadFeaturesOptions {
+            max_missing_id_fraction: match (matches.is_present("skip-missing-ids"), strict) {
+                (true, true) => MAX_MISSING_ID_FRACTION,
+                (true, false) => 1.0,
+                (false, _) => 0.0,
+            },
+        };
+
+        let threads = if matches.is_present("deterministic") {
+            1
+        } else {
+            value_t!(matches, "threads", usize).unwrap_or_else(|e| exit_with_clap_error(e))
+        };
+
+        if src == Some(Path::new(STDIN_SRC)) {
+            check_stdin_annotations(matches, length_source);
+        }
+
+        Self {
+            src,
+            feature_type: matches.value_of("feature-type").unwrap(),
+            feature_id,
+            length_source,
+            upstream_flank: flank_length(matches, "upstream-flank", length_source),
+            downstream_flank: flank_length(matches, "downstream-flank", length_source),
+            use_mmap: matches.is_present("mmap"),
+            threads,
+            self_check: matches.is_present("self-check"),
+            read_features_options,
+            length_cap: parse_length_cap(matches),
+            fragment_length_distribution: parse_fragment_length_distribution(matches),
+            resource_usage,
+            annotations: RefCell::default(),
+        }
+    }
+
+    pub(crate) fn features(&self) -> Features {
+        let src = self.src.unwrap_or_else(|| {
+            exit_with_error(
+                "--annotations is required for fpkm, tpm, and cpm",
+                clap::ErrorKind::MissingRequiredArgument,
+            )
+        });
+
+        let start = Instant::now();
+
+        // Exon bins are built from the raw exons, which must not be merged
+        // first.
+        let merge = self.length_source != "exon-bin";
+
+        let features = open_features(
+            src,
+            self.feature_type,
+            self.feature_id,
+            self.use_mmap,
+            self.threads,
+            merge,
+            &self.read_features_options,
+        )
+        .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy()));
+
+        if self.self_check {
+            check_invariants("annotations", &features);
+        }
+
+        self.resource_usage
+            .borrow_mut()
+            .add("annotations", start.elapsed(), features.len() as u64);
+
+        features
+    }
+
+    pub(crate) fn lengths(&self, features: &Features) -> Lengths {
+        let mut lengths = match self.length_source {
+            "feature" => calculate_lengths(features),
+            "gene-body" => {
+                calculate_flanked_span_lengths(features, self.upstream_flank, self.downstream_flank)
+            }
+            "exon-bin" => calculate_exon_bin_lengths(features),
+            _ => {
+                let mut lengths = calculate_lengths(features);
+                let src = self.src.unwrap();
+                restrict_lengths(&mut lengths, src, self.length_source, self.feature_id)
+                    .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy()));
+                lengths
+            }
+        };
+
+        if let Some(cap) = self.length_cap {
+            cap_feature_lengths(&mut lengths, cap);
+        }
+
+        match &self.fragment_length_distribution {
+            Some(distribution) => calculate_effective_lengths(&lengths, distribution),
+            None => lengths,
+        }
+    }
+
+    // Reports read the reference sequence names, biotypes, and transcript ID
+    // aliases of features on first use. These are read together, so the
+    // annotations are reread at most once for all reports.
+    pub(crate) fn annotations(&self, arg: &str) -> Rc<Annotations> {
+        if let Some(annotations) = &*self.annotations.borrow() {
+            return Rc::clone(annotations);
+        }
+
+        let src = require_annotations(self.src, arg);
+
+        let annotations = read_annotations(
+            src,
+            self.feature_type,
+            self.feature_id,
+            Some(TRANSCRIPT_ID_KEY),
+        )
+        .map(Rc::new)
+        .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy()));
+
+        *self.annotations.borrow_mut() = Some(Rc::clone(&annotations));
+
+        annotations
+    }
+
+    // Aliases are only used to suggest a feature ID for a missing feature, so
+    // they are not read unless a feature is missing.
+    pub(crate) fn aliases(&self) -> Option<Aliases> {
+        let src = self.src.filter(|&src| src != Path::new(STDIN_SRC))?;
+        let key = alias_key(self.feature_id)?;
+        read_aliases(src, self.feature_type, self.feature_id, key).ok()
+    }
+}
+
+// The arguments of the default (quantify) mode that are used after the
+// annotations are read. These are parsed and checked before any input is
+// read.
+struct QuantifyOptions<'a> {
+    counts_srcs: Vec<&'a Path>,
+    unit: &'a str,
+    read_counts_options: ReadCountsOptions,
+    strict: bool,
+    self_check: bool,
+    tolerance: f64,
+    tpm_sum_tolerance: f64,
+    id_normalization: Normalization,
+    summation: Summation,
+    seed: u64,
+    top_count: Option<usize>,
+    max_mitochondrial_fraction: Option<f64>,
+    max_rrna_fraction: Option<f64>,
+    stratify_by: Option<&'a str>,
+    detection_threshold: f64,
+    min_detected_fraction: Option<f64>,
+    min_value: f64,
+    rank_offset: f64,
+    inverse_normal: bool,
+    output_format: OutputFormat,
+    gene_universe: Option<Vec<String>>,
+    gene_metadata: Option<GeneMetadata>,
+    missing_metadata: MissingMetadata,
+    transforms: Vec<Transform>,
+    chunk_size: Option<usize>,
+    downsample_depth: Option<u64>,
+}
+
+impl<'a> QuantifyOptions<'a> {
+    fn from_matches(matches: &'a ArgMatches<'_>) -> Self {
+        let counts_srcs = matches
+            .values_of_os("counts")
+            .map(|values| values.map(Path::new).collect())
+            .unwrap_or_default();
+
+        let read_counts_options = ReadCountsOptions {
+            meta_prefix: matches
+                .value_of("meta-prefix")
+                .filter(|prefix| !prefix.is_empty())
+                .map(String::from),
+            lenient_numbers: matches.is_present("lenient-numbers"),
+            strandedness: match matches.value_of("strandedness") {
+                Some("forward") => Strandedness::Forward,
+                Some("reverse") => Strandedness::Reverse,
+                _ => Strandedness::Unstranded,
+            },
+        };
+
+        let tolerance =
+            value_t!(matches, "tolerance", f64).unwrap_or_else(|e| exit_with_clap_error(e));
+        let tpm_sum_tolerance =
+            value_t!(matches, "tpm-sum-tolerance", f64).unwrap_or_else(|e| exit_with_clap_error(e));
+
+        let id_normalization = match matches.value_of("id-normalize") {
+            Some("case") => Normalization::Case,
+            Some("alnum") => Normalization::Alphanumeric,
+            _ => Normalization::None,
+        };
+
+        let summation = if matches.is_present("compensated-summation") {
+            Summation::Compensated
+        } else {
+            Summation::Naive
+        };
+
+        let seed = value_t!(matches, "seed", u64).unwrap_or_else(|e| exit_with_clap_error(e));
+        let top_count = optional_value_t(matches, "top");
+        let max_mitochondrial_fraction = optional_value_t(matches, "max-mitochondrial-fraction");
+        let max_rrna_fraction = optional_value_t(matches, "max-rrna-fraction");
+
+        let stratify_by = matches.value_of("stratify-by");
+
+        if stratify_by.is_some()
+            && !matches.is_present("qc-report")
+            && !matches.is_present("group-summary")
+        {
+            exit_with_error(
+                "--stratify-by requires --qc-report or --group-summary",
+                clap::ErrorKind::MissingRequiredArgument,
+            );
+        }
+
+        let detection_threshold = value_t!(matches, "detection-threshold", f64)
+            .unwrap_or_else(|e| exit_with_clap_error(e));
+
+        let min_detected_fraction = optional_value_t(matches, "min-detected-fraction");
+
+        if let Some(fraction) = min_detected_fraction {
+            if !(0.0..=1.0).contains(&fraction) {
+                exit_with_error(
+                    "--min-detected-fraction must be in [0, 1]",
+                    clap::ErrorKind::InvalidValue,
+                );
+            }
+        }
+
+        let min_value =
+            value_t!(matches, "min-value", f64).unwrap_or_else(|e| exit_with_clap_error(e));
+
+        let rank_offset = optional_value_t(matches, "rank-offset").unwrap_or(BLOM_OFFSET);
+
+        if !(0.0..0.5).contains(&rank_offset) {
+            exit_with_error(
+                "--rank-offset must be in [0, 0.5)",
+                clap::ErrorKind::InvalidValue,
+            );
+        }
+
+        let transform_features = matches
+            .value_of("transform-features")
+            .map(|src| read_names(src).unwrap_or_else(|e| exit_with_io_error(e, src)));
+
+        let gene_metadata = matches
+            .value_of("gene-metadata")
+            .map(|src| read_input(src, read_gene_metadata));
+
+        let missing_metadata = match matches.value_of("missing-metadata") {
+            Some("drop") => MissingMetadata::Drop,
+            Some("error") => MissingMetadata::Error,
+            _ => MissingMetadata::Na,
+        };
+
+        let transforms = matches
+            .values_of("transform")
+            .map(|values| {
+                values
+                    .map(|value| parse_transform(value, transform_features.as_ref()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let chunk_size = optional_value_t(matches, "chunk-size");
+
+        if chunk_size == Some(0) {
+            exit_with_error("--chunk-size must be > 0", clap::ErrorKind::InvalidValue);
+        }
+
+        Self {
+            counts_srcs,
+            unit: matches.value_of("unit").unwrap(),
+            read_counts_options,
+            strict: matches.is_present("strict"),
+            self_check: matches.is_present("self-check"),
+            tolerance,
+            tpm_sum_tolerance,
+            id_normalization,
+            summation,
+            seed,
+            top_count,
+            max_mitochondrial_fraction,
+            max_rrna_fraction,
+            stratify_by,
+            detection_threshold,
+            min_detected_fraction,
+            min_value,
+            rank_offset,
+            inverse_normal: matches.is_present("inverse-normal"),
+            output_format: parse_output_format(matches),
+            gene_universe: read_gene_universe(matches),
+            gene_metadata,
+            missing_metadata,
+            transforms,
+            chunk_size,
+            downsample_depth: optional_value_t(matches, "downsample"),
+        }
+    }
+}
+
+// The arguments that need the expressions of the samples merged into a
+// matrix. Without any of these, a single sample is written as it is.
+const MATRIX_ARGS: [&str; 19] = [
+    "signatures",
+    "reference-genes",
+    "genome",
+    "reference-distribution",
+    "inverse-normal",
+    "transform",
+    "fold-changes",
+    "group-summary",
+    "batch-center",
+    "detection",
+    "gene-lists",
+    "top",
+    "ercc",
+    "reference-sequence-summary",
+    "biotype-summary",
+    "sex-report",
+    "indexed-output",
+    "bundle",
+    "min-detected-fraction",
+];
+
+// How the expressions of the samples are written to stdout.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum OutputMode {
+    // The samples are merged and written in chunks of the given number of
+    // samples.
+    Chunked(usize),
+    // A single sample is written as a list of features.
+    SingleSample,
+    // The samples are merged into a matrix, which is normalized, transformed,
+    // and reported on before it is written.
+    Matrix,
+}
+
+fn output_mode(
+    matches: &ArgMatches<'_>,
+    sample_count: usize,
+    chunk_size: Option<usize>,
+) -> OutputMode {
+    match chunk_size {
+        Some(chunk_size) if sample_count > 1 => OutputMode::Chunked(chunk_size),
+        _ if sample_count == 1 && !MATRIX_ARGS.iter().any(|arg| matches.is_present(arg)) => {
+            OutputMode::SingleSample
+        }
+        _ => OutputMode::Matrix,
+    }
+}
+
+pub(crate) fn run_quantify(matches: &ArgMatches<'_>) {
+    let resource_usage = RefCell::new(ResourceUsage::new());
+
+    check_input_checksums(matches);
+
+    let options = QuantifyOptions::from_matches(matches);
+    let loader = AnnotationLoader::from_matches(matches, &resource_usage);
+    let unit = options.unit;
+
+    let mut rng = Pcg64::seed_from_u64(options.seed);
+
+    #[cfg(feature = "parallel")]
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(loader.threads)
+        .build_global()
+        .unwrap();
+
+    let antisense_src = matches.value_of("antisense");
+
+    let matrix_src = match options.counts_srcs[..] {
+        [src] if src.is_dir() => Some(src),
+        _ => None,
+    };
+
+    let pseudobulk_src = matches.value_of("pseudobulk");
+
+    if pseudobulk_src.is_some() && matrix_src.is_none() {
+        exit_with_error(
+            "--pseudobulk requires 10x Genomics input",
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    if let Some(counts_src) = matrix_src.filter(|_| pseudobulk_src.is_none()) {
+        quantify_10x(
+            matches,
+            counts_src,
+            &loader,
+            options.downsample_depth,
+            &mut rng,
+            &resource_usage,
+        );
+        return;
+    }
+
+    if matches.is_present("log1p") {
+        exit_with_error(
+            "--log1p requires 10x Genomics input",
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    let size_factors_src = matches.value_of("size-factors");
+
+    if size_factors_src.is_some() && unit != "scaled" {
+        exit_with_error(
+            "--size-factors requires --unit scaled",
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    // Scaled counts are normalized by size factors rather than lengths, so
+    // annotations are optional and only used to check feature IDs.
+    let features = match unit {
+        "scaled" if loader.src.is_none() => Features::default(),
+        "fpkm" | "tpm" | "cpm" | "scaled" => loader.features(),
+        _ => exit_with_error(
+            &format!("--unit {} requires 10x Genomics input", unit),
+            clap::ErrorKind::InvalidValue,
+        ),
+    };
+
+    if loader.src.is_some() && features.is_empty() {
+        warn_or_exit(
+            &format!(
+                "annotations have no '{}' records; check --type",
+                loader.feature_type
+            ),
+            options.strict,
+        );
+    }
+
+    let lengths = loader.lengths(&features);
+    let unit_lengths = calculate_unit_lengths(&features);
+    let explain_gene = matches.value_of("explain-gene");
+
+    if let Some(id) = explain_gene {
+        explain_feature(io::stderr(), &loader, &features, &lengths, id).unwrap();
+    }
+
+    if let Some(dst) = matches.value_of("length-report") {
+        let annotations = loader.annotations("--length-report");
+        write_output(dst, |writer| {
+            write_lengths_by_biotype(writer, &lengths, &annotations.biotypes)
+        });
+    }
+
+    if let Some(src) = matches.value_of("watch") {
+        run_watch(
+            matches,
+            src,
+            &lengths,
+            &unit_lengths,
+            options.summation,
+            &options.read_counts_options,
+        );
+    }
+
+    let start = Instant::now();
+
+    let mut samples = match (matrix_src, pseudobulk_src) {
+        (Some(counts_src), Some(pseudobulk_src)) => {
+            read_pseudobulk_samples(matches, counts_src, pseudobulk_src)
+        }
+        _ => read_count_samples(&options.counts_srcs, &options.read_counts_options),
+    };
+
+    let record_count = samples.iter().map(|(_, counts)| counts.len() as u64).sum();
+    resource_usage
+        .borrow_mut()
+        .add("counts", start.elapsed(), record_count);
+
+    let id_map = matches
+        .value_of("id-map")
+        .map(|src| read_input(src, read_id_map));
+
+    if let Some(id_map) = &id_map {
+        let unmapped_ids = map_sample_ids(&mut samples, id_map);
+
+        if let Some(dst) = matches.value_of("unmapped-ids") {
+            write_output(dst, |writer| write_ids(writer, &unmapped_ids));
+        }
+    }
+
+    if loader.src.is_some() {
+        if options.id_normalization != Normalization::None {
+            for (_, counts) in &mut samples {
+                *counts = match_feature_ids(counts, &lengths, options.id_normalization);
+            }
+        }
+
+        check_unmatched_feature_ids(&samples, &lengths, options.strict);
+    }
+
+    if antisense_src.is_some() && samples.len() > 1 {
+        exit_with_error(
+            "--antisense requires a single sample",
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    if let Some(dst) = matches.value_of("rarefaction") {
+        write_output(dst, |writer| {
+            write_sample_rarefaction_curves(writer, &samples, options.downsample_depth, &mut rng)
+        });
+    }
+
+    let samples: Vec<(String, Counts)> = samples
+        .into_iter()
+        .map(|(name, counts)| {
+            let counts = match options.downsample_depth {
+                Some(depth) => downsample_counts(&counts, depth, &mut rng),
+                None => counts,
+            };
+
+            (name, counts)
+        })
+        .collect();
+
+    if options.self_check {
+        for (name, counts) in &samples {
+            check_invariants(&format!("counts of {}", name), counts);
+        }
+    }
+
+    if let Some(dst) = matches.value_of("method-comparison") {
+        write_output(dst, |writer| {
+            write_method_comparisons(writer, &samples, &lengths, &loader)
+        });
+    }
+
+    let raw_counts_dst = matches.value_of("raw-counts");
+    let emit_size_factors_dst = matches.value_of("emit-size-factors");
+    let bundle_dst = matches.value_of("bundle");
+    let calculates_size_factors = unit == "scaled" && size_factors_src.is_none();
+
+    let count_matrix = if raw_counts_dst.is_some()
+        || bundle_dst.is_some()
+        || emit_size_factors_dst.is_some()
+        || calculates_size_factors
+    {
+        Some(build_count_matrix(&samples))
+    } else {
+        None
+    };
+
+    if let Some(dst) = raw_counts_dst {
+        let matrix = count_matrix.as_ref().unwrap();
+        write_output(dst, |writer| {
+            write_matrix(
+                formatted_writer(writer, options.output_format),
+                "count",
+                matrix,
+            )
+        });
+    }
+
+    let calculated_size_factors = if emit_size_factors_dst.is_some() || calculates_size_factors {
+        calculate_sample_size_factors(count_matrix.as_ref().unwrap(), emit_size_factors_dst)
+    } else {
+        SizeFactors::new()
+    };
+
+    let size_factors = match size_factors_src {
+        Some(src) => read_input(src, read_size_factors),
+        None => calculated_size_factors,
+    };
+
+    if let Some(dst) = matches.value_of("qc-report") {
+        write_qc_report(matches, dst, &options, &loader, &samples);
+    }
+
+    let calculator = ExpressionCalculator {
+        unit,
+        lengths: &lengths,
+        unit_lengths: &unit_lengths,
+        size_factors,
+        summation: options.summation,
+        loader: &loader,
+    };
+
+    let antisense_expressions = antisense_src.map(|src| {
+        let antisense_counts = open_counts(Path::new(src))
+            .and_then(|reader| read_counts_with_options(reader, &options.read_counts_options))
+            .unwrap_or_else(|e| exit_with_io_error(e, src));
+        let antisense_counts = match &id_map {
+            Some(id_map) => map_feature_ids(&antisense_counts, id_map).0,
+            None => antisense_counts,
+        };
+        let antisense_counts =
+            match_feature_ids(&antisense_counts, &lengths, options.id_normalization);
+        let (sense_name, sense_counts) = &samples[0];
+
+        calculator.calculate_antisense(sense_name, sense_counts, &antisense_counts)
+    });
+
+    let method = match unit {
+        "fpkm" => Method::Fpkm,
+        "tpm" => Method::Tpm,
+        "cpm" => Method::Cpm,
+        "scaled" => Method::Scaled,
+        _ => unreachable!(),
+    };
+
+    let gene_effective_lengths = read_gene_effective_lengths(matches, unit, samples.len(), &loader);
+
+    let sample_indices: HashMap<String, usize> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| (name.clone(), i))
+        .collect();
+
+    // Features without transcript abundances keep their lengths.
+    let sample_lengths = |name: &str| -> Option<Lengths> {
+        let gene_lengths = match gene_effective_lengths.len() {
+            0 => return None,
+            1 => &gene_effective_lengths[0],
+            _ => &gene_effective_lengths[sample_indices[name]],
+        };
+
+        let mut lengths = lengths.clone();
+        lengths.extend(gene_lengths.iter().map(|(id, &len)| (id.clone(), len)));
+        Some(lengths)
+    };
+
+    let calculate_expression_set = |name: String, counts: Counts| {
+        let weighted_lengths = sample_lengths(&name);
+        let lengths = weighted_lengths.as_ref().unwrap_or(&lengths);
+        let values = calculator.calculate(&name, &counts, lengths);
+
+        if unit == "tpm" || unit == "cpm" {
+            check_tpms(&name, &values, options.tpm_sum_tolerance, options.strict);
+        }
+
+        if let Some(id) = explain_gene {
+            let lengths = if unit == "cpm" {
+                &unit_lengths
+            } else {
+                lengths
+            };
+            write_sample_explanation(io::stderr(), id, &name, unit, &counts, lengths, &values)
+                .unwrap();
+        }
+
+        ExpressionSet {
+            method,
+            library_size: Some(sum_counts(&counts)),
+            sample_name: name,
+            values,
+        }
+    };
+
+    let abundances_srcs: Vec<&str> = matches
+        .values_of("abundances")
+        .map(|values| values.collect())
+        .unwrap_or_default();
+
+    let sample_count = samples.len() + abundances_srcs.len();
+
+    let sets = samples
+        .into_iter()
+        .map(|(name, counts)| calculate_expression_set(name, counts))
+        .chain(
+            abundances_srcs
+                .iter()
+                .map(|src| read_abundance_set(src, method, &options, &calculator)),
+        );
+
+    let stdout = io::stdout();
+    let mut handle = formatted_writer(stdout.lock(), options.output_format);
+
+    match output_mode(matches, sample_count, options.chunk_size) {
+        OutputMode::Chunked(chunk_size) => {
+            let (dir, writer) = write_chunks(sets, sample_count, chunk_size, &lengths);
+
+            timed_write(&resource_usage, record_count, || {
+                writer.finish(handle, Unit::from(method).as_str())
+            })
+            .unwrap();
+
+            drop(dir);
+        }
+        OutputMode::SingleSample => {
+            let expressions = collect_expressions(sets, options.self_check);
+            let set = &expressions[0];
+
+            if let Some(src) = matches.value_of("compare-against") {
+                let matrix = ExpressionMatrix::from_samples(&[(
+                    set.sample_name.clone(),
+                    set.values.clone(),
+                )]);
+                compare_against(src, Unit::from(method), &matrix, options.tolerance);
+            }
+
+            let ids = select_sample_ids(&set.values, &options);
+
+            timed_write(&resource_usage, set.values.len() as u64, || {
+                write_single_sample(
+                    handle,
+                    &set.values,
+                    antisense_expressions.as_ref(),
+                    ids.as_deref(),
+                    options.gene_metadata.as_ref(),
+                )
+            })
+            .unwrap();
+        }
+        OutputMode::Matrix => {
+            let expressions = collect_expressions(sets, options.self_check);
+
+            let matrix = merge_samples(&expressions, &lengths);
+            let matrix =
+                normalize_matrix(&mut handle, matches, &loader, &features, &lengths, matrix);
+            let (matrix, matrix_unit) = transform_matrix(matrix, Unit::from(method), &options);
+
+            write_matrix_reports(matches, &options, &loader, &matrix, matrix_unit);
+
+            let matrix = filter_matrix(matrix, &options);
+
+            if let Some(dst) = matches.value_of("indexed-output") {
+                write_indexed_matrix(dst, matrix_unit.as_str(), &matrix)
+                    .unwrap_or_else(|e| exit_with_io_error(e, dst));
+            }
+
+            if let Some(dst) = bundle_dst {
+                let counts = count_matrix.as_ref().unwrap();
+                write_matrix_bundle(
+                    matches,
+                    dst,
+                    &options,
+                    &matrix,
+                    matrix_unit,
+                    counts,
+                    &lengths,
+                );
+            }
+
+            if options.self_check {
+                check_invariants("matrix", &matrix);
+            }
+
+            if let Some(src) = matches.value_of("compare-against") {
+                compare_against(src, matrix_unit, &matrix, options.tolerance);
+            }
+
+            timed_write(
+                &resource_usage,
+                matrix.data().len() as u64,
+                || match &options.gene_metadata {
+                    Some(metadata) => {
+                        write_matrix_with_metadata(handle, matrix_unit.as_str(), &matrix, metadata)
+                    }
+                    None => write_matrix(handle, matrix_unit.as_str(), &matrix),
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    write_resource_report(
+        matches.value_of("resource-report"),
+        &resource_usage.borrow(),
+    );
+}
+
+// Parses the value of an optional argument, exiting if it is invalid.
+fn optional_value_t<T>(matches: &ArgMatches<'_>, name: &str) -> Option<T>
+where
+    T: FromStr,
+{
+    if matches.is_present(name) {
+        Some(value_t!(matches, name, T).unwrap_or_else(|e| exit_with_clap_error(e)))
+    } else {
+        None
+    }
+}
+
+fn parse_length_cap(matches: &ArgMatches<'_>) -> Option<LengthCap> {
+    if let Some(max_length) = optional_value_t(matches, "max-length") {
+        return Some(LengthCap::Max(max_length));
+    }
+
+    let q = optional_value_t(matches, "winsorize-lengths")?;
+
+    if q <= 0.0 || q > 1.0 {
+        exit_with_error(
+            "--winsorize-lengths must be in (0, 1]",
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    Some(LengthCap::Quantile(q))
+}
+
+// Reads the fragment length distribution or builds one from a mean and
+// standard deviation.
+fn parse_fragment_length_distribution(
+    matches: &ArgMatches<'_>,
+) -> Option<FragmentLengthDistribution> {
+    if let Some(src) = matches.value_of("fragment-length-distribution") {
+        return Some(read_input(src, read_fragment_length_distribution));
+    }
+
+    let mean = optional_value_t(matches, "fragment-length-mean")?;
+    let sd = optional_value_t(matches, "fragment-length-sd").unwrap_or(0.0);
+
+    if mean <= 0.0 || sd < 0.0 {
+        exit_with_error(
+            "--fragment-length-mean must be > 0 and --fragment-length-sd must be >= 0",
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    Some(FragmentLengthDistribution::from_normal(mean, sd))
+}
+
+fn read_gene_universe(matches: &ArgMatches<'_>) -> Option<Vec<String>> {
+    let src = matches.value_of("gene-universe")?;
+    let universe = read_names(src).unwrap_or_else(|e| exit_with_io_error(e, src));
+
+    let mut seen = HashSet::new();
+
+    if let Some(id) = universe.iter().find(|id| !seen.insert(*id)) {
+        exit_with_error(
+            &format!("duplicate feature in --gene-universe: {}", id),
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    Some(universe)
+}
+
+// Annotations from stdin can only be read once.
+fn check_stdin_annotations(matches: &ArgMatches<'_>, length_source: &str) {
+    let rereads_annotations = ["three-prime-utr", "last-exon"].contains(&length_source);
+
+    if rereads_annotations {
+        exit_with_error(
+            &format!(
+                "--length-source {} cannot be used with annotations from stdin",
+                length_source
+            ),
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    for arg in &[
+        "mmap",
+        "qc-report",
+        "length-report",
+        "transcript-abundances",
+        "genome",
+        "reference-sequence-summary",
+        "biotype-summary",
+    ] {
+        if matches.is_present(arg) {
+            exit_with_error(
+                &format!("--{} cannot be used with annotations from stdin", arg),
+                clap::ErrorKind::ArgumentConflict,
+            );
+        }
+    }
+}
+
+// Explains how the intervals and length of a feature are built from its
+// records.
+fn explain_feature<W>(
+    writer: W,
+    loader: &AnnotationLoader<'_>,
+    features: &Features,
+    lengths: &Lengths,
+    id: &str,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let src = require_annotations(loader.src, "--explain-gene");
+
+    if !features.contains_key(id) {
+        exit_with_error(
+            &format!("--explain-gene: feature not in annotations: {}", id),
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    // The records are read again, as the features are merged when they are
+    // read. Standard input cannot be read again.
+    let records = if src == Path::new(STDIN_SRC) {
+        None
+    } else {
+        let mut records = gtf::read_features(src, loader.feature_type, loader.feature_id)
+            .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy()));
+        records.remove(id)
+    };
+
+    let explanation = FeatureExplanation {
+        id,
+        feature_type: loader.feature_type,
+        records: records.as_deref(),
+        intervals: &features[id],
+        length_source: loader.length_source,
+        length: lengths.get(id).copied(),
+    };
+
+    write_feature_explanation(writer, &explanation)
+}
+
+// Writes the length distributions of all features and of the features of each
+// biotype.
+fn write_lengths_by_biotype<W>(writer: W, lengths: &Lengths, biotypes: &Biotypes) -> io::Result<()>
+where
+    W: Write,
+{
+    let mut lengths_by_biotype: BTreeMap<&str, Vec<u64>> = BTreeMap::new();
+
+    for (id, &len) in lengths {
+        if let Some(biotype) = biotypes.get(id) {
+            lengths_by_biotype.entry(biotype).or_default().push(len);
+        }
+    }
+
+    let mut distributions = Vec::with_capacity(lengths_by_biotype.len() + 1);
+
+    if let Some(distribution) = summarize_lengths(lengths.values().copied()) {
+        distributions.push((ALL_BIOTYPES, distribution));
+    }
+
+    for (biotype, lengths) in lengths_by_biotype {
+        if let Some(distribution) = summarize_lengths(lengths) {
+            distributions.push((biotype, distribution));
+        }
+    }
+
+    write_length_report(writer, &distributions)
+}
+
+// Reads the pseudobulk samples of 10x Genomics counts, i.e., the summed counts
+// of the barcodes assigned to each group.
+fn read_pseudobulk_samples(
+    matches: &ArgMatches<'_>,
+    counts_src: &Path,
+    pseudobulk_src: &str,
+) -> Vec<(String, Counts)> {
+    let mut counts = read_10x(counts_src)
+        .unwrap_or_else(|e| exit_with_io_error(e, &counts_src.to_string_lossy()));
+
+    if let Some(src) = matches.value_of("barcodes") {
+        filter_barcodes(&mut counts, src).unwrap_or_else(|e| exit_with_io_error(e, src));
+    }
+
+    let assignments = read_input(pseudobulk_src, read_group_assignments);
+    let barcodes: HashSet<&str> = assignments.iter().map(|(b, _)| b.as_str()).collect();
+
+    let unassigned_count = counts
+        .column_names()
+        .iter()
+        .filter(|name| !barcodes.contains(name.as_str()))
+        .count();
+
+    if unassigned_count > 0 {
+        info!(
+            "{} barcodes are not assigned to a group and were skipped",
+            unassigned_count
+        );
+    }
+
+    aggregate(&counts, &assignments)
+}
+
+fn read_count_samples(srcs: &[&Path], options: &ReadCountsOptions) -> Vec<(String, Counts)> {
+    let mut samples = Vec::with_capacity(srcs.len());
+
+    for &src in srcs {
+        samples.extend(
+            read_samples(src, options)
+                .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy())),
+        );
+    }
+
+    samples
+}
+
+// Maps the feature IDs of the samples and returns the IDs that are not in the
+// ID map.
+fn map_sample_ids(samples: &mut [(String, Counts)], id_map: &IdMap) -> BTreeSet<FeatureId> {
+    let mut unmapped_ids = BTreeSet::new();
+
+    for (_, counts) in samples {
+        let (mapped_counts, ids) = map_feature_ids(counts, id_map);
+        *counts = mapped_counts;
+        unmapped_ids.extend(ids);
+    }
+
+    if !unmapped_ids.is_empty() {
+        warn!(
+            "{} counts feature IDs are not in the ID map",
+            unmapped_ids.len()
+        );
+    }
+
+    unmapped_ids
+}
+
+// A mismatch of most feature IDs, e.g., Ensembl IDs against gene names, is
+// more likely an error in the arguments than missing features.
+fn check_unmatched_feature_ids(samples: &[(String, Counts)], lengths: &Lengths, strict: bool) {
+    for (name, counts) in samples {
+        let unmatched_ids = find_unmatched_feature_ids(counts, lengths);
+        let fraction = unmatched_ids.len() as f64 / counts.len() as f64;
+
+        if fraction > MAX_UNMATCHED_FEATURE_ID_FRACTION {
+            let examples: Vec<&str> = unmatched_ids
+                .iter()
+                .take(UNMATCHED_FEATURE_ID_EXAMPLE_COUNT)
+                .map(|id| id.as_str())
+                .collect();
+
+            warn_or_exit(
+                &format!(
+                    "{}: {} of {} counts feature IDs are not in the annotations (e.g., {}); check --id and the annotations",
+                    name,
+                    unmatched_ids.len(),
+                    counts.len(),
+                    examples.join(", ")
+                ),
+                strict,
+            );
+        }
+    }
+}
+
+// Writes the rarefaction curves of the samples up to the downsampling depth
+// or, by default, the largest library size.
+fn write_sample_rarefaction_curves<W, R>(
+    writer: W,
+    samples: &[(String, Counts)],
+    max_depth: Option<u64>,
+    rng: &mut R,
+) -> io::Result<()>
+where
+    W: Write,
+    R: Rng,
+{
+    let curves: Vec<_> = samples
+        .iter()
+        .map(|(sample_name, counts)| (sample_name.as_str(), sorted_count_values(counts)))
+        .collect();
+
+    let max_depth = max_depth.unwrap_or_else(|| {
+        curves
+            .iter()
+            .map(|(_, values)| values.iter().sum())
+            .max()
+            .unwrap_or(0)
+    });
+
+    let depths = build_rarefaction_depths(max_depth);
+    write_rarefaction_curves(writer, &curves, &depths, rng)
+}
+
+fn write_method_comparisons<W>(
+    writer: W,
+    samples: &[(String, Counts)],
+    lengths: &Lengths,
+    loader: &AnnotationLoader<'_>,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let counts: Vec<&Counts> = samples.iter().map(|(_, counts)| counts).collect();
+    let tmm_factors = calculate_tmm_factors(&counts);
+
+    let mut writer = MethodComparisonWriter::new(writer)?;
+
+    for ((name, counts), &tmm_factor) in samples.iter().zip(&tmm_factors) {
+        let comparisons = compare_methods(counts, lengths, tmm_factor)
+            .unwrap_or_else(|e| exit_with_calculation_error(e, lengths, loader.aliases().as_ref()));
+
+        let shifted = comparisons
+            .iter()
+            .filter(|c| c.max_rank_shift() > 0.0)
+            .count();
+
+        info!(
+            "{}: {} of {} features change rank between methods (TMM factor: {})",
+            name,
+            shifted,
+            comparisons.len(),
+            tmm_factor
+        );
+
+        writer.write_sample(name, &comparisons)?;
+    }
+
+    Ok(())
+}
+
+// Calculates the size factors of the samples of a count matrix and writes them
+// to `dst`, if given.
+fn calculate_sample_size_factors(matrix: &ExpressionMatrix, dst: Option<&str>) -> SizeFactors {
+    let size_factors = calculate_size_factors(matrix).unwrap_or_else(|| {
+        exit_with_error(
+            "size factors cannot be calculated: no feature is counted in every sample",
+            clap::ErrorKind::InvalidValue,
+        )
+    });
+
+    if let Some(dst) = dst {
+        write_output(dst, |writer| {
+            write_size_factors(writer, matrix.column_names(), &size_factors)
+        });
+    }
+
+    matrix
+        .column_names()
+        .iter()
+        .cloned()
+        .zip(size_factors)
+        .collect()
+}
+
+// Writes the QC metrics of the samples, optionally stratified by a sample
+// sheet column, and warns about samples above the maximum mitochondrial and
+// rRNA count fractions.
+fn write_qc_report(
+    matches: &ArgMatches<'_>,
+    dst: &str,
+    options: &QuantifyOptions<'_>,
+    loader: &AnnotationLoader<'_>,
+    samples: &[(String, Counts)],
+) {
+    let annotations = loader.annotations("--qc-report");
+    let reference_sequence_names = &annotations.reference_sequence_names;
+    let biotypes = &annotations.biotypes;
+
+    let metrics: Vec<_> = samples
+        .iter()
+        .map(|(name, counts)| {
+            let metrics = QcMetrics {
+                count: sum_counts(counts),
+                mitochondrial_fraction: calculate_count_fraction(counts, |id| {
+                    is_mitochondrial(id, reference_sequence_names, biotypes)
+                }),
+                rrna_fraction: calculate_count_fraction(counts, |id| is_rrna(id, biotypes)),
+            };
+
+            (name.as_str(), metrics)
+        })
+        .collect();
+
+    for (name, m) in &metrics {
+        if let Some(max) = options.max_mitochondrial_fraction {
+            if m.mitochondrial_fraction > max {
+                warn!(
+                    "{}: mitochondrial count fraction {} exceeds {}",
+                    name, m.mitochondrial_fraction, max
+                );
+            }
+        }
+
+        if let Some(max) = options.max_rrna_fraction {
+            if m.rrna_fraction > max {
+                warn!(
+                    "{}: rRNA count fraction {} exceeds {}",
+                    name, m.rrna_fraction, max
+                );
+            }
+        }
+    }
+
+    match options.stratify_by {
+        Some(column) => {
+            let src = matches.value_of("sample-sheet").unwrap();
+            let names: Vec<String> = samples.iter().map(|(name, _)| name.clone()).collect();
+            let sample_sheet = open_sample_sheet(src, &names);
+            let strata = sample_strata(&sample_sheet, &names, column);
+            write_output(dst, |writer| {
+                write_stratified_qc_metrics(writer, column, &strata, &metrics)
+            });
+        }
+        None => write_output(dst, |writer| write_qc_metrics(writer, &metrics)),
+    }
+}
+
+// Calculates the expressions of samples in the unit given by the arguments.
+struct ExpressionCalculator<'a> {
+    unit: &'a str,
+    lengths: &'a Lengths,
+    unit_lengths: &'a Lengths,
+    size_factors: SizeFactors,
+    summation: Summation,
+    loader: &'a AnnotationLoader<'a>,
+}
+
+impl ExpressionCalculator<'_> {
+    fn size_factor(&self, name: &str) -> f64 {
+        self.size_factors.get(name).copied().unwrap_or_else(|| {
+            exit_with_error(
+                &format!("missing size factor for sample: {}", name),
+                clap::ErrorKind::InvalidValue,
+            )
+        })
+    }
+
+    // `lengths` are the feature lengths of the sample, which are weighted by
+    // its transcript abundances, if given.
+    fn calculate(&self, name: &str, counts: &Counts, lengths: &Lengths) -> Expressions {
+        let result = match self.unit {
+            "fpkm" => calculate_fpkms(counts, lengths),
+            "tpm" => calculate_tpms_with_summation(counts, lengths, self.summation),
+            "cpm" => calculate_tpms_with_summation(counts, self.unit_lengths, self.summation),
+            "scaled" => Ok(calculate_scaled_counts(counts, self.size_factor(name))),
+            _ => unreachable!(),
+        };
+
+        result.unwrap_or_else(|e| self.exit_with_calculation_error(e, lengths))
+    }
+
+    fn calculate_antisense(
+        &self,
+        sense_name: &str,
+        sense_counts: &Counts,
+        antisense_counts: &Counts,
+    ) -> Expressions {
+        let sense_sum = sum_counts(sense_counts);
+        let antisense_sum = sum_counts(antisense_counts);
+
+        if sense_sum + antisense_sum > 0 {
+            info!(
+                "antisense rate: {}",
+                antisense_sum as f64 / (sense_sum + antisense_sum) as f64
+            );
+        }
+
+        let result = match self.unit {
+            "fpkm" => calculate_antisense_fpkms(sense_counts, antisense_counts, self.lengths),
+            "tpm" => calculate_antisense_tpms_with_summation(
+                sense_counts,
+                antisense_counts,
+                self.lengths,
+                self.summation,
+            ),
+            "cpm" => calculate_antisense_tpms_with_summation(
+                sense_counts,
+                antisense_counts,
+                self.unit_lengths,
+                self.summation,
+            ),
+            "scaled" => Ok(calculate_scaled_counts(
+                antisense_counts,
+                self.size_factor(sense_name),
+            )),
+            _ => unreachable!(),
+        };
+
+        result.unwrap_or_else(|e| self.exit_with_calculation_error(e, self.lengths))
+    }
+
+    fn exit_with_calculation_error(&self, e: Error, lengths: &Lengths) -> ! {
+        exit_with_calculation_error(e, lengths, self.loader.aliases().as_ref())
+    }
+}
+
+// Reads the abundances of a sample, e.g., from a transcript quantifier, as
+// FPKMs and converts them to the unit of the arguments.
+fn read_abundance_set(
+    src: &str,
+    method: Method,
+    options: &QuantifyOptions<'_>,
+    calculator: &ExpressionCalculator<'_>,
+) -> ExpressionSet {
+    let fpkms = read_input(src, read_abundances);
+
+    let values = match options.unit {
+        "fpkm" => fpkms,
+        "tpm" => {
+            let tpms = calculate_tpms_from_fpkms_with_summation(&fpkms, options.summation)
+                .unwrap_or_else(|e| calculator.exit_with_calculation_error(e, calculator.lengths));
+            check_tpms(
+                &sample_name(src),
+                &tpms,
+                options.tpm_sum_tolerance,
+                options.strict,
+            );
+            tpms
+        }
+        _ => exit_with_error(
+            &format!("--abundances cannot be used with --unit {}", options.unit),
+            clap::ErrorKind::ArgumentConflict,
+        ),
+    };
+
+    ExpressionSet {
+        method,
+        library_size: None,
+        sample_name: sample_name(src),
+        values,
+    }
+}
+
+// Reads the gene effective lengths weighted by the transcript abundances of
+// each sample. A single file is used for all samples.
+fn read_gene_effective_lengths(
+    matches: &ArgMatches<'_>,
+    unit: &str,
+    sample_count: usize,
+    loader: &AnnotationLoader<'_>,
+) -> Vec<Lengths> {
+    let srcs: Vec<&str> = matches
+        .values_of("transcript-abundances")
+        .map(|values| values.collect())
+        .unwrap_or_default();
+
+    if srcs.is_empty() {
+        return Vec::new();
+    }
+
+    if unit != "fpkm" && unit != "tpm" {
+        exit_with_error(
+            &format!(
+                "--transcript-abundances cannot be used with --unit {}",
+                unit
+            ),
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    if srcs.len() != 1 && srcs.len() != sample_count {
+        exit_with_error(
+            "--transcript-abundances must be given once or once per sample",
+            clap::ErrorKind::WrongNumberOfValues,
+        );
+    }
+
+    let annotations = loader.annotations("--transcript-abundances");
+    let transcript_genes = &annotations.aliases;
+
+    srcs.iter()
+        .map(|src| {
+            let abundances = read_input(src, read_transcript_abundances);
+            calculate_gene_effective_lengths(&abundances, transcript_genes)
+        })
+        .collect()
+}
+
+fn collect_expressions<I>(sets: I, self_check: bool) -> Vec<ExpressionSet>
+where
+    I: Iterator<Item = ExpressionSet>,
+{
+    let expressions: Vec<_> = sets.collect();
+
+    if self_check {
+        for set in &expressions {
+            check_invariants(&format!("expressions of {}", set.sample_name), &set.values);
+        }
+    }
+
+    expressions
+}
+
+// Records the time since the last stage as computation and the time to write
+// the output as writing.
+fn timed_write<F>(resource_usage: &RefCell<ResourceUsage>, count: u64, f: F) -> io::Result<()>
+where
+    F: FnOnce() -> io::Result<()>,
+{
+    resource_usage.borrow_mut().add_remaining("compute", count);
+
+    let start = Instant::now();
+    f()?;
+
+    resource_usage
+        .borrow_mut()
+        .add("write", start.elapsed(), count);
+
+    Ok(())
+}
+
+// Selects the features of a single sample in the order of the gene universe
+// or gene metadata, if either is given.
+fn select_sample_ids(
+    expressions: &Expressions,
+    options: &QuantifyOptions<'_>,
+) -> Option<Vec<String>> {
+    if options.gene_universe.is_none() && options.gene_metadata.is_none() {
+        return None;
+    }
+
+    let ids = match &options.gene_universe {
+        Some(universe) => {
+            check_gene_universe(universe, expressions.keys(), options.strict);
+            universe.clone()
+        }
+        None => expressions.keys().map(|id| id.to_string()).collect(),
+    };
+
+    let ids = match &options.gene_metadata {
+        Some(metadata) => check_gene_metadata(metadata, ids, options.missing_metadata),
+        None => ids,
+    };
+
+    Some(ids)
+}
+
+// Writes a single sample as a list of features, either with its antisense
+// expressions or only the selected features.
+fn write_single_sample<W>(
+    writer: W,
+    expressions: &Expressions,
+    antisense_expressions: Option<&Expressions>,
+    ids: Option<&[String]>,
+    gene_metadata: Option<&GeneMetadata>,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    if let Some(ids) = ids {
+        return write_expressions_in_order(writer, expressions, ids, gene_metadata);
+    }
+
+    match antisense_expressions {
+        Some(antisense_expressions) => {
+            write_stranded_expressions(writer, expressions, antisense_expressions)
+        }
+        None => write_expressions(writer, expressions),
+    }
+}
+
+// Calculates and writes the samples in chunks of `chunk_size` to a new
+// temporary directory. The directory is removed when it is dropped, so it must
+// outlive the writer.
+fn write_chunks<I>(
+    sets: I,
+    sample_count: usize,
+    chunk_size: usize,
+    lengths: &Lengths,
+) -> (TempDir, ChunkedMatrixWriter)
+where
+    I: Iterator<Item = ExpressionSet>,
+{
+    let dir = TempDir::new("noodles-fpkm")
+        .unwrap_or_else(|e| exit_with_io_error(e, &env::temp_dir().to_string_lossy()));
+
+    let mut writer = ChunkedMatrixWriter::new(dir.path());
+    let mut names = HashSet::new();
+    let mut chunk = Vec::with_capacity(chunk_size);
+
+    let mut write_chunk = |chunk: &mut Vec<ExpressionSet>| {
+        let matrix = merge_expression_sets(chunk)
+            .unwrap_or_else(|e| exit_with_calculation_error(e, lengths, None));
+        writer.write_chunk(&matrix).unwrap();
+        chunk.clear();
+    };
+
+    for set in sets {
+        if !names.insert(set.sample_name.clone()) {
+            exit_with_error(
+                "sample names (counts file stems) must be unique",
+                clap::ErrorKind::InvalidValue,
+            );
+        }
+
+        chunk.push(set);
+
+        if chunk.len() == chunk_size {
+            write_chunk(&mut chunk);
+            info!("wrote {} of {} samples", names.len(), sample_count);
+        }
+    }
+
+    if !chunk.is_empty() {
+        write_chunk(&mut chunk);
+    }
+
+    (dir, writer)
+}
+
+// Groups samples by a sample sheet column and fails if the sample sheet has
+// no such column.
+fn sample_strata<'a>(
+    sample_sheet: &'a SampleSheet,
+    names: &[String],
+    column: &str,
+) -> Vec<(&'a str, Vec<usize>)> {
+    if !sample_sheet.has_column(column) {
+        exit_with_error(
+            &format!("--stratify-by {}: no such sample sheet column", column),
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    sample_sheet.strata(names, column)
+}
+
+// Returns the annotations source, exiting if it is not given. `arg` is the
+// option that reads the annotations.
+fn require_annotations<'a>(src: Option<&'a Path>, arg: &str) -> &'a Path {
+    src.unwrap_or_else(|| {
+        exit_with_error(
+            &format!("{} requires --annotations", arg),
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+    })
+}
+
+fn require_samples(matrix: &ExpressionMatrix, arg: &str) {
+    if matrix.column_names().len() < 2 {
+        exit_with_error(
+            &format!("{} requires at least 2 samples", arg),
+            clap::ErrorKind::TooFewValues,
+        );
+    }
+}
+
+fn check_tpms(name: &str, tpms: &Expressions, tolerance: f64, strict: bool) {
+    match check_tpm_sum(tpms, tolerance) {
+        Ok(sum) => info!("{}: TPM sum: {}", name, sum),
+        Err(sum) => warn_or_exit(
+            &format!("{}: TPMs sum to {}, not {}", name, sum, TPM_SUM),
+            strict,
+        ),
+    }
+}
+
+// Logs the number of features in the gene universe without values and warns
+// of, or with `strict`, fails on, features not in the gene universe, which
+// are dropped.
+fn check_gene_universe<I, S>(universe: &[String], ids: I, strict: bool)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let universe_ids: HashSet<&str> = universe.iter().map(|id| id.as_str()).collect();
+
+    let mut present_count = 0;
+    let mut extra_count = 0;
+    let mut example = None;
+
+    for id in ids {
+        let id = id.as_ref();
+
+        if universe_ids.contains(id) {
+            present_count += 1;
+        } else {
+            extra_count += 1;
+            example.get_or_insert_with(|| id.to_string());
+        }
+    }
+
+    let missing_count = universe.len() - present_count;
+
+    if missing_count > 0 {
+        info!(
+            "{} features in the gene universe have no values and were given 0",
+            missing_count
+        );
+    }
+
+    if let Some(example) = example {
+        warn_or_exit(
+            &format!(
+                "{} features are not in the gene universe (e.g., {})",
+                extra_count, example
+            ),
+            strict,
+        );
+    }
+}
+
+// Applies the missing metadata policy to features, in order, and returns the
+// features to write.
+fn check_gene_metadata(
+    metadata: &GeneMetadata,
+    ids: Vec<String>,
+    policy: MissingMetadata,
+) -> Vec<String> {
+    let (present, missing): (Vec<String>, Vec<String>) = ids
+        .iter()
+        .cloned()
+        .partition(|id| metadata.get(id).is_some());
+
+    let example = match missing.first() {
+        Some(id) => id,
+        None => return ids,
+    };
+
+    match policy {
+        MissingMetadata::Na => {
+            info!(
+                "{} features are not in the gene metadata and were given NA",
+                missing.len()
+            );
+            ids
+        }
+        MissingMetadata::Drop => {
+            info!(
+                "{} features are not in the gene metadata and were dropped",
+                missing.len()
+            );
+            present
+        }
+        MissingMetadata::Error => exit_with_error(
+            &format!(
+                "{} features are not in the gene metadata (e.g., {})",
+                missing.len(),
+                example
+            ),
+            clap::ErrorKind::InvalidValue,
+        ),
+    }
+}
+
+// Differences are errors, and the result is not written.
+fn compare_against(src: &str, unit: Unit, matrix: &ExpressionMatrix, tolerance: f64) {
+    const MAX_REPORTED_DIFFERENCES: usize = 20;
+
+    let (expected_unit, expected) = read_matrix_or_expressions(src, Some(unit.as_str()), unit);
+
+    if expected_unit != unit {
+        exit_with_error(
+            &format!(
+                "{}: unit is {}, but the result is {}",
+                src, expected_unit, unit
+            ),
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    let differences = compare_matrices(matrix, &expected, tolerance);
+
+    if differences.is_empty() {
+        info!("result matches {} (tolerance: {})", src, tolerance);
+        return;
+    }
+
+    let mut message = format!(
+        "result differs from {} beyond --tolerance {}:",
+        src, tolerance
+    );
+
+    for difference in differences.iter().take(MAX_REPORTED_DIFFERENCES) {
+        message.push_str(&format!("\n  {}", difference));
+    }
+
+    if differences.len() > MAX_REPORTED_DIFFERENCES {
+        message.push_str(&format!(
+            "\n  ... and {} more",
+            differences.len() - MAX_REPORTED_DIFFERENCES
+        ));
+    }
+
+    exit_with_error(&message, clap::ErrorKind::InvalidValue);
+}
+
+#[derive(Clone, Copy)]
+enum LengthCap {
+    Max(u64),
+    Quantile(f64),
+}
+
+// Pathological records, e.g., multi-megabase readthrough "genes", are capped
+// before effective lengths are calculated, and each capped feature is
+// reported, as its values are no longer comparable to other tools.
+fn cap_feature_lengths(lengths: &mut Lengths, cap: LengthCap) {
+    const MAX_EXAMPLES: usize = 5;
+
+    let max_length = match cap {
+        LengthCap::Max(max_length) => max_length,
+        LengthCap::Quantile(q) => match length_quantile(lengths, q) {
+            Some(max_length) => max_length,
+            None => return,
+        },
+    };
+
+    let capped = cap_lengths(lengths, max_length);
+
+    if capped.is_empty() {
+        return;
+    }
+
+    let examples: Vec<String> = capped
+        .iter()
+        .take(MAX_EXAMPLES)
+        .map(|(id, len)| format!("{} ({})", id, len))
+        .collect();
+
+    let ellipsis = if capped.len() > MAX_EXAMPLES {
+        ", ..."
+    } else {
+        ""
+    };
+
+    warn!(
+        "capped the lengths of {} feature(s) at {}: {}{}",
+        capped.len(),
+        max_length,
+        examples.join(", "),
+        ellipsis
+    );
+}
+
+// Flanks only extend spans, so they require the gene-body length source.
+fn flank_length(matches: &ArgMatches<'_>, name: &str, length_source: &str) -> u64 {
+    if !matches.is_present(name) {
+        return 0;
+    }
+
+    if length_source != "gene-body" {
+        exit_with_error(
+            &format!("--{} requires --length-source gene-body", name),
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    value_t!(matches, name, u64).unwrap_or_else(|e| exit_with_clap_error(e))
+}
+
+fn parse_transform(s: &str, features: Option<&Vec<String>>) -> Transform {
+    const SCALE_TOTAL_PREFIX: &str = "scale-total=";
+
+    if s == "median-center" {
+        return Transform::MedianCenter;
+    }
+
+    let target = s
+        .strip_prefix(SCALE_TOTAL_PREFIX)
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|target| target.is_finite() && *target > 0.0);
+
+    match target {
+        Some(target) => Transform::ScaleTotal {
+            target,
+            features: features.cloned(),
+        },
+        None => exit_with_error(
+            &format!(
+                "invalid --transform: {} (expected median-center or scale-total=<float>)",
+                s
+            ),
+            clap::ErrorKind::InvalidValue,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::app::app;
+
+    use super::*;
+
+    #[test]
+    fn test_output_mode() {
+        let matches = app().get_matches_from(&["noodles-fpkm", "-a", "a.gtf", "s1.txt"]);
+        assert_eq!(output_mode(&matches, 1, None), OutputMode::SingleSample);
+        assert_eq!(output_mode(&matches, 1, Some(2)), OutputMode::SingleSample);
+        assert_eq!(output_mode(&matches, 2, None), OutputMode::Matrix);
+        assert_eq!(output_mode(&matches, 2, Some(2)), OutputMode::Chunked(2));
+
+        let matches = app().get_matches_from(&[
+            "noodles-fpkm",
+            "-a",
+            "a.gtf",
+            "--detection",
+            "detection.tsv",
+            "s1.txt",
+        ]);
+        assert_eq!(output_mode(&matches, 1, None), OutputMode::Matrix);
+    }
+
+    #[cfg(all(feature = "mmap", feature = "parallel"))]
+    #[test]
+    fn test_open_features_for_exon_bins() -> io::Result<()> {
+        let data = b"\
+chr1\tHAVANA\texon\t2\t7\t.\t+\t.\tgene_id \"A\";
+chr1\tHAVANA\texon\t5\t9\t.\t+\t.\tgene_id \"A\";
+";
+
+        let dir = TempDir::new("noodles-fpkm-test")?;
+
+        let src = dir.path().join("annotations.gtf");
+        std::fs::write(&src, &data[..])?;
+
+        let options = ReadFeaturesOptions::default();
+        let open = |use_mmap, threads| {
+            open_features(&src, "exon", "gene_id", use_mmap, threads, false, &options)
+        };
+
+        // The stdin reader.
+        let expected = calculate_exon_bin_lengths(&read_features_from_reader_with_options(
+            &data[..],
+            "exon",
+            "gene_id",
+            &options,
+        )?);
+
+        assert_eq!(expected.len(), 3);
+        assert_eq!(calculate_exon_bin_lengths(&open(false, 1)?), expected);
+        assert_eq!(calculate_exon_bin_lengths(&open(true, 1)?), expected);
+        assert_eq!(calculate_exon_bin_lengths(&open(false, 2)?), expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,407 @@
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+use clap::ArgMatches;
+use log::{info, warn};
+use noodles_fpkm::{
+    cqn::{normalize_gc_and_length, DEFAULT_BIN_COUNT},
+    expression_set::{merge_expression_sets, ExpressionSet},
+    features::Features,
+    fold_change::log2_fold_changes,
+    groups::{center_batches, group_means, group_medians, stratify_groups},
+    lengths::Lengths,
+    matrix::ExpressionMatrix,
+    qc::{
+        infer_sex, summarize_by_biotype, summarize_by_reference_sequence, DEFAULT_FEMALE_GENES,
+        DEFAULT_MALE_GENES,
+    },
+    quantile_mapping::{inverse_normal_transform, map_to_reference, read_reference_distribution},
+    sample_sheet::SampleSheet,
+    sequence::read_gc_contents,
+    signatures::{read_signatures, score_signatures},
+    spike_ins::{fit_dose_responses, read_ercc_concentrations, Mix},
+    stability::rank_reference_genes,
+    top::top_features,
+    transform::apply_transforms,
+    unit::Unit,
+};
+
+use super::{
+    check_gene_metadata, check_gene_universe, require_samples, sample_strata, AnnotationLoader,
+    QuantifyOptions,
+};
+use crate::{
+    cli::reports::{
+        write_bundle, write_detection, write_dose_responses, write_gene_lists, write_group_summary,
+        write_sex_inferences, write_stabilities, write_top_features, Bundle,
+        REFERENCE_GENE_CANDIDATES,
+    },
+    exit_with_calculation_error, exit_with_error, exit_with_io_error, formatted_writer,
+    open_sample_sheet, read_input, write_batch_adjustment_header, write_matrix, write_output,
+};
+
+pub(super) fn merge_samples(expressions: &[ExpressionSet], lengths: &Lengths) -> ExpressionMatrix {
+    let names: HashSet<_> = expressions.iter().map(|set| &set.sample_name).collect();
+
+    if names.len() < expressions.len() {
+        exit_with_error(
+            "sample names (counts file stems) must be unique",
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    merge_expression_sets(expressions)
+        .unwrap_or_else(|e| exit_with_calculation_error(e, lengths, None))
+}
+
+// Normalizes the matrix by GC content, batch, and a reference distribution.
+// The batch adjustment is written as a header to `writer`.
+pub(super) fn normalize_matrix<W>(
+    mut writer: W,
+    matches: &ArgMatches<'_>,
+    loader: &AnnotationLoader<'_>,
+    features: &Features,
+    lengths: &Lengths,
+    mut matrix: ExpressionMatrix,
+) -> ExpressionMatrix
+where
+    W: Write,
+{
+    if let Some(src) = matches.value_of("genome") {
+        require_samples(&matrix, "--genome");
+
+        let annotations = loader.annotations("--genome");
+        let reference_sequence_names = &annotations.reference_sequence_names;
+        let gc_contents = read_gc_contents(src, features, reference_sequence_names)
+            .unwrap_or_else(|e| exit_with_io_error(e, src));
+
+        info!(
+            "calculated GC content for {} of {} features",
+            gc_contents.len(),
+            features.len()
+        );
+
+        matrix = normalize_gc_and_length(&matrix, &gc_contents, lengths, DEFAULT_BIN_COUNT);
+    }
+
+    if matches.is_present("batch-center") {
+        require_samples(&matrix, "--batch-center");
+
+        let src = matches.value_of("sample-sheet").unwrap();
+        let sample_sheet = open_sample_sheet(src, matrix.column_names());
+
+        if !sample_sheet.has_batches() {
+            exit_with_error(
+                "--batch-center requires a batch column in the sample sheet",
+                clap::ErrorKind::MissingRequiredArgument,
+            );
+        }
+
+        let batches = sample_sheet.batches(matrix.column_names());
+        matrix = center_batches(&matrix, &batches);
+
+        write_batch_adjustment_header(&mut writer, &batches).unwrap();
+    }
+
+    if let Some(src) = matches.value_of("reference-distribution") {
+        let reference = read_input(src, read_reference_distribution);
+        matrix = map_to_reference(&matrix, &reference);
+    }
+
+    matrix
+}
+
+pub(super) fn transform_matrix(
+    mut matrix: ExpressionMatrix,
+    mut unit: Unit,
+    options: &QuantifyOptions<'_>,
+) -> (ExpressionMatrix, Unit) {
+    if options.inverse_normal {
+        require_samples(&matrix, "--inverse-normal");
+        matrix = inverse_normal_transform(&matrix, options.rank_offset);
+        unit = Unit::NormalScore;
+    }
+
+    if !options.transforms.is_empty() {
+        matrix = apply_transforms(&matrix, &options.transforms);
+        unit = options
+            .transforms
+            .iter()
+            .fold(unit, |unit, transform| transform.unit(unit));
+    }
+
+    (matrix, unit)
+}
+
+// Writes the reports of the merged matrix, each to its own output file.
+pub(super) fn write_matrix_reports(
+    matches: &ArgMatches<'_>,
+    options: &QuantifyOptions<'_>,
+    loader: &AnnotationLoader<'_>,
+    matrix: &ExpressionMatrix,
+    unit: Unit,
+) {
+    let output_format = options.output_format;
+
+    if let Some(dst) = matches.value_of("fold-changes") {
+        let fold_changes = calculate_fold_changes(matches, matrix);
+        write_output(dst, |writer| {
+            write_matrix(
+                formatted_writer(writer, output_format),
+                Unit::Log2FoldChange.as_str(),
+                &fold_changes,
+            )
+        });
+    }
+
+    if let Some(dst) = matches.value_of("group-summary") {
+        let src = matches.value_of("sample-sheet").unwrap();
+        let sample_sheet = open_sample_sheet(src, matrix.column_names());
+        write_output(dst, |writer| {
+            write_group_summary_of(
+                formatted_writer(writer, output_format),
+                matrix,
+                unit,
+                &sample_sheet,
+                options.stratify_by,
+            )
+        });
+    }
+
+    if let Some(dst) = matches.value_of("detection") {
+        let detected = matrix.detect(options.detection_threshold);
+        write_output(dst, |writer| write_detection(writer, &detected));
+    }
+
+    if let Some(dst) = matches.value_of("gene-lists") {
+        write_gene_lists(dst, matrix, options.detection_threshold)
+            .unwrap_or_else(|e| exit_with_io_error(e, dst));
+    }
+
+    if let Some(n) = options.top_count {
+        let top = top_features(matrix, n);
+        let dst = matches.value_of("top-report").unwrap();
+        write_output(dst, |writer| {
+            write_top_features(writer, matrix.column_names(), &top)
+        });
+    }
+
+    if let Some(dst) = matches.value_of("reference-sequence-summary") {
+        let annotations = loader.annotations("--reference-sequence-summary");
+        let summary =
+            summarize_by_reference_sequence(matrix, &annotations.reference_sequence_names);
+
+        write_output(dst, |writer| {
+            write_matrix(
+                formatted_writer(writer, output_format),
+                "reference_sequence",
+                &summary,
+            )
+        });
+    }
+
+    if let Some(dst) = matches.value_of("biotype-summary") {
+        let annotations = loader.annotations("--biotype-summary");
+        let biotypes = &annotations.biotypes;
+
+        if biotypes.is_empty() {
+            warn!("annotations have no gene_biotype or gene_type attributes");
+        }
+
+        let summary = summarize_by_biotype(matrix, biotypes);
+
+        write_output(dst, |writer| {
+            write_matrix(formatted_writer(writer, output_format), "biotype", &summary)
+        });
+    }
+
+    if let Some(dst) = matches.value_of("sex-report") {
+        let female_genes: Vec<&str> = matches
+            .values_of("female-genes")
+            .map(|values| values.collect())
+            .unwrap_or_else(|| DEFAULT_FEMALE_GENES.to_vec());
+
+        let male_genes: Vec<&str> = matches
+            .values_of("male-genes")
+            .map(|values| values.collect())
+            .unwrap_or_else(|| DEFAULT_MALE_GENES.to_vec());
+
+        let inferences = infer_sex(
+            matrix,
+            &female_genes,
+            &male_genes,
+            options.detection_threshold,
+        );
+
+        write_output(dst, |writer| {
+            write_sex_inferences(writer, matrix.column_names(), &inferences)
+        });
+    }
+
+    if let Some(src) = matches.value_of("ercc") {
+        let mix = match matches.value_of("ercc-mix") {
+            Some("2") => Mix::Two,
+            _ => Mix::One,
+        };
+
+        let concentrations = read_input(src, |reader| read_ercc_concentrations(reader, mix));
+        let dose_responses = fit_dose_responses(matrix, &concentrations);
+
+        let dst = matches.value_of("ercc-report").unwrap();
+        write_output(dst, |writer| {
+            write_dose_responses(writer, matrix.column_names(), &dose_responses)
+        });
+    }
+
+    if let Some(src) = matches.value_of("signatures") {
+        let signatures = read_input(src, read_signatures);
+
+        require_samples(matrix, "--signatures");
+
+        let scores = score_signatures(matrix, &signatures);
+        let dst = matches.value_of("scores").unwrap();
+        write_output(dst, |writer| {
+            write_matrix(
+                formatted_writer(writer, output_format),
+                "signature",
+                &scores,
+            )
+        });
+    }
+
+    if let Some(dst) = matches.value_of("reference-genes") {
+        require_samples(matrix, "--reference-genes");
+
+        let stabilities = rank_reference_genes(matrix, REFERENCE_GENE_CANDIDATES);
+        write_output(dst, |writer| write_stabilities(writer, &stabilities));
+    }
+}
+
+// Calculates the log2 fold changes of the samples, or of the means of each
+// condition with --fold-change-groups, against the control condition.
+fn calculate_fold_changes(matches: &ArgMatches<'_>, matrix: &ExpressionMatrix) -> ExpressionMatrix {
+    let src = matches.value_of("sample-sheet").unwrap();
+    let sample_sheet = open_sample_sheet(src, matrix.column_names());
+    let control = matches.value_of("control").unwrap();
+
+    let groups = sample_sheet.groups(matrix.column_names());
+
+    let k = groups
+        .iter()
+        .position(|(condition, _)| *condition == control)
+        .unwrap_or_else(|| {
+            exit_with_error(
+                &format!("--control {}: no samples in condition", control),
+                clap::ErrorKind::InvalidValue,
+            )
+        });
+
+    if matches.is_present("fold-change-groups") {
+        let means = group_means(matrix, &groups);
+        log2_fold_changes(&means, &[k])
+    } else {
+        log2_fold_changes(matrix, &groups[k].1)
+    }
+}
+
+// Writes the means and medians of each condition, optionally stratified by a
+// sample sheet column.
+fn write_group_summary_of<W>(
+    writer: W,
+    matrix: &ExpressionMatrix,
+    unit: Unit,
+    sample_sheet: &SampleSheet,
+    stratify_by: Option<&str>,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let groups = sample_sheet.groups(matrix.column_names());
+
+    let stratified_groups = stratify_by.map(|column| {
+        let strata = sample_strata(sample_sheet, matrix.column_names(), column);
+        stratify_groups(&groups, &strata)
+    });
+
+    let groups = match &stratified_groups {
+        Some(stratified_groups) => stratified_groups
+            .iter()
+            .map(|(name, columns)| (name.as_str(), columns.clone()))
+            .collect(),
+        None => groups,
+    };
+
+    let means = group_means(matrix, &groups);
+    let medians = group_medians(matrix, &groups);
+
+    write_group_summary(writer, unit.as_str(), &groups, &means, &medians)
+}
+
+// Drops the features that are detected in too few samples and selects the
+// features of the gene universe and gene metadata.
+pub(super) fn filter_matrix(
+    mut matrix: ExpressionMatrix,
+    options: &QuantifyOptions<'_>,
+) -> ExpressionMatrix {
+    if let Some(fraction) = options.min_detected_fraction {
+        let row_count = matrix.row_names().len();
+        matrix = matrix.filter_by_prevalence(options.min_value, fraction);
+
+        info!(
+            "{} of {} features are detected in less than {} of samples and were dropped",
+            row_count - matrix.row_names().len(),
+            row_count,
+            fraction
+        );
+    }
+
+    if let Some(universe) = &options.gene_universe {
+        check_gene_universe(universe, matrix.row_names(), options.strict);
+        matrix = matrix.select_rows(universe);
+    }
+
+    if let Some(metadata) = &options.gene_metadata {
+        let ids = check_gene_metadata(
+            metadata,
+            matrix.row_names().to_vec(),
+            options.missing_metadata,
+        );
+
+        if ids.len() < matrix.row_names().len() {
+            matrix = matrix.select_rows(&ids);
+        }
+    }
+
+    matrix
+}
+
+pub(super) fn write_matrix_bundle(
+    matches: &ArgMatches<'_>,
+    dst: &str,
+    options: &QuantifyOptions<'_>,
+    matrix: &ExpressionMatrix,
+    unit: Unit,
+    count_matrix: &ExpressionMatrix,
+    lengths: &Lengths,
+) {
+    let counts = count_matrix
+        .select_rows(matrix.row_names())
+        .select_columns(matrix.column_names());
+
+    let sample_sheet = matches
+        .value_of("sample-sheet")
+        .map(|src| open_sample_sheet(src, matrix.column_names()));
+
+    let bundle = Bundle {
+        unit: unit.as_str(),
+        matrix,
+        counts: &counts,
+        lengths,
+        sample_sheet: sample_sheet.as_ref(),
+        gene_metadata: options.gene_metadata.as_ref(),
+    };
+
+    write_bundle(dst, &bundle).unwrap_or_else(|e| exit_with_io_error(e, dst));
+}
@@ -0,0 +1,959 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use log::{info, warn};
+use noodles_fpkm::{
+    counts::{sum_counts, Counts},
+    downsampling::rarefaction_curve,
+    feature_id::FeatureId,
+    features::Feature,
+    gene_metadata::GeneMetadata,
+    lengths::{LengthDistribution, Lengths},
+    matrix::ExpressionMatrix,
+    method_comparison::{MethodComparison, COMPARED_METHODS},
+    qc::SexInference,
+    resources::{peak_rss, write_resource_usage, ResourceUsage},
+    sample_sheet::SampleSheet,
+    spike_ins::DoseResponse,
+    stability::Stability,
+    top::TopFeature,
+    Expressions,
+};
+use rand::Rng;
+
+use crate::{
+    write_matrix, write_matrix_row, write_metadata_fields, write_output, DETECTED_TOTALS_NAME,
+};
+
+const RAREFACTION_STEPS: u64 = 10;
+
+pub(crate) const REFERENCE_GENE_CANDIDATES: usize = 1000;
+
+pub(crate) const ALL_BIOTYPES: &str = "__all";
+
+// Returns evenly spaced depths up to and including `max_depth`.
+pub(crate) fn build_rarefaction_depths(max_depth: u64) -> Vec<u64> {
+    let mut depths: Vec<_> = (1..=RAREFACTION_STEPS)
+        .map(|i| max_depth * i / RAREFACTION_STEPS)
+        .filter(|&depth| depth > 0)
+        .collect();
+
+    depths.dedup();
+
+    depths
+}
+
+pub(crate) fn write_rarefaction_curves<W, R>(
+    mut writer: W,
+    samples: &[(&str, Vec<u64>)],
+    depths: &[u64],
+    rng: &mut R,
+) -> io::Result<()>
+where
+    W: Write,
+    R: Rng,
+{
+    for (name, counts) in samples {
+        for (depth, detected) in rarefaction_curve(counts, depths, rng) {
+            writeln!(writer, "{}\t{}\t{}", name, depth, detected)?;
+        }
+    }
+
+    writer.flush()
+}
+
+// Returns the counts sorted by feature ID, so that downsampling with a seed is
+// reproducible.
+pub(crate) fn sorted_count_values(counts: &Counts) -> Vec<u64> {
+    let mut entries: Vec<_> = counts.iter().collect();
+    entries.sort_unstable_by_key(|(name, _)| *name);
+    entries.iter().map(|(_, &count)| count).collect()
+}
+
+pub(crate) struct FeatureExplanation<'a> {
+    pub(crate) id: &'a str,
+    pub(crate) feature_type: &'a str,
+    pub(crate) records: Option<&'a [Feature]>,
+    pub(crate) intervals: &'a [Feature],
+    pub(crate) length_source: &'a str,
+    pub(crate) length: Option<u64>,
+}
+
+// Explains the length of a feature: the intervals of its records, as read, and
+// the merged intervals its length is calculated from.
+pub(crate) fn write_feature_explanation<W>(
+    mut writer: W,
+    explanation: &FeatureExplanation<'_>,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let id = explanation.id;
+
+    match explanation.records {
+        Some(records) => writeln!(
+            writer,
+            "{}: {} '{}' records: {}",
+            id,
+            records.len(),
+            explanation.feature_type,
+            format_intervals(records)
+        )?,
+        None => writeln!(
+            writer,
+            "{}: '{}' records are not listed for standard input",
+            id, explanation.feature_type
+        )?,
+    }
+
+    let merged_length: u64 = explanation.intervals.iter().map(|f| f.len()).sum();
+
+    writeln!(
+        writer,
+        "{}: merged intervals: {} ({} bp)",
+        id,
+        format_intervals(explanation.intervals),
+        merged_length
+    )?;
+
+    match explanation.length {
+        Some(length) => writeln!(
+            writer,
+            "{}: length: {} (--length-source {})",
+            id, length, explanation.length_source
+        ),
+        None => writeln!(
+            writer,
+            "{}: no length (--length-source {})",
+            id, explanation.length_source
+        ),
+    }
+}
+
+// Explains the arithmetic of the expression value of a feature in a sample.
+pub(crate) fn write_sample_explanation<W>(
+    mut writer: W,
+    id: &str,
+    sample_name: &str,
+    unit: &str,
+    counts: &Counts,
+    lengths: &Lengths,
+    values: &Expressions,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let count = counts.get(id).copied().unwrap_or(0);
+    let library_size = sum_counts(counts);
+
+    writeln!(
+        writer,
+        "{} ({}): count: {}, library size: {}",
+        id, sample_name, count, library_size
+    )?;
+
+    let value = match values.get(id) {
+        Some(value) => value,
+        None => return writeln!(writer, "{} ({}): not in the counts", id, sample_name),
+    };
+
+    let length = lengths.get(id).copied().unwrap_or(0);
+
+    match unit {
+        "fpkm" => writeln!(
+            writer,
+            "{} ({}): fpkm = count * 1e9 / (length * library size) = {} * 1e9 / ({} * {}) = {}",
+            id, sample_name, count, length, library_size, value
+        ),
+        "tpm" | "cpm" => {
+            let rate_sum: f64 = counts
+                .iter()
+                .filter_map(|(id, &n)| lengths.get(id).map(|&len| n as f64 / len as f64))
+                .sum();
+
+            writeln!(
+                writer,
+                "{} ({}): {} = (count / length) / sum(count / length) * 1e6 = ({} / {}) / {} * 1e6 = {}",
+                id, sample_name, unit, count, length, rate_sum, value
+            )
+        }
+        _ => writeln!(writer, "{} ({}): {} = {}", id, sample_name, unit, value),
+    }
+}
+
+fn format_intervals(intervals: &[Feature]) -> String {
+    intervals
+        .iter()
+        .map(|f| format!("[{}, {}]", f.start, f.end))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Detected features are written as a binary matrix, followed by a row of the
+// number of detected features in each sample.
+pub(crate) fn write_detection<W>(mut writer: W, detected: &ExpressionMatrix) -> io::Result<()>
+where
+    W: Write,
+{
+    write_matrix(&mut writer, "detected", detected)?;
+
+    write!(writer, "{}", DETECTED_TOTALS_NAME)?;
+
+    for column in detected.data().gencolumns() {
+        write!(writer, "\t{}", column.sum())?;
+    }
+
+    writeln!(writer)?;
+
+    writer.flush()
+}
+
+// Writes two lists of feature IDs per sample, one per line, in row order: the
+// features with a value of at least the threshold (`<sample>.above.txt`) and
+// the rest (`<sample>.below.txt`).
+pub(crate) fn write_gene_lists(
+    dst: &str,
+    matrix: &ExpressionMatrix,
+    threshold: f64,
+) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    let dst = Path::new(dst);
+
+    for name in matrix.column_names() {
+        let column = matrix.column(name).unwrap();
+
+        let mut above =
+            File::create(dst.join(format!("{}.above.txt", name))).map(BufWriter::new)?;
+        let mut below =
+            File::create(dst.join(format!("{}.below.txt", name))).map(BufWriter::new)?;
+
+        for (id, &value) in matrix.row_names().iter().zip(column.iter()) {
+            if value >= threshold {
+                writeln!(above, "{}", id)?;
+            } else {
+                writeln!(below, "{}", id)?;
+            }
+        }
+
+        above.flush()?;
+        below.flush()?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_top_features<W>(
+    mut writer: W,
+    names: &[String],
+    top_features: &[Vec<TopFeature>],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    for (sample_name, features) in names.iter().zip(top_features) {
+        for (i, feature) in features.iter().enumerate() {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                sample_name,
+                i + 1,
+                feature.name,
+                feature.value,
+                feature.cumulative_fraction
+            )?;
+        }
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn write_dose_responses<W>(
+    mut writer: W,
+    names: &[String],
+    dose_responses: &[Option<DoseResponse>],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    for (name, dose_response) in names.iter().zip(dose_responses) {
+        match dose_response {
+            Some(fit) => writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                name, fit.n, fit.slope, fit.intercept, fit.r_squared
+            )?,
+            None => warn!("{}: too few spike-ins detected for dose-response fit", name),
+        }
+    }
+
+    writer.flush()
+}
+
+pub(crate) struct QcMetrics {
+    pub(crate) count: u64,
+    pub(crate) mitochondrial_fraction: f64,
+    pub(crate) rrna_fraction: f64,
+}
+
+// Writes the method comparisons of samples as a long table, one row per
+// sample and feature.
+pub(crate) struct MethodComparisonWriter<W> {
+    inner: W,
+}
+
+impl<W> MethodComparisonWriter<W>
+where
+    W: Write,
+{
+    pub(crate) fn new(mut inner: W) -> io::Result<Self> {
+        write!(inner, "sample\tid")?;
+
+        for method in &COMPARED_METHODS {
+            write!(inner, "\t{}", method.as_str())?;
+        }
+
+        for method in &COMPARED_METHODS {
+            write!(inner, "\t{}_rank", method.as_str())?;
+        }
+
+        writeln!(inner, "\tmax_rank_shift")?;
+
+        Ok(Self { inner })
+    }
+
+    pub(crate) fn write_sample(
+        &mut self,
+        name: &str,
+        comparisons: &[MethodComparison],
+    ) -> io::Result<()> {
+        for comparison in comparisons {
+            write!(self.inner, "{}\t{}", name, comparison.id)?;
+
+            for value in comparison.values.iter().chain(&comparison.ranks) {
+                write!(self.inner, "\t{}", value)?;
+            }
+
+            writeln!(self.inner, "\t{}", comparison.max_rank_shift())?;
+        }
+
+        self.inner.flush()
+    }
+}
+
+pub(crate) fn write_length_report<W>(
+    mut writer: W,
+    distributions: &[(&str, LengthDistribution)],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    writeln!(writer, "biotype\tcount\tmin\tq1\tmedian\tq3\tmax\tmean")?;
+
+    for (biotype, d) in distributions {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            biotype, d.count, d.min, d.q1, d.median, d.q3, d.max, d.mean
+        )?;
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn write_qc_metrics<W>(mut writer: W, metrics: &[(&str, QcMetrics)]) -> io::Result<()>
+where
+    W: Write,
+{
+    writeln!(
+        writer,
+        "sample\tcount\tmitochondrial_fraction\trrna_fraction"
+    )?;
+
+    for (name, m) in metrics {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            name, m.count, m.mitochondrial_fraction, m.rrna_fraction
+        )?;
+    }
+
+    writer.flush()
+}
+
+// Writes the QC metrics of samples grouped by stratum, with the number of
+// samples in each stratum listed in comment lines before the header. Samples
+// with no value in the stratifying column are omitted.
+pub(crate) fn write_stratified_qc_metrics<W>(
+    mut writer: W,
+    column: &str,
+    strata: &[(&str, Vec<usize>)],
+    metrics: &[(&str, QcMetrics)],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    for (stratum, indices) in strata {
+        writeln!(writer, "# {}: n={}", stratum, indices.len())?;
+    }
+
+    writeln!(
+        writer,
+        "{}\tsample\tcount\tmitochondrial_fraction\trrna_fraction",
+        column
+    )?;
+
+    for (stratum, indices) in strata {
+        for &i in indices {
+            let (name, m) = &metrics[i];
+
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                stratum, name, m.count, m.mitochondrial_fraction, m.rrna_fraction
+            )?;
+        }
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn write_sex_inferences<W>(
+    mut writer: W,
+    names: &[String],
+    inferences: &[SexInference],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    for (name, inference) in names.iter().zip(inferences) {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            name, inference.female_expression, inference.male_expression, inference.sex
+        )?;
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn write_size_factors<W>(
+    mut writer: W,
+    names: &[String],
+    size_factors: &[f64],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    for (name, size_factor) in names.iter().zip(size_factors) {
+        writeln!(writer, "{}\t{}", name, size_factor)?;
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn write_group_summary<W>(
+    mut writer: W,
+    label: &str,
+    groups: &[(&str, Vec<usize>)],
+    means: &ExpressionMatrix,
+    medians: &ExpressionMatrix,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    for (name, columns) in groups {
+        writeln!(writer, "# {}: n={}", name, columns.len())?;
+    }
+
+    write!(writer, "{}", label)?;
+
+    for (name, _) in groups {
+        write!(writer, "\t{}_mean\t{}_median", name, name)?;
+    }
+
+    writeln!(writer)?;
+
+    let rows = means.data().outer_iter().zip(medians.data().outer_iter());
+
+    for (name, (mean_row, median_row)) in means.row_names().iter().zip(rows) {
+        let values: Vec<f64> = mean_row
+            .iter()
+            .zip(median_row.iter())
+            .flat_map(|(&mean, &median)| vec![mean, median])
+            .collect();
+
+        write_matrix_row(&mut writer, name, values.iter())?;
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn write_stabilities<W>(mut writer: W, stabilities: &[Stability]) -> io::Result<()>
+where
+    W: Write,
+{
+    for (i, stability) in stabilities.iter().enumerate() {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}",
+            i + 1,
+            stability.name,
+            stability.mean,
+            stability.cv,
+            stability.m
+        )?;
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn write_resource_report(dst: Option<&str>, usage: &ResourceUsage) {
+    let elapsed = usage.elapsed().as_secs_f64();
+
+    match peak_rss() {
+        Some(n) => info!("wall time: {:.3} s, peak RSS: {} bytes", elapsed, n),
+        None => info!("wall time: {:.3} s", elapsed),
+    }
+
+    if let Some(dst) = dst {
+        write_output(dst, |writer| write_resource_usage(writer, usage));
+    }
+}
+
+// The outputs of a run as plain files for, e.g., a `SummarizedExperiment`.
+pub(crate) struct Bundle<'a> {
+    pub(crate) unit: &'a str,
+    pub(crate) matrix: &'a ExpressionMatrix,
+    pub(crate) counts: &'a ExpressionMatrix,
+    pub(crate) lengths: &'a Lengths,
+    pub(crate) sample_sheet: Option<&'a SampleSheet>,
+    pub(crate) gene_metadata: Option<&'a GeneMetadata>,
+}
+
+// Writes `counts.tsv`, `coldata.tsv`, `rowdata.tsv`, and `<unit>.tsv` to a
+// directory. The rows and columns of every file are in the order of the
+// expression matrix.
+pub(crate) fn write_bundle(dst: &str, bundle: &Bundle<'_>) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    let dst = Path::new(dst);
+
+    let writer = File::create(dst.join("counts.tsv")).map(BufWriter::new)?;
+    write_matrix(writer, "count", bundle.counts)?;
+
+    let writer = File::create(dst.join("coldata.tsv")).map(BufWriter::new)?;
+    write_column_data(writer, bundle.matrix.column_names(), bundle.sample_sheet)?;
+
+    let writer = File::create(dst.join("rowdata.tsv")).map(BufWriter::new)?;
+    write_row_data(
+        writer,
+        bundle.matrix.row_names(),
+        bundle.lengths,
+        bundle.gene_metadata,
+    )?;
+
+    let writer = File::create(dst.join(format!("{}.tsv", bundle.unit))).map(BufWriter::new)?;
+    write_matrix(writer, bundle.unit, bundle.matrix)
+}
+
+// Without a sample sheet, only the sample names are written.
+fn write_column_data<W>(
+    mut writer: W,
+    names: &[String],
+    sample_sheet: Option<&SampleSheet>,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let sample_sheet = match sample_sheet {
+        Some(sample_sheet) => sample_sheet,
+        None => {
+            writeln!(writer, "sample")?;
+
+            for name in names {
+                writeln!(writer, "{}", name)?;
+            }
+
+            return writer.flush();
+        }
+    };
+
+    let has_batches = sample_sheet.has_batches();
+
+    if has_batches {
+        writeln!(writer, "sample\tcondition\tbatch")?;
+    } else {
+        writeln!(writer, "sample\tcondition")?;
+    }
+
+    for name in names {
+        let sample = sample_sheet
+            .samples()
+            .iter()
+            .find(|sample| &sample.name == name);
+
+        let condition = sample.map(|s| s.condition.as_str()).unwrap_or("NA");
+        write!(writer, "{}\t{}", name, condition)?;
+
+        if has_batches {
+            let batch = sample.and_then(|s| s.batch.as_deref()).unwrap_or("NA");
+            write!(writer, "\t{}", batch)?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    writer.flush()
+}
+
+// Features without a length are written as NA.
+fn write_row_data<W>(
+    mut writer: W,
+    ids: &[String],
+    lengths: &Lengths,
+    gene_metadata: Option<&GeneMetadata>,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    write!(writer, "feature_id\tlength")?;
+
+    if let Some(metadata) = gene_metadata {
+        for name in metadata.column_names() {
+            write!(writer, "\t{}", name)?;
+        }
+    }
+
+    writeln!(writer)?;
+
+    for id in ids {
+        match lengths.get(id.as_str()) {
+            Some(length) => write!(writer, "{}\t{}", id, length)?,
+            None => write!(writer, "{}\tNA", id)?,
+        }
+
+        if let Some(metadata) = gene_metadata {
+            write_metadata_fields(&mut writer, metadata, id)?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    writer.flush()
+}
+
+pub(crate) fn write_ids<W>(mut writer: W, ids: &BTreeSet<FeatureId>) -> io::Result<()>
+where
+    W: Write,
+{
+    for id in ids {
+        writeln!(writer, "{}", id)?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use noodles_fpkm::{
+        calculate_fpkms,
+        groups::{group_means, group_medians},
+        lengths::summarize_lengths,
+        sample_sheet::read_sample_sheet,
+    };
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+
+    #[test]
+    fn test_build_rarefaction_depths() {
+        assert_eq!(
+            build_rarefaction_depths(1000),
+            [100, 200, 300, 400, 500, 600, 700, 800, 900, 1000]
+        );
+        assert_eq!(build_rarefaction_depths(3), [1, 2, 3]);
+        assert!(build_rarefaction_depths(0).is_empty());
+    }
+
+    #[test]
+    fn test_write_rarefaction_curves() {
+        let samples = [("sample1", vec![5, 0, 5])];
+        let mut rng = Pcg64::seed_from_u64(0);
+
+        let mut buf = Vec::new();
+        let depths = build_rarefaction_depths(10);
+        write_rarefaction_curves(&mut buf, &samples, &depths, &mut rng).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        assert_eq!(actual.lines().count(), 10);
+        assert!(actual.starts_with("sample1\t1\t1\n"));
+    }
+
+    #[test]
+    fn test_write_length_report() -> io::Result<()> {
+        let distribution = summarize_lengths(vec![8, 2, 5]).unwrap();
+
+        let mut buf = Vec::new();
+        write_length_report(&mut buf, &[(ALL_BIOTYPES, distribution)])?;
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "\
+biotype\tcount\tmin\tq1\tmedian\tq3\tmax\tmean
+__all\t3\t2\t2\t5\t8\t8\t5
+";
+
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_group_summary() -> io::Result<()> {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("AAAS")],
+            vec![
+                String::from("sample1"),
+                String::from("sample2"),
+                String::from("sample3"),
+            ],
+            ndarray::arr2(&[[1.0, 3.0, 8.0]]),
+        );
+
+        let groups = [("control", vec![0, 1]), ("treated", vec![2])];
+        let means = group_means(&matrix, &groups);
+        let medians = group_medians(&matrix, &groups);
+
+        let mut buf = Vec::new();
+        write_group_summary(&mut buf, "tpm", &groups, &means, &medians)?;
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "\
+# control: n=2
+# treated: n=1
+tpm\tcontrol_mean\tcontrol_median\ttreated_mean\ttreated_median
+AAAS\t2\t2\t8\t8
+";
+
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_sample_explanation() -> io::Result<()> {
+        let counts: Counts = [
+            (FeatureId::from("AAAS"), 2),
+            (FeatureId::from("RPL37AP1"), 6),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let lengths: Lengths = [
+            (FeatureId::from("AAAS"), 1000),
+            (FeatureId::from("RPL37AP1"), 2000),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let values = calculate_fpkms(&counts, &lengths).unwrap();
+
+        let mut buf = Vec::new();
+        write_sample_explanation(&mut buf, "AAAS", "s1", "fpkm", &counts, &lengths, &values)?;
+
+        let expected = "\
+AAAS (s1): count: 2, library size: 8
+AAAS (s1): fpkm = count * 1e9 / (length * library size) = 2 * 1e9 / (1000 * 8) = 250000
+";
+
+        assert_eq!(String::from_utf8(buf).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_method_comparison_writer() -> io::Result<()> {
+        let comparisons = [MethodComparison {
+            id: FeatureId::from("AAAS"),
+            values: [250000.0, 400000.0, 500000.0, 250000.0],
+            ranks: [1.0, 1.0, 1.0, 2.5],
+        }];
+
+        let mut buf = Vec::new();
+        let mut writer = MethodComparisonWriter::new(&mut buf)?;
+        writer.write_sample("s1", &comparisons)?;
+
+        let expected = "\
+sample\tid\tfpkm\ttpm\tfpkm_uq\ttmm_cpm\tfpkm_rank\ttpm_rank\tfpkm_uq_rank\ttmm_cpm_rank\tmax_rank_shift
+s1\tAAAS\t250000\t400000\t500000\t250000\t1\t1\t1\t2.5\t1.5
+";
+
+        assert_eq!(String::from_utf8(buf).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_column_data() {
+        let names = [String::from("sample2"), String::from("sample1")];
+
+        let data = "sample\tcondition\tbatch\nsample1\ttreated\t1\nsample2\tcontrol\t2\n";
+        let sample_sheet = read_sample_sheet(data.as_bytes()).unwrap();
+
+        let mut buf = Vec::new();
+        write_column_data(&mut buf, &names, Some(&sample_sheet)).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "\
+sample\tcondition\tbatch
+sample2\tcontrol\t2
+sample1\ttreated\t1
+";
+
+        assert_eq!(actual, expected);
+
+        let mut buf = Vec::new();
+        write_column_data(&mut buf, &names, None).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "sample\nsample2\nsample1\n"
+        );
+    }
+
+    #[test]
+    fn test_write_detection() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("AAAS"), String::from("RPL37AP1")],
+            vec![String::from("sample1"), String::from("sample2")],
+            ndarray::arr2(&[[0.5, 2.0], [1.0, 3.0]]),
+        );
+
+        let mut buf = Vec::new();
+        write_detection(&mut buf, &matrix.detect(1.0)).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "\
+detected\tsample1\tsample2
+AAAS\t0\t1
+RPL37AP1\t1\t1
+__detected\t1\t2
+";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_write_top_features() {
+        let names = [String::from("sample1")];
+        let top = [vec![
+            TopFeature {
+                name: String::from("HBB"),
+                value: 600000.0,
+                cumulative_fraction: 0.6,
+            },
+            TopFeature {
+                name: String::from("MT-CO1"),
+                value: 300000.0,
+                cumulative_fraction: 0.9,
+            },
+        ]];
+
+        let mut buf = Vec::new();
+        write_top_features(&mut buf, &names, &top).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "sample1\t1\tHBB\t600000\t0.6\nsample1\t2\tMT-CO1\t300000\t0.9\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_write_dose_responses() {
+        let names = [String::from("sample1"), String::from("sample2")];
+        let dose_responses = [
+            Some(DoseResponse {
+                n: 40,
+                slope: 0.95,
+                intercept: 1.5,
+                r_squared: 0.9,
+            }),
+            None,
+        ];
+
+        let mut buf = Vec::new();
+        write_dose_responses(&mut buf, &names, &dose_responses).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        assert_eq!(actual, "sample1\t40\t0.95\t1.5\t0.9\n");
+    }
+
+    #[test]
+    fn test_write_qc_metrics() {
+        let metrics = [(
+            "sample1",
+            QcMetrics {
+                count: 6360,
+                mitochondrial_fraction: 0.25,
+                rrna_fraction: 0.0,
+            },
+        )];
+
+        let mut buf = Vec::new();
+        write_qc_metrics(&mut buf, &metrics).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "\
+sample\tcount\tmitochondrial_fraction\trrna_fraction
+sample1\t6360\t0.25\t0
+";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_write_sex_inferences() {
+        use noodles_fpkm::qc::Sex;
+
+        let names = [String::from("sample1")];
+        let inferences = [SexInference {
+            female_expression: 85.0,
+            male_expression: 0.5,
+            sex: Sex::Female,
+        }];
+
+        let mut buf = Vec::new();
+        write_sex_inferences(&mut buf, &names, &inferences).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        assert_eq!(actual, "sample1\t85\t0.5\tfemale\n");
+    }
+
+    #[test]
+    fn test_write_size_factors() {
+        let names = [String::from("sample1"), String::from("sample2")];
+
+        let mut buf = Vec::new();
+        write_size_factors(&mut buf, &names, &[0.5, 2.0]).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        assert_eq!(actual, "sample1\t0.5\nsample2\t2\n");
+    }
+
+    #[test]
+    fn test_write_stabilities() {
+        let stabilities = [Stability {
+            name: String::from("GAPDH"),
+            mean: 20.0,
+            cv: 0.25,
+            m: 0.5,
+        }];
+
+        let mut buf = Vec::new();
+        write_stabilities(&mut buf, &stabilities).unwrap();
+
+        assert_eq!(buf, b"1\tGAPDH\t20\t0.25\t0.5\n");
+    }
+}
@@ -0,0 +1,240 @@
+use std::{cell::RefCell, collections::HashSet, io, path::Path, time::Instant};
+
+use clap::ArgMatches;
+use log::{info, warn};
+use noodles_fpkm::{
+    calculate_sparse_fpkms, calculate_sparse_tpms,
+    downsampling::downsample,
+    lengths::calculate_unit_lengths,
+    mtx::{read_10x, read_names, write_10x},
+    normalization::{log1p, normalize_median, normalize_total, COUNTS_PER_10K},
+    resources::ResourceUsage,
+    sparse::SparseExpressionMatrix,
+};
+use rand_pcg::Pcg64;
+
+use crate::{
+    cli::{
+        quantify::AnnotationLoader,
+        reports::{build_rarefaction_depths, write_rarefaction_curves, write_resource_report},
+    },
+    exit_with_calculation_error, exit_with_error, exit_with_io_error, parse_output_format,
+    write_output,
+};
+
+pub(crate) fn filter_barcodes(matrix: &mut SparseExpressionMatrix, src: &str) -> io::Result<()> {
+    let whitelist: HashSet<String> = read_names(src)?.into_iter().collect();
+
+    let total = matrix.column_names().len();
+    matrix.retain_columns(|barcode| whitelist.contains(barcode));
+    let kept = matrix.column_names().len();
+
+    info!(
+        "kept {} of {} barcodes ({} dropped)",
+        kept,
+        total,
+        total - kept
+    );
+
+    Ok(())
+}
+
+// Returns the first argument given that cannot be used with 10x Genomics input.
+//
+// `--id-normalize` has a default, so it is only a conflict when it is not
+// `none`.
+fn tenx_conflicting_arg(matches: &ArgMatches) -> Option<&'static str> {
+    const ARGS: [&str; 14] = [
+        "abundances",
+        "transcript-abundances",
+        "antisense",
+        "raw-counts",
+        "size-factors",
+        "emit-size-factors",
+        "qc-report",
+        "length-report",
+        "indexed-output",
+        "compensated-summation",
+        "id-normalize",
+        "id-map",
+        "compare-against",
+        "method-comparison",
+    ];
+
+    ARGS.iter().copied().find(|&arg| match arg {
+        "id-normalize" => matches.value_of(arg) != Some("none"),
+        _ => matches.is_present(arg),
+    })
+}
+
+// Quantifies the barcodes of a 10x Genomics matrix directory and writes them
+// to --output.
+pub(crate) fn quantify_10x(
+    matches: &ArgMatches<'_>,
+    counts_src: &Path,
+    loader: &AnnotationLoader<'_>,
+    downsample_depth: Option<u64>,
+    rng: &mut Pcg64,
+    resource_usage: &RefCell<ResourceUsage>,
+) {
+    let unit = matches.value_of("unit").unwrap();
+
+    if unit == "scaled" {
+        exit_with_error(
+            "--unit scaled cannot be used with 10x Genomics input",
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    if let Some(arg) = tenx_conflicting_arg(matches) {
+        exit_with_error(
+            &format!("--{} cannot be used with 10x Genomics input", arg),
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    if !parse_output_format(matches).is_default() {
+        exit_with_error(
+            "--delimiter and --decimal-separator cannot be used with 10x Genomics input",
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    let dst = matches.value_of_os("output").unwrap_or_else(|| {
+        exit_with_error(
+            "--output is required for 10x Genomics input",
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+    });
+
+    let start = Instant::now();
+    let mut counts = read_10x(counts_src)
+        .unwrap_or_else(|e| exit_with_io_error(e, &counts_src.to_string_lossy()));
+    resource_usage
+        .borrow_mut()
+        .add("counts", start.elapsed(), counts.nnz() as u64);
+
+    if let Some(src) = matches.value_of("barcodes") {
+        filter_barcodes(&mut counts, src).unwrap_or_else(|e| exit_with_io_error(e, src));
+    }
+
+    if let Some(dst) = matches.value_of("rarefaction") {
+        let samples: Vec<_> = (0..counts.shape().1)
+            .map(|j| {
+                let name = counts.column_names()[j].as_str();
+                let values: Vec<u64> = counts.column(j).1.iter().map(|&n| n as u64).collect();
+                (name, values)
+            })
+            .collect();
+
+        let max_depth = downsample_depth.unwrap_or_else(|| {
+            samples
+                .iter()
+                .map(|(_, values)| values.iter().sum())
+                .max()
+                .unwrap_or(0)
+        });
+
+        let depths = build_rarefaction_depths(max_depth);
+        write_output(dst, |writer| {
+            write_rarefaction_curves(writer, &samples, &depths, rng)
+        });
+    }
+
+    if let Some(depth) = downsample_depth {
+        let shallow_column_count = downsample(&mut counts, depth, rng);
+
+        if shallow_column_count > 0 {
+            warn!(
+                "{} barcodes have no more than {} counts and were not downsampled",
+                shallow_column_count, depth
+            );
+        }
+    }
+
+    let mut expressions = match unit {
+        "fpkm" => {
+            let lengths = loader.lengths(&loader.features());
+            calculate_sparse_fpkms(&counts, &lengths).unwrap_or_else(|e| {
+                exit_with_calculation_error(e, &lengths, loader.aliases().as_ref())
+            })
+        }
+        "tpm" => {
+            let lengths = loader.lengths(&loader.features());
+            calculate_sparse_tpms(&counts, &lengths).unwrap_or_else(|e| {
+                exit_with_calculation_error(e, &lengths, loader.aliases().as_ref())
+            })
+        }
+        "cpm" => {
+            let lengths = calculate_unit_lengths(&loader.features());
+            calculate_sparse_tpms(&counts, &lengths).unwrap_or_else(|e| {
+                exit_with_calculation_error(e, &lengths, loader.aliases().as_ref())
+            })
+        }
+        "cp10k" => {
+            normalize_total(&mut counts, COUNTS_PER_10K);
+            counts
+        }
+        "median" => {
+            if let Some(median) = normalize_median(&mut counts) {
+                info!("median library size: {}", median);
+            }
+
+            counts
+        }
+        _ => unreachable!(),
+    };
+
+    if matches.is_present("log1p") {
+        log1p(&mut expressions);
+    }
+
+    resource_usage
+        .borrow_mut()
+        .add_remaining("compute", expressions.nnz() as u64);
+
+    let start = Instant::now();
+    write_10x(dst, &expressions).unwrap();
+    resource_usage
+        .borrow_mut()
+        .add("write", start.elapsed(), expressions.nnz() as u64);
+
+    write_resource_report(
+        matches.value_of("resource-report"),
+        &resource_usage.borrow(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::app::app;
+
+    use super::*;
+
+    #[test]
+    fn test_tenx_conflicting_arg() {
+        let args = [
+            "noodles-fpkm",
+            "-a",
+            "annotations.gtf",
+            "-o",
+            "out",
+            "tenx/",
+        ];
+        let matches = app().get_matches_from(&args);
+        assert_eq!(tenx_conflicting_arg(&matches), None);
+
+        let args = [
+            "noodles-fpkm",
+            "-a",
+            "annotations.gtf",
+            "-o",
+            "out",
+            "--id-normalize",
+            "case",
+            "tenx/",
+        ];
+        let matches = app().get_matches_from(&args);
+        assert_eq!(tenx_conflicting_arg(&matches), Some("id-normalize"));
+    }
+}
@@ -0,0 +1,188 @@
+use std::{fs, path::Path, thread, time::Duration};
+
+use clap::{value_t, ArgMatches};
+use log::{info, warn};
+use noodles_fpkm::{
+    calculate_fpkms, calculate_tpms_with_summation,
+    counts::{sum_counts, ReadCountsOptions},
+    error_report::ErrorReport,
+    expression_set::{merge_expression_sets, ExpressionSet, Method},
+    lengths::Lengths,
+    summation::Summation,
+    unit::Unit,
+    watch::DirectoryWatcher,
+};
+
+use crate::{
+    exit_with_clap_error, exit_with_error, exit_with_io_error, read_samples, write_expressions,
+    write_matrix, write_output,
+};
+
+const DEFAULT_WATCH_INTERVAL: u64 = 5;
+
+const WATCH_MATRIX_NAME: &str = "matrix.tsv";
+
+// Quantifies each counts file that appears in `src`, writing its expressions
+// to `dst/<sample>.tsv` and rewriting the matrix of all samples so far to
+// `dst/matrix.tsv`. Files that cannot be read or quantified are skipped.
+fn watch_counts(
+    src: &str,
+    dst: &str,
+    interval: Duration,
+    method: Method,
+    lengths: &Lengths,
+    summation: Summation,
+    read_counts_options: &ReadCountsOptions,
+) -> ! {
+    let dst = Path::new(dst);
+    fs::create_dir_all(dst).unwrap_or_else(|e| exit_with_io_error(e, &dst.to_string_lossy()));
+
+    let mut watcher = DirectoryWatcher::new(src);
+    let mut sets: Vec<ExpressionSet> = Vec::new();
+
+    info!("watching {} for counts files", src);
+
+    loop {
+        let paths = watcher
+            .poll()
+            .unwrap_or_else(|e| exit_with_io_error(e, src));
+
+        let set_count = sets.len();
+
+        for path in paths {
+            let samples = match read_samples(&path, read_counts_options) {
+                Ok(samples) => samples,
+                Err(e) => {
+                    warn!("{}: skipped: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            for (name, counts) in samples {
+                if sets.iter().any(|set| set.sample_name == name) {
+                    warn!(
+                        "{}: skipped: duplicate sample name: {}",
+                        path.display(),
+                        name
+                    );
+                    continue;
+                }
+
+                let result = match method {
+                    Method::Fpkm => calculate_fpkms(&counts, lengths),
+                    _ => calculate_tpms_with_summation(&counts, lengths, summation),
+                };
+
+                let values = match result {
+                    Ok(values) => values,
+                    Err(e) => {
+                        warn!(
+                            "{}: skipped: {}",
+                            path.display(),
+                            ErrorReport::from(&e).message
+                        );
+                        continue;
+                    }
+                };
+
+                let sample_dst = dst.join(format!("{}.tsv", name));
+                write_output(&sample_dst.to_string_lossy(), |writer| {
+                    write_expressions(writer, &values)
+                });
+
+                info!("quantified {} from {}", name, path.display());
+
+                sets.push(ExpressionSet {
+                    method,
+                    library_size: Some(sum_counts(&counts)),
+                    sample_name: name,
+                    values,
+                });
+            }
+        }
+
+        if sets.len() > set_count {
+            // The matrix is replaced atomically, so readers never see a
+            // partially written file.
+            let matrix = merge_expression_sets(&sets).unwrap();
+            let tmp = dst.join(format!(".{}.tmp", WATCH_MATRIX_NAME));
+            write_output(&tmp.to_string_lossy(), |writer| {
+                write_matrix(writer, Unit::from(method).as_str(), &matrix)
+            });
+
+            let matrix_dst = dst.join(WATCH_MATRIX_NAME);
+            fs::rename(&tmp, &matrix_dst)
+                .unwrap_or_else(|e| exit_with_io_error(e, &matrix_dst.to_string_lossy()));
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+// Quantifies counts files as they are added to the --watch directory.
+pub(crate) fn run_watch(
+    matches: &ArgMatches<'_>,
+    src: &str,
+    lengths: &Lengths,
+    unit_lengths: &Lengths,
+    summation: Summation,
+    read_counts_options: &ReadCountsOptions,
+) -> ! {
+    let unit = matches.value_of("unit").unwrap();
+
+    for arg in &[
+        "abundances",
+        "transcript-abundances",
+        "antisense",
+        "raw-counts",
+        "size-factors",
+        "emit-size-factors",
+        "rarefaction",
+        "downsample",
+        "qc-report",
+        "chunk-size",
+        "indexed-output",
+        "id-map",
+    ] {
+        if matches.is_present(arg) {
+            exit_with_error(
+                &format!("--{} cannot be used with --watch", arg),
+                clap::ErrorKind::ArgumentConflict,
+            );
+        }
+    }
+
+    let method = match unit {
+        "fpkm" => Method::Fpkm,
+        "tpm" => Method::Tpm,
+        "cpm" => Method::Cpm,
+        _ => exit_with_error(
+            &format!("--watch cannot be used with --unit {}", unit),
+            clap::ErrorKind::ArgumentConflict,
+        ),
+    };
+
+    let lengths = if method == Method::Cpm {
+        unit_lengths
+    } else {
+        lengths
+    };
+
+    let dst = matches.value_of("watch-output").unwrap();
+
+    let interval = if matches.is_present("watch-interval") {
+        value_t!(matches, "watch-interval", u64).unwrap_or_else(|e| exit_with_clap_error(e))
+    } else {
+        DEFAULT_WATCH_INTERVAL
+    };
+
+    watch_counts(
+        src,
+        dst,
+        Duration::from_secs(interval),
+        method,
+        lengths,
+        summation,
+        read_counts_options,
+    )
+}
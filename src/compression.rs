@@ -0,0 +1,25 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use flate2::read::MultiGzDecoder;
+
+const GZ_EXTENSION: &str = "gz";
+
+// Opens a file for buffered reading, decompressing it if its extension is
+// `.gz`.
+pub(crate) fn open<P>(src: P) -> io::Result<Box<dyn BufRead>>
+where
+    P: AsRef<Path>,
+{
+    let path = src.as_ref();
+    let file = File::open(path)?;
+
+    if path.extension().and_then(|ext| ext.to_str()) == Some(GZ_EXTENSION) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
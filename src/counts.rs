@@ -1,16 +1,109 @@
 use std::{
     collections::hash_map::{Entry, HashMap},
     io::{self, Read},
+    path::Path,
+    str,
 };
 
-use csv::StringRecord;
+use csv::{ByteRecord, Trim};
+use log::warn;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{
+    feature_id::FeatureId,
+    features::with_line_number,
+    mtx::{self, is_mtx, read_mtx},
+    sparse::SparseExpressionMatrix,
+};
 
 const NAME_INDEX: usize = 0;
 const COUNT_INDEX: usize = 1;
 
+// The annotation columns that featureCounts writes between the feature ID and
+// the counts.
+static FEATURE_COUNTS_ANNOTATION_HEADERS: [&str; 5] = ["Chr", "Start", "End", "Strand", "Length"];
+
+// The header of featureCounts output up to the first sample.
+static FEATURE_COUNTS_HEADER_PREFIX: &str = "Geneid\tChr\tStart\tEnd\tStrand\tLength\t";
+
+// The summary rows at the top of STAR `ReadsPerGene.out.tab` files.
+static STAR_META_NAMES: [&str; 4] = ["N_unmapped", "N_multimapping", "N_noFeature", "N_ambiguous"];
+
+// The header of salmon `quant.sf` files.
+static SALMON_QUANT_HEADER: &str = "Name\tLength\tEffectiveLength\tTPM\tNumReads";
+static SALMON_NAME_COLUMN: &str = "Name";
+static SALMON_NUM_READS_COLUMN: &str = "NumReads";
+
 static HTSEQ_COUNT_META_PREFIX: &str = "__";
+const COMMENT_PREFIX: u8 = b'#';
+const BAM_EXTENSION: &str = ".bam";
+
+// Separates a message from a suggestion of how to fix the error, e.g.,
+// `invalid count: 6.5; hint: ...`.
+pub(crate) const HINT_DELIMITER: &str = "; hint: ";
 
-pub type Counts = HashMap<String, u64>;
+pub type Counts = HashMap<FeatureId, u64>;
+
+/// Options for reading counts.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReadCountsOptions {
+    /// The identifier prefix that marks the start of metadata rows, e.g.,
+    /// `__no_feature`. Reading stops at the first identifier with this prefix.
+    /// When `None`, all rows are read as counts.
+    ///
+    /// The default is `__`, as used by htseq-count.
+    pub meta_prefix: Option<String>,
+    /// Whether counts written with thousands separators (`1,234`) or decimal
+    /// commas (`12,0`), e.g., by spreadsheets, are accepted. A comma followed
+    /// by three digits is a thousands separator, and decimal commas must be
+    /// followed only by zeros. Converted counts are logged as a warning.
+    ///
+    /// The default is `false`.
+    pub lenient_numbers: bool,
+    /// The strandedness of the library, which selects the column of counts
+    /// in STAR `ReadsPerGene.out.tab` files. Other formats have a single
+    /// count per sample.
+    ///
+    /// The default is [`Strandedness::Unstranded`].
+    ///
+    /// [`Strandedness::Unstranded`]: enum.Strandedness.html#variant.Unstranded
+    pub strandedness: Strandedness,
+}
+
+impl Default for ReadCountsOptions {
+    fn default() -> Self {
+        ReadCountsOptions {
+            meta_prefix: Some(HTSEQ_COUNT_META_PREFIX.into()),
+            lenient_numbers: false,
+            strandedness: Strandedness::default(),
+        }
+    }
+}
+
+/// The strandedness of a library, as counted by STAR `--quantMode GeneCounts`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Strandedness {
+    /// Reads are counted on either strand (column 2; htseq-count `-s no`).
+    #[default]
+    Unstranded,
+    /// Reads are counted on the strand of the feature (column 3; htseq-count
+    /// `-s yes`).
+    Forward,
+    /// Reads are counted on the strand opposite the feature (column 4;
+    /// htseq-count `-s reverse`).
+    Reverse,
+}
+
+impl Strandedness {
+    fn star_count_index(self) -> usize {
+        match self {
+            Strandedness::Unstranded => 1,
+            Strandedness::Forward => 2,
+            Strandedness::Reverse => 3,
+        }
+    }
+}
 
 /// Reads TSV-formatted data and returns a map of feature ID-count pairs.
 ///
@@ -19,7 +112,12 @@ pub type Counts = HashMap<String, u64>;
 ///
 /// Reading stops at EOF or the first identifier that starts with "__". This
 /// prefix is considered to be a special counter or extra metadata, as defined
-/// by [htseq-count] > 0.5.4.
+/// by [htseq-count] > 0.5.4. Blank lines and comment lines, i.e., lines that
+/// start with `#`, are skipped.
+///
+/// Files edited in spreadsheet programs are also accepted: Windows (CRLF) line
+/// endings, a leading UTF-8 byte order mark (BOM), and whitespace around
+/// fields are ignored.
 ///
 /// [htseq-count]: https://htseq.readthedocs.io/en/release_0.11.1/count.html#usage
 ///
@@ -43,57 +141,914 @@ pub type Counts = HashMap<String, u64>;
 /// assert_eq!(counts["RPL37AP1"], 5714);
 /// ```
 pub fn read_counts<R>(reader: R) -> io::Result<Counts>
+where
+    R: Read,
+{
+    read_counts_with_options(reader, &ReadCountsOptions::default())
+}
+
+/// Reads TSV-formatted data using the given options and returns a map of
+/// feature ID-count pairs.
+///
+/// This is [`read_counts`] with a configurable metadata prefix, e.g., for
+/// counters that use a convention other than htseq-count's.
+///
+/// [`read_counts`]: fn.read_counts.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::counts::{read_counts_with_options, ReadCountsOptions};
+///
+/// let data = "\
+/// AAAS\t645
+/// N_unmapped\t136550
+/// ";
+///
+/// let options = ReadCountsOptions {
+///     meta_prefix: Some(String::from("N_")),
+///     ..Default::default()
+/// };
+///
+/// let counts = read_counts_with_options(data.as_bytes(), &options).unwrap();
+///
+/// assert_eq!(counts.len(), 1);
+/// assert_eq!(counts["AAAS"], 645);
+/// ```
+pub fn read_counts_with_options<R>(reader: R, options: &ReadCountsOptions) -> io::Result<Counts>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .comment(Some(COMMENT_PREFIX))
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(reader);
+
+    let meta_prefix = options.meta_prefix.as_deref();
+    let mut conversions = Conversions::default();
+
+    let mut counts = Counts::default();
+    let mut record = ByteRecord::new();
+
+    while rdr.read_byte_record(&mut record)? {
+        let line_number = record_line_number(&record);
+        let name = parse_name(&record).map_err(|e| with_line_number(e, line_number))?;
+
+        if let Some(prefix) = meta_prefix {
+            if name.starts_with(prefix) {
+                break;
+            }
+        }
+
+        let count = parse_count(&record)
+            .or_else(|e| parse_lenient_count(&record, COUNT_INDEX, options, &mut conversions, e))
+            .map_err(|e| with_line_number(e, line_number))?;
+
+        insert_count(&mut counts, name, count).map_err(|e| with_line_number(e, line_number))?;
+    }
+
+    conversions.warn();
+
+    Ok(counts)
+}
+
+/// Reads a TSV-formatted table of counts of multiple samples.
+///
+/// The first row is a header. Its first cell is ignored, and the remaining
+/// cells are the sample names. Each subsequent row is a feature identifier
+/// followed by the count in each sample. This is, e.g., the output of
+/// featureCounts given multiple BAM files; its annotation columns (`Chr`,
+/// `Start`, `End`, `Strand`, and `Length`) are skipped.
+///
+/// Comment lines, blank lines, and metadata rows are handled as in
+/// [`read_counts_with_options`]. The samples are returned in column order.
+///
+/// [`read_counts_with_options`]: fn.read_counts_with_options.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::counts::{read_counts_table, ReadCountsOptions};
+///
+/// let data = "\
+/// Geneid\tsample1\tsample2
+/// AAAS\t645\t512
+/// RPL37AP1\t5714\t6022
+/// ";
+///
+/// let samples = read_counts_table(data.as_bytes(), &ReadCountsOptions::default()).unwrap();
+///
+/// assert_eq!(samples.len(), 2);
+/// assert_eq!(samples[0].0, "sample1");
+/// assert_eq!(samples[1].1["AAAS"], 512);
+/// ```
+pub fn read_counts_table<R>(
+    reader: R,
+    options: &ReadCountsOptions,
+) -> io::Result<Vec<(String, Counts)>>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .comment(Some(COMMENT_PREFIX))
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(reader);
+
+    let headers = rdr.headers()?.clone();
+
+    let first_count_index = headers
+        .iter()
+        .skip(COUNT_INDEX)
+        .zip(FEATURE_COUNTS_ANNOTATION_HEADERS.iter())
+        .take_while(|(a, b)| a == *b)
+        .count()
+        + COUNT_INDEX;
+
+    let mut samples: Vec<(String, Counts)> = headers
+        .iter()
+        .skip(first_count_index)
+        .map(|name| (name.into(), Counts::default()))
+        .collect();
+
+    let meta_prefix = options.meta_prefix.as_deref();
+    let mut conversions = Conversions::default();
+    let mut record = ByteRecord::new();
+
+    while rdr.read_byte_record(&mut record)? {
+        let line_number = record_line_number(&record);
+        let name = parse_name(&record).map_err(|e| with_line_number(e, line_number))?;
+
+        if let Some(prefix) = meta_prefix {
+            if name.starts_with(prefix) {
+                break;
+            }
+        }
+
+        for (i, (_, counts)) in samples.iter_mut().enumerate() {
+            let j = first_count_index + i;
+
+            parse_count_at(&record, j)
+                .or_else(|e| parse_lenient_count(&record, j, options, &mut conversions, e))
+                .and_then(|count| insert_count(counts, name, count))
+                .map_err(|e| with_line_number(e, line_number))?;
+        }
+    }
+
+    conversions.warn();
+
+    Ok(samples)
+}
+
+/// Returns whether TSV-formatted data is a table of counts of multiple
+/// samples rather than a list of counts of a single sample.
+///
+/// This checks whether the first line that is not blank or a comment has
+/// more than two fields. See [`read_counts_table`].
+///
+/// [`read_counts_table`]: fn.read_counts_table.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::counts::is_counts_table;
+///
+/// assert!(is_counts_table(b"Geneid\tsample1\tsample2\nAAAS\t645\t512\n"));
+/// assert!(!is_counts_table(b"AAAS\t645\nRPL37AP1\t5714\n"));
+/// ```
+pub fn is_counts_table(buf: &[u8]) -> bool {
+    buf.split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .find(|line| !line.is_empty() && line[0] != COMMENT_PREFIX)
+        .map(|line| {
+            line.split(|&b| b == b'\t')
+                .filter(|f| !f.is_empty())
+                .count()
+                > 2
+        })
+        .unwrap_or(false)
+}
+
+/// Reads the counts of a STAR `ReadsPerGene.out.tab` file.
+///
+/// Each row is a feature identifier followed by three counts: unstranded, on
+/// the strand of the feature, and on the opposite strand. The column is
+/// selected by the strandedness in the options. The summary rows at the top
+/// (`N_unmapped`, `N_multimapping`, `N_noFeature`, and `N_ambiguous`) are
+/// skipped. Comment lines, blank lines, and metadata rows are otherwise
+/// handled as in [`read_counts_with_options`].
+///
+/// [`read_counts_with_options`]: fn.read_counts_with_options.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::counts::{read_star_counts, ReadCountsOptions, Strandedness};
+///
+/// let data = "\
+/// N_unmapped\t2674\t2674\t2674
+/// N_multimapping\t3205\t3205\t3205
+/// N_noFeature\t1592\t38077\t38296
+/// N_ambiguous\t1081\t64\t36
+/// AAAS\t645\t12\t633
+/// ";
+///
+/// let counts = read_star_counts(data.as_bytes(), &ReadCountsOptions::default())?;
+/// assert_eq!(counts.len(), 1);
+/// assert_eq!(counts["AAAS"], 645);
+///
+/// let options = ReadCountsOptions {
+///     strandedness: Strandedness::Reverse,
+///     ..Default::default()
+/// };
+/// let counts = read_star_counts(data.as_bytes(), &options)?;
+/// assert_eq!(counts["AAAS"], 633);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_star_counts<R>(reader: R, options: &ReadCountsOptions) -> io::Result<Counts>
 where
     R: Read,
 {
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(false)
         .delimiter(b'\t')
+        .comment(Some(COMMENT_PREFIX))
+        .flexible(true)
+        .trim(Trim::All)
         .from_reader(reader);
 
-    let mut counts = Counts::new();
+    let count_index = options.strandedness.star_count_index();
+    let meta_prefix = options.meta_prefix.as_deref();
+    let mut conversions = Conversions::default();
 
-    for result in rdr.records() {
-        let record = result?;
+    let mut counts = Counts::default();
+    let mut record = ByteRecord::new();
 
-        let name = parse_name(&record)?;
+    while rdr.read_byte_record(&mut record)? {
+        let line_number = record_line_number(&record);
+        let name = parse_name(&record).map_err(|e| with_line_number(e, line_number))?;
 
-        if name.starts_with(HTSEQ_COUNT_META_PREFIX) {
-            break;
+        if STAR_META_NAMES.contains(&name) {
+            continue;
         }
 
-        let count = parse_count(&record)?;
+        if let Some(prefix) = meta_prefix {
+            if name.starts_with(prefix) {
+                break;
+            }
+        }
+
+        parse_count_at(&record, count_index)
+            .or_else(|e| parse_lenient_count(&record, count_index, options, &mut conversions, e))
+            .and_then(|count| insert_count(&mut counts, name, count))
+            .map_err(|e| with_line_number(e, line_number))?;
+    }
+
+    conversions.warn();
+
+    Ok(counts)
+}
+
+/// Reads the estimated counts of a salmon `quant.sf` file.
+///
+/// The header names the columns; the feature identifier is `Name`, and the
+/// count is `NumReads`. Estimated counts are fractional, so they are rounded
+/// to the nearest integer. salmon quantifies transcripts, so the features,
+/// e.g., `--type transcript --id transcript_id`, must be transcripts, too.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::counts::read_salmon_quant;
+///
+/// let data = "\
+/// Name\tLength\tEffectiveLength\tTPM\tNumReads
+/// ENST00000209873\t2541\t2370.254\t31.802\t644.628
+/// ENST00000415118\t8\t9.000\t0.000\t0.000
+/// ";
+///
+/// let counts = read_salmon_quant(data.as_bytes())?;
+/// assert_eq!(counts["ENST00000209873"], 645);
+/// assert_eq!(counts["ENST00000415118"], 0);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_salmon_quant<R>(reader: R) -> io::Result<Counts>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .trim(Trim::All)
+        .from_reader(reader);
+
+    let headers = rdr.byte_headers()?.clone();
+    let name_index = find_column(&headers, SALMON_NAME_COLUMN)?;
+    let num_reads_index = find_column(&headers, SALMON_NUM_READS_COLUMN)?;
+
+    let mut counts = Counts::default();
+    let mut record = ByteRecord::new();
+
+    while rdr.read_byte_record(&mut record)? {
+        let line_number = record_line_number(&record);
+
+        let name = record
+            .get(name_index)
+            .and_then(|s| str::from_utf8(s).ok())
+            .ok_or_else(|| invalid_cell("name", record.get(name_index)))
+            .map_err(|e| with_line_number(e, line_number))?;
 
-        insert_count(&mut counts, name, count)?;
+        parse_estimated_count(record.get(num_reads_index))
+            .and_then(|count| insert_count(&mut counts, name, count))
+            .map_err(|e| with_line_number(e, line_number))?;
     }
 
     Ok(counts)
 }
 
-fn parse_name(record: &StringRecord) -> io::Result<&str> {
+fn find_column(headers: &ByteRecord, name: &str) -> io::Result<usize> {
+    headers
+        .iter()
+        .position(|header| header == name.as_bytes())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("missing column: {}", name),
+            )
+        })
+}
+
+// Rounds an estimated (fractional) count to the nearest integer.
+fn parse_estimated_count(cell: Option<&[u8]>) -> io::Result<u64> {
+    cell.and_then(|s| str::from_utf8(s).ok())
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|n| n.is_finite() && *n >= 0.0 && *n < u64::MAX as f64)
+        .map(|n| n.round() as u64)
+        .ok_or_else(|| invalid_cell("count", cell))
+}
+
+// Splits a sparse matrix of features by samples into the counts of each
+// sample. Every feature has a count in every sample.
+fn sparse_counts(matrix: &SparseExpressionMatrix) -> io::Result<Vec<(String, Counts)>> {
+    let mut zeros = Counts::default();
+
+    for name in matrix.row_names() {
+        insert_count(&mut zeros, name, 0)?;
+    }
+
+    let mut samples = Vec::with_capacity(matrix.column_names().len());
+
+    for (j, sample_name) in matrix.column_names().iter().enumerate() {
+        let mut counts = zeros.clone();
+        let (rows, values) = matrix.column(j);
+
+        for (&i, &value) in rows.iter().zip(values) {
+            if value < 0.0 || value.fract() != 0.0 || value >= u64::MAX as f64 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid count: {}", value),
+                ));
+            }
+
+            counts.insert(matrix.row_names()[i].as_str().into(), value as u64);
+        }
+
+        samples.push((sample_name.clone(), counts));
+    }
+
+    Ok(samples)
+}
+
+/// A format of counts.
+///
+/// Count sources are registered in [`COUNT_SOURCES`] and chosen by
+/// [`detect_count_source`], so a new format only needs an implementation of
+/// this trait and an entry in the registry.
+///
+/// [`COUNT_SOURCES`]: static.COUNT_SOURCES.html
+/// [`detect_count_source`]: fn.detect_count_source.html
+pub trait CountSource: Sync {
+    /// Returns the name of the format.
+    fn name(&self) -> &'static str;
+
+    /// Returns whether the data is in this format.
+    fn detect(&self, buf: &[u8]) -> bool;
+
+    /// Reads the counts of each sample from the data.
+    ///
+    /// `sample_name` is the name of the sample when the format does not name
+    /// its samples, e.g., the file stem of the input.
+    fn read_samples(
+        &self,
+        buf: &[u8],
+        sample_name: &str,
+        options: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>>;
+
+    /// Reads the counts of each sample from the data of the file `src`.
+    ///
+    /// By default, this is [`read_samples`] with the file stem of `src` as
+    /// the sample name. Formats that span multiple files, e.g., MTX, read the
+    /// other files next to `src`.
+    ///
+    /// [`read_samples`]: #tymethod.read_samples
+    fn read_samples_from_path(
+        &self,
+        src: &Path,
+        buf: &[u8],
+        options: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>> {
+        let sample_name = src.file_stem().unwrap_or(src.as_os_str());
+        self.read_samples(buf, &sample_name.to_string_lossy(), options)
+    }
+}
+
+/// The counts of featureCounts, which is a counts table with annotation
+/// columns. See [`read_counts_table`].
+///
+/// featureCounts names samples by the paths of their BAM files, which are
+/// reduced to their file stems.
+///
+/// [`read_counts_table`]: fn.read_counts_table.html
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FeatureCounts;
+
+impl CountSource for FeatureCounts {
+    fn name(&self) -> &'static str {
+        "featureCounts"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        first_line(buf)
+            .map(|line| line.starts_with(FEATURE_COUNTS_HEADER_PREFIX.as_bytes()))
+            .unwrap_or(false)
+    }
+
+    fn read_samples(
+        &self,
+        buf: &[u8],
+        _: &str,
+        options: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>> {
+        read_counts_table(buf, options).map(with_bam_stems)
+    }
+}
+
+/// The counts of a single sample, as written by STAR `--quantMode
+/// GeneCounts` (`ReadsPerGene.out.tab`). See [`read_star_counts`].
+///
+/// [`read_star_counts`]: fn.read_star_counts.html
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StarReadsPerGene;
+
+impl CountSource for StarReadsPerGene {
+    fn name(&self) -> &'static str {
+        "STAR"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        first_line(buf)
+            .and_then(|line| line.split(|&b| b == b'\t').next())
+            .map(|name| name == STAR_META_NAMES[0].as_bytes())
+            .unwrap_or(false)
+    }
+
+    fn read_samples(
+        &self,
+        buf: &[u8],
+        sample_name: &str,
+        options: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>> {
+        let counts = read_star_counts(buf, options)?;
+        Ok(vec![(sample_name.into(), counts)])
+    }
+}
+
+/// The estimated counts of a single sample, as written by salmon
+/// (`quant.sf`). See [`read_salmon_quant`].
+///
+/// salmon names its output `quant.sf` in a directory named for the sample,
+/// so the sample is named by the directory.
+///
+/// [`read_salmon_quant`]: fn.read_salmon_quant.html
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SalmonQuant;
+
+impl CountSource for SalmonQuant {
+    fn name(&self) -> &'static str {
+        "salmon"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        first_line(buf)
+            .map(|line| line.starts_with(SALMON_QUANT_HEADER.as_bytes()))
+            .unwrap_or(false)
+    }
+
+    fn read_samples(
+        &self,
+        buf: &[u8],
+        sample_name: &str,
+        _: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>> {
+        let counts = read_salmon_quant(buf)?;
+        Ok(vec![(sample_name.into(), counts)])
+    }
+
+    fn read_samples_from_path(
+        &self,
+        src: &Path,
+        buf: &[u8],
+        options: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>> {
+        let dir_name = src
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .filter(|_| src.file_stem().map(|stem| stem == "quant").unwrap_or(false));
+
+        match dir_name {
+            Some(name) => self.read_samples(buf, &name.to_string_lossy(), options),
+            None => {
+                let sample_name = src.file_stem().unwrap_or(src.as_os_str());
+                self.read_samples(buf, &sample_name.to_string_lossy(), options)
+            }
+        }
+    }
+}
+
+/// The counts of samples in a Matrix Market exchange (MTX) file of features
+/// by samples. See [`read_mtx`].
+///
+/// The matrix is not self-describing, so the feature IDs and sample names are
+/// read from the features (`features.tsv` or `genes.tsv`) and barcodes
+/// (`barcodes.tsv`) files next to it, as in a 10x Genomics matrix directory.
+/// Counts must be nonnegative integers.
+///
+/// [`read_mtx`]: ../mtx/fn.read_mtx.html
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Mtx;
+
+impl CountSource for Mtx {
+    fn name(&self) -> &'static str {
+        "MTX"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        is_mtx(buf)
+    }
+
+    fn read_samples(
+        &self,
+        _: &[u8],
+        _: &str,
+        _: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>> {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "MTX counts must be read from a file with features and barcodes files next to it",
+        ))
+    }
+
+    fn read_samples_from_path(
+        &self,
+        src: &Path,
+        buf: &[u8],
+        _: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>> {
+        let dir = src.parent().unwrap_or_else(|| Path::new(""));
+        let (row_names, column_names) = mtx::read_10x_names(dir)?;
+        let matrix = read_mtx(buf, row_names, column_names)?;
+        sparse_counts(&matrix)
+    }
+}
+
+/// The counts of a single sample, as written by htseq-count. See
+/// [`read_counts_with_options`].
+///
+/// [`read_counts_with_options`]: fn.read_counts_with_options.html
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct HtseqCount;
+
+impl CountSource for HtseqCount {
+    fn name(&self) -> &'static str {
+        "htseq-count"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        !is_counts_table(buf)
+    }
+
+    fn read_samples(
+        &self,
+        buf: &[u8],
+        sample_name: &str,
+        options: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>> {
+        let counts = read_counts_with_options(buf, options)?;
+        Ok(vec![(sample_name.into(), counts)])
+    }
+}
+
+/// A table of the counts of multiple samples, e.g., as written by
+/// featureCounts. See [`read_counts_table`].
+///
+/// featureCounts names samples by the paths of their BAM files, which are
+/// reduced to their file stems.
+///
+/// [`read_counts_table`]: fn.read_counts_table.html
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CountsTable;
+
+impl CountSource for CountsTable {
+    fn name(&self) -> &'static str {
+        "table"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        is_counts_table(buf)
+    }
+
+    fn read_samples(
+        &self,
+        buf: &[u8],
+        _: &str,
+        options: &ReadCountsOptions,
+    ) -> io::Result<Vec<(String, Counts)>> {
+        read_counts_table(buf, options).map(with_bam_stems)
+    }
+}
+
+/// The registered count sources, in the order they are detected.
+///
+/// BAM files are not a count source: reads must be counted against the
+/// annotations first, e.g., with htseq-count or featureCounts.
+pub static COUNT_SOURCES: [&dyn CountSource; 6] = [
+    &FeatureCounts,
+    &StarReadsPerGene,
+    &SalmonQuant,
+    &Mtx,
+    &CountsTable,
+    &HtseqCount,
+];
+
+/// Returns the first registered count source that detects the format of the
+/// data.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::counts::detect_count_source;
+///
+/// let source = detect_count_source(b"Geneid\tsample1\tsample2\nAAAS\t645\t512\n");
+/// assert_eq!(source.map(|s| s.name()), Some("table"));
+///
+/// let source = detect_count_source(b"AAAS\t645\n");
+/// assert_eq!(source.map(|s| s.name()), Some("htseq-count"));
+/// ```
+pub fn detect_count_source(buf: &[u8]) -> Option<&'static dyn CountSource> {
+    COUNT_SOURCES
+        .iter()
+        .copied()
+        .find(|source| source.detect(buf))
+}
+
+// Returns the first line that is not blank or a comment, without its line
+// ending or a leading byte order mark.
+fn first_line(buf: &[u8]) -> Option<&[u8]> {
+    let buf = buf.strip_prefix(b"\xef\xbb\xbf").unwrap_or(buf);
+
+    buf.split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .find(|line| !line.is_empty() && line[0] != COMMENT_PREFIX)
+}
+
+// featureCounts names samples by the paths of their BAM files, which are
+// reduced to their file stems.
+fn with_bam_stems(samples: Vec<(String, Counts)>) -> Vec<(String, Counts)> {
+    samples
+        .into_iter()
+        .map(|(name, counts)| {
+            if name.ends_with(BAM_EXTENSION) {
+                let stem = Path::new(&name)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                (stem, counts)
+            } else {
+                (name, counts)
+            }
+        })
+        .collect()
+}
+
+/// Reads TSV-formatted data from an asynchronous reader and returns a map of
+/// feature ID-count pairs.
+///
+/// This is the asynchronous variant of [`read_counts`]. The input is read
+/// asynchronously into memory in full and then parsed, i.e., it is not parsed
+/// as it streams in, so peak memory includes the raw input.
+///
+/// [`read_counts`]: fn.read_counts.html
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// use noodles_fpkm::counts::read_counts_async;
+///
+/// let data = "\
+/// AAAS\t645
+/// RPL37AP1\t5714
+/// ";
+///
+/// let counts = read_counts_async(data.as_bytes()).await?;
+///
+/// assert_eq!(counts.len(), 2);
+/// assert_eq!(counts["AAAS"], 645);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "async")]
+pub async fn read_counts_async<R>(mut reader: R) -> io::Result<Counts>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).await?;
+    read_counts(&buf[..])
+}
+
+// Returns the 1-based line number a record starts on, or 0 if it is unknown.
+fn record_line_number(record: &ByteRecord) -> u64 {
+    record.position().map(|p| p.line()).unwrap_or_default()
+}
+
+fn parse_name(record: &ByteRecord) -> io::Result<&str> {
     let cell = record.get(NAME_INDEX);
 
-    cell.ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("invalid name: {:?}", cell),
-        )
+    cell.and_then(|s| str::from_utf8(s).ok())
+        .ok_or_else(|| invalid_cell("name", cell))
+}
+
+fn parse_count(record: &ByteRecord) -> io::Result<u64> {
+    parse_count_at(record, COUNT_INDEX)
+}
+
+fn parse_count_at(record: &ByteRecord, i: usize) -> io::Result<u64> {
+    let cell = record.get(i);
+
+    cell.and_then(parse_u64).ok_or_else(|| {
+        let e = invalid_cell("count", cell);
+
+        match cell.and_then(count_hint) {
+            Some(hint) => io::Error::new(e.kind(), format!("{}{}{}", e, HINT_DELIMITER, hint)),
+            None => e,
+        }
     })
 }
 
-fn parse_count(record: &StringRecord) -> io::Result<u64> {
-    let cell = record.get(COUNT_INDEX);
+// Retries a count that failed to parse (`e`) as a localized number when
+// lenient numbers are enabled.
+fn parse_lenient_count(
+    record: &ByteRecord,
+    i: usize,
+    options: &ReadCountsOptions,
+    conversions: &mut Conversions,
+    e: io::Error,
+) -> io::Result<u64> {
+    if options.lenient_numbers {
+        if let Some((s, count)) = record.get(i).and_then(parse_localized_u64) {
+            conversions.add(record_line_number(record), s, count);
+            return Ok(count);
+        }
+    }
+
+    Err(e)
+}
+
+// Parses a count written with thousands separators (`1,234,567`) or a decimal
+// comma followed only by zeros (`12,0`). A comma followed by three digits is a
+// thousands separator. The cell is returned with the count.
+fn parse_localized_u64(cell: &[u8]) -> Option<(&str, u64)> {
+    let s = str::from_utf8(cell).ok()?;
+    parse_grouped_u64(s)
+        .or_else(|| parse_decimal_comma_u64(s))
+        .map(|n| (s, n))
+}
+
+fn parse_grouped_u64(s: &str) -> Option<u64> {
+    let mut groups = s.split(',');
+    let first = groups.next()?;
+
+    if first.is_empty() || first.len() > 3 {
+        return None;
+    }
+
+    let mut digits = String::from(first);
+
+    for group in groups {
+        if group.len() != 3 {
+            return None;
+        }
+
+        digits.push_str(group);
+    }
+
+    parse_u64(digits.as_bytes())
+}
 
-    cell.and_then(|s| s.parse().ok()).ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("invalid count: {:?}", cell),
-        )
+fn parse_decimal_comma_u64(s: &str) -> Option<u64> {
+    let (int, frac) = s.split_once(',')?;
+
+    if frac.is_empty() || !frac.bytes().all(|b| b == b'0') {
+        return None;
+    }
+
+    parse_u64(int.as_bytes())
+}
+
+// Counts converted from localized numbers, which are logged once per input.
+#[derive(Default)]
+struct Conversions {
+    count: usize,
+    example: Option<(u64, String, u64)>,
+}
+
+impl Conversions {
+    fn add(&mut self, line_number: u64, cell: &str, count: u64) {
+        self.count += 1;
+        self.example
+            .get_or_insert_with(|| (line_number, cell.into(), count));
+    }
+
+    fn warn(&self) {
+        if let Some((line_number, cell, count)) = &self.example {
+            warn!(
+                "converted {} counts with thousands separators or decimal commas (e.g., line {}: {} to {})",
+                self.count, line_number, cell, count
+            );
+        }
+    }
+}
+
+// The message ends with the offending value, so it can be reported on its own
+// (see `ErrorReport::from_io_error`).
+fn invalid_cell(name: &str, cell: Option<&[u8]>) -> io::Error {
+    let message = match cell {
+        Some([]) | None => format!("missing {}", name),
+        Some(s) => format!("invalid {}: {}", name, String::from_utf8_lossy(s)),
+    };
+
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Guesses why a cell is not a count.
+fn count_hint(cell: &[u8]) -> Option<&'static str> {
+    let s = str::from_utf8(cell).ok()?;
+
+    if s.is_empty() {
+        None
+    } else if s.bytes().all(|b| b.is_ascii_digit()) {
+        Some("count is too large")
+    } else if s.starts_with('-') && s.parse::<f64>().is_ok() {
+        Some("counts cannot be negative")
+    } else if s.parse::<f64>().map(|n| n.is_finite()).unwrap_or(false) {
+        Some("looks like a float; counts must be integers, so round estimated counts, e.g., from salmon or RSEM")
+    } else if s.contains(',')
+        && s.bytes()
+            .all(|b| b.is_ascii_digit() || b == b',' || b == b'.')
+    {
+        Some("looks like a number with a thousands separator or decimal comma")
+    } else if s.starts_with(|c: char| c.is_alphabetic()) {
+        Some("looks like a header; remove the header line, or check that the file is a counts file")
+    } else {
+        None
+    }
+}
+
+// Parses an unsigned decimal integer directly from bytes, skipping UTF-8
+// validation.
+pub(crate) fn parse_u64(s: &[u8]) -> Option<u64> {
+    if s.is_empty() {
+        return None;
+    }
+
+    s.iter().try_fold(0u64, |n, &b| {
+        if b.is_ascii_digit() {
+            n.checked_mul(10)?.checked_add(u64::from(b - b'0'))
+        } else {
+            None
+        }
     })
 }
 
 fn insert_count<'a>(counts: &'a mut Counts, name: &str, count: u64) -> io::Result<&'a mut u64> {
-    match counts.entry(name.to_string()) {
+    match counts.entry(name.into()) {
         Entry::Vacant(e) => Ok(e.insert(count)),
         Entry::Occupied(_) => Err(io::Error::new(
             io::ErrorKind::InvalidInput,
@@ -107,12 +1062,12 @@ fn insert_count<'a>(counts: &'a mut Counts, name: &str, count: u64) -> io::Resul
 /// # Example
 ///
 /// ```
-/// use noodles_fpkm::counts::sum_counts;
+/// use noodles_fpkm::{counts::sum_counts, feature_id::FeatureId};
 ///
 /// let counts = [
-///     (String::from("AAAS"), 645),
-///     (String::from("AC009952.3"), 1),
-///     (String::from("RPL37AP1"), 5714),
+///     (FeatureId::from("AAAS"), 645),
+///     (FeatureId::from("AC009952.3"), 1),
+///     (FeatureId::from("RPL37AP1"), 5714),
 /// ].iter().cloned().collect();
 ///
 /// assert_eq!(sum_counts(&counts), 6360);
@@ -123,10 +1078,142 @@ pub fn sum_counts(counts: &Counts) -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use csv::StringRecord;
-
     use super::*;
 
+    #[test]
+    fn test_counts_table_read_samples() -> io::Result<()> {
+        let data = b"Geneid\tdata/sample1.bam\tsample2\nAAAS\t645\t512\n";
+
+        let samples = CountsTable.read_samples(data, "counts", &ReadCountsOptions::default())?;
+        let names: Vec<_> = samples.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["sample1", "sample2"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_count_source() {
+        let detect = |buf: &[u8]| detect_count_source(buf).map(|source| source.name());
+
+        assert_eq!(
+            detect(b"# Program:featureCounts v2.0.1\nGeneid\tChr\tStart\tEnd\tStrand\tLength\ts1.bam\n"),
+            Some("featureCounts")
+        );
+        assert_eq!(
+            detect(b"N_unmapped\t2674\t2674\t2674\nAAAS\t645\t12\t633\n"),
+            Some("STAR")
+        );
+        assert_eq!(
+            detect(b"Name\tLength\tEffectiveLength\tTPM\tNumReads\n"),
+            Some("salmon")
+        );
+        assert_eq!(
+            detect(b"%%MatrixMarket matrix coordinate integer general\n1 1 0\n"),
+            Some("MTX")
+        );
+        assert_eq!(detect(b"Geneid\ts1\ts2\nAAAS\t645\t512\n"), Some("table"));
+        assert_eq!(detect(b"AAAS\t645\n"), Some("htseq-count"));
+    }
+
+    #[test]
+    fn test_feature_counts_read_samples() -> io::Result<()> {
+        let data = b"Geneid\tChr\tStart\tEnd\tStrand\tLength\tdata/sample1.bam\nAAAS\tchr12\t1\t2083\t-\t2083\t645\n";
+
+        let samples = FeatureCounts.read_samples(data, "counts", &ReadCountsOptions::default())?;
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].0, "sample1");
+        assert_eq!(samples[0].1["AAAS"], 645);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_star_counts_with_forward_strandedness() -> io::Result<()> {
+        let data = "\
+N_unmapped\t2674\t2674\t2674
+N_multimapping\t3205\t3205\t3205
+N_noFeature\t1592\t38077\t38296
+N_ambiguous\t1081\t64\t36
+AAAS\t645\t12\t633
+RPL37AP1\t5714\t5701\t13
+";
+
+        let options = ReadCountsOptions {
+            strandedness: Strandedness::Forward,
+            ..Default::default()
+        };
+        let counts = read_star_counts(data.as_bytes(), &options)?;
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["AAAS"], 12);
+        assert_eq!(counts["RPL37AP1"], 5701);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_salmon_quant_with_invalid_count() {
+        let data = "Name\tLength\tEffectiveLength\tTPM\tNumReads\nENST00000209873\t2541\t2370.254\t31.802\t-1\n";
+        let e = read_salmon_quant(data.as_bytes()).unwrap_err();
+        assert_eq!(e.to_string(), "line 2: invalid count: -1");
+
+        let data = "Name\tLength\tTPM\nENST00000209873\t2541\t31.802\n";
+        assert!(read_salmon_quant(data.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_salmon_quant_read_samples_from_path() -> io::Result<()> {
+        let data = b"Name\tLength\tEffectiveLength\tTPM\tNumReads\nENST00000209873\t2541\t2370.254\t31.802\t644.628\n";
+        let options = ReadCountsOptions::default();
+
+        let src = Path::new("salmon/sample1/quant.sf");
+        let samples = SalmonQuant.read_samples_from_path(src, data, &options)?;
+        assert_eq!(samples[0].0, "sample1");
+        assert_eq!(samples[0].1["ENST00000209873"], 645);
+
+        let src = Path::new("sample2.sf");
+        let samples = SalmonQuant.read_samples_from_path(src, data, &options)?;
+        assert_eq!(samples[0].0, "sample2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mtx_read_samples_from_path() -> io::Result<()> {
+        use std::fs;
+
+        let dir = std::env::temp_dir().join(format!("noodles-fpkm-counts-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("features.tsv"), "AAAS\tAAAS\nRPL37AP1\tRPL37AP1\n")?;
+        fs::write(dir.join("barcodes.tsv"), "sample1\nsample2\n")?;
+
+        let src = dir.join("matrix.mtx");
+        let data = b"%%MatrixMarket matrix coordinate integer general\n2 2 2\n2 1 3\n1 2 5\n";
+
+        let actual = Mtx.read_samples_from_path(&src, data, &ReadCountsOptions::default());
+        let invalid = Mtx.read_samples_from_path(
+            &src,
+            b"%%MatrixMarket matrix coordinate real general\n2 2 1\n1 1 0.5\n",
+            &ReadCountsOptions::default(),
+        );
+        fs::remove_dir_all(&dir)?;
+
+        let samples = actual?;
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].0, "sample1");
+        assert_eq!(samples[0].1["AAAS"], 0);
+        assert_eq!(samples[0].1["RPL37AP1"], 3);
+        assert_eq!(samples[1].0, "sample2");
+        assert_eq!(samples[1].1["AAAS"], 5);
+
+        assert!(invalid.is_err());
+        assert!(Mtx
+            .read_samples(data, "matrix", &ReadCountsOptions::default())
+            .is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_counts_with_duplicate_identifiers() {
         let data = "\
@@ -140,27 +1227,189 @@ __no_feature\t136550
         assert!(read_counts(data.as_bytes()).is_err());
     }
 
+    #[test]
+    fn test_read_counts_with_duplicate_identifiers_error_message() {
+        let data = "AAAS\t645\nAC009952.3\t1\nAC009952.3\t0\n";
+        let e = read_counts(data.as_bytes()).unwrap_err();
+        assert_eq!(e.to_string(), "line 3: duplicate identifier 'AC009952.3'");
+    }
+
+    #[test]
+    fn test_read_counts_with_comments_and_blank_lines() {
+        let data = "\
+# Program:featureCounts v2.0.1
+
+AAAS\t645
+#AC009952.3\t1
+
+RPL37AP1\t5714
+";
+
+        let counts = read_counts(data.as_bytes()).unwrap();
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["AAAS"], 645);
+        assert_eq!(counts["RPL37AP1"], 5714);
+    }
+
+    #[test]
+    fn test_read_counts_with_crlf_whitespace_and_bom() {
+        let data = "\u{feff}AAAS\t645\r\n AC009952.3 \t 1 \r\nRPL37AP1\t5714\t\r\n";
+
+        let counts = read_counts(data.as_bytes()).unwrap();
+
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts["AAAS"], 645);
+        assert_eq!(counts["AC009952.3"], 1);
+        assert_eq!(counts["RPL37AP1"], 5714);
+    }
+
+    #[test]
+    fn test_read_counts_with_options() {
+        let data = "\
+AAAS\t645
+__no_feature\t136550
+";
+
+        let options = ReadCountsOptions {
+            meta_prefix: None,
+            ..Default::default()
+        };
+        let counts = read_counts_with_options(data.as_bytes(), &options).unwrap();
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["__no_feature"], 136550);
+    }
+
+    #[test]
+    fn test_read_counts_table_with_feature_counts_output() {
+        let data = "\
+# Program:featureCounts v2.0.1; Command:\"featureCounts\" \"-a\" \"a.gtf\"
+Geneid\tChr\tStart\tEnd\tStrand\tLength\tsample1.bam\tsample2.bam
+AAAS\tchr12\t53307456\t53324864\t-\t2083\t645\t512
+RPL37AP1\tchr8\t44892713\t44892766\t+\t54\t5714\t6022
+";
+
+        let samples = read_counts_table(data.as_bytes(), &ReadCountsOptions::default()).unwrap();
+
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].0, "sample1.bam");
+        assert_eq!(samples[0].1["AAAS"], 645);
+        assert_eq!(samples[1].0, "sample2.bam");
+        assert_eq!(samples[1].1["RPL37AP1"], 6022);
+    }
+
+    #[test]
+    fn test_read_counts_table_with_missing_count() {
+        let data = "Geneid\tsample1\tsample2\nAAAS\t645\n";
+        assert!(read_counts_table(data.as_bytes(), &ReadCountsOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_is_counts_table() {
+        assert!(is_counts_table(b"# comment\n\nGeneid\ts1\ts2\r\n"));
+        assert!(!is_counts_table(b"AAAS\t645\t\r\n"));
+        assert!(!is_counts_table(b""));
+    }
+
     #[test]
     fn test_parse_name() {
-        let record = StringRecord::from(vec!["AAAS", "645"]);
+        let record = ByteRecord::from(vec!["AAAS", "645"]);
         assert_eq!(parse_name(&record).unwrap(), "AAAS");
     }
 
+    #[test]
+    fn test_parse_name_with_invalid_utf8() {
+        let record = ByteRecord::from(vec![&b"\xff"[..], b"645"]);
+        assert!(parse_name(&record).is_err());
+    }
+
     #[test]
     fn test_parse_count() {
-        let record = StringRecord::from(vec!["AAAS", "645"]);
+        let record = ByteRecord::from(vec!["AAAS", "645"]);
         assert_eq!(parse_count(&record).unwrap(), 645);
 
-        let record = StringRecord::from(vec!["AAAS", ""]);
+        let record = ByteRecord::from(vec!["AAAS", ""]);
         assert!(parse_count(&record).is_err());
 
-        let record = StringRecord::from(vec!["AAAS", "x"]);
+        let record = ByteRecord::from(vec!["AAAS", "x"]);
         assert!(parse_count(&record).is_err());
     }
 
+    #[test]
+    fn test_parse_count_at() {
+        let record = ByteRecord::from(vec!["AAAS", "645", "512"]);
+        assert_eq!(parse_count_at(&record, 2).unwrap(), 512);
+        assert!(parse_count_at(&record, 3).is_err());
+    }
+
+    #[test]
+    fn test_parse_count_with_hints() {
+        let parse_error = |cell: &str| {
+            let record = ByteRecord::from(vec!["AAAS", cell]);
+            parse_count(&record).unwrap_err().to_string()
+        };
+
+        assert_eq!(parse_error(""), "missing count");
+        assert_eq!(
+            parse_error("-3"),
+            "invalid count: -3; hint: counts cannot be negative"
+        );
+        assert!(parse_error("6.5").starts_with("invalid count: 6.5; hint: looks like a float"));
+        assert!(parse_error("1,234").contains("thousands separator"));
+        assert!(parse_error("count").contains("looks like a header"));
+        assert!(parse_error("99999999999999999999").ends_with("count is too large"));
+        assert_eq!(parse_error("?"), "invalid count: ?");
+    }
+
+    #[test]
+    fn test_read_counts_with_lenient_numbers() {
+        let data = "AAAS\t1,645\nRPL37AP1\t12,0\n";
+
+        assert!(read_counts(data.as_bytes()).is_err());
+
+        let options = ReadCountsOptions {
+            lenient_numbers: true,
+            ..Default::default()
+        };
+        let counts = read_counts_with_options(data.as_bytes(), &options).unwrap();
+
+        assert_eq!(counts["AAAS"], 1645);
+        assert_eq!(counts["RPL37AP1"], 12);
+    }
+
+    #[test]
+    fn test_parse_localized_u64() {
+        assert_eq!(parse_localized_u64(b"1,234"), Some(("1,234", 1234)));
+        assert_eq!(
+            parse_localized_u64(b"12,345,678"),
+            Some(("12,345,678", 12345678))
+        );
+        assert_eq!(parse_localized_u64(b"12,0"), Some(("12,0", 12)));
+        assert_eq!(parse_localized_u64(b"12,000"), Some(("12,000", 12000)));
+        assert_eq!(parse_localized_u64(b"1234,00"), Some(("1234,00", 1234)));
+        assert_eq!(parse_localized_u64(b"1,2345"), None);
+        assert_eq!(parse_localized_u64(b"3,5"), None);
+        assert_eq!(parse_localized_u64(b"1234,567"), None);
+        assert_eq!(parse_localized_u64(b",5"), None);
+        assert_eq!(parse_localized_u64(b"1,2,3"), None);
+    }
+
+    #[test]
+    fn test_parse_u64() {
+        assert_eq!(parse_u64(b"0"), Some(0));
+        assert_eq!(parse_u64(b"645"), Some(645));
+        assert_eq!(parse_u64(b"18446744073709551615"), Some(u64::MAX));
+
+        assert!(parse_u64(b"").is_none());
+        assert!(parse_u64(b"-1").is_none());
+        assert!(parse_u64(b"6.5").is_none());
+        assert!(parse_u64(b"18446744073709551616").is_none());
+    }
+
     #[test]
     fn test_insert_count() {
-        let mut counts = Counts::new();
+        let mut counts = Counts::default();
 
         assert!(insert_count(&mut counts, "AAAS", 645).is_ok());
         assert_eq!(counts["AAAS"], 645);
@@ -0,0 +1,225 @@
+use std::cmp::Ordering;
+
+use ndarray::Array2;
+
+use crate::{lengths::Lengths, matrix::ExpressionMatrix, sequence::GcContents};
+
+/// The default number of quantile bins per covariate.
+pub const DEFAULT_BIN_COUNT: usize = 5;
+
+/// Corrects GC content and length bias across samples, similar to conditional
+/// quantile normalization (CQN).
+///
+/// Features are grouped into `bin_count` × `bin_count` bins by quantiles of
+/// their GC content and length. Within each bin, the median `log2(x + 1)`
+/// expression of each sample is shifted to the mean of the medians across
+/// samples. A sample whose high-GC genes are systematically depleted, e.g.,
+/// because of a library preparation batch, is then corrected toward the other
+/// samples.
+///
+/// Only expressed (> 0) values are used and adjusted. Features without a GC
+/// content or length are left unchanged.
+///
+/// # Panics
+///
+/// Panics when `bin_count` is 0.
+pub fn normalize_gc_and_length(
+    matrix: &ExpressionMatrix,
+    gc_contents: &GcContents,
+    lengths: &Lengths,
+    bin_count: usize,
+) -> ExpressionMatrix {
+    assert!(bin_count > 0, "bin count must be > 0");
+
+    let covariates: Vec<Option<(f64, f64)>> = matrix
+        .row_names()
+        .iter()
+        .map(|name| {
+            let gc_content = gc_contents.get(name.as_str())?;
+            let len = lengths.get(name.as_str())?;
+            Some((*gc_content, *len as f64))
+        })
+        .collect();
+
+    let rows: Vec<usize> = (0..covariates.len())
+        .filter(|&i| covariates[i].is_some())
+        .collect();
+
+    let gc_bins = assign_quantile_bins(&rows, |i| covariates[i].map(|c| c.0), bin_count);
+    let len_bins = assign_quantile_bins(&rows, |i| covariates[i].map(|c| c.1), bin_count);
+
+    let data = matrix.data();
+    let sample_count = data.cols();
+    let total_bin_count = bin_count * bin_count;
+
+    let mut bins = vec![None; data.rows()];
+
+    for (k, &i) in rows.iter().enumerate() {
+        bins[i] = Some(gc_bins[k] * bin_count + len_bins[k]);
+    }
+
+    let mut medians = Array2::from_elem((total_bin_count, sample_count), f64::NAN);
+
+    for j in 0..sample_count {
+        let mut values = vec![Vec::new(); total_bin_count];
+
+        for (i, bin) in bins.iter().enumerate() {
+            let x = data[[i, j]];
+
+            if let Some(bin) = bin {
+                if x > 0.0 {
+                    values[*bin].push((x + 1.0).log2());
+                }
+            }
+        }
+
+        for (b, bin_values) in values.into_iter().enumerate() {
+            if let Some(median) = median(bin_values) {
+                medians[[b, j]] = median;
+            }
+        }
+    }
+
+    let targets: Vec<f64> = medians
+        .outer_iter()
+        .map(|row| {
+            let values: Vec<f64> = row.iter().cloned().filter(|m| !m.is_nan()).collect();
+            values.iter().sum::<f64>() / values.len() as f64
+        })
+        .collect();
+
+    let mut normalized = data.clone();
+
+    for (i, bin) in bins.iter().enumerate() {
+        let b = match bin {
+            Some(b) => *b,
+            None => continue,
+        };
+
+        for j in 0..sample_count {
+            let x = data[[i, j]];
+
+            if x > 0.0 {
+                let offset = medians[[b, j]] - targets[b];
+                let y = (x + 1.0).log2() - offset;
+                normalized[[i, j]] = (y.exp2() - 1.0).max(0.0);
+            }
+        }
+    }
+
+    ExpressionMatrix::new(
+        matrix.row_names().to_vec(),
+        matrix.column_names().to_vec(),
+        normalized,
+    )
+}
+
+// Assigns each row to one of `bin_count` bins of (roughly) equal size, in
+// order of its covariate.
+fn assign_quantile_bins<F>(rows: &[usize], covariate: F, bin_count: usize) -> Vec<usize>
+where
+    F: Fn(usize) -> Option<f64>,
+{
+    let mut order: Vec<usize> = (0..rows.len()).collect();
+    order.sort_by(|&a, &b| compare(covariate(rows[a]), covariate(rows[b])));
+
+    let mut bins = vec![0; rows.len()];
+
+    for (rank, k) in order.into_iter().enumerate() {
+        bins[k] = rank * bin_count / rows.len();
+    }
+
+    bins
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    let mid = values.len() / 2;
+
+    match values.len() % 2 {
+        0 => Some((values[mid - 1] + values[mid]) / 2.0),
+        _ => Some(values[mid]),
+    }
+}
+
+fn compare(a: Option<f64>, b: Option<f64>) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_id::FeatureId;
+
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_normalize_gc_and_length() {
+        // sample2 is depleted of the high-GC genes (c, d) by a factor of 4.
+        let matrix = ExpressionMatrix::new(
+            vec![
+                String::from("a"),
+                String::from("b"),
+                String::from("c"),
+                String::from("d"),
+                String::from("e"),
+            ],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[
+                [7.0, 7.0],
+                [15.0, 15.0],
+                [31.0, 7.0],
+                [63.0, 15.0],
+                [3.0, 3.0],
+            ]),
+        );
+
+        let gc_contents: GcContents = [
+            (FeatureId::from("a"), 0.3),
+            (FeatureId::from("b"), 0.35),
+            (FeatureId::from("c"), 0.6),
+            (FeatureId::from("d"), 0.65),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let lengths: Lengths = [
+            (FeatureId::from("a"), 1000),
+            (FeatureId::from("b"), 1000),
+            (FeatureId::from("c"), 1000),
+            (FeatureId::from("d"), 1000),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let normalized = normalize_gc_and_length(&matrix, &gc_contents, &lengths, 2);
+
+        // The low-GC bin is the same in both samples.
+        assert!((normalized.get("a", "sample1").unwrap() - 7.0).abs() < 1e-9);
+        assert!((normalized.get("b", "sample2").unwrap() - 15.0).abs() < 1e-9);
+
+        // The high-GC bin is shifted to the mean of the medians.
+        let c1 = normalized.get("c", "sample1").unwrap();
+        let c2 = normalized.get("c", "sample2").unwrap();
+        assert!((c1 - c2).abs() < 1e-9);
+        assert!((c1 - 15.0).abs() < 1e-9);
+
+        // e has no GC content.
+        assert_eq!(normalized.get("e", "sample1"), Some(3.0));
+    }
+
+    #[test]
+    fn test_assign_quantile_bins() {
+        let values = [0.5, 0.1, 0.9, 0.3];
+        let bins = assign_quantile_bins(&[0, 1, 2, 3], |i| Some(values[i]), 2);
+        assert_eq!(bins, [1, 0, 1, 0]);
+    }
+}
@@ -0,0 +1,212 @@
+use rand::Rng;
+use rand_distr::{Binomial, Distribution};
+
+use crate::{counts::Counts, sparse::SparseExpressionMatrix};
+
+/// Downsamples a set of feature counts to a target depth.
+///
+/// Reads are drawn from a multinomial distribution whose probabilities are
+/// the relative counts, i.e., with replacement. For a fixed seed, the result
+/// is reproducible, as features are sampled in order of their IDs. If the
+/// total count is not greater than `depth`, the counts are returned
+/// unchanged.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     counts::Counts, downsampling::downsample_counts, feature_id::FeatureId,
+/// };
+/// use rand::SeedableRng;
+/// use rand_pcg::Pcg64;
+///
+/// let counts: Counts = [
+///     (FeatureId::from("AAAS"), 645),
+///     (FeatureId::from("AC009952.3"), 1),
+///     (FeatureId::from("RPL37AP1"), 5714),
+/// ].iter().cloned().collect();
+///
+/// let mut rng = Pcg64::seed_from_u64(0);
+/// let downsampled = downsample_counts(&counts, 1000, &mut rng);
+///
+/// assert_eq!(downsampled.values().sum::<u64>(), 1000);
+/// ```
+pub fn downsample_counts<R>(counts: &Counts, depth: u64, rng: &mut R) -> Counts
+where
+    R: Rng,
+{
+    let mut entries: Vec<_> = counts.iter().collect();
+    entries.sort_unstable_by_key(|(name, _)| *name);
+
+    let values: Vec<u64> = entries.iter().map(|(_, &count)| count).collect();
+
+    if values.iter().sum::<u64>() <= depth {
+        return counts.clone();
+    }
+
+    let sampled_values = sample_multinomial(&values, depth, rng);
+
+    entries
+        .into_iter()
+        .zip(sampled_values)
+        .map(|((name, _), count)| (name.clone(), count))
+        .collect()
+}
+
+/// Downsamples each column of a sparse count matrix to a target depth.
+///
+/// Each column is sampled as in [`downsample_counts`], and features that are
+/// no longer observed are removed. Columns with a library size not greater
+/// than `depth` are left unchanged, and the number of these columns is
+/// returned.
+///
+/// [`downsample_counts`]: fn.downsample_counts.html
+pub fn downsample<R>(matrix: &mut SparseExpressionMatrix, depth: u64, rng: &mut R) -> usize
+where
+    R: Rng,
+{
+    let mut shallow_column_count = 0;
+
+    for j in 0..matrix.shape().1 {
+        let (_, values) = matrix.column_mut(j);
+        let counts: Vec<u64> = values.iter().map(|&value| value as u64).collect();
+
+        if counts.iter().sum::<u64>() <= depth {
+            shallow_column_count += 1;
+            continue;
+        }
+
+        for (value, count) in values
+            .iter_mut()
+            .zip(sample_multinomial(&counts, depth, rng))
+        {
+            *value = count as f64;
+        }
+    }
+
+    matrix.prune();
+
+    shallow_column_count
+}
+
+/// Calculates a rarefaction curve, i.e., the number of detected features at
+/// each of the given depths.
+///
+/// For each depth, the counts are downsampled, and the features with a
+/// nonzero count are detected. Depths greater than the total count are
+/// skipped.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::downsampling::rarefaction_curve;
+/// use rand::SeedableRng;
+/// use rand_pcg::Pcg64;
+///
+/// let mut rng = Pcg64::seed_from_u64(0);
+/// let curve = rarefaction_curve(&[645, 1, 5714], &[1, 1000, 10000], &mut rng);
+///
+/// assert_eq!(curve.len(), 2);
+/// assert_eq!(curve[0], (1, 1));
+/// ```
+pub fn rarefaction_curve<R>(counts: &[u64], depths: &[u64], rng: &mut R) -> Vec<(u64, usize)>
+where
+    R: Rng,
+{
+    let total = counts.iter().sum();
+
+    depths
+        .iter()
+        .filter(|&&depth| depth <= total)
+        .map(|&depth| {
+            let detected = sample_multinomial(counts, depth, rng)
+                .into_iter()
+                .filter(|&count| count > 0)
+                .count();
+
+            (depth, detected)
+        })
+        .collect()
+}
+
+// Samples `n` draws from a multinomial distribution with probabilities
+// proportional to `counts`, as a sequence of conditional binomial draws.
+fn sample_multinomial<R>(counts: &[u64], n: u64, rng: &mut R) -> Vec<u64>
+where
+    R: Rng,
+{
+    let mut remaining_total: u64 = counts.iter().sum();
+    let mut remaining_n = n;
+
+    counts
+        .iter()
+        .map(|&count| {
+            if remaining_n == 0 || count == 0 {
+                remaining_total -= count;
+                return 0;
+            }
+
+            let p = (count as f64 / remaining_total as f64).min(1.0);
+            let binomial = Binomial::new(remaining_n, p).expect("p must be in [0, 1]");
+            let k = binomial.sample(rng);
+
+            remaining_total -= count;
+            remaining_n -= k;
+
+            k
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+
+    #[test]
+    fn test_downsample_counts_is_reproducible() {
+        let counts: Counts = (0..100)
+            .map(|i| (format!("gene{}", i).into(), i * 10))
+            .collect();
+
+        let a = downsample_counts(&counts, 1000, &mut Pcg64::seed_from_u64(42));
+        let b = downsample_counts(&counts, 1000, &mut Pcg64::seed_from_u64(42));
+
+        assert_eq!(a, b);
+        assert_eq!(a.values().sum::<u64>(), 1000);
+        assert_eq!(a["gene0"], 0);
+    }
+
+    #[test]
+    fn test_downsample() {
+        let mut matrix = SparseExpressionMatrix::from_triplets(
+            vec![String::from("AAAS"), String::from("RPL37AP1")],
+            vec![String::from("cell1"), String::from("cell2")],
+            vec![(0, 0, 645.0), (1, 0, 5714.0), (1, 1, 3.0)],
+        );
+
+        let mut rng = Pcg64::seed_from_u64(0);
+        let shallow_column_count = downsample(&mut matrix, 100, &mut rng);
+
+        assert_eq!(shallow_column_count, 1);
+        assert_eq!(matrix.column(0).1.iter().sum::<f64>(), 100.0);
+        assert_eq!(matrix.column(1), (&[1][..], &[3.0][..]));
+    }
+
+    #[test]
+    fn test_sample_multinomial() {
+        let mut rng = Pcg64::seed_from_u64(0);
+
+        let counts = [3, 0, 5, 2];
+
+        let sampled = sample_multinomial(&counts, 4, &mut rng);
+        assert_eq!(sampled.iter().sum::<u64>(), 4);
+        assert_eq!(sampled[1], 0);
+
+        assert_eq!(sample_multinomial(&[0, 7, 0], 4, &mut rng), [0, 4, 0]);
+
+        assert_eq!(sample_multinomial(&counts, 0, &mut rng), [0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,288 @@
+use std::{fmt::Write as _, io};
+
+use crate::{counts::HINT_DELIMITER, Error};
+
+const LINE_PREFIX: &str = "line ";
+const VALUE_DELIMITER: &str = ": ";
+
+/// A structured description of an error, e.g., for workflow managers.
+///
+/// Fields that are not known are `None`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ErrorReport {
+    /// A stable, snake case identifier of the kind of error, e.g.,
+    /// `invalid_data`.
+    pub code: String,
+    /// The human-readable message.
+    pub message: String,
+    /// The input file that caused the error.
+    pub file: Option<String>,
+    /// The (1-based) line number in the input file that caused the error.
+    pub line_number: Option<u64>,
+    /// The offending value, e.g., a feature ID or an unparsable field.
+    pub value: Option<String>,
+}
+
+impl ErrorReport {
+    /// Creates an error report with a code and message.
+    pub fn new<C, M>(code: C, message: M) -> ErrorReport
+    where
+        C: Into<String>,
+        M: Into<String>,
+    {
+        ErrorReport {
+            code: code.into(),
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Creates an error report from an I/O error.
+    ///
+    /// Readers in this crate prefix messages with the line number of the
+    /// record that caused the error (`line <n>: `) and end them with the
+    /// offending value (`<description>: <value>` or `<description> '<value>'`),
+    /// optionally followed by a hint (`; hint: <hint>`). The line number and value are split from the
+    /// message when present.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io;
+    /// use noodles_fpkm::error_report::ErrorReport;
+    ///
+    /// let e = io::Error::new(io::ErrorKind::InvalidData, "line 8: invalid count: x");
+    /// let report = ErrorReport::from_io_error(&e);
+    ///
+    /// assert_eq!(report.code, "invalid_data");
+    /// assert_eq!(report.message, "invalid count: x");
+    /// assert_eq!(report.line_number, Some(8));
+    /// assert_eq!(report.value, Some(String::from("x")));
+    /// ```
+    pub fn from_io_error(e: &io::Error) -> ErrorReport {
+        let code = snake_case(&format!("{:?}", e.kind()));
+        let message = e.to_string();
+
+        let (line_number, message) = match split_line_number(&message) {
+            Some((n, rest)) => (Some(n), rest),
+            None => (None, message.as_str()),
+        };
+
+        let value = message
+            .split(HINT_DELIMITER)
+            .next()
+            .and_then(split_value)
+            .map(String::from);
+
+        ErrorReport {
+            code,
+            message: message.into(),
+            line_number,
+            value,
+            ..Default::default()
+        }
+    }
+
+    /// Serializes the error report as a single-line JSON object.
+    ///
+    /// Every field is written; unknown fields are `null`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::error_report::ErrorReport;
+    ///
+    /// let mut report = ErrorReport::new("missing_feature", "feature not in annotations: AAAS");
+    /// report.value = Some(String::from("AAAS"));
+    ///
+    /// assert_eq!(
+    ///     report.to_json(),
+    ///     r#"{"code":"missing_feature","message":"feature not in annotations: AAAS","file":null,"line":null,"value":"AAAS"}"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut s = String::new();
+
+        s.push_str("{\"code\":");
+        push_json_string(&mut s, &self.code);
+        s.push_str(",\"message\":");
+        push_json_string(&mut s, &self.message);
+
+        s.push_str(",\"file\":");
+        match &self.file {
+            Some(file) => push_json_string(&mut s, file),
+            None => s.push_str("null"),
+        }
+
+        s.push_str(",\"line\":");
+        match self.line_number {
+            Some(n) => write!(s, "{}", n).unwrap(),
+            None => s.push_str("null"),
+        }
+
+        s.push_str(",\"value\":");
+        match &self.value {
+            Some(value) => push_json_string(&mut s, value),
+            None => s.push_str("null"),
+        }
+
+        s.push('}');
+
+        s
+    }
+}
+
+impl From<&Error> for ErrorReport {
+    fn from(e: &Error) -> Self {
+        match e {
+            Error::MissingFeature(id) => ErrorReport {
+                value: Some(id.to_string()),
+                ..ErrorReport::new(
+                    "missing_feature",
+                    format!("feature not in annotations: {}", id),
+                )
+            },
+            Error::ZeroLengthFeature(id) => ErrorReport {
+                value: Some(id.to_string()),
+                ..ErrorReport::new(
+                    "zero_length_feature",
+                    format!("feature has a length of 0: {}", id),
+                )
+            },
+            Error::ZeroDenominator => {
+                ErrorReport::new("zero_denominator", "sample has no counts or expression")
+            }
+            Error::MixedMethods(a, b) => ErrorReport::new(
+                "mixed_methods",
+                format!("cannot combine {} and {} values", a, b),
+            ),
+            Error::UnsupportedMethod(method) => ErrorReport {
+                value: Some(method.to_string()),
+                ..ErrorReport::new(
+                    "unsupported_method",
+                    format!("cannot calculate {} values", method),
+                )
+            },
+        }
+    }
+}
+
+/// Converts a camel case name, e.g., a `Debug`-formatted error kind, to snake
+/// case.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::error_report::snake_case;
+/// assert_eq!(snake_case("InvalidValue"), "invalid_value");
+/// ```
+pub fn snake_case(name: &str) -> String {
+    let mut s = String::with_capacity(name.len());
+
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                s.push('_');
+            }
+
+            s.push(c.to_ascii_lowercase());
+        } else {
+            s.push(c);
+        }
+    }
+
+    s
+}
+
+fn split_line_number(message: &str) -> Option<(u64, &str)> {
+    let rest = message.strip_prefix(LINE_PREFIX)?;
+    let (n, rest) = rest.split_once(VALUE_DELIMITER)?;
+    n.parse().ok().map(|n| (n, rest))
+}
+
+fn split_value(message: &str) -> Option<&str> {
+    if let Some((_, value)) = message.split_once(VALUE_DELIMITER) {
+        return Some(value);
+    }
+
+    message
+        .strip_suffix('\'')
+        .and_then(|s| s.split_once(" '"))
+        .map(|(_, value)| value)
+}
+
+// Appends a value as a JSON string literal.
+pub(crate) fn push_json_string(s: &mut String, value: &str) {
+    s.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if c.is_control() => write!(s, "\\u{:04x}", c as u32).unwrap(),
+            c => s.push(c),
+        }
+    }
+
+    s.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_io_error_without_line_number_or_value() {
+        let e = io::Error::new(io::ErrorKind::NotFound, "No such file or directory");
+        let report = ErrorReport::from_io_error(&e);
+
+        assert_eq!(report.code, "not_found");
+        assert_eq!(report.message, "No such file or directory");
+        assert!(report.line_number.is_none());
+        assert!(report.value.is_none());
+    }
+
+    #[test]
+    fn test_from_io_error_with_hint() {
+        let e = io::Error::new(
+            io::ErrorKind::InvalidData,
+            "line 2: invalid count: -3; hint: counts cannot be negative",
+        );
+        let report = ErrorReport::from_io_error(&e);
+
+        assert_eq!(
+            report.message,
+            "invalid count: -3; hint: counts cannot be negative"
+        );
+        assert_eq!(report.line_number, Some(2));
+        assert_eq!(report.value, Some(String::from("-3")));
+    }
+
+    #[test]
+    fn test_from_io_error_with_quoted_value() {
+        let e = io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "line 3: duplicate identifier 'AC009952.3'",
+        );
+        let report = ErrorReport::from_io_error(&e);
+
+        assert_eq!(report.message, "duplicate identifier 'AC009952.3'");
+        assert_eq!(report.line_number, Some(3));
+        assert_eq!(report.value, Some(String::from("AC009952.3")));
+    }
+
+    #[test]
+    fn test_to_json_with_escaped_characters() {
+        let mut report = ErrorReport::new("invalid_data", "invalid name: \"a\\b\"\t\u{1}");
+        report.file = Some(String::from("counts.txt"));
+        report.line_number = Some(3);
+
+        assert_eq!(
+            report.to_json(),
+            r#"{"code":"invalid_data","message":"invalid name: \"a\\b\"\t\u0001","file":"counts.txt","line":3,"value":null}"#
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+use crate::{matrix::ExpressionMatrix, Error, Expressions};
+
+/// A method used to calculate expression values.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Method {
+    /// Fragments per kilobase per million mapped reads.
+    Fpkm,
+    /// Transcripts per million.
+    Tpm,
+    /// Counts per million.
+    Cpm,
+    /// Counts divided by a size factor.
+    Scaled,
+}
+
+impl Method {
+    /// Returns the name of the method, as used in the header of a table.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Fpkm => "fpkm",
+            Method::Tpm => "tpm",
+            Method::Cpm => "cpm",
+            Method::Scaled => "scaled",
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The expression values of a sample and how they were calculated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpressionSet {
+    /// The method used to calculate the values.
+    pub method: Method,
+    /// The total count of the sample, when the values were calculated from
+    /// counts.
+    pub library_size: Option<u64>,
+    /// The name of the sample.
+    pub sample_name: String,
+    /// The map of feature ID-expression value pairs.
+    pub values: Expressions,
+}
+
+/// Merges expression sets into a matrix, one column per set.
+///
+/// All sets must be calculated by the same method, so that, e.g., FPKM and
+/// TPM values are never mixed in a matrix. See
+/// [`ExpressionMatrix::from_samples`].
+///
+/// [`ExpressionMatrix::from_samples`]: ../matrix/struct.ExpressionMatrix.html#method.from_samples
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     expression_set::{merge_expression_sets, ExpressionSet, Method},
+///     feature_id::FeatureId,
+///     Error,
+/// };
+///
+/// let a = ExpressionSet {
+///     method: Method::Tpm,
+///     library_size: Some(6359),
+///     sample_name: String::from("sample1"),
+///     values: [(FeatureId::from("AAAS"), 1e6)].iter().cloned().collect(),
+/// };
+///
+/// let mut b = a.clone();
+/// b.sample_name = String::from("sample2");
+///
+/// let matrix = merge_expression_sets(&[a.clone(), b.clone()])?;
+/// assert_eq!(matrix.column_names(), ["sample1", "sample2"]);
+///
+/// b.method = Method::Fpkm;
+///
+/// assert!(matches!(
+///     merge_expression_sets(&[a, b]),
+///     Err(Error::MixedMethods(Method::Tpm, Method::Fpkm))
+/// ));
+/// # Ok::<(), Error>(())
+/// ```
+pub fn merge_expression_sets(sets: &[ExpressionSet]) -> Result<ExpressionMatrix, Error> {
+    if let Some(first) = sets.first() {
+        if let Some(set) = sets.iter().find(|set| set.method != first.method) {
+            return Err(Error::MixedMethods(first.method, set.method));
+        }
+    }
+
+    let columns: Vec<_> = sets
+        .iter()
+        .map(|set| (set.sample_name.as_str(), &set.values))
+        .collect();
+
+    Ok(ExpressionMatrix::from_columns(&columns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_expression_sets_with_no_sets() -> Result<(), Error> {
+        let matrix = merge_expression_sets(&[])?;
+        assert!(matrix.row_names().is_empty());
+        assert!(matrix.column_names().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use std::{borrow::Borrow, borrow::Cow, fmt, ops::Deref, sync::Arc};
+
+use crate::ids::Normalization;
+
+const VERSION_SEPARATOR: char = '.';
+
+/// A feature identifier, e.g., `ENSG00000157191.19` or `NECAP2`.
+///
+/// This is the key of the maps of counts, features, lengths, and expressions.
+/// The identifier is reference counted, so clones share the same string.
+///
+/// A feature ID can be borrowed as a `str`, so maps keyed by feature IDs can
+/// be indexed by string slices.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{counts::Counts, feature_id::FeatureId};
+///
+/// let id = FeatureId::from("ENSG00000157191.19");
+/// assert_eq!(id.to_string(), "ENSG00000157191.19");
+/// assert_eq!(id.without_version(), "ENSG00000157191");
+///
+/// let counts: Counts = [(id, 8)].iter().cloned().collect();
+/// assert_eq!(counts["ENSG00000157191.19"], 8);
+/// ```
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct FeatureId(Arc<str>);
+
+impl FeatureId {
+    /// Returns the identifier as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns whether two feature IDs share the same string.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(&this.0, &other.0)
+    }
+
+    /// Returns the identifier without a numeric version suffix, e.g., `.19` in
+    /// `ENSG00000157191.19`.
+    ///
+    /// Identifiers without a version suffix are returned as is.
+    pub fn without_version(&self) -> &str {
+        strip_version(self.as_str())
+    }
+
+    /// Returns the normalized form of the identifier. See
+    /// [`Normalization::normalize`].
+    ///
+    /// [`Normalization::normalize`]: ../ids/enum.Normalization.html#method.normalize
+    pub fn normalize(&self, normalization: Normalization) -> Cow<'_, str> {
+        normalization.normalize(self.as_str())
+    }
+}
+
+impl Deref for FeatureId {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for FeatureId {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for FeatureId {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for FeatureId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for FeatureId {
+    fn from(s: &str) -> Self {
+        FeatureId(Arc::from(s))
+    }
+}
+
+impl From<String> for FeatureId {
+    fn from(s: String) -> Self {
+        FeatureId(Arc::from(s))
+    }
+}
+
+impl From<Arc<str>> for FeatureId {
+    fn from(s: Arc<str>) -> Self {
+        FeatureId(s)
+    }
+}
+
+impl PartialEq<str> for FeatureId {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for FeatureId {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+// Removes a numeric version suffix, e.g., `.19` in `ENSG00000157191.19`.
+pub(crate) fn strip_version(id: &str) -> &str {
+    match id.rfind(VERSION_SEPARATOR) {
+        Some(i) if i + 1 < id.len() && id[i + 1..].bytes().all(|b| b.is_ascii_digit()) => &id[..i],
+        _ => id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_version() {
+        assert_eq!(strip_version("ENSG00000157191.19"), "ENSG00000157191");
+        assert_eq!(strip_version("ENSG00000157191"), "ENSG00000157191");
+        assert_eq!(strip_version("RP4-798A10.1-001"), "RP4-798A10.1-001");
+        assert_eq!(strip_version("AAAS."), "AAAS.");
+    }
+}
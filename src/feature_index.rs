@@ -0,0 +1,425 @@
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    feature_id::FeatureId,
+    features::{Feature, Features},
+    gtf::{ReferenceSequenceNames, Strands},
+    lengths::Lengths,
+};
+
+/// The magic number at the start of a feature index.
+pub const MAGIC_NUMBER: &[u8; 4] = b"NFI\x01";
+
+/// The strand of a feature.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Strand {
+    /// The forward strand (`+`).
+    Forward,
+    /// The reverse strand (`-`).
+    Reverse,
+    /// An unknown or unstranded feature (`.`).
+    Unknown,
+}
+
+impl Strand {
+    /// Parses a strand from a GTF strand column. Any value other than `+` or
+    /// `-` is unknown.
+    pub fn from_bytes(s: &[u8]) -> Self {
+        match s {
+            b"+" => Strand::Forward,
+            b"-" => Strand::Reverse,
+            _ => Strand::Unknown,
+        }
+    }
+
+    fn as_byte(self) -> u8 {
+        match self {
+            Strand::Forward => b'+',
+            Strand::Reverse => b'-',
+            Strand::Unknown => b'.',
+        }
+    }
+}
+
+/// A feature in a feature index.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IndexedFeature {
+    /// The feature ID.
+    pub id: FeatureId,
+    /// The strand of the feature.
+    pub strand: Strand,
+    /// The length of the feature, as used to normalize its counts.
+    pub length: u64,
+    /// The intervals of the feature, sorted by start.
+    pub intervals: Vec<Feature>,
+}
+
+impl IndexedFeature {
+    fn start(&self) -> u64 {
+        self.intervals.first().map(|f| f.start).unwrap_or(0)
+    }
+
+    /// Returns whether an interval of the feature overlaps the 1-based,
+    /// closed interval [`start`, `end`].
+    pub fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.intervals
+            .iter()
+            .any(|f| f.start <= end && start <= f.end)
+    }
+}
+
+/// The features of annotations grouped by reference sequence, e.g., for reuse
+/// by other tools.
+///
+/// Reference sequences are sorted by name, and the features of each reference
+/// sequence are sorted by start and then ID.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FeatureIndex {
+    reference_sequences: Vec<(String, Vec<IndexedFeature>)>,
+}
+
+impl FeatureIndex {
+    /// Builds a feature index.
+    ///
+    /// Features without a reference sequence name or a length are not
+    /// indexed, and features without a strand are unstranded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::{
+    ///     feature_id::FeatureId,
+    ///     feature_index::{FeatureIndex, Strand},
+    ///     features::{Feature, Features},
+    ///     gtf::{ReferenceSequenceNames, Strands},
+    ///     lengths::calculate_lengths,
+    /// };
+    ///
+    /// let mut features = Features::default();
+    /// features.insert(FeatureId::from("AAAS"), vec![Feature::new(1, 100), Feature::new(201, 300)]);
+    ///
+    /// let lengths = calculate_lengths(&features);
+    ///
+    /// let mut reference_sequence_names = ReferenceSequenceNames::default();
+    /// reference_sequence_names.insert(FeatureId::from("AAAS"), String::from("chr12"));
+    ///
+    /// let mut strands = Strands::default();
+    /// strands.insert(FeatureId::from("AAAS"), Strand::Reverse);
+    ///
+    /// let index = FeatureIndex::build(&features, &lengths, &reference_sequence_names, &strands);
+    ///
+    /// let hits: Vec<_> = index.query("chr12", 150, 250).collect();
+    /// assert_eq!(hits.len(), 1);
+    /// assert_eq!(hits[0].length, 200);
+    /// assert_eq!(hits[0].strand, Strand::Reverse);
+    ///
+    /// assert_eq!(index.query("chr12", 101, 200).count(), 0);
+    /// assert_eq!(index.query("chr1", 150, 250).count(), 0);
+    /// ```
+    pub fn build(
+        features: &Features,
+        lengths: &Lengths,
+        reference_sequence_names: &ReferenceSequenceNames,
+        strands: &Strands,
+    ) -> Self {
+        let mut reference_sequences: Vec<(String, Vec<IndexedFeature>)> = Vec::new();
+
+        for (id, intervals) in features {
+            let (name, length) = match (reference_sequence_names.get(id), lengths.get(id)) {
+                (Some(name), Some(&length)) => (name, length),
+                _ => continue,
+            };
+
+            let mut intervals = intervals.clone();
+            intervals.sort_by_key(|f| (f.start, f.end));
+
+            let feature = IndexedFeature {
+                id: id.clone(),
+                strand: strands.get(id).copied().unwrap_or(Strand::Unknown),
+                length,
+                intervals,
+            };
+
+            match reference_sequences.iter_mut().find(|(n, _)| n == name) {
+                Some((_, features)) => features.push(feature),
+                None => reference_sequences.push((name.clone(), vec![feature])),
+            }
+        }
+
+        reference_sequences.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (_, features) in &mut reference_sequences {
+            features.sort_by(|a, b| (a.start(), &a.id).cmp(&(b.start(), &b.id)));
+        }
+
+        FeatureIndex {
+            reference_sequences,
+        }
+    }
+
+    /// Returns the reference sequences and their features.
+    pub fn reference_sequences(&self) -> &[(String, Vec<IndexedFeature>)] {
+        &self.reference_sequences
+    }
+
+    /// Returns the number of indexed features.
+    pub fn len(&self) -> usize {
+        self.reference_sequences
+            .iter()
+            .map(|(_, features)| features.len())
+            .sum()
+    }
+
+    /// Returns whether the index has no features.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the features on a reference sequence with an interval that
+    /// overlaps the 1-based, closed interval [`start`, `end`].
+    ///
+    /// A feature overlaps if any of its intervals does, so a region entirely
+    /// in an intron, i.e., between two intervals, does not overlap the
+    /// feature.
+    pub fn query<'a>(
+        &'a self,
+        reference_sequence_name: &str,
+        start: u64,
+        end: u64,
+    ) -> impl Iterator<Item = &'a IndexedFeature> {
+        let features = self
+            .reference_sequences
+            .iter()
+            .find(|(name, _)| name == reference_sequence_name)
+            .map(|(_, features)| features.as_slice())
+            .unwrap_or_default();
+
+        features
+            .iter()
+            .take_while(move |feature| feature.start() <= end)
+            .filter(move |feature| feature.overlaps(start, end))
+    }
+}
+
+/// Writes a feature index in a compact binary format.
+///
+/// The index starts with the magic number `NFI\x01`. Integers are
+/// little-endian, and strings are UTF-8, prefixed with their length (`u32`).
+/// The rest is the number of reference sequences (`u32`), then for each, its
+/// name and number of features (`u32`), then for each feature, its ID, strand
+/// (`+`, `-`, or `.`), length (`u64`), and number of intervals (`u32`), and
+/// then the start and end (`u64`) of each interval.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::feature_index::{read_feature_index, write_feature_index, FeatureIndex};
+///
+/// let index = FeatureIndex::default();
+///
+/// let mut buf = Vec::new();
+/// write_feature_index(&mut buf, &index)?;
+///
+/// assert_eq!(read_feature_index(&buf[..])?, index);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn write_feature_index<W>(mut writer: W, index: &FeatureIndex) -> io::Result<()>
+where
+    W: Write,
+{
+    writer.write_all(MAGIC_NUMBER)?;
+    write_len(&mut writer, index.reference_sequences.len())?;
+
+    for (name, features) in &index.reference_sequences {
+        write_string(&mut writer, name)?;
+        write_len(&mut writer, features.len())?;
+
+        for feature in features {
+            write_string(&mut writer, feature.id.as_str())?;
+            writer.write_all(&[feature.strand.as_byte()])?;
+            writer.write_all(&feature.length.to_le_bytes())?;
+            write_len(&mut writer, feature.intervals.len())?;
+
+            for interval in &feature.intervals {
+                writer.write_all(&interval.start.to_le_bytes())?;
+                writer.write_all(&interval.end.to_le_bytes())?;
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+/// Reads a feature index written by [`write_feature_index`].
+///
+/// [`write_feature_index`]: fn.write_feature_index.html
+pub fn read_feature_index<R>(mut reader: R) -> io::Result<FeatureIndex>
+where
+    R: Read,
+{
+    let mut magic_number = [0; 4];
+    reader.read_exact(&mut magic_number)?;
+
+    if &magic_number != MAGIC_NUMBER {
+        return Err(invalid_data(String::from(
+            "invalid feature index: invalid magic number",
+        )));
+    }
+
+    let reference_sequence_count = read_u32(&mut reader)?;
+    let mut reference_sequences = Vec::new();
+
+    for _ in 0..reference_sequence_count {
+        let name = read_string(&mut reader)?;
+        let feature_count = read_u32(&mut reader)?;
+        let mut features = Vec::new();
+
+        for _ in 0..feature_count {
+            let id = read_string(&mut reader)?;
+
+            let mut strand = [0; 1];
+            reader.read_exact(&mut strand)?;
+
+            let strand = match strand[0] {
+                b'+' => Strand::Forward,
+                b'-' => Strand::Reverse,
+                b'.' => Strand::Unknown,
+                b => {
+                    return Err(invalid_data(format!(
+                        "invalid feature index: invalid strand: {}",
+                        b
+                    )))
+                }
+            };
+
+            let length = read_u64(&mut reader)?;
+            let interval_count = read_u32(&mut reader)?;
+            let mut intervals = Vec::new();
+
+            for _ in 0..interval_count {
+                let start = read_u64(&mut reader)?;
+                let end = read_u64(&mut reader)?;
+                intervals.push(Feature::new(start, end));
+            }
+
+            features.push(IndexedFeature {
+                id: FeatureId::from(id),
+                strand,
+                length,
+                intervals,
+            });
+        }
+
+        reference_sequences.push((name, features));
+    }
+
+    Ok(FeatureIndex {
+        reference_sequences,
+    })
+}
+
+fn write_len<W>(writer: &mut W, len: usize) -> io::Result<()>
+where
+    W: Write,
+{
+    let len = u32::try_from(len).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    writer.write_all(&len.to_le_bytes())
+}
+
+fn write_string<W>(writer: &mut W, s: &str) -> io::Result<()>
+where
+    W: Write,
+{
+    write_len(writer, s.len())?;
+    writer.write_all(s.as_bytes())
+}
+
+fn read_u32<R>(reader: &mut R) -> io::Result<u32>
+where
+    R: Read,
+{
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R>(reader: &mut R) -> io::Result<u64>
+where
+    R: Read,
+{
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string<R>(reader: &mut R) -> io::Result<String>
+where
+    R: Read,
+{
+    let len = read_u32(reader)?;
+    let mut buf = Vec::new();
+    reader.take(u64::from(len)).read_to_end(&mut buf)?;
+
+    if buf.len() != len as usize {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_feature_index_round_trip() -> io::Result<()> {
+        let mut features = Features::default();
+        features.insert(
+            FeatureId::from("DDX11L1"),
+            vec![Feature::new(12613, 12721), Feature::new(11869, 12227)],
+        );
+        features.insert(
+            FeatureId::from("NECAP2"),
+            vec![Feature::new(16440672, 16440853)],
+        );
+        features.insert(
+            FeatureId::from("XIST"),
+            vec![Feature::new(73820651, 73852753)],
+        );
+
+        let lengths = crate::lengths::calculate_lengths(&features);
+
+        let mut reference_sequence_names = ReferenceSequenceNames::default();
+        reference_sequence_names.insert(FeatureId::from("DDX11L1"), String::from("chr1"));
+        reference_sequence_names.insert(FeatureId::from("NECAP2"), String::from("chr1"));
+
+        let mut strands = Strands::default();
+        strands.insert(FeatureId::from("DDX11L1"), Strand::Forward);
+
+        let index = FeatureIndex::build(&features, &lengths, &reference_sequence_names, &strands);
+
+        assert_eq!(index.len(), 2);
+
+        let (name, chr1_features) = &index.reference_sequences()[0];
+        assert_eq!(name, "chr1");
+        assert_eq!(chr1_features[0].id.as_str(), "DDX11L1");
+        assert_eq!(chr1_features[0].intervals[0], Feature::new(11869, 12227));
+        assert_eq!(chr1_features[1].strand, Strand::Unknown);
+
+        let mut buf = Vec::new();
+        write_feature_index(&mut buf, &index)?;
+        assert_eq!(read_feature_index(&buf[..])?, index);
+
+        assert!(read_feature_index(&buf[..buf.len() - 1]).is_err());
+        assert!(read_feature_index(&b"NFI\x02"[..]).is_err());
+
+        Ok(())
+    }
+}
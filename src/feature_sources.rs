@@ -0,0 +1,868 @@
+use std::{borrow::Cow, collections::hash_map::Entry, io, str};
+
+use crate::{
+    counts::parse_u64,
+    feature_id::FeatureId,
+    features::{
+        with_line_number, Feature, FeatureSource, Features, ReadFeaturesOptions, RecordTally,
+    },
+    gtf::is_skipped_line,
+    lengths::Lengths,
+    sequence,
+};
+
+const GFF3_FIELD_COUNT: usize = 9;
+const GFF3_TYPE_INDEX: usize = 2;
+const GFF3_START_INDEX: usize = 3;
+const GFF3_END_INDEX: usize = 4;
+const GFF3_ATTRIBUTES_INDEX: usize = 8;
+static GFF3_VERSION_PREFIX: &[u8] = b"##gff-version 3";
+static GFF3_FASTA_DIRECTIVE: &[u8] = b"##FASTA";
+
+const BED_MIN_FIELD_COUNT: usize = 4;
+const BED12_FIELD_COUNT: usize = 12;
+static BLOCK_FEATURE_TYPE: &str = "exon";
+
+static SAF_HEADER_PREFIX: &[u8] = b"GeneID\tChr\tStart\tEnd\tStrand";
+
+const REF_FLAT_FIELD_COUNT: usize = 11;
+static REF_FLAT_TRANSCRIPT_IDS: [&str; 2] = ["transcript_id", "transcript_name"];
+
+static FASTA_HEADER_PREFIX: &[u8] = b">";
+
+static LENGTH_HEADER: &str = "length";
+
+/// The GFF3 format.
+///
+/// Records of the feature type are grouped by the value of the feature ID
+/// attribute, e.g., `gene_id` or `Parent`. Values are percent-decoded, and a
+/// record with multiple values (`Parent=t1,t2`) is added to each. Records
+/// that lack the attribute are handled as in GTF/GFFv2 (see
+/// [`ReadFeaturesOptions`]). Reading stops at an embedded FASTA section
+/// (`##FASTA`).
+///
+/// Data is detected as GFF3 when it has a `##gff-version 3` header or its
+/// first attribute is a `key=value` pair.
+///
+/// [`ReadFeaturesOptions`]: ../features/struct.ReadFeaturesOptions.html
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Gff3;
+
+impl FeatureSource for Gff3 {
+    fn name(&self) -> &'static str {
+        "gff3"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        if lines(buf)
+            .take_while(|(_, line)| is_skipped_line(line))
+            .any(|(_, line)| line.starts_with(GFF3_VERSION_PREFIX))
+        {
+            return true;
+        }
+
+        records(buf)
+            .next()
+            .map(|(_, line)| {
+                let fields = split_fields(line);
+
+                fields.len() == GFF3_FIELD_COUNT
+                    && fields[GFF3_ATTRIBUTES_INDEX]
+                        .iter()
+                        .find(|&&b| b == b' ' || b == b'=')
+                        == Some(&b'=')
+            })
+            .unwrap_or(false)
+    }
+
+    fn read_features(
+        &self,
+        buf: &[u8],
+        feature_type: &str,
+        feature_id: &str,
+        options: &ReadFeaturesOptions,
+    ) -> io::Result<Features> {
+        let mut features = Features::new();
+        let mut tally = RecordTally::default();
+
+        for (line_number, line) in records(buf) {
+            let fields = split_fields(line);
+
+            if fields.len() != GFF3_FIELD_COUNT {
+                return Err(with_line_number(
+                    invalid_data(format!(
+                        "invalid record: expected {} fields, got {}",
+                        GFF3_FIELD_COUNT,
+                        fields.len()
+                    )),
+                    line_number,
+                ));
+            }
+
+            if fields[GFF3_TYPE_INDEX] != feature_type.as_bytes() {
+                continue;
+            }
+
+            let feature = parse_interval(&fields, GFF3_START_INDEX, GFF3_END_INDEX, 0)
+                .map_err(|e| with_line_number(e, line_number))?;
+
+            let attributes = parse_str(fields[GFF3_ATTRIBUTES_INDEX], "attributes")
+                .map_err(|e| with_line_number(e, line_number))?;
+
+            match find_attribute(attributes, feature_id) {
+                Some(value) => {
+                    tally.add();
+
+                    for id in value.split(',') {
+                        push(&mut features, &percent_decode(id), feature.clone());
+                    }
+                }
+                None => tally.add_missing(Some(line_number)),
+            }
+        }
+
+        tally.check(feature_type, feature_id, options)?;
+
+        Ok(features)
+    }
+}
+
+/// The BED format (BED4 to BED12), e.g., as exported from the UCSC Table
+/// Browser.
+///
+/// BED has neither feature types nor attributes, so each record is a feature
+/// named by its name (column 4). When the feature type is `exon`, the blocks
+/// of a BED12 record are its intervals; otherwise, or for records with fewer
+/// columns, the interval is the whole record. Positions are converted from
+/// 0-based, half-open to 1-based, inclusive.
+///
+/// Data is detected as BED when its first record has at least 4 fields and
+/// its start and end are integers.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Bed;
+
+impl FeatureSource for Bed {
+    fn name(&self) -> &'static str {
+        "bed"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        records(buf)
+            .next()
+            .map(|(_, line)| {
+                let fields = split_fields(line);
+
+                fields.len() >= BED_MIN_FIELD_COUNT
+                    && parse_u64(fields[1]).is_some()
+                    && parse_u64(fields[2]).is_some()
+            })
+            .unwrap_or(false)
+    }
+
+    fn read_features(
+        &self,
+        buf: &[u8],
+        feature_type: &str,
+        _: &str,
+        _: &ReadFeaturesOptions,
+    ) -> io::Result<Features> {
+        let mut features = Features::new();
+
+        for (line_number, line) in records(buf) {
+            let fields = split_fields(line);
+
+            if fields.len() < BED_MIN_FIELD_COUNT {
+                return Err(with_line_number(
+                    invalid_data(format!(
+                        "invalid record: expected at least {} fields, got {}",
+                        BED_MIN_FIELD_COUNT,
+                        fields.len()
+                    )),
+                    line_number,
+                ));
+            }
+
+            let name =
+                parse_str(fields[3], "name").map_err(|e| with_line_number(e, line_number))?;
+
+            let intervals =
+                if feature_type == BLOCK_FEATURE_TYPE && fields.len() >= BED12_FIELD_COUNT {
+                    parse_blocks(&fields)
+                } else {
+                    parse_interval(&fields, 1, 2, 1).map(|feature| vec![feature])
+                }
+                .map_err(|e| with_line_number(e, line_number))?;
+
+            for feature in intervals {
+                push(&mut features, name, feature);
+            }
+        }
+
+        Ok(features)
+    }
+}
+
+// Builds the intervals of the blocks of a BED12 record from the block sizes
+// (column 11) and block starts (column 12), which are relative to the start.
+fn parse_blocks(fields: &[&[u8]]) -> io::Result<Vec<Feature>> {
+    let start = parse_field(fields, 1, "start")?;
+    let sizes = parse_list(fields[10], "block sizes")?;
+    let starts = parse_list(fields[11], "block starts")?;
+
+    if sizes.len() != starts.len() || sizes.is_empty() {
+        return Err(invalid_data(String::from(
+            "invalid record: block sizes and starts differ in length",
+        )));
+    }
+
+    Ok(starts
+        .into_iter()
+        .zip(sizes)
+        .filter(|(_, size)| *size > 0)
+        .map(|(block_start, size)| {
+            let block_start = start + block_start;
+            Feature::new(block_start + 1, block_start + size)
+        })
+        .collect())
+}
+
+/// The Simplified Annotation Format (SAF) of featureCounts.
+///
+/// Each row is an interval of a feature: `GeneID`, `Chr`, `Start`, `End`, and
+/// `Strand`, with 1-based, inclusive positions. SAF has neither feature types
+/// nor attributes, so the feature type and ID are not used.
+///
+/// Data is detected as SAF by its header.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Saf;
+
+impl FeatureSource for Saf {
+    fn name(&self) -> &'static str {
+        "saf"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        records(buf)
+            .next()
+            .map(|(_, line)| line.starts_with(SAF_HEADER_PREFIX))
+            .unwrap_or(false)
+    }
+
+    fn read_features(
+        &self,
+        buf: &[u8],
+        _: &str,
+        _: &str,
+        _: &ReadFeaturesOptions,
+    ) -> io::Result<Features> {
+        let mut features = Features::new();
+
+        for (line_number, line) in records(buf).skip(1) {
+            let fields = split_fields(line);
+
+            let result = parse_str(fields[0], "GeneID").and_then(|id| {
+                let feature = parse_interval(&fields, 2, 3, 0)?;
+                Ok((id, feature))
+            });
+
+            let (id, feature) = result.map_err(|e| with_line_number(e, line_number))?;
+            push(&mut features, id, feature);
+        }
+
+        Ok(features)
+    }
+}
+
+/// The refFlat format of the UCSC Genome Browser, e.g., `refFlat.txt.gz`.
+///
+/// Each row is a transcript. The feature type selects its intervals: `exon`
+/// for its exons, `CDS` for its coding region, and `transcript` or `gene`
+/// for its whole span. Transcripts are grouped by transcript name (column 2)
+/// when the feature ID is `transcript_id` or `transcript_name` and by gene
+/// name (column 1) otherwise. Positions are converted from 0-based,
+/// half-open to 1-based, inclusive.
+///
+/// Data is detected as refFlat when its first record has 11 fields, a strand,
+/// and integer positions.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RefFlat;
+
+impl FeatureSource for RefFlat {
+    fn name(&self) -> &'static str {
+        "refFlat"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        records(buf)
+            .next()
+            .map(|(_, line)| {
+                let fields = split_fields(line);
+
+                fields.len() == REF_FLAT_FIELD_COUNT
+                    && (fields[3] == b"+" || fields[3] == b"-")
+                    && fields[4..=8].iter().all(|f| parse_u64(f).is_some())
+            })
+            .unwrap_or(false)
+    }
+
+    fn read_features(
+        &self,
+        buf: &[u8],
+        feature_type: &str,
+        feature_id: &str,
+        _: &ReadFeaturesOptions,
+    ) -> io::Result<Features> {
+        let id_index = if REF_FLAT_TRANSCRIPT_IDS.contains(&feature_id) {
+            1
+        } else {
+            0
+        };
+
+        let mut features = Features::new();
+
+        for (line_number, line) in records(buf) {
+            let fields = split_fields(line);
+
+            if fields.len() != REF_FLAT_FIELD_COUNT {
+                return Err(with_line_number(
+                    invalid_data(format!(
+                        "invalid record: expected {} fields, got {}",
+                        REF_FLAT_FIELD_COUNT,
+                        fields.len()
+                    )),
+                    line_number,
+                ));
+            }
+
+            let id = parse_str(fields[id_index], "name")
+                .map_err(|e| with_line_number(e, line_number))?;
+
+            let intervals = match feature_type {
+                "exon" => parse_exons(&fields),
+                "CDS" => parse_cds(&fields),
+                "transcript" | "gene" => parse_interval(&fields, 4, 5, 1).map(|f| vec![f]),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("refFlat has no '{}' features", feature_type),
+                    ))
+                }
+            }
+            .map_err(|e| with_line_number(e, line_number))?;
+
+            for feature in intervals {
+                push(&mut features, id, feature);
+            }
+        }
+
+        Ok(features)
+    }
+}
+
+// Builds the coding region of a refFlat record. Noncoding transcripts, whose
+// CDS start and end (columns 7 and 8) are equal, have none.
+fn parse_cds(fields: &[&[u8]]) -> io::Result<Vec<Feature>> {
+    let start = parse_field(fields, 6, "start")?;
+    let end = parse_field(fields, 7, "end")?;
+
+    if start == end {
+        Ok(Vec::new())
+    } else {
+        checked_interval(start + 1, end).map(|feature| vec![feature])
+    }
+}
+
+// Builds the intervals of the exons of a refFlat record from the exon starts
+// (column 10) and ends (column 11).
+fn parse_exons(fields: &[&[u8]]) -> io::Result<Vec<Feature>> {
+    let starts = parse_list(fields[9], "exon starts")?;
+    let ends = parse_list(fields[10], "exon ends")?;
+
+    if starts.len() != ends.len() {
+        return Err(invalid_data(String::from(
+            "invalid record: exon starts and ends differ in length",
+        )));
+    }
+
+    starts
+        .into_iter()
+        .zip(ends)
+        .map(|(start, end)| checked_interval(start + 1, end))
+        .collect()
+}
+
+/// The lengths of sequences in a FASTA, e.g., a transcriptome.
+///
+/// Each sequence is a feature named by its header up to the first whitespace,
+/// and its length is the length of the sequence. The feature type and ID are
+/// not used.
+///
+/// Data is detected as FASTA when its first line is a header (`>`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FastaLengths;
+
+impl FeatureSource for FastaLengths {
+    fn name(&self) -> &'static str {
+        "fasta"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        records(buf)
+            .next()
+            .map(|(_, line)| line.starts_with(FASTA_HEADER_PREFIX))
+            .unwrap_or(false)
+    }
+
+    fn read_features(
+        &self,
+        buf: &[u8],
+        feature_type: &str,
+        feature_id: &str,
+        options: &ReadFeaturesOptions,
+    ) -> io::Result<Features> {
+        self.read_lengths(buf, feature_type, feature_id, options)
+            .map(features_from_lengths)
+    }
+
+    fn read_lengths(
+        &self,
+        buf: &[u8],
+        _: &str,
+        _: &str,
+        _: &ReadFeaturesOptions,
+    ) -> io::Result<Lengths> {
+        let mut lengths = Lengths::new();
+        let mut entry: Option<(u64, String, u64)> = None;
+
+        for (line_number, line) in lines(buf) {
+            if let Some(header) = line.strip_prefix(FASTA_HEADER_PREFIX) {
+                if let Some((line_number, name, len)) = entry.take() {
+                    insert_length(&mut lengths, &name, len)
+                        .map_err(|e| with_line_number(e, line_number))?;
+                }
+
+                let name = sequence::parse_name(&String::from_utf8_lossy(header));
+                entry = Some((line_number, name, 0));
+            } else if let Some((_, _, len)) = entry.as_mut() {
+                let end = line
+                    .iter()
+                    .rposition(|b| !b.is_ascii_whitespace())
+                    .map(|i| i + 1)
+                    .unwrap_or_default();
+
+                *len += end as u64;
+            }
+        }
+
+        if let Some((line_number, name, len)) = entry {
+            insert_length(&mut lengths, &name, len)
+                .map_err(|e| with_line_number(e, line_number))?;
+        }
+
+        Ok(lengths)
+    }
+}
+
+/// A table of precomputed feature lengths, e.g., from a previous run or
+/// another tool.
+///
+/// The first row is a header whose second column is `length`, e.g.,
+/// `gene_id length`, and each subsequent row is a feature ID and its length.
+/// The header is required, as a headerless table of lengths cannot be told
+/// apart from counts. The feature type and ID are not used.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PrecomputedLengths;
+
+impl FeatureSource for PrecomputedLengths {
+    fn name(&self) -> &'static str {
+        "lengths"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        records(buf)
+            .next()
+            .map(|(_, line)| {
+                let fields = split_fields(line);
+
+                fields.len() == 2
+                    && str::from_utf8(fields[1])
+                        .map(|s| s.trim().eq_ignore_ascii_case(LENGTH_HEADER))
+                        .unwrap_or(false)
+            })
+            .unwrap_or(false)
+    }
+
+    fn read_features(
+        &self,
+        buf: &[u8],
+        feature_type: &str,
+        feature_id: &str,
+        options: &ReadFeaturesOptions,
+    ) -> io::Result<Features> {
+        self.read_lengths(buf, feature_type, feature_id, options)
+            .map(features_from_lengths)
+    }
+
+    fn read_lengths(
+        &self,
+        buf: &[u8],
+        _: &str,
+        _: &str,
+        _: &ReadFeaturesOptions,
+    ) -> io::Result<Lengths> {
+        let mut lengths = Lengths::new();
+
+        for (line_number, line) in records(buf).skip(1) {
+            let fields = split_fields(line);
+
+            parse_str(fields[0], "feature ID")
+                .and_then(|id| {
+                    let len = parse_field(&fields, 1, "length")?;
+                    insert_length(&mut lengths, id, len)
+                })
+                .map_err(|e| with_line_number(e, line_number))?;
+        }
+
+        Ok(lengths)
+    }
+}
+
+// Lengths are represented as features with a single interval of the length.
+// Empty sequences have no intervals.
+fn features_from_lengths(lengths: Lengths) -> Features {
+    lengths
+        .into_iter()
+        .map(|(id, len)| {
+            let intervals = if len > 0 {
+                vec![Feature::new(1, len)]
+            } else {
+                Vec::new()
+            };
+
+            (id, intervals)
+        })
+        .collect()
+}
+
+fn insert_length(lengths: &mut Lengths, id: &str, len: u64) -> io::Result<()> {
+    match lengths.entry(FeatureId::from(id)) {
+        Entry::Vacant(entry) => {
+            entry.insert(len);
+            Ok(())
+        }
+        Entry::Occupied(_) => Err(invalid_data(format!("duplicate feature ID: {}", id))),
+    }
+}
+
+// Returns each line with its 1-based line number, without its line ending.
+fn lines(buf: &[u8]) -> impl Iterator<Item = (u64, &[u8])> {
+    buf.split(|&b| b == b'\n')
+        .enumerate()
+        .map(|(i, line)| (i as u64 + 1, line.strip_suffix(b"\r").unwrap_or(line)))
+}
+
+// Returns the lines that are records, up to an embedded FASTA section.
+fn records(buf: &[u8]) -> impl Iterator<Item = (u64, &[u8])> {
+    lines(buf)
+        .take_while(|(_, line)| *line != GFF3_FASTA_DIRECTIVE)
+        .filter(|(_, line)| !is_skipped_line(line))
+}
+
+fn split_fields(line: &[u8]) -> Vec<&[u8]> {
+    line.split(|&b| b == b'\t').collect()
+}
+
+fn parse_str<'a>(field: &'a [u8], name: &str) -> io::Result<&'a str> {
+    str::from_utf8(field)
+        .ok()
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| {
+            invalid_data(format!(
+                "invalid {}: {}",
+                name,
+                String::from_utf8_lossy(field)
+            ))
+        })
+}
+
+fn parse_field(fields: &[&[u8]], i: usize, name: &str) -> io::Result<u64> {
+    let field = fields
+        .get(i)
+        .ok_or_else(|| invalid_data(format!("missing {}", name)))?;
+
+    parse_u64(field).ok_or_else(|| {
+        invalid_data(format!(
+            "invalid {}: {}",
+            name,
+            String::from_utf8_lossy(field)
+        ))
+    })
+}
+
+// Parses a comma-separated list of integers, which may end with a comma.
+fn parse_list(field: &[u8], name: &str) -> io::Result<Vec<u64>> {
+    let field = field.strip_suffix(b",").unwrap_or(field);
+
+    field
+        .split(|&b| b == b',')
+        .map(|value| {
+            parse_u64(value).ok_or_else(|| {
+                invalid_data(format!(
+                    "invalid {}: {}",
+                    name,
+                    String::from_utf8_lossy(field)
+                ))
+            })
+        })
+        .collect()
+}
+
+// Parses the start and end of an interval. `offset` is added to the start,
+// e.g., 1 to convert a 0-based, half-open interval to 1-based, inclusive.
+fn parse_interval(
+    fields: &[&[u8]],
+    start_index: usize,
+    end_index: usize,
+    offset: u64,
+) -> io::Result<Feature> {
+    let start = parse_field(fields, start_index, "start")?;
+    let end = parse_field(fields, end_index, "end")?;
+    checked_interval(start + offset, end)
+}
+
+fn checked_interval(start: u64, end: u64) -> io::Result<Feature> {
+    if end < start {
+        Err(invalid_data(format!(
+            "invalid record: end ({}) < start ({})",
+            end, start
+        )))
+    } else {
+        Ok(Feature::new(start, end))
+    }
+}
+
+// Returns the value of the first attribute with the given key, e.g., `g1` of
+// `ID=g1;Name=AAAS` for `ID`.
+fn find_attribute<'a>(attributes: &'a str, key: &str) -> Option<&'a str> {
+    attributes
+        .split(';')
+        .filter_map(|attribute| attribute.trim().split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, value)| value)
+}
+
+// Decodes percent-encoded characters, e.g., `%3B` (`;`), in a GFF3 value.
+fn percent_decode(s: &str) -> Cow<'_, str> {
+    if !s.contains('%') {
+        return Cow::Borrowed(s);
+    }
+
+    let bytes = s.as_bytes();
+    let mut buf = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let decoded = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match decoded {
+            Some(b) => {
+                buf.push(b);
+                i += 3;
+            }
+            None => {
+                buf.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    Cow::Owned(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn push(features: &mut Features, id: &str, feature: Feature) {
+    features
+        .entry(FeatureId::from(id))
+        .or_default()
+        .push(feature);
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::features::detect_feature_source;
+
+    use super::*;
+
+    fn read(source: &dyn FeatureSource, data: &[u8], ty: &str, id: &str) -> io::Result<Features> {
+        source.read_features(data, ty, id, &ReadFeaturesOptions::default())
+    }
+
+    #[test]
+    fn test_detect_feature_source() {
+        let detect = |buf: &[u8]| detect_feature_source(buf).map(|source| source.name());
+
+        assert_eq!(
+            detect(b"##gff-version 3\nchr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tParent=t1\n"),
+            Some("gff3")
+        );
+        assert_eq!(detect(b"chr1\t11868\t12227\tg1\t0\t+\n"), Some("bed"));
+        assert_eq!(
+            detect(b"GeneID\tChr\tStart\tEnd\tStrand\ng1\tchr1\t11869\t12227\t+\n"),
+            Some("saf")
+        );
+        assert_eq!(
+            detect(b"DDX11L1\tNR_046018\tchr1\t+\t11873\t14409\t14409\t14409\t3\t11873,12612,13220,\t12227,12721,14409,\n"),
+            Some("refFlat")
+        );
+        assert_eq!(detect(b">chr1 description\nACGT\n"), Some("fasta"));
+        assert_eq!(detect(b"gene_id\tlength\ng1\t2541\n"), Some("lengths"));
+        assert!(detect(b"AAAS\t645\n").is_none());
+    }
+
+    #[test]
+    fn test_gff3_read_features() -> io::Result<()> {
+        let data = b"\
+##gff-version 3
+chr1\tHAVANA\tgene\t11869\t14409\t.\t+\t.\tID=g1
+chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tID=e1;Parent=t1,t2
+chr1\tHAVANA\texon\t12613\t12721\t.\t+\t.\tParent=t%3B1
+##FASTA
+>chr1
+ACGT
+";
+
+        let features = read(&Gff3, data, "exon", "Parent")?;
+        assert_eq!(features.len(), 3);
+        assert_eq!(features["t1"], [Feature::new(11869, 12227)]);
+        assert_eq!(features["t2"], [Feature::new(11869, 12227)]);
+        assert_eq!(features["t;1"], [Feature::new(12613, 12721)]);
+
+        assert!(read(&Gff3, data, "exon", "ID").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bed_read_features() -> io::Result<()> {
+        let data = b"\
+track name=genes
+chr1\t11868\t14409\tg1\t0\t+\t11868\t14409\t0\t2\t359,1189,\t0,1352,
+chr1\t14403\t29570\tg2
+";
+
+        let features = read(&Bed, data, "exon", "gene_id")?;
+        assert_eq!(
+            features["g1"],
+            [Feature::new(11869, 12227), Feature::new(13221, 14409)]
+        );
+        assert_eq!(features["g2"], [Feature::new(14404, 29570)]);
+
+        let features = read(&Bed, data, "gene", "gene_id")?;
+        assert_eq!(features["g1"], [Feature::new(11869, 14409)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_saf_read_features() -> io::Result<()> {
+        let data = b"\
+GeneID\tChr\tStart\tEnd\tStrand
+g1\tchr1\t11869\t12227\t+
+g1\tchr1\t12613\t12721\t+
+";
+
+        let features = read(&Saf, data, "exon", "gene_id")?;
+        assert_eq!(
+            features["g1"],
+            [Feature::new(11869, 12227), Feature::new(12613, 12721)]
+        );
+
+        let data = b"GeneID\tChr\tStart\tEnd\tStrand\ng1\tchr1\t12227\t11869\t+\n";
+        let e = read(&Saf, data, "exon", "gene_id").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "line 2: invalid record: end (11869) < start (12227)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ref_flat_read_features() -> io::Result<()> {
+        let data = b"DDX11L1\tNR_046018\tchr1\t+\t11873\t14409\t14409\t14409\t3\t11873,12612,13220,\t12227,12721,14409,\n";
+
+        let features = read(&RefFlat, data, "exon", "gene_name")?;
+        assert_eq!(
+            features["DDX11L1"],
+            [
+                Feature::new(11874, 12227),
+                Feature::new(12613, 12721),
+                Feature::new(13221, 14409)
+            ]
+        );
+
+        let features = read(&RefFlat, data, "transcript", "transcript_id")?;
+        assert_eq!(features["NR_046018"], [Feature::new(11874, 14409)]);
+
+        let features = read(&RefFlat, data, "CDS", "gene_name")?;
+        assert!(features.is_empty());
+
+        assert!(read(&RefFlat, data, "start_codon", "gene_name").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fasta_lengths_read_lengths() -> io::Result<()> {
+        let data = b">t1 AAAS\nACGT\nAC\n>t2\n\n>t3\nA\n";
+        let options = ReadFeaturesOptions::default();
+
+        let lengths = FastaLengths.read_lengths(data, "exon", "gene_id", &options)?;
+        assert_eq!(lengths.len(), 3);
+        assert_eq!(lengths["t1"], 6);
+        assert_eq!(lengths["t2"], 0);
+        assert_eq!(lengths["t3"], 1);
+
+        let features = FastaLengths.read_features(data, "exon", "gene_id", &options)?;
+        assert_eq!(features["t1"], [Feature::new(1, 6)]);
+        assert!(features["t2"].is_empty());
+
+        assert!(FastaLengths
+            .read_lengths(b">t1\nA\n>t1\nC\n", "exon", "gene_id", &options)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_precomputed_lengths_read_lengths() -> io::Result<()> {
+        let data = b"gene_id\tLength\nAAAS\t2541\nRPL37AP1\t54\n";
+        let options = ReadFeaturesOptions::default();
+
+        let lengths = PrecomputedLengths.read_lengths(data, "exon", "gene_id", &options)?;
+        assert_eq!(lengths.len(), 2);
+        assert_eq!(lengths["AAAS"], 2541);
+
+        let data = b"gene_id\tlength\nAAAS\tx\n";
+        let e = PrecomputedLengths
+            .read_lengths(data, "exon", "gene_id", &options)
+            .unwrap_err();
+        assert_eq!(e.to_string(), "line 2: invalid length: x");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("AAAS"), "AAAS");
+        assert_eq!(percent_decode("a%3Bb%2Cc"), "a;b,c");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
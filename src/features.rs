@@ -1,8 +1,77 @@
-use std::{collections::HashMap, io, path::Path};
+use std::{
+    collections::HashMap,
+    error, fmt, fs,
+    io::{self, BufRead, Read},
+    path::Path,
+};
 
+use log::warn;
 use noodles::formats::gff;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt};
 
-pub type Features = HashMap<String, Vec<Feature>>;
+use crate::{
+    compression,
+    feature_id::FeatureId,
+    feature_sources::{Bed, FastaLengths, Gff3, PrecomputedLengths, RefFlat, Saf},
+    group_by::GroupBy,
+    gtf::{self, is_skipped_line, is_track_or_browser_line, log_skipped_lines, Gtf},
+    lengths::{calculate_lengths, Lengths},
+    HashBuilder,
+};
+
+/// A map of feature IDs to their intervals.
+///
+/// The intervals are as read, i.e., in input order and possibly overlapping,
+/// unless they are read by a merged reader, e.g., [`read_merged_features`],
+/// which sorts and merges them.
+///
+/// [`read_merged_features`]: fn.read_merged_features.html
+///
+/// Features are `Send` and `Sync`, and feature IDs are reference counted, so
+/// parsed annotations can be shared by threads, e.g., in an `Arc`, rather than
+/// cloned.
+pub type Features = HashMap<FeatureId, Vec<Feature>>;
+
+// Features while they are parsed, keyed with the internal hasher, as the map
+// is looked up once per record.
+pub(crate) type FeatureMap = HashMap<FeatureId, Vec<Feature>, HashBuilder>;
+
+// The number of bytes of annotations per feature, used to pre-size maps. A
+// GENCODE GTF has about one gene per 20 KiB, so this overestimates the number
+// of genes rather than rehashing while parsing.
+const BYTES_PER_FEATURE: u64 = 16 * 1024;
+
+pub(crate) fn estimated_feature_count(len: u64) -> usize {
+    (len / BYTES_PER_FEATURE) as usize
+}
+
+fn estimated_feature_count_of<P>(src: P) -> usize
+where
+    P: AsRef<Path>,
+{
+    fs::metadata(src)
+        .map(|metadata| estimated_feature_count(metadata.len()))
+        .unwrap_or_default()
+}
+
+// The number of line numbers of skipped records that are logged.
+const MISSING_ATTRIBUTE_EXAMPLE_COUNT: usize = 3;
+
+/// Options for reading features.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReadFeaturesOptions {
+    /// The maximum fraction of records of the feature type that can lack the
+    /// feature ID attribute.
+    ///
+    /// Records that lack the attribute are skipped, and their count is logged
+    /// with a few example line numbers. If the fraction of these records is
+    /// greater than the maximum, reading fails.
+    ///
+    /// The default is 0, i.e., any record that lacks the attribute is an
+    /// error.
+    pub max_missing_id_fraction: f64,
+}
 
 // 1-based, inclusive
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -35,6 +104,142 @@ impl Feature {
     }
 }
 
+/// A format of annotations.
+///
+/// Feature sources are registered in [`FEATURE_SOURCES`] and chosen by
+/// [`detect_feature_source`], so a new format only needs an implementation of
+/// this trait and an entry in the registry.
+///
+/// [`FEATURE_SOURCES`]: static.FEATURE_SOURCES.html
+/// [`detect_feature_source`]: fn.detect_feature_source.html
+pub trait FeatureSource: Sync {
+    /// Returns the name of the format.
+    fn name(&self) -> &'static str;
+
+    /// Returns whether the data is in this format.
+    fn detect(&self, buf: &[u8]) -> bool;
+
+    /// Reads the features of the given type from the data, grouped by the
+    /// given feature identifier.
+    fn read_features(
+        &self,
+        buf: &[u8],
+        feature_type: &str,
+        feature_id: &str,
+        options: &ReadFeaturesOptions,
+    ) -> io::Result<Features>;
+
+    /// Reads the features of the given type from a file, decompressing it if
+    /// its extension is `.gz`.
+    ///
+    /// By default, this reads the whole file and calls [`read_features`].
+    /// Formats that can be streamed override this.
+    ///
+    /// [`read_features`]: #tymethod.read_features
+    fn read_features_from_path(
+        &self,
+        src: &Path,
+        feature_type: &str,
+        feature_id: &str,
+        options: &ReadFeaturesOptions,
+    ) -> io::Result<Features> {
+        let mut buf = Vec::new();
+        compression::open(src)?.read_to_end(&mut buf)?;
+        self.read_features(&buf, feature_type, feature_id, options)
+    }
+
+    /// Reads the lengths of features from the data.
+    ///
+    /// By default, this calculates the lengths of the features read by
+    /// [`read_features`]. Formats that only have lengths, e.g., a table of
+    /// precomputed lengths, override this.
+    ///
+    /// [`read_features`]: #tymethod.read_features
+    fn read_lengths(
+        &self,
+        buf: &[u8],
+        feature_type: &str,
+        feature_id: &str,
+        options: &ReadFeaturesOptions,
+    ) -> io::Result<Lengths> {
+        self.read_features(buf, feature_type, feature_id, options)
+            .map(|features| calculate_lengths(&features))
+    }
+}
+
+/// The registered feature sources, in the order they are detected.
+pub static FEATURE_SOURCES: [&dyn FeatureSource; 7] = [
+    &Gtf,
+    &Gff3,
+    &Saf,
+    &RefFlat,
+    &Bed,
+    &FastaLengths,
+    &PrecomputedLengths,
+];
+
+/// Returns the first registered feature source that detects the format of the
+/// data.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::features::detect_feature_source;
+///
+/// let data = b"chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";\n";
+/// let source = detect_feature_source(data);
+/// assert_eq!(source.map(|s| s.name()), Some("gtf"));
+///
+/// assert!(detect_feature_source(b"AAAS\t645\n").is_none());
+/// ```
+pub fn detect_feature_source(buf: &[u8]) -> Option<&'static dyn FeatureSource> {
+    FEATURE_SOURCES
+        .iter()
+        .copied()
+        .find(|source| source.detect(buf))
+}
+
+/// Returns the first registered feature source that detects the format of a
+/// file.
+///
+/// Only the head of the file, i.e., its header and comment lines and first
+/// record, is read. The file is decompressed if its extension is `.gz`.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::features::detect_feature_source_of;
+///
+/// let source = detect_feature_source_of("test/fixtures/annotations.gtf")?;
+/// assert_eq!(source.map(|s| s.name()), Some("gtf"));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn detect_feature_source_of<P>(src: P) -> io::Result<Option<&'static dyn FeatureSource>>
+where
+    P: AsRef<Path>,
+{
+    let mut reader = compression::open(src)?;
+    let mut head = Vec::new();
+
+    loop {
+        let start = head.len();
+
+        if reader.read_until(b'\n', &mut head)? == 0 {
+            break;
+        }
+
+        let line = &head[start..];
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        if !is_skipped_line(line) {
+            break;
+        }
+    }
+
+    Ok(detect_feature_source(&head))
+}
+
 /// Merges a list of overlapping intervals into a list of non-overlapping intervals.
 ///
 /// The intervals are assumed to be inclusive.
@@ -68,17 +273,50 @@ impl Feature {
 pub fn merge_intervals(intervals: &[Feature]) -> Vec<Feature> {
     assert!(!intervals.is_empty());
 
-    let mut intervals = intervals.to_vec();
-    intervals.sort_unstable_by_key(|i| i.start);
+    let mut merged_intervals = intervals.to_vec();
+    merge_intervals_in_place(&mut merged_intervals);
+    merged_intervals
+}
 
-    let mut merged_intervals = Vec::with_capacity(intervals.len());
-    merged_intervals.push(intervals[0].clone());
+/// Merges a list of overlapping intervals in place.
+///
+/// This is the same as [`merge_intervals`] but reuses the given list for the
+/// output. The list is only sorted if it is not already sorted by start
+/// position, which is the common case for exons read from a GTF. An empty list
+/// is left empty.
+///
+/// [`merge_intervals`]: fn.merge_intervals.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::features::{merge_intervals_in_place, Feature};
+///
+/// let mut features = vec![
+///     Feature::new(9, 12), Feature::new(2, 5), Feature::new(10, 15),
+/// ];
+///
+/// merge_intervals_in_place(&mut features);
+/// assert_eq!(features, [Feature::new(2, 5), Feature::new(9, 15)]);
+/// ```
+pub fn merge_intervals_in_place(intervals: &mut Vec<Feature>) {
+    if intervals.is_empty() {
+        return;
+    }
+
+    if !is_sorted_by_start(intervals) {
+        intervals.sort_unstable_by_key(|i| i.start);
+    }
 
-    for b in intervals {
-        let a = merged_intervals.last_mut().expect("list cannot be empty");
+    let mut last = 0;
+
+    for i in 1..intervals.len() {
+        let b = intervals[i].clone();
+        let a = &mut intervals[last];
 
         if b.start > a.end {
-            merged_intervals.push(b);
+            last += 1;
+            intervals[last] = b;
             continue;
         }
 
@@ -87,7 +325,53 @@ pub fn merge_intervals(intervals: &[Feature]) -> Vec<Feature> {
         }
     }
 
-    merged_intervals
+    intervals.truncate(last + 1);
+}
+
+/// Splits a list of overlapping intervals into disjoint bins.
+///
+/// Unlike [`merge_intervals`], overlapping intervals are not combined but cut
+/// at each of their boundaries, as DEXSeq flattens exons into exonic bins.
+/// Each position covered by an interval is in exactly one bin, and bins are
+/// sorted by position.
+///
+/// [`merge_intervals`]: fn.merge_intervals.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::features::{flatten_intervals, Feature};
+///
+/// let features = [Feature::new(2, 7), Feature::new(5, 9), Feature::new(12, 15)];
+///
+/// assert_eq!(
+///     flatten_intervals(&features),
+///     [Feature::new(2, 4), Feature::new(5, 7), Feature::new(8, 9), Feature::new(12, 15)],
+/// );
+/// ```
+pub fn flatten_intervals(intervals: &[Feature]) -> Vec<Feature> {
+    // Boundaries are the first position of each segment.
+    let mut boundaries: Vec<u64> = intervals
+        .iter()
+        .flat_map(|i| vec![i.start, i.end + 1])
+        .collect();
+
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    boundaries
+        .windows(2)
+        .map(|w| Feature::new(w[0], w[1] - 1))
+        .filter(|bin| {
+            intervals
+                .iter()
+                .any(|i| i.start <= bin.start && bin.end <= i.end)
+        })
+        .collect()
+}
+
+fn is_sorted_by_start(intervals: &[Feature]) -> bool {
+    intervals.windows(2).all(|w| w[0].start <= w[1].start)
 }
 
 /// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2.
@@ -122,38 +406,714 @@ pub fn read_features<P>(src: P, feature_type: &str, feature_id: &str) -> io::Res
 where
     P: AsRef<Path>,
 {
-    let mut reader = gff::open(src)?;
-    let mut features: Features = HashMap::new();
+    read_features_with_options(
+        src,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+/// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2 with
+/// options.
+///
+/// This is the same as [`read_features`], but records that lack the feature
+/// ID attribute can be skipped. See [`ReadFeaturesOptions`].
+///
+/// [`read_features`]: fn.read_features.html
+/// [`ReadFeaturesOptions`]: struct.ReadFeaturesOptions.html
+pub fn read_features_with_options<P>(
+    src: P,
+    feature_type: &str,
+    feature_id: &str,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features>
+where
+    P: AsRef<Path>,
+{
+    let capacity = estimated_feature_count_of(&src);
+    let reader = gff::open(src)?;
+    parse_features_with_capacity(
+        reader,
+        feature_type,
+        feature_id,
+        Vec::push,
+        options,
+        capacity,
+    )
+}
+
+/// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2 reader.
+///
+/// This is the same as [`read_features`], but the input is read from any
+/// reader, e.g., an in-memory buffer or a network stream. The input must be
+/// uncompressed.
+///
+/// [`read_features`]: fn.read_features.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::features::{read_features_from_reader, Feature};
+///
+/// let data = b"\
+/// chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";
+/// chr1\tHAVANA\texon\t12613\t12721\t.\t+\t.\tgene_id \"g1\";
+/// ";
+///
+/// let features = read_features_from_reader(&data[..], "exon", "gene_id")?;
+///
+/// assert_eq!(
+///     &features["g1"],
+///     &[Feature::new(11869, 12227), Feature::new(12613, 12721)],
+/// );
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_features_from_reader<R>(
+    reader: R,
+    feature_type: &str,
+    feature_id: &str,
+) -> io::Result<Features>
+where
+    R: BufRead,
+{
+    read_features_from_reader_with_options(
+        reader,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+/// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2 reader
+/// with options.
+///
+/// This is the same as [`read_features_with_options`], but the input is read
+/// from any reader. The input must be uncompressed.
+///
+/// [`read_features_with_options`]: fn.read_features_with_options.html
+pub fn read_features_from_reader_with_options<R>(
+    reader: R,
+    feature_type: &str,
+    feature_id: &str,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features>
+where
+    R: BufRead,
+{
+    let reader = gff::Reader::new(reader);
+    parse_features(reader, feature_type, feature_id, Vec::push, options)
+}
+
+/// Builds a map of feature ID-merged feature vector pairs from a GTF/GFFv2.
+///
+/// This is the same as [`read_features`], except intervals are merged as they
+/// are read rather than all being kept in memory until the end. When the
+/// input is sorted by position, as GENCODE and Ensembl GTFs are, each list
+/// holds only nonoverlapping intervals, and peak memory is bound by the
+/// merged (rather than raw) number of intervals.
+///
+/// Unsorted input is still accepted. A new interval is only merged with the
+/// one previously read for the same feature, so each list is sorted and
+/// merged again once all records are read. The lists are always sorted by
+/// start position and nonoverlapping.
+///
+/// [`read_features`]: fn.read_features.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::features::{read_merged_features, Feature};
+///
+/// let features = read_merged_features(
+///     "test/fixtures/annotations.gtf",
+///     "gene",
+///     "gene_name",
+/// ).unwrap();
+///
+/// assert_eq!(&features["DDX11L1"], &[Feature::new(11869, 14409)]);
+/// ```
+pub fn read_merged_features<P>(src: P, feature_type: &str, feature_id: &str) -> io::Result<Features>
+where
+    P: AsRef<Path>,
+{
+    read_merged_features_with_options(
+        src,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+/// Builds a map of feature ID-merged feature vector pairs from a GTF/GFFv2
+/// with options.
+///
+/// This is the same as [`read_merged_features`], but records that lack the
+/// feature ID attribute can be skipped.
+///
+/// [`read_merged_features`]: fn.read_merged_features.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::features::{read_merged_features_with_options, ReadFeaturesOptions};
+///
+/// let options = ReadFeaturesOptions {
+///     max_missing_id_fraction: 0.5,
+/// };
+///
+/// let features = read_merged_features_with_options(
+///     "test/fixtures/annotations.gtf",
+///     "exon",
+///     "gene_name",
+///     &options,
+/// )?;
+///
+/// assert_eq!(features.len(), 2);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_merged_features_with_options<P>(
+    src: P,
+    feature_type: &str,
+    feature_id: &str,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features>
+where
+    P: AsRef<Path>,
+{
+    let capacity = estimated_feature_count_of(&src);
+    let reader = gff::open(src)?;
+    parse_features_with_capacity(
+        reader,
+        feature_type,
+        feature_id,
+        push_merged,
+        options,
+        capacity,
+    )
+    .map(merge_features)
+}
+
+/// Builds a map of feature ID-merged feature vector pairs from a GTF/GFFv2
+/// reader.
+///
+/// This is the same as [`read_merged_features`], but the input is read from
+/// any reader. The input must be uncompressed.
+///
+/// [`read_merged_features`]: fn.read_merged_features.html
+pub fn read_merged_features_from_reader<R>(
+    reader: R,
+    feature_type: &str,
+    feature_id: &str,
+) -> io::Result<Features>
+where
+    R: BufRead,
+{
+    read_merged_features_from_reader_with_options(
+        reader,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+/// Builds a map of feature ID-merged feature vector pairs from a GTF/GFFv2
+/// reader with options.
+///
+/// This is the same as [`read_merged_features_with_options`], but the input
+/// is read from any reader. The input must be uncompressed.
+///
+/// [`read_merged_features_with_options`]: fn.read_merged_features_with_options.html
+pub fn read_merged_features_from_reader_with_options<R>(
+    reader: R,
+    feature_type: &str,
+    feature_id: &str,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features>
+where
+    R: BufRead,
+{
+    let reader = gff::Reader::new(reader);
+    parse_features(reader, feature_type, feature_id, push_merged, options).map(merge_features)
+}
+
+/// Builds a map of feature ID-feature vector pairs from an asynchronous
+/// GTF/GFFv2 reader.
+///
+/// This is the asynchronous variant of [`read_features`]. The input must be
+/// uncompressed. It is read asynchronously into memory in full and then
+/// parsed, i.e., it is not parsed as it streams in, so peak memory includes
+/// the raw input.
+///
+/// [`read_features`]: fn.read_features.html
+///
+/// # Example
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// use noodles_fpkm::features::{read_features_async, Feature};
+///
+/// let file = tokio::fs::File::open("test/fixtures/annotations.gtf").await?;
+/// let features = read_features_async(file, "exon", "gene_name").await?;
+///
+/// assert_eq!(features.len(), 2);
+/// assert_eq!(&features["NECAP2"], &[Feature::new(16440672, 16440853)]);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "async")]
+pub async fn read_features_async<R>(
+    mut reader: R,
+    feature_type: &str,
+    feature_id: &str,
+) -> io::Result<Features>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).await?;
+
+    let reader = gff::Reader::new(&buf[..]);
+    parse_features(
+        reader,
+        feature_type,
+        feature_id,
+        Vec::push,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+pub(crate) fn parse_features<R>(
+    reader: gff::Reader<R>,
+    feature_type: &str,
+    feature_id: &str,
+    push: fn(&mut Vec<Feature>, Feature),
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features>
+where
+    R: Read,
+{
+    parse_features_with_capacity(reader, feature_type, feature_id, push, options, 0)
+}
+
+fn parse_features_with_capacity<R>(
+    mut reader: gff::Reader<R>,
+    feature_type: &str,
+    feature_id: &str,
+    push: fn(&mut Vec<Feature>, Feature),
+    options: &ReadFeaturesOptions,
+    capacity: usize,
+) -> io::Result<Features>
+where
+    R: Read,
+{
+    let mut features = FeatureMap::with_capacity_and_hasher(capacity, Default::default());
+    let mut last_line_number = 0;
+    let mut skipped_line_count = 0;
+    let mut tally = RecordTally::default();
+    let group_by: GroupBy = feature_id.parse()?;
 
     for result in reader.records() {
         let row = result?;
-        let record = gff::Record::new(row);
+        let line_number = row.position().map(|position| position.line());
 
-        let ty = record.feature().map_err(invalid_data)?;
+        // Comment and blank lines are skipped by the reader, which leaves gaps
+        // in the line numbers of the rows.
+        if let Some(n) = line_number {
+            skipped_line_count += n.saturating_sub(last_line_number + 1);
+            last_line_number = n;
+        }
 
-        if ty != feature_type {
+        if row.len() == 1 && is_track_or_browser_line(row[0].as_bytes()) {
+            skipped_line_count += 1;
             continue;
         }
 
-        let start = record.start().map_err(invalid_data)?;
-        let end = record.end().map_err(invalid_data)?;
+        let fields = row.iter().map(str::as_bytes);
+
+        let gtf::Record { id, feature, .. } =
+            match gtf::parse_fields(fields, feature_type, &group_by) {
+                Ok(Some(record)) => record,
+                Ok(None) => continue,
+                Err(e) if is_missing_attribute(&e) => {
+                    tally.add_missing(line_number);
+                    continue;
+                }
+                Err(e) => match line_number {
+                    Some(n) => return Err(with_line_number(e, n)),
+                    None => return Err(e),
+                },
+            };
 
-        let attributes = record.attributes().map_err(invalid_data)?;
-        let id = attributes.get(feature_id).ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("missing attribute '{}'", feature_id),
-            )
-        })?;
+        tally.add();
 
-        let list = features.entry(id.to_string()).or_default();
-        let feature = Feature::new(start, end);
-        list.push(feature);
+        match features.get_mut(&*id) {
+            Some(list) => push(list, feature),
+            None => {
+                features.insert(id.as_ref().into(), vec![feature]);
+            }
+        }
     }
 
-    Ok(features)
+    tally.check(feature_type, feature_id, options)?;
+    log_skipped_lines(skipped_line_count);
+
+    Ok(features.into_iter().collect())
 }
 
-fn invalid_data(e: gff::record::Error) -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidData, e)
+// The error of a record of the feature type that lacks the feature ID
+// attribute.
+#[derive(Debug)]
+pub(crate) struct MissingAttribute(pub(crate) String);
+
+impl fmt::Display for MissingAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "missing attribute '{}'", self.0)
+    }
+}
+
+impl error::Error for MissingAttribute {}
+
+pub(crate) fn missing_attribute(feature_id: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        MissingAttribute(feature_id.into()),
+    )
+}
+
+pub(crate) fn is_missing_attribute(e: &io::Error) -> bool {
+    matches!(e.get_ref(), Some(inner) if inner.is::<MissingAttribute>())
+}
+
+// Tallies the records of the feature type and those that lack the feature ID
+// attribute.
+//
+// A mistyped `--id` is missing from every record, so rather than failing on the
+// first record, parsing continues to report how many records are affected.
+#[derive(Debug, Default)]
+pub(crate) struct RecordTally {
+    record_count: u64,
+    missing_count: u64,
+    missing_line_numbers: Vec<u64>,
+}
+
+impl RecordTally {
+    pub(crate) fn add(&mut self) {
+        self.record_count += 1;
+    }
+
+    pub(crate) fn add_missing(&mut self, line_number: Option<u64>) {
+        self.record_count += 1;
+        self.missing_count += 1;
+
+        if let Some(n) = line_number {
+            if self.missing_line_numbers.len() < MISSING_ATTRIBUTE_EXAMPLE_COUNT {
+                self.missing_line_numbers.push(n);
+            }
+        }
+    }
+
+    // Adds the tally of records that follow the records of this tally, e.g.,
+    // of the next chunk of the input.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn merge(&mut self, other: RecordTally) {
+        self.record_count += other.record_count;
+        self.missing_count += other.missing_count;
+
+        let n = MISSING_ATTRIBUTE_EXAMPLE_COUNT.saturating_sub(self.missing_line_numbers.len());
+        self.missing_line_numbers
+            .extend(other.missing_line_numbers.into_iter().take(n));
+    }
+
+    // Fails if the fraction of records that lack the feature ID attribute is
+    // greater than the maximum, or otherwise, logs the skipped records.
+    pub(crate) fn check(
+        &self,
+        feature_type: &str,
+        feature_id: &str,
+        options: &ReadFeaturesOptions,
+    ) -> io::Result<()> {
+        if self.missing_count == 0 {
+            return Ok(());
+        }
+
+        let fraction = self.missing_count as f64 / self.record_count as f64;
+
+        if fraction <= options.max_missing_id_fraction {
+            let line_numbers: Vec<_> = self
+                .missing_line_numbers
+                .iter()
+                .map(|n| n.to_string())
+                .collect();
+
+            warn!(
+                "skipped {} of {} '{}' records missing attribute '{}', e.g., line(s) {}",
+                self.missing_count,
+                self.record_count,
+                feature_type,
+                feature_id,
+                line_numbers.join(", ")
+            );
+
+            return Ok(());
+        }
+
+        let e = io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "missing attribute '{}' in {} of {} '{}' records",
+                feature_id, self.missing_count, self.record_count, feature_type
+            ),
+        );
+
+        match self.missing_line_numbers.first() {
+            Some(&n) => Err(with_line_number(e, n)),
+            None => Err(e),
+        }
+    }
+}
+
+// Adds the line number of the record that caused an error to its message.
+pub(crate) fn with_line_number(e: io::Error, line_number: u64) -> io::Error {
+    io::Error::new(e.kind(), format!("line {}: {}", line_number, e))
+}
+
+fn push_merged(intervals: &mut Vec<Feature>, feature: Feature) {
+    if let Some(last) = intervals.last_mut() {
+        if feature.start <= last.end && last.start <= feature.end {
+            last.start = last.start.min(feature.start);
+            last.end = last.end.max(feature.end);
+            return;
+        }
+    }
+
+    intervals.push(feature);
+}
+
+// Finishes merging intervals added by `push_merged`, which only merges
+// overlapping intervals that are adjacent in the input.
+fn merge_features(mut features: Features) -> Features {
+    for intervals in features.values_mut() {
+        merge_intervals_in_place(intervals);
+    }
+
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_features_with_end_before_start() {
+        let data = b"\
+chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";
+chr1\tHAVANA\texon\t12721\t12613\t.\t+\t.\tgene_id \"g1\";
+";
+
+        let reader = gff::Reader::new(&data[..]);
+        let e = parse_features(
+            reader,
+            "exon",
+            "gene_id",
+            Vec::push,
+            &ReadFeaturesOptions::default(),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            e.to_string(),
+            "line 2: invalid record: end (12613) < start (12721)"
+        );
+    }
+
+    #[test]
+    fn test_parse_features_with_missing_attribute() {
+        let data = b"\
+chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\"; gene_name \"DDX11L1\";
+chr1\tHAVANA\texon\t12613\t12721\t.\t+\t.\tgene_id \"g1\";
+chr1\tHAVANA\tgene\t11869\t14409\t.\t+\t.\tgene_id \"g1\";
+chr1\tHAVANA\texon\t13221\t14409\t.\t+\t.\tgene_id \"g1\";
+";
+
+        let reader = gff::Reader::new(&data[..]);
+        let e = parse_features(
+            reader,
+            "exon",
+            "gene_name",
+            Vec::push,
+            &ReadFeaturesOptions::default(),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            e.to_string(),
+            "line 2: missing attribute 'gene_name' in 2 of 3 'exon' records"
+        );
+
+        let options = ReadFeaturesOptions {
+            max_missing_id_fraction: 0.5,
+        };
+
+        let reader = gff::Reader::new(&data[..]);
+        assert!(parse_features(reader, "exon", "gene_name", Vec::push, &options).is_err());
+
+        let options = ReadFeaturesOptions {
+            max_missing_id_fraction: 1.0,
+        };
+
+        let reader = gff::Reader::new(&data[..]);
+        let features = parse_features(reader, "exon", "gene_name", Vec::push, &options).unwrap();
+
+        assert_eq!(features.len(), 1);
+        assert_eq!(&features["DDX11L1"], &[Feature::new(11869, 12227)]);
+    }
+
+    #[test]
+    fn test_parse_features_with_track_and_browser_lines() -> io::Result<()> {
+        let data = b"\
+track name=genes
+browser position chr1:11869-14409
+#!genome-build GRCh38.p13
+chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";
+";
+
+        let reader = gff::Reader::new(&data[..]);
+        let features = parse_features(
+            reader,
+            "exon",
+            "gene_id",
+            Vec::push,
+            &ReadFeaturesOptions::default(),
+        )?;
+        assert_eq!(features["g1"], [Feature::new(11869, 12227)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_feature_source_of() -> io::Result<()> {
+        use std::{env, io::Write};
+
+        use flate2::{write::GzEncoder, Compression};
+
+        let dir = env::temp_dir().join(format!("noodles-fpkm-features-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let src = dir.join("annotations.gtf.gz");
+        let mut writer = GzEncoder::new(fs::File::create(&src)?, Compression::default());
+        writer.write_all(
+            b"#!genome-build GRCh38.p13\r\n\r\nchr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";\r\n",
+        )?;
+        writer.finish()?;
+        let gtf = detect_feature_source_of(&src)?.map(|source| source.name());
+
+        let src = dir.join("counts.txt");
+        fs::write(&src, b"AAAS\t645\n")?;
+        let unknown = detect_feature_source_of(&src)?.map(|source| source.name());
+
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(gtf, Some("gtf"));
+        assert!(unknown.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_intervals_in_place() {
+        let mut intervals = vec![
+            Feature::new(16, 21),
+            Feature::new(2, 5),
+            Feature::new(10, 15),
+            Feature::new(5, 7),
+            Feature::new(9, 12),
+            Feature::new(3, 4),
+        ];
+
+        merge_intervals_in_place(&mut intervals);
+
+        assert_eq!(
+            intervals,
+            [
+                Feature::new(2, 7),
+                Feature::new(9, 15),
+                Feature::new(16, 21)
+            ]
+        );
+
+        let mut intervals = Vec::new();
+        merge_intervals_in_place(&mut intervals);
+        assert!(intervals.is_empty());
+    }
+
+    #[test]
+    fn test_flatten_intervals() {
+        let intervals = [
+            Feature::new(10, 20),
+            Feature::new(1, 5),
+            Feature::new(15, 25),
+            Feature::new(15, 20),
+        ];
+
+        assert_eq!(
+            flatten_intervals(&intervals),
+            [
+                Feature::new(1, 5),
+                Feature::new(10, 14),
+                Feature::new(15, 20),
+                Feature::new(21, 25)
+            ]
+        );
+
+        assert!(flatten_intervals(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_read_merged_features_from_reader() -> io::Result<()> {
+        let data = b"\
+chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";
+chr1\tHAVANA\texon\t12010\t12057\t.\t+\t.\tgene_id \"g1\";
+chr1\tHAVANA\texon\t12613\t12721\t.\t+\t.\tgene_id \"g1\";
+";
+
+        let features = read_merged_features_from_reader(&data[..], "exon", "gene_id")?;
+        assert_eq!(
+            features["g1"],
+            [Feature::new(11869, 12227), Feature::new(12613, 12721)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_merged_features_from_reader_with_unsorted_input() -> io::Result<()> {
+        let data = b"\
+chr1\tHAVANA\texon\t12613\t12721\t.\t+\t.\tgene_id \"g1\";
+chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";
+chr1\tHAVANA\texon\t12200\t12650\t.\t+\t.\tgene_id \"g1\";
+";
+
+        let features = read_merged_features_from_reader(&data[..], "exon", "gene_id")?;
+        assert_eq!(features["g1"], [Feature::new(11869, 12721)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_merged() {
+        let mut intervals = Vec::new();
+
+        push_merged(&mut intervals, Feature::new(2, 5));
+        push_merged(&mut intervals, Feature::new(3, 4));
+        push_merged(&mut intervals, Feature::new(5, 7));
+        assert_eq!(intervals, [Feature::new(2, 7)]);
+
+        push_merged(&mut intervals, Feature::new(8, 9));
+        assert_eq!(intervals, [Feature::new(2, 7), Feature::new(8, 9)]);
+
+        push_merged(&mut intervals, Feature::new(1, 8));
+        assert_eq!(intervals, [Feature::new(2, 7), Feature::new(1, 9)]);
+    }
 }
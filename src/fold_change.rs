@@ -0,0 +1,73 @@
+use crate::matrix::ExpressionMatrix;
+
+/// The value added to expression values before taking the ratio, so features
+/// not expressed in the control do not have infinite fold changes.
+pub const PSEUDOCOUNT: f64 = 1.0;
+
+/// Calculates the log2 fold change of each value versus the control.
+///
+/// The control value of a feature is the mean of its values in the control
+/// columns, and each value x becomes `log2((x + 1) / (control + 1))`. These are
+/// descriptive ratios, not tests of differential expression.
+///
+/// # Panics
+///
+/// Panics when there are no control columns or a control column index is out
+/// of bounds.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{fold_change::log2_fold_changes, matrix::ExpressionMatrix};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+///     vec![String::from("sample1"), String::from("sample2"), String::from("sample3")],
+///     arr2(&[[3.0, 5.0, 19.0], [1.0, 1.0, 0.0]]),
+/// );
+///
+/// let fold_changes = log2_fold_changes(&matrix, &[0, 1]);
+///
+/// assert_eq!(fold_changes.get("AAAS", "sample1"), Some(0.8f64.log2()));
+/// assert_eq!(fold_changes.get("AAAS", "sample3"), Some(2.0));
+/// assert_eq!(fold_changes.get("RPL37AP1", "sample3"), Some(-1.0));
+/// ```
+pub fn log2_fold_changes(matrix: &ExpressionMatrix, control_columns: &[usize]) -> ExpressionMatrix {
+    assert!(!control_columns.is_empty(), "missing control columns");
+
+    let mut fold_changes = matrix.clone();
+
+    for mut row in fold_changes.data_mut().genrows_mut() {
+        let control =
+            control_columns.iter().map(|&j| row[j]).sum::<f64>() / control_columns.len() as f64;
+
+        for value in row.iter_mut() {
+            *value = ((*value + PSEUDOCOUNT) / (control + PSEUDOCOUNT)).log2();
+        }
+    }
+
+    fold_changes
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_log2_fold_changes_of_controls() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("a"), String::from("b")],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[0.0, 7.0], [2.0, 2.0]]),
+        );
+
+        let fold_changes = log2_fold_changes(&matrix, &[0]);
+
+        assert_eq!(fold_changes.get("a", "sample1"), Some(0.0));
+        assert_eq!(fold_changes.get("a", "sample2"), Some(3.0));
+        assert_eq!(fold_changes.get("b", "sample2"), Some(0.0));
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    convert::TryFrom,
+    io::{self, Read},
+};
+
+use csv::StringRecord;
+
+use crate::lengths::Lengths;
+
+const COMMENT_PREFIX: u8 = b'#';
+
+// The number of standard deviations past the mean that a normal distribution
+// of fragment lengths is discretized to.
+const NORMAL_SD_COUNT: f64 = 10.0;
+
+/// A distribution of fragment lengths.
+///
+/// This is used to calculate the effective length of a feature, i.e., the
+/// expected number of positions a fragment can start at, as salmon and RSEM
+/// do.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FragmentLengthDistribution {
+    // The cumulative probabilities and cumulative probability-weighted lengths
+    // of fragments up to each length, starting at 0.
+    cumulative_probabilities: Vec<f64>,
+    cumulative_lengths: Vec<f64>,
+}
+
+impl FragmentLengthDistribution {
+    /// Creates a distribution from the weights of fragment lengths, indexed by
+    /// length.
+    ///
+    /// The weights, e.g., counts, are normalized to probabilities.
+    ///
+    /// # Panics
+    ///
+    /// Panics when a weight is negative or not finite or all weights are 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::fragment_lengths::FragmentLengthDistribution;
+    ///
+    /// let distribution = FragmentLengthDistribution::new(&[0.0, 0.0, 1.0, 3.0]);
+    /// assert_eq!(distribution.effective_length(10), 8.25);
+    /// ```
+    pub fn new(weights: &[f64]) -> FragmentLengthDistribution {
+        assert!(
+            weights.iter().all(|w| w.is_finite() && *w >= 0.0),
+            "invalid fragment length weight"
+        );
+
+        let total: f64 = weights.iter().sum();
+        assert!(total > 0.0, "missing fragment length weights");
+
+        let mut cumulative_probabilities = Vec::with_capacity(weights.len());
+        let mut cumulative_lengths = Vec::with_capacity(weights.len());
+        let mut p = 0.0;
+        let mut m = 0.0;
+
+        for (len, w) in weights.iter().enumerate() {
+            p += w / total;
+            m += len as f64 * w / total;
+            cumulative_probabilities.push(p);
+            cumulative_lengths.push(m);
+        }
+
+        FragmentLengthDistribution {
+            cumulative_probabilities,
+            cumulative_lengths,
+        }
+    }
+
+    /// Creates a distribution from a normal distribution of fragment lengths.
+    ///
+    /// The normal distribution is discretized to lengths from 1 to 10
+    /// standard deviations past the mean. A standard deviation of 0 is a
+    /// fixed fragment length, i.e., the mean rounded to the nearest integer,
+    /// which gives the classic mean-based effective length.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the mean is not positive or the standard deviation is
+    /// negative.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::fragment_lengths::FragmentLengthDistribution;
+    ///
+    /// let distribution = FragmentLengthDistribution::from_normal(250.0, 0.0);
+    /// assert_eq!(distribution.effective_length(1000), 751.0);
+    /// ```
+    pub fn from_normal(mean: f64, sd: f64) -> FragmentLengthDistribution {
+        assert!(mean > 0.0, "invalid fragment length mean");
+        assert!(sd >= 0.0, "invalid fragment length standard deviation");
+
+        if sd == 0.0 {
+            let len = (mean.round() as usize).max(1);
+            let mut weights = vec![0.0; len + 1];
+            weights[len] = 1.0;
+            return FragmentLengthDistribution::new(&weights);
+        }
+
+        let max_len = (mean + NORMAL_SD_COUNT * sd).ceil() as usize;
+
+        let weights: Vec<f64> = (0..=max_len)
+            .map(|len| match len {
+                0 => 0.0,
+                _ => {
+                    let z = (len as f64 - mean) / sd;
+                    (-0.5 * z * z).exp()
+                }
+            })
+            .collect();
+
+        FragmentLengthDistribution::new(&weights)
+    }
+
+    /// Returns the effective length of a feature of the given length.
+    ///
+    /// The effective length is `length - mean + 1`, where `mean` is the mean
+    /// length of fragments no longer than the feature. As in salmon, a feature
+    /// shorter than every fragment keeps its length.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::fragment_lengths::FragmentLengthDistribution;
+    ///
+    /// let distribution = FragmentLengthDistribution::new(&[0.0, 0.0, 1.0, 1.0]);
+    ///
+    /// assert_eq!(distribution.effective_length(10), 8.5);
+    /// assert_eq!(distribution.effective_length(2), 1.0);
+    /// assert_eq!(distribution.effective_length(1), 1.0);
+    /// ```
+    pub fn effective_length(&self, length: u64) -> f64 {
+        let last_index = self.cumulative_probabilities.len() - 1;
+        let i = usize::try_from(length).map_or(last_index, |i| i.min(last_index));
+
+        let p = self.cumulative_probabilities[i];
+
+        if p <= 0.0 {
+            return length as f64;
+        }
+
+        let mean = self.cumulative_lengths[i] / p;
+
+        length as f64 - mean + 1.0
+    }
+}
+
+/// Reads a TSV-formatted fragment length distribution.
+///
+/// The input has two columns: a fragment length (integer) and its weight, e.g.,
+/// a count from an insert size histogram or a probability. Lines starting with
+/// `#` are skipped.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::fragment_lengths::read_fragment_length_distribution;
+///
+/// let data = "\
+/// #insert_size\tcount
+/// 2\t1
+/// 3\t3
+/// ";
+///
+/// let distribution = read_fragment_length_distribution(data.as_bytes())?;
+/// assert_eq!(distribution.effective_length(10), 8.25);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_fragment_length_distribution<R>(reader: R) -> io::Result<FragmentLengthDistribution>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .comment(Some(COMMENT_PREFIX))
+        .from_reader(reader);
+
+    let mut weights = Vec::new();
+    let mut record = StringRecord::new();
+
+    while rdr.read_record(&mut record)? {
+        let (len, weight) = parse_record(&record)?;
+
+        if len >= weights.len() {
+            weights.resize(len + 1, 0.0);
+        }
+
+        weights[len] += weight;
+    }
+
+    if !weights.iter().any(|&w| w > 0.0) {
+        return Err(invalid_data(String::from(
+            "missing fragment length weights",
+        )));
+    }
+
+    Ok(FragmentLengthDistribution::new(&weights))
+}
+
+/// Replaces each length with its effective length, rounded to the nearest
+/// integer.
+///
+/// Effective lengths are at least 1. See
+/// [`FragmentLengthDistribution::effective_length`].
+///
+/// [`FragmentLengthDistribution::effective_length`]: struct.FragmentLengthDistribution.html#method.effective_length
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     feature_id::FeatureId,
+///     fragment_lengths::{calculate_effective_lengths, FragmentLengthDistribution},
+///     lengths::Lengths,
+/// };
+///
+/// let lengths: Lengths = [
+///     (FeatureId::from("AAAS"), 1000),
+///     (FeatureId::from("RPL37AP1"), 54),
+/// ].iter().cloned().collect();
+///
+/// let distribution = FragmentLengthDistribution::from_normal(250.0, 25.0);
+/// let effective_lengths = calculate_effective_lengths(&lengths, &distribution);
+///
+/// assert_eq!(effective_lengths["AAAS"], 751);
+/// assert_eq!(effective_lengths["RPL37AP1"], 4);
+/// ```
+pub fn calculate_effective_lengths(
+    lengths: &Lengths,
+    distribution: &FragmentLengthDistribution,
+) -> Lengths {
+    lengths
+        .iter()
+        .map(|(name, &len)| {
+            let effective_length = distribution.effective_length(len).round() as u64;
+            (name.clone(), effective_length.max(1))
+        })
+        .collect()
+}
+
+fn parse_record(record: &StringRecord) -> io::Result<(usize, f64)> {
+    let len = record
+        .get(0)
+        .and_then(|s| s.trim().parse().ok())
+        .ok_or_else(|| invalid_data(format!("invalid fragment length: {:?}", record)))?;
+
+    let weight = record
+        .get(1)
+        .and_then(|s| s.trim().parse::<f64>().ok())
+        .filter(|w| w.is_finite() && *w >= 0.0)
+        .ok_or_else(|| invalid_data(format!("invalid fragment length weight: {:?}", record)))?;
+
+    Ok((len, weight))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_length_with_short_features() {
+        let distribution = FragmentLengthDistribution::from_normal(200.0, 20.0);
+
+        // Only the shortest fragments fit, so the effective length is much
+        // less than the mean-based length would be, but still positive.
+        let effective_length = distribution.effective_length(150);
+        assert!(effective_length > 1.0 && effective_length < 20.0);
+
+        assert_eq!(distribution.effective_length(0), 0.0);
+    }
+
+    #[test]
+    fn test_read_fragment_length_distribution_with_invalid_input() {
+        assert!(read_fragment_length_distribution(&b"2\t0\n"[..]).is_err());
+        assert!(read_fragment_length_distribution(&b"2\t-1\n"[..]).is_err());
+        assert!(read_fragment_length_distribution(&b"x\t1\n"[..]).is_err());
+        assert!(read_fragment_length_distribution(&b""[..]).is_err());
+    }
+}
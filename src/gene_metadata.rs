@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+};
+
+use csv::StringRecord;
+
+use crate::{features::with_line_number, HashBuilder};
+
+/// A policy for features that are not in a metadata table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MissingMetadata {
+    /// The metadata of the feature is written as `NA`.
+    Na,
+    /// The feature is dropped from the output.
+    Drop,
+    /// The feature is an error.
+    Error,
+}
+
+/// A table of metadata per feature, e.g., gene symbols and biotypes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GeneMetadata {
+    column_names: Vec<String>,
+    rows: HashMap<String, Vec<String>, HashBuilder>,
+}
+
+impl GeneMetadata {
+    /// Returns the names of the metadata columns.
+    ///
+    /// This excludes the feature ID column.
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    /// Returns the metadata of a feature.
+    pub fn get(&self, id: &str) -> Option<&[String]> {
+        self.rows.get(id).map(|values| values.as_slice())
+    }
+
+    /// Returns the number of features with metadata.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns whether there are no features with metadata.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+/// Reads a TSV-formatted table of metadata keyed by feature ID.
+///
+/// The first row is a header. The first column is the feature ID, and the
+/// remaining columns are metadata. Every row must have the same number of
+/// fields as the header, and feature IDs must be unique.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::gene_metadata::read_gene_metadata;
+///
+/// let data = "\
+/// gene_id\tsymbol\tbiotype
+/// ENSG00000094914\tAAAS\tprotein_coding
+/// ";
+///
+/// let metadata = read_gene_metadata(data.as_bytes())?;
+///
+/// assert_eq!(metadata.column_names(), ["symbol", "biotype"]);
+/// assert_eq!(
+///     metadata.get("ENSG00000094914"),
+///     Some(&[String::from("AAAS"), String::from("protein_coding")][..])
+/// );
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_gene_metadata<R>(reader: R) -> io::Result<GeneMetadata>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_reader(reader);
+
+    let mut record = StringRecord::new();
+
+    if !rdr.read_record(&mut record)? {
+        return Err(invalid_data(String::from("missing header")));
+    }
+
+    let column_names: Vec<String> = record.iter().skip(1).map(String::from).collect();
+    let mut rows = HashMap::default();
+
+    while rdr.read_record(&mut record)? {
+        let line_number = record.position().map(|p| p.line()).unwrap_or_default();
+
+        if record.len() != column_names.len() + 1 {
+            return Err(with_line_number(
+                invalid_data(format!(
+                    "invalid number of fields: expected {}, got {}",
+                    column_names.len() + 1,
+                    record.len()
+                )),
+                line_number,
+            ));
+        }
+
+        let id = record[0].to_string();
+        let values = record.iter().skip(1).map(String::from).collect();
+
+        if rows.contains_key(&id) {
+            let e = invalid_data(format!("duplicate feature ID: {}", id));
+            return Err(with_line_number(e, line_number));
+        }
+
+        rows.insert(id, values);
+    }
+
+    Ok(GeneMetadata { column_names, rows })
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_gene_metadata_with_invalid_input() {
+        assert!(read_gene_metadata(&b""[..]).is_err());
+
+        let data = b"gene_id\tsymbol\nAAAS\tAAAS\tx\n";
+        let e = read_gene_metadata(&data[..]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "line 2: invalid number of fields: expected 2, got 3"
+        );
+
+        let data = b"gene_id\tsymbol\nAAAS\tAAAS\nAAAS\tAAAS\n";
+        let e = read_gene_metadata(&data[..]).unwrap_err();
+        assert_eq!(e.to_string(), "line 3: duplicate feature ID: AAAS");
+    }
+}
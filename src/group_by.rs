@@ -0,0 +1,228 @@
+use std::{io, str};
+
+use crate::features::missing_attribute;
+
+const CONCATENATION_OPERATOR: char = '+';
+const QUOTE: char = '"';
+
+/// A column of a GTF record that can be used in a [`GroupBy`] expression.
+///
+/// [`GroupBy`]: struct.GroupBy.html
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Column {
+    /// The reference sequence name (column 1), e.g., `chr1`.
+    Seqname,
+    /// The source (column 2), e.g., `HAVANA`.
+    Source,
+    /// The strand (column 7), e.g., `+`.
+    Strand,
+}
+
+impl Column {
+    fn from_name(s: &str) -> Option<Self> {
+        match s {
+            "seqname" => Some(Column::Seqname),
+            "source" => Some(Column::Source),
+            "strand" => Some(Column::Strand),
+            _ => None,
+        }
+    }
+}
+
+/// A term of a [`GroupBy`] expression.
+///
+/// [`GroupBy`]: struct.GroupBy.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Term {
+    /// The value of a record column.
+    Column(Column),
+    /// The value of a record attribute, e.g., `gene_id`.
+    Attribute(String),
+    /// A string literal, e.g., `":"`.
+    Literal(String),
+}
+
+/// An expression of the key that records are grouped by into features.
+///
+/// An expression is one or more terms joined by `+`, whose values are
+/// concatenated. A term is a quoted string literal, a column name (`seqname`,
+/// `source`, or `strand`), or otherwise, the name of an attribute. The plain
+/// attribute name used by `--id`, e.g., `gene_id`, is an expression of a
+/// single term.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::group_by::{Column, GroupBy, Term};
+///
+/// let group_by: GroupBy = r#"gene_id + ":" + seqname"#.parse()?;
+///
+/// assert_eq!(
+///     group_by.terms(),
+///     [
+///         Term::Attribute(String::from("gene_id")),
+///         Term::Literal(String::from(":")),
+///         Term::Column(Column::Seqname),
+///     ]
+/// );
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GroupBy {
+    terms: Vec<Term>,
+}
+
+impl GroupBy {
+    /// Returns the terms of the expression.
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    /// Returns the attribute name if the expression is a single attribute.
+    ///
+    /// Records are then grouped by the value of the attribute as is, without
+    /// building a new key.
+    pub fn as_attribute(&self) -> Option<&str> {
+        match self.terms.as_slice() {
+            [Term::Attribute(key)] => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Evaluates the expression for a record, given the values of its columns
+    /// and attributes.
+    ///
+    /// This fails if the record lacks an attribute of the expression, or a
+    /// value is not valid UTF-8.
+    pub(crate) fn evaluate<'a, C, A>(&self, column: C, attribute: A) -> io::Result<String>
+    where
+        C: Fn(Column) -> &'a [u8],
+        A: Fn(&str) -> Option<&'a [u8]>,
+    {
+        let mut key = String::new();
+
+        for term in &self.terms {
+            let value = match term {
+                Term::Column(c) => column(*c),
+                Term::Attribute(name) => attribute(name).ok_or_else(|| missing_attribute(name))?,
+                Term::Literal(s) => {
+                    key.push_str(s);
+                    continue;
+                }
+            };
+
+            let value =
+                str::from_utf8(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            key.push_str(value);
+        }
+
+        Ok(key)
+    }
+}
+
+impl str::FromStr for GroupBy {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut terms = Vec::new();
+        let mut rest = s.trim_start();
+
+        loop {
+            let (term, tail) = parse_term(rest).ok_or_else(|| invalid_expression(s))?;
+            terms.push(term);
+
+            rest = tail.trim_start();
+
+            if rest.is_empty() {
+                break;
+            }
+
+            rest = rest
+                .strip_prefix(CONCATENATION_OPERATOR)
+                .ok_or_else(|| invalid_expression(s))?
+                .trim_start();
+        }
+
+        Ok(GroupBy { terms })
+    }
+}
+
+// Parses a term at the start of the input and returns it with the rest of the
+// input.
+fn parse_term(s: &str) -> Option<(Term, &str)> {
+    if let Some(rest) = s.strip_prefix(QUOTE) {
+        let end = rest.find(QUOTE)?;
+        let literal = Term::Literal(rest[..end].to_string());
+        return Some((literal, &rest[end + 1..]));
+    }
+
+    let end = s
+        .find(|c: char| c.is_whitespace() || c == CONCATENATION_OPERATOR || c == QUOTE)
+        .unwrap_or(s.len());
+
+    let name = &s[..end];
+
+    if name.is_empty() {
+        return None;
+    }
+
+    let term = match Column::from_name(name) {
+        Some(column) => Term::Column(column),
+        None => Term::Attribute(name.to_string()),
+    };
+
+    Some((term, &s[end..]))
+}
+
+fn invalid_expression(s: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid group-by expression: {}", s),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        let group_by: GroupBy = "gene_id".parse().unwrap();
+        assert_eq!(group_by.as_attribute(), Some("gene_id"));
+
+        let group_by: GroupBy = r#"gene_name+"|"+strand"#.parse().unwrap();
+        assert_eq!(group_by.terms().len(), 3);
+        assert!(group_by.as_attribute().is_none());
+
+        assert!("".parse::<GroupBy>().is_err());
+        assert!("gene_id +".parse::<GroupBy>().is_err());
+        assert!("gene_id seqname".parse::<GroupBy>().is_err());
+        assert!(r#"gene_id + ":"#.parse::<GroupBy>().is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let group_by: GroupBy = r#"gene_id + ":" + seqname"#.parse().unwrap();
+
+        let column = |c| match c {
+            Column::Seqname => &b"chr1"[..],
+            _ => &b""[..],
+        };
+
+        let key = group_by
+            .evaluate(column, |name| {
+                if name == "gene_id" {
+                    Some(&b"AAAS"[..])
+                } else {
+                    None
+                }
+            })
+            .unwrap();
+
+        assert_eq!(key, "AAAS:chr1");
+
+        let e = group_by.evaluate(column, |_| None).unwrap_err();
+        assert_eq!(e.to_string(), "missing attribute 'gene_id'");
+    }
+}
@@ -0,0 +1,252 @@
+use std::cmp::Ordering;
+
+use ndarray::Array2;
+
+use crate::matrix::ExpressionMatrix;
+
+/// Calculates the mean value of each feature in each group of columns.
+///
+/// Groups are named lists of column indices, e.g., as returned by
+/// [`SampleSheet::groups`]. The result has one column per group, named by the
+/// group.
+///
+/// [`SampleSheet::groups`]: ../sample_sheet/struct.SampleSheet.html#method.groups
+///
+/// # Panics
+///
+/// Panics when a group has no columns, a column index is out of bounds, or
+/// group names are not unique.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{groups::group_means, matrix::ExpressionMatrix};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS")],
+///     vec![String::from("sample1"), String::from("sample2"), String::from("sample3")],
+///     arr2(&[[3.0, 5.0, 15.0]]),
+/// );
+///
+/// let means = group_means(&matrix, &[("control", vec![0, 1]), ("treated", vec![2])]);
+///
+/// assert_eq!(means.column_names(), ["control", "treated"]);
+/// assert_eq!(means.get("AAAS", "control"), Some(4.0));
+/// assert_eq!(means.get("AAAS", "treated"), Some(15.0));
+/// ```
+pub fn group_means(matrix: &ExpressionMatrix, groups: &[(&str, Vec<usize>)]) -> ExpressionMatrix {
+    summarize_groups(matrix, groups, |values| {
+        values.iter().sum::<f64>() / values.len() as f64
+    })
+}
+
+/// Calculates the median value of each feature in each group of columns.
+///
+/// This is the same as [`group_means`] but with the median, which is less
+/// sensitive to an outlying replicate.
+///
+/// [`group_means`]: fn.group_means.html
+///
+/// # Panics
+///
+/// Panics when a group has no columns, a column index is out of bounds, or
+/// group names are not unique.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{groups::group_medians, matrix::ExpressionMatrix};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS")],
+///     vec![String::from("sample1"), String::from("sample2"), String::from("sample3")],
+///     arr2(&[[3.0, 40.0, 5.0]]),
+/// );
+///
+/// let medians = group_medians(&matrix, &[("control", vec![0, 1, 2])]);
+///
+/// assert_eq!(medians.get("AAAS", "control"), Some(5.0));
+/// ```
+pub fn group_medians(matrix: &ExpressionMatrix, groups: &[(&str, Vec<usize>)]) -> ExpressionMatrix {
+    summarize_groups(matrix, groups, |values| {
+        values.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        let mid = values.len() / 2;
+
+        match values.len() % 2 {
+            0 => (values[mid - 1] + values[mid]) / 2.0,
+            _ => values[mid],
+        }
+    })
+}
+
+/// Splits each group of columns by strata, e.g., conditions by tissue.
+///
+/// Groups and strata are named lists of column indices, e.g., as returned by
+/// [`SampleSheet::groups`] and [`SampleSheet::strata`]. The result has a group
+/// for each pair of stratum and group that share columns, named
+/// `<stratum>:<group>`, in the order of the strata and then of the groups.
+///
+/// [`SampleSheet::groups`]: ../sample_sheet/struct.SampleSheet.html#method.groups
+/// [`SampleSheet::strata`]: ../sample_sheet/struct.SampleSheet.html#method.strata
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::groups::stratify_groups;
+///
+/// let groups = [("control", vec![0, 1]), ("treated", vec![2, 3])];
+/// let strata = [("liver", vec![0, 2, 3]), ("lung", vec![1])];
+///
+/// assert_eq!(
+///     stratify_groups(&groups, &strata),
+///     [
+///         (String::from("liver:control"), vec![0]),
+///         (String::from("liver:treated"), vec![2, 3]),
+///         (String::from("lung:control"), vec![1]),
+///     ]
+/// );
+/// ```
+pub fn stratify_groups(
+    groups: &[(&str, Vec<usize>)],
+    strata: &[(&str, Vec<usize>)],
+) -> Vec<(String, Vec<usize>)> {
+    let mut stratified_groups = Vec::new();
+
+    for (stratum, stratum_columns) in strata {
+        for (group, group_columns) in groups {
+            let columns: Vec<usize> = group_columns
+                .iter()
+                .copied()
+                .filter(|j| stratum_columns.contains(j))
+                .collect();
+
+            if !columns.is_empty() {
+                stratified_groups.push((format!("{}:{}", stratum, group), columns));
+            }
+        }
+    }
+
+    stratified_groups
+}
+
+/// Centers the mean `log2(x + 1)` expression of each feature in each batch on
+/// its mean across all samples.
+///
+/// This is a lightweight batch adjustment: the mean of each feature's log
+/// expression in each batch is shifted to its overall mean, removing additive
+/// batch effects. Unlike, e.g., ComBat, it does not shrink batch estimates or
+/// protect conditions that are confounded with batches. Values are transformed
+/// back to the original scale, and negative values are clamped to 0.
+///
+/// Columns not in any batch are left unchanged.
+///
+/// # Panics
+///
+/// Panics when a batch has no columns or a column index is out of bounds.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{groups::center_batches, matrix::ExpressionMatrix};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS")],
+///     vec![String::from("sample1"), String::from("sample2")],
+///     arr2(&[[1.0, 7.0]]),
+/// );
+///
+/// let centered = center_batches(&matrix, &[("b1", vec![0]), ("b2", vec![1])]);
+///
+/// assert_eq!(centered.get("AAAS", "sample1"), Some(3.0));
+/// assert_eq!(centered.get("AAAS", "sample2"), Some(3.0));
+/// ```
+pub fn center_batches(
+    matrix: &ExpressionMatrix,
+    batches: &[(&str, Vec<usize>)],
+) -> ExpressionMatrix {
+    let columns: Vec<usize> = batches
+        .iter()
+        .flat_map(|(_, columns)| columns.iter().copied())
+        .collect();
+
+    let mut centered = matrix.clone();
+
+    if columns.is_empty() {
+        return centered;
+    }
+
+    for mut row in centered.data_mut().genrows_mut() {
+        let log_values: Vec<f64> = row.iter().map(|x| (x + 1.0).log2()).collect();
+        let mean = columns.iter().map(|&j| log_values[j]).sum::<f64>() / columns.len() as f64;
+
+        for (_, batch_columns) in batches {
+            assert!(!batch_columns.is_empty(), "missing batch columns");
+
+            let batch_mean = batch_columns.iter().map(|&j| log_values[j]).sum::<f64>()
+                / batch_columns.len() as f64;
+
+            for &j in batch_columns {
+                let y = log_values[j] - batch_mean + mean;
+                row[j] = (y.exp2() - 1.0).max(0.0);
+            }
+        }
+    }
+
+    centered
+}
+
+fn summarize_groups<F>(
+    matrix: &ExpressionMatrix,
+    groups: &[(&str, Vec<usize>)],
+    f: F,
+) -> ExpressionMatrix
+where
+    F: Fn(&mut [f64]) -> f64,
+{
+    let data = matrix.data();
+    let mut summaries = Array2::zeros((data.rows(), groups.len()));
+    let mut values = Vec::new();
+
+    for (k, (_, columns)) in groups.iter().enumerate() {
+        assert!(!columns.is_empty(), "missing group columns");
+
+        for i in 0..data.rows() {
+            values.clear();
+            values.extend(columns.iter().map(|&j| data[[i, j]]));
+            summaries[[i, k]] = f(&mut values);
+        }
+    }
+
+    let column_names = groups.iter().map(|(name, _)| name.to_string()).collect();
+
+    ExpressionMatrix::new(matrix.row_names().to_vec(), column_names, summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_group_medians_with_even_group() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("a")],
+            vec![
+                String::from("sample1"),
+                String::from("sample2"),
+                String::from("sample3"),
+            ],
+            arr2(&[[8.0, 2.0, 1.0]]),
+        );
+
+        let medians = group_medians(&matrix, &[("treated", vec![0, 1]), ("control", vec![2])]);
+
+        assert_eq!(medians.get("a", "treated"), Some(5.0));
+        assert_eq!(medians.get("a", "control"), Some(1.0));
+    }
+}
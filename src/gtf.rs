@@ -0,0 +1,1327 @@
+#[cfg(feature = "mmap")]
+use std::fs::File;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::{self, Read},
+    iter, mem,
+    path::Path,
+    slice, str,
+};
+
+use log::info;
+
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::{
+    compression,
+    counts::parse_u64,
+    feature_id::FeatureId,
+    feature_index::Strand,
+    features::{
+        self, estimated_feature_count, is_missing_attribute, missing_attribute, with_line_number,
+        Feature, FeatureMap, FeatureSource, Features, ReadFeaturesOptions, RecordTally,
+    },
+    group_by::{Column, GroupBy},
+    HashBuilder,
+};
+
+pub type ReferenceSequenceNames = HashMap<FeatureId, String>;
+pub type Biotypes = HashMap<FeatureId, String>;
+pub type Aliases = HashMap<FeatureId, String>;
+pub type Strands = HashMap<FeatureId, Strand>;
+
+const REFERENCE_SEQUENCE_NAME_INDEX: usize = 0;
+const SOURCE_INDEX: usize = 1;
+const FEATURE_INDEX: usize = 2;
+const START_INDEX: usize = 3;
+const END_INDEX: usize = 4;
+const STRAND_INDEX: usize = 6;
+const ATTRIBUTES_INDEX: usize = 8;
+const FIELD_COUNT: usize = 9;
+
+const COMMENT_PREFIX: u8 = b'#';
+const TRACK_PREFIX: &[u8] = b"track";
+const BROWSER_PREFIX: &[u8] = b"browser";
+
+const EXON_TYPE: &str = "exon";
+const TRANSCRIPT_ID_KEY: &[u8] = b"transcript_id";
+const REVERSE_STRAND: &[u8] = b"-";
+
+// Ensembl uses `gene_biotype`, and GENCODE uses `gene_type`.
+const BIOTYPE_KEYS: [&[u8]; 2] = [b"gene_biotype", b"gene_type"];
+
+/// The feature type of 3' UTRs in Ensembl GTFs.
+pub const THREE_PRIME_UTR_TYPE: &str = "three_prime_utr";
+
+// The number of chunks per thread. More chunks than threads helps balance
+// work when some chunks are denser in matching records than others.
+#[cfg(feature = "parallel")]
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// The GTF/GFFv2 format. See [`parse_features`].
+///
+/// Data is detected as GTF/GFFv2 when its first record has 9 fields and its
+/// first attribute is a space-separated key-value pair, e.g., `gene_id
+/// "ENSG00000223972.5"`, rather than a GFF3 `key=value` pair.
+///
+/// [`parse_features`]: fn.parse_features.html
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Gtf;
+
+impl FeatureSource for Gtf {
+    fn name(&self) -> &'static str {
+        "gtf"
+    }
+
+    fn detect(&self, buf: &[u8]) -> bool {
+        lines(buf)
+            .next()
+            .map(|(_, line)| is_gtf_record(line))
+            .unwrap_or(false)
+    }
+
+    fn read_features(
+        &self,
+        buf: &[u8],
+        feature_type: &str,
+        feature_id: &str,
+        options: &ReadFeaturesOptions,
+    ) -> io::Result<Features> {
+        parse_features_with_options(buf, feature_type, feature_id, options)
+    }
+
+    fn read_features_from_path(
+        &self,
+        src: &Path,
+        feature_type: &str,
+        feature_id: &str,
+        options: &ReadFeaturesOptions,
+    ) -> io::Result<Features> {
+        features::read_features_with_options(src, feature_type, feature_id, options)
+    }
+}
+
+fn is_gtf_record(line: &[u8]) -> bool {
+    let fields: Vec<_> = line.split(|&b| b == b'\t').collect();
+
+    if fields.len() != FIELD_COUNT {
+        return false;
+    }
+
+    let attributes = fields[ATTRIBUTES_INDEX];
+
+    match attributes.iter().position(|&b| b == b' ' || b == b'=') {
+        Some(i) => attributes[i] == b' ',
+        None => false,
+    }
+}
+
+/// Builds a map of feature ID-feature vector pairs from a memory-mapped
+/// GTF/GFFv2.
+///
+/// This is the same as [`read_features`], but the file is memory-mapped and
+/// parsed with [`parse_features`]. The file must be uncompressed.
+///
+/// [`read_features`]: ../features/fn.read_features.html
+/// [`parse_features`]: fn.parse_features.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{features::Feature, gtf::read_features_mmap};
+///
+/// let features = read_features_mmap(
+///     "test/fixtures/annotations.gtf",
+///     "exon",
+///     "gene_name",
+/// ).unwrap();
+///
+/// assert_eq!(features.len(), 2);
+/// assert_eq!(&features["NECAP2"], &[Feature::new(16440672, 16440853)]);
+/// ```
+#[cfg(feature = "mmap")]
+pub fn read_features_mmap<P>(src: P, feature_type: &str, feature_id: &str) -> io::Result<Features>
+where
+    P: AsRef<Path>,
+{
+    read_features_mmap_with_options(
+        src,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+/// Builds a map of feature ID-feature vector pairs from a memory-mapped
+/// GTF/GFFv2 with options.
+///
+/// This is the same as [`read_features_mmap`], but records that lack the
+/// feature ID attribute can be skipped. See [`ReadFeaturesOptions`].
+///
+/// [`read_features_mmap`]: fn.read_features_mmap.html
+/// [`ReadFeaturesOptions`]: ../features/struct.ReadFeaturesOptions.html
+#[cfg(feature = "mmap")]
+pub fn read_features_mmap_with_options<P>(
+    src: P,
+    feature_type: &str,
+    feature_id: &str,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features>
+where
+    P: AsRef<Path>,
+{
+    let file = File::open(src)?;
+
+    // SAFETY: The map is read-only and dropped before returning. Concurrent
+    // modification of the file while it is being read is undefined behavior,
+    // as it would be for any other memory-mapped reader.
+    let mmap = unsafe { Mmap::map(&file)? };
+
+    parse_features_with_options(&mmap, feature_type, feature_id, options)
+}
+
+/// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2 using
+/// multiple threads.
+///
+/// The file is read fully into memory (and decompressed if its extension is
+/// `.gz`) before being parsed by [`parse_features_parallel`].
+///
+/// [`parse_features_parallel`]: fn.parse_features_parallel.html
+#[cfg(feature = "parallel")]
+pub fn read_features_parallel<P>(
+    src: P,
+    feature_type: &str,
+    feature_id: &str,
+) -> io::Result<Features>
+where
+    P: AsRef<Path>,
+{
+    read_features_parallel_with_options(
+        src,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+/// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2 using
+/// multiple threads with options.
+///
+/// This is the same as [`read_features_parallel`], but records that lack the
+/// feature ID attribute can be skipped. See [`ReadFeaturesOptions`].
+///
+/// [`read_features_parallel`]: fn.read_features_parallel.html
+/// [`ReadFeaturesOptions`]: ../features/struct.ReadFeaturesOptions.html
+#[cfg(feature = "parallel")]
+pub fn read_features_parallel_with_options<P>(
+    src: P,
+    feature_type: &str,
+    feature_id: &str,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features>
+where
+    P: AsRef<Path>,
+{
+    let buf = read_to_end(src)?;
+    parse_features_parallel_with_options(&buf, feature_type, feature_id, options)
+}
+
+/// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2 buffer
+/// using multiple threads.
+///
+/// The buffer is split into line-aligned chunks that are parsed in parallel
+/// on the current rayon thread pool. The per-chunk maps are then combined in
+/// input order, so the result, including the order of intervals in each
+/// list, is the same as [`parse_features`]. If multiple chunks fail, the
+/// error from the earliest chunk is returned.
+///
+/// [`parse_features`]: fn.parse_features.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::gtf::{parse_features, parse_features_parallel};
+///
+/// let data = std::fs::read("test/fixtures/annotations.gtf")?;
+///
+/// assert_eq!(
+///     parse_features_parallel(&data, "exon", "gene_name")?,
+///     parse_features(&data, "exon", "gene_name")?,
+/// );
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "parallel")]
+pub fn parse_features_parallel(
+    src: &[u8],
+    feature_type: &str,
+    feature_id: &str,
+) -> io::Result<Features> {
+    parse_features_parallel_with_options(
+        src,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+#[cfg(feature = "parallel")]
+fn parse_features_parallel_with_options(
+    src: &[u8],
+    feature_type: &str,
+    feature_id: &str,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features> {
+    let group_by: GroupBy = feature_id.parse()?;
+    let chunk_count = rayon::current_num_threads() * CHUNKS_PER_THREAD;
+    let chunks = split_lines(src, chunk_count);
+
+    // Each chunk ends at a line boundary, so the line offset of a chunk is
+    // the number of lines in the chunks before it.
+    let line_counts: Vec<u64> = chunks
+        .par_iter()
+        .map(|chunk| chunk.iter().filter(|&&b| b == b'\n').count() as u64)
+        .collect();
+
+    let line_offsets: Vec<u64> = line_counts
+        .iter()
+        .scan(0, |offset, &n| {
+            let line_offset = *offset;
+            *offset += n;
+            Some(line_offset)
+        })
+        .collect();
+
+    let results: Vec<_> = chunks
+        .par_iter()
+        .zip(line_offsets)
+        .map(|(chunk, line_offset)| parse_lines(chunk, line_offset, feature_type, &group_by))
+        .collect();
+
+    let capacity = estimated_feature_count(src.len() as u64);
+    let mut features = FeatureMap::with_capacity_and_hasher(capacity, Default::default());
+    let mut skipped_line_count = 0;
+    let mut tally = RecordTally::default();
+
+    for result in results {
+        let (chunk_features, chunk_skipped_line_count, chunk_tally) = result?;
+
+        for (id, intervals) in chunk_features {
+            features.entry(id).or_default().extend(intervals);
+        }
+
+        skipped_line_count += chunk_skipped_line_count;
+        tally.merge(chunk_tally);
+    }
+
+    tally.check(feature_type, feature_id, options)?;
+    log_skipped_lines(skipped_line_count);
+
+    Ok(features.into_iter().collect())
+}
+
+// Splits the buffer into at most `n` chunks of roughly equal size, each
+// ending at a line boundary.
+#[cfg(feature = "parallel")]
+fn split_lines(src: &[u8], n: usize) -> Vec<&[u8]> {
+    let chunk_size = (src.len() / n.max(1)).max(1);
+    let mut chunks = Vec::with_capacity(n);
+    let mut start = 0;
+
+    while start < src.len() {
+        let end = (start + chunk_size).min(src.len());
+
+        let end = match src[end..].iter().position(|&b| b == b'\n') {
+            Some(i) => end + i + 1,
+            None => src.len(),
+        };
+
+        chunks.push(&src[start..end]);
+        start = end;
+    }
+
+    chunks
+}
+
+/// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2 buffer.
+///
+/// Lines are scanned as byte slices. Comment (`#`) and blank lines are
+/// skipped, and only the start, end, and attributes fields of records that
+/// match `feature_type` are parsed.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{features::Feature, gtf::parse_features};
+///
+/// let data = b"\
+/// ##format: gtf
+/// chr1\tHAVANA\tgene\t11869\t14409\t.\t+\t.\tgene_id \"ENSG00000223972.5\";
+/// chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"ENSG00000223972.5\";
+/// chr1\tHAVANA\texon\t12613\t12721\t.\t+\t.\tgene_id \"ENSG00000223972.5\";
+/// ";
+///
+/// let features = parse_features(data, "exon", "gene_id").unwrap();
+///
+/// assert_eq!(
+///     &features["ENSG00000223972.5"],
+///     &[Feature::new(11869, 12227), Feature::new(12613, 12721)],
+/// );
+/// ```
+pub fn parse_features(src: &[u8], feature_type: &str, feature_id: &str) -> io::Result<Features> {
+    parse_features_with_options(
+        src,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+/// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2 buffer
+/// with options.
+///
+/// This is the same as [`parse_features`], but records that lack the feature
+/// ID attribute can be skipped. See [`ReadFeaturesOptions`].
+///
+/// [`parse_features`]: fn.parse_features.html
+/// [`ReadFeaturesOptions`]: ../features/struct.ReadFeaturesOptions.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{features::ReadFeaturesOptions, gtf::parse_features_with_options};
+///
+/// let data = b"\
+/// chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";
+/// chr1\tHAVANA\texon\t12613\t12721\t.\t+\t.\ttranscript_id \"t1\";
+/// ";
+///
+/// let options = ReadFeaturesOptions {
+///     max_missing_id_fraction: 0.5,
+/// };
+///
+/// let features = parse_features_with_options(data, "exon", "gene_id", &options)?;
+///
+/// assert_eq!(features.len(), 1);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn parse_features_with_options(
+    src: &[u8],
+    feature_type: &str,
+    feature_id: &str,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features> {
+    let group_by: GroupBy = feature_id.parse()?;
+    let (features, skipped_line_count, tally) = parse_lines(src, 0, feature_type, &group_by)?;
+    tally.check(feature_type, feature_id, options)?;
+    log_skipped_lines(skipped_line_count);
+    Ok(features.into_iter().collect())
+}
+
+// Parses features and returns them with the number of skipped lines and the
+// tally of records, which is left to the caller to check. Line numbers start
+// after `line_offset`, i.e., the number of lines before `src` in the input.
+fn parse_lines(
+    src: &[u8],
+    line_offset: u64,
+    feature_type: &str,
+    group_by: &GroupBy,
+) -> io::Result<(FeatureMap, u64, RecordTally)> {
+    let capacity = estimated_feature_count(src.len() as u64);
+    let mut features = FeatureMap::with_capacity_and_hasher(capacity, Default::default());
+    let mut lines = lines(src);
+    let mut tally = RecordTally::default();
+
+    for (line_number, line) in &mut lines {
+        let line_number = line_offset + line_number;
+
+        let Record { id, feature, .. } = match parse_line(line, feature_type, group_by) {
+            Ok(Some(record)) => record,
+            Ok(None) => continue,
+            Err(e) if is_missing_attribute(&e) => {
+                tally.add_missing(Some(line_number));
+                continue;
+            }
+            Err(e) => return Err(with_line_number(e, line_number)),
+        };
+
+        tally.add();
+
+        match features.get_mut(&*id) {
+            Some(list) => list.push(feature),
+            None => {
+                features.insert(id.as_ref().into(), vec![feature]);
+            }
+        }
+    }
+
+    Ok((features, lines.skipped_line_count, tally))
+}
+
+pub(crate) fn log_skipped_lines(skipped_line_count: u64) {
+    if skipped_line_count > 0 {
+        info!(
+            "skipped {} header, comment, track, browser, and blank lines in annotations",
+            skipped_line_count
+        );
+    }
+}
+
+/// Builds a map of feature ID-feature vector pairs from a GTF/GFFv2.
+///
+/// If the extension of the path is `.gz`, the file is decompressed. The file
+/// is read fully into memory and parsed with [`parse_features`].
+///
+/// [`parse_features`]: fn.parse_features.html
+pub fn read_features<P>(src: P, feature_type: &str, feature_id: &str) -> io::Result<Features>
+where
+    P: AsRef<Path>,
+{
+    let buf = read_to_end(src)?;
+    parse_features(&buf, feature_type, feature_id)
+}
+
+/// Builds a map of feature ID-last exon vector pairs from a GTF/GFFv2.
+///
+/// If the extension of the path is `.gz`, the file is decompressed. See
+/// [`parse_last_exons`].
+///
+/// [`parse_last_exons`]: fn.parse_last_exons.html
+pub fn read_last_exons<P>(src: P, feature_id: &str) -> io::Result<Features>
+where
+    P: AsRef<Path>,
+{
+    let buf = read_to_end(src)?;
+    parse_last_exons(&buf, feature_id)
+}
+
+/// Builds a map of feature ID-last exon vector pairs from a GTF/GFFv2 buffer.
+///
+/// The last exon of each transcript (`transcript_id`) is the 3'-most exon,
+/// i.e., the exon with the greatest end on the forward strand or the least
+/// start on the reverse strand. Transcripts are then grouped by `feature_id`,
+/// e.g., `gene_id`.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{features::Feature, gtf::parse_last_exons};
+///
+/// let data = b"\
+/// chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+/// chr1\tHAVANA\texon\t12613\t12721\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+/// chr1\tHAVANA\texon\t14696\t24886\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t2\";
+/// chr1\tHAVANA\texon\t14404\t14501\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t2\";
+/// ";
+///
+/// let features = parse_last_exons(data, "gene_id").unwrap();
+///
+/// assert_eq!(&features["g1"], &[Feature::new(12613, 12721)]);
+/// assert_eq!(&features["g2"], &[Feature::new(14404, 14501)]);
+/// ```
+pub fn parse_last_exons(src: &[u8], feature_id: &str) -> io::Result<Features> {
+    let group_by: GroupBy = feature_id.parse()?;
+    let capacity = estimated_feature_count(src.len() as u64);
+    let mut last_exons: HashMap<&[u8], (Cow<'_, str>, Feature), HashBuilder> =
+        HashMap::with_capacity_and_hasher(capacity, Default::default());
+    let mut transcript_ids = Vec::new();
+
+    for (line_number, line) in lines(src) {
+        let record = match parse_record(line_number, line, EXON_TYPE, &group_by)? {
+            Some(record) => record,
+            None => continue,
+        };
+
+        let transcript_id = find_attribute(record.attributes, TRANSCRIPT_ID_KEY)
+            .ok_or_else(|| invalid_data(String::from("missing attribute 'transcript_id'")))?;
+
+        let is_reverse = record.strand == REVERSE_STRAND;
+
+        match last_exons.get_mut(transcript_id) {
+            Some((_, last)) => {
+                let is_downstream = if is_reverse {
+                    record.feature.start < last.start
+                } else {
+                    record.feature.end > last.end
+                };
+
+                if is_downstream {
+                    *last = record.feature;
+                }
+            }
+            None => {
+                transcript_ids.push(transcript_id);
+                last_exons.insert(transcript_id, (record.id, record.feature));
+            }
+        }
+    }
+
+    let mut features = Features::default();
+
+    for transcript_id in transcript_ids {
+        let (id, feature) = last_exons[transcript_id].clone();
+        features
+            .entry(id.as_ref().into())
+            .or_default()
+            .push(feature);
+    }
+
+    Ok(features)
+}
+
+/// Feature properties read from a GTF/GFFv2 in a single pass.
+///
+/// See [`read_annotations`].
+///
+/// [`read_annotations`]: fn.read_annotations.html
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Annotations {
+    /// The reference sequence name of each feature.
+    pub reference_sequence_names: ReferenceSequenceNames,
+    /// The strand of each feature.
+    pub strands: Strands,
+    /// The biotype of each feature.
+    pub biotypes: Biotypes,
+    /// The feature ID of each alias.
+    pub aliases: Aliases,
+}
+
+/// Reads the reference sequence names, strands, biotypes, and aliases of
+/// features from a GTF/GFFv2.
+///
+/// If the extension of the path is `.gz`, the file is decompressed. The file
+/// is read once and parsed with [`parse_annotations`].
+///
+/// [`parse_annotations`]: fn.parse_annotations.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{feature_index::Strand, gtf::read_annotations};
+///
+/// let annotations = read_annotations(
+///     "test/fixtures/annotations.gtf",
+///     "exon",
+///     "gene_id",
+///     Some("gene_name"),
+/// )?;
+///
+/// let id = &annotations.aliases["NECAP2"];
+/// assert_eq!(id, "ENSG00000157191.19");
+/// assert_eq!(annotations.reference_sequence_names[id.as_str()], "chr1");
+/// assert_eq!(annotations.strands[id.as_str()], Strand::Forward);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_annotations<P>(
+    src: P,
+    feature_type: &str,
+    feature_id: &str,
+    alias_key: Option<&str>,
+) -> io::Result<Annotations>
+where
+    P: AsRef<Path>,
+{
+    let buf = read_to_end(src)?;
+    parse_annotations(&buf, feature_type, feature_id, alias_key)
+}
+
+/// Parses the reference sequence names, strands, biotypes, and aliases of
+/// features from a GTF/GFFv2 buffer.
+///
+/// Records are filtered as in [`parse_features`]. For each feature, the
+/// reference sequence name, strand, and biotype are those of its first record.
+/// The biotype is the value of the `gene_biotype` (Ensembl) or `gene_type`
+/// (GENCODE) attribute. Features without either attribute have no biotype.
+///
+/// Aliases are only read if `alias_key` is given. The alias of a record is
+/// the value of the `alias_key` attribute, e.g., `gene_name` for features
+/// identified by `gene_id`. If an alias is used by multiple features, it maps
+/// to the first.
+///
+/// [`parse_features`]: fn.parse_features.html
+pub fn parse_annotations(
+    src: &[u8],
+    feature_type: &str,
+    feature_id: &str,
+    alias_key: Option<&str>,
+) -> io::Result<Annotations> {
+    let group_by: GroupBy = feature_id.parse()?;
+    let mut annotations = Annotations::default();
+
+    for (line_number, line) in lines(src) {
+        let record = match parse_record(line_number, line, feature_type, &group_by)? {
+            Some(record) => record,
+            None => continue,
+        };
+
+        add_annotations(&mut annotations, &record, alias_key)
+            .map_err(|e| with_line_number(e, line_number))?;
+    }
+
+    Ok(annotations)
+}
+
+fn add_annotations(
+    annotations: &mut Annotations,
+    record: &Record<'_>,
+    alias_key: Option<&str>,
+) -> io::Result<()> {
+    if let Some(alias) = alias_key.and_then(|key| find_attribute(record.attributes, key.as_bytes()))
+    {
+        let alias = parse_str(alias)?;
+
+        if !annotations.aliases.contains_key(alias) {
+            annotations
+                .aliases
+                .insert(alias.into(), record.id.as_ref().into());
+        }
+    }
+
+    if annotations
+        .reference_sequence_names
+        .contains_key(&*record.id)
+    {
+        return Ok(());
+    }
+
+    let id: FeatureId = record.id.as_ref().into();
+
+    let reference_sequence_name = parse_str(record.reference_sequence_name)?;
+    annotations
+        .reference_sequence_names
+        .insert(id.clone(), reference_sequence_name.into());
+
+    annotations
+        .strands
+        .insert(id.clone(), Strand::from_bytes(record.strand));
+
+    let biotype = BIOTYPE_KEYS
+        .iter()
+        .find_map(|key| find_attribute(record.attributes, key));
+
+    if let Some(biotype) = biotype {
+        let biotype = parse_str(biotype)?;
+        annotations.biotypes.insert(id, biotype.into());
+    }
+
+    Ok(())
+}
+
+/// Builds a map of feature ID-reference sequence name pairs from a
+/// GTF/GFFv2.
+///
+/// This reads the reference sequence names of [`read_annotations`]. If a
+/// feature has records on multiple reference sequences, the first is used.
+///
+/// [`read_annotations`]: fn.read_annotations.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::gtf::read_reference_sequence_names;
+///
+/// let names = read_reference_sequence_names(
+///     "test/fixtures/annotations.gtf",
+///     "exon",
+///     "gene_name",
+/// ).unwrap();
+///
+/// assert_eq!(names["NECAP2"], "chr1");
+/// ```
+pub fn read_reference_sequence_names<P>(
+    src: P,
+    feature_type: &str,
+    feature_id: &str,
+) -> io::Result<ReferenceSequenceNames>
+where
+    P: AsRef<Path>,
+{
+    read_annotations(src, feature_type, feature_id, None)
+        .map(|annotations| annotations.reference_sequence_names)
+}
+
+/// Builds a map of feature ID-strand pairs from a GTF/GFFv2.
+///
+/// This reads the strands of [`read_annotations`]. If a feature has records
+/// on multiple strands, the first is used.
+///
+/// [`read_annotations`]: fn.read_annotations.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{feature_index::Strand, gtf::read_strands};
+///
+/// let strands = read_strands("test/fixtures/annotations.gtf", "exon", "gene_name").unwrap();
+///
+/// assert_eq!(strands["NECAP2"], Strand::Forward);
+/// ```
+pub fn read_strands<P>(src: P, feature_type: &str, feature_id: &str) -> io::Result<Strands>
+where
+    P: AsRef<Path>,
+{
+    read_annotations(src, feature_type, feature_id, None).map(|annotations| annotations.strands)
+}
+
+/// Builds a map of feature ID-biotype pairs from a GTF/GFFv2.
+///
+/// This reads the biotypes of [`read_annotations`]. Features without a
+/// `gene_biotype` or `gene_type` attribute are not included.
+///
+/// [`read_annotations`]: fn.read_annotations.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::gtf::read_biotypes;
+///
+/// let biotypes = read_biotypes("test/fixtures/annotations.gtf", "exon", "gene_name").unwrap();
+///
+/// assert_eq!(biotypes["DDX11L1"], "transcribed_unprocessed_pseudogene");
+/// ```
+pub fn read_biotypes<P>(src: P, feature_type: &str, feature_id: &str) -> io::Result<Biotypes>
+where
+    P: AsRef<Path>,
+{
+    read_annotations(src, feature_type, feature_id, None).map(|annotations| annotations.biotypes)
+}
+
+/// Builds a map of alias-feature ID pairs from a GTF/GFFv2.
+///
+/// This reads the aliases of [`read_annotations`] with the given alias key.
+///
+/// [`read_annotations`]: fn.read_annotations.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::gtf::read_aliases;
+///
+/// let aliases =
+///     read_aliases("test/fixtures/annotations.gtf", "exon", "gene_id", "gene_name").unwrap();
+///
+/// assert_eq!(aliases["NECAP2"], "ENSG00000157191.19");
+/// ```
+pub fn read_aliases<P>(
+    src: P,
+    feature_type: &str,
+    feature_id: &str,
+    alias_key: &str,
+) -> io::Result<Aliases>
+where
+    P: AsRef<Path>,
+{
+    read_annotations(src, feature_type, feature_id, Some(alias_key))
+        .map(|annotations| annotations.aliases)
+}
+
+fn read_to_end<P>(src: P) -> io::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    let mut buf = Vec::new();
+    compression::open(src)?.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+type IsNewline = fn(&u8) -> bool;
+
+// An iterator over the record lines and their 1-based line numbers.
+//
+// Header (`#!`) and comment (`#`) lines, UCSC track and browser lines, and
+// blank lines are skipped and counted.
+struct Lines<'a> {
+    inner: iter::Enumerate<slice::Split<'a, u8, IsNewline>>,
+    skipped_line_count: u64,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = (u64, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (i, line) = self.inner.next()?;
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+            if is_skipped_line(line) {
+                self.skipped_line_count += 1;
+                continue;
+            }
+
+            return Some((i as u64 + 1, line));
+        }
+    }
+}
+
+fn lines(src: &[u8]) -> Lines<'_> {
+    let src = src.strip_suffix(b"\n").unwrap_or(src);
+    let is_newline: IsNewline = |&b| b == b'\n';
+
+    Lines {
+        inner: src.split(is_newline).enumerate(),
+        skipped_line_count: 0,
+    }
+}
+
+// Returns whether the line is not a record, i.e., it is blank, a header or
+// comment line, or a UCSC track or browser line. The line must not include
+// its line terminator.
+pub(crate) fn is_skipped_line(line: &[u8]) -> bool {
+    line.is_empty() || line[0] == COMMENT_PREFIX || is_track_or_browser_line(line)
+}
+
+// Returns whether the line is a UCSC track or browser line, e.g., `track
+// name=genes`.
+pub(crate) fn is_track_or_browser_line(line: &[u8]) -> bool {
+    [TRACK_PREFIX, BROWSER_PREFIX].iter().any(|prefix| {
+        line.starts_with(prefix)
+            && !matches!(line.get(prefix.len()), Some(b) if !b.is_ascii_whitespace())
+    })
+}
+
+pub(crate) struct Record<'a> {
+    reference_sequence_name: &'a [u8],
+    strand: &'a [u8],
+    attributes: &'a [u8],
+    pub(crate) id: Cow<'a, str>,
+    pub(crate) feature: Feature,
+}
+
+fn parse_record<'a>(
+    line_number: u64,
+    line: &'a [u8],
+    feature_type: &str,
+    group_by: &GroupBy,
+) -> io::Result<Option<Record<'a>>> {
+    parse_line(line, feature_type, group_by).map_err(|e| with_line_number(e, line_number))
+}
+
+fn parse_line<'a>(
+    line: &'a [u8],
+    feature_type: &str,
+    group_by: &GroupBy,
+) -> io::Result<Option<Record<'a>>> {
+    parse_fields(
+        line.splitn(FIELD_COUNT, |&b| b == b'\t'),
+        feature_type,
+        group_by,
+    )
+}
+
+// Parses a record from its fields. This is shared by the byte-slice readers in
+// this module and the buffered readers in `features`, which split lines into
+// fields with the GFF reader.
+pub(crate) fn parse_fields<'a, I>(
+    values: I,
+    feature_type: &str,
+    group_by: &GroupBy,
+) -> io::Result<Option<Record<'a>>>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut fields = [&b""[..]; FIELD_COUNT];
+    let mut n = 0;
+
+    for (field, value) in fields.iter_mut().zip(values) {
+        *field = value;
+        n += 1;
+    }
+
+    if n < FIELD_COUNT {
+        return Err(invalid_data(format!(
+            "invalid record: expected {} fields, got {}",
+            FIELD_COUNT, n
+        )));
+    }
+
+    if fields[FEATURE_INDEX] != feature_type.as_bytes() {
+        return Ok(None);
+    }
+
+    let start = parse_position(fields[START_INDEX], "start")?;
+    let end = parse_position(fields[END_INDEX], "end")?;
+
+    if end < start {
+        return Err(invalid_data(format!(
+            "invalid record: end ({}) < start ({})",
+            end, start
+        )));
+    }
+
+    let id = match group_by.as_attribute() {
+        Some(key) => {
+            let id = find_attribute(fields[ATTRIBUTES_INDEX], key.as_bytes())
+                .ok_or_else(|| missing_attribute(key))?;
+
+            parse_str(id).map(Cow::from)?
+        }
+        None => Cow::from(group_by.evaluate(
+            |column| match column {
+                Column::Seqname => fields[REFERENCE_SEQUENCE_NAME_INDEX],
+                Column::Source => fields[SOURCE_INDEX],
+                Column::Strand => fields[STRAND_INDEX],
+            },
+            |key| find_attribute(fields[ATTRIBUTES_INDEX], key.as_bytes()),
+        )?),
+    };
+
+    Ok(Some(Record {
+        reference_sequence_name: fields[REFERENCE_SEQUENCE_NAME_INDEX],
+        strand: fields[STRAND_INDEX],
+        attributes: fields[ATTRIBUTES_INDEX],
+        id,
+        feature: Feature::new(start, end),
+    }))
+}
+
+fn parse_position(s: &[u8], name: &str) -> io::Result<u64> {
+    parse_u64(s)
+        .ok_or_else(|| invalid_data(format!("invalid {}: {}", name, String::from_utf8_lossy(s))))
+}
+
+// Finds the value of the attribute with the given key, e.g., `gene_id` in
+// `gene_id "ENSG00000223972.5"; level 2;`. Surrounding quotes are removed.
+fn find_attribute<'a>(attributes: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    for attribute in split_attributes(attributes) {
+        let attribute = trim(attribute);
+
+        if attribute.len() > key.len() && attribute.starts_with(key) && attribute[key.len()] == b' '
+        {
+            let value = trim(&attribute[key.len() + 1..]);
+            let value = value
+                .strip_prefix(b"\"")
+                .and_then(|v| v.strip_suffix(b"\""))
+                .unwrap_or(value);
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+// An iterator over the `;`-separated attributes of a record. A `;` in a quoted
+// value, e.g., `note "a; b"`, does not end the attribute.
+struct SplitAttributes<'a> {
+    src: &'a [u8],
+}
+
+impl<'a> Iterator for SplitAttributes<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.src.is_empty() {
+            return None;
+        }
+
+        let mut is_quoted = false;
+
+        let i = self.src.iter().position(|&b| {
+            if b == b'"' {
+                is_quoted = !is_quoted;
+            }
+
+            b == b';' && !is_quoted
+        });
+
+        let attribute = match i {
+            Some(i) => {
+                let attribute = &self.src[..i];
+                self.src = &self.src[i + 1..];
+                attribute
+            }
+            None => mem::take(&mut self.src),
+        };
+
+        Some(attribute)
+    }
+}
+
+fn split_attributes(src: &[u8]) -> SplitAttributes<'_> {
+    SplitAttributes { src }
+}
+
+fn trim(s: &[u8]) -> &[u8] {
+    let start = s
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(s.len());
+    let end = s
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+    &s[start..end]
+}
+
+fn parse_str(s: &[u8]) -> io::Result<&str> {
+    str::from_utf8(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_features_matches_fixture() {
+        let data = std::fs::read("test/fixtures/annotations.gtf").unwrap();
+        let features = parse_features(&data, "exon", "gene_name").unwrap();
+
+        assert_eq!(features.len(), 2);
+        assert_eq!(
+            &features["DDX11L1"],
+            &[Feature::new(11869, 12227), Feature::new(12613, 12721)]
+        );
+        assert_eq!(&features["NECAP2"], &[Feature::new(16440672, 16440853)]);
+    }
+
+    #[test]
+    fn test_parse_features_with_missing_attribute() {
+        let data = b"chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"ENSG00000223972.5\";\n";
+        let e = parse_features(data, "exon", "gene_name").unwrap_err();
+
+        assert_eq!(
+            e.to_string(),
+            "line 1: missing attribute 'gene_name' in 1 of 1 'exon' records"
+        );
+    }
+
+    #[test]
+    fn test_parse_features_with_quoted_semicolon() -> io::Result<()> {
+        let data =
+            b"chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tnote \"a; gene_id x\"; gene_id \"g1\";\n";
+
+        let features = parse_features(data, "exon", "gene_id")?;
+        assert_eq!(&features["g1"], &[Feature::new(11869, 12227)]);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parse_features_parallel_with_missing_attribute() {
+        let mut data = Vec::new();
+
+        for i in 1..=64 {
+            data.extend_from_slice(b"chr1\tHAVANA\texon\t1\t10\t.\t+\t.\t");
+
+            if i % 16 != 0 {
+                data.extend_from_slice(b"gene_id \"g1\";");
+            }
+
+            data.push(b'\n');
+        }
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+
+        let e = pool
+            .install(|| parse_features_parallel(&data, "exon", "gene_id"))
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "line 16: missing attribute 'gene_id' in 4 of 64 'exon' records"
+        );
+
+        let options = ReadFeaturesOptions {
+            max_missing_id_fraction: 0.1,
+        };
+
+        let features = pool
+            .install(|| parse_features_parallel_with_options(&data, "exon", "gene_id", &options))
+            .unwrap();
+        assert_eq!(
+            features,
+            parse_features_with_options(&data, "exon", "gene_id", &options).unwrap()
+        );
+        assert_eq!(features["g1"].len(), 60);
+    }
+
+    #[test]
+    fn test_parse_features_with_group_by_expression() {
+        let data = b"\
+chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";
+chrY\tHAVANA\texon\t12613\t12721\t.\t+\t.\tgene_id \"g1\";
+";
+
+        let features = parse_features(data, "exon", r#"gene_id + ":" + seqname"#).unwrap();
+
+        assert_eq!(features.len(), 2);
+        assert_eq!(&features["g1:chr1"], &[Feature::new(11869, 12227)]);
+        assert_eq!(&features["g1:chrY"], &[Feature::new(12613, 12721)]);
+    }
+
+    #[test]
+    fn test_parse_features_with_end_before_start() {
+        let data = b"##format: gtf\nchr1\tHAVANA\texon\t12227\t11869\t.\t+\t.\tgene_id \"g1\";\n";
+
+        let e = parse_features(data, "exon", "gene_id").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "line 2: invalid record: end (11869) < start (12227)"
+        );
+
+        #[cfg(feature = "parallel")]
+        {
+            let e = parse_features_parallel(data, "exon", "gene_id").unwrap_err();
+            assert_eq!(
+                e.to_string(),
+                "line 2: invalid record: end (11869) < start (12227)"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_lines_with_skipped_lines() -> io::Result<()> {
+        let data = b"\
+#!genome-build GRCh38.p13
+track name=genes
+browser position chr1:11869-14409
+
+chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\";
+trackless\tHAVANA\texon\t12613\t12721\t.\t+\t.\tgene_id \"g1\";
+";
+
+        let (features, skipped_line_count, _) = parse_lines(data, 0, "exon", &"gene_id".parse()?)?;
+        assert_eq!(features["g1"].len(), 2);
+        assert_eq!(skipped_line_count, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_gtf_record() {
+        assert!(is_gtf_record(
+            b"chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tgene_id \"g1\"; gene_name \"A=B\";"
+        ));
+        assert!(!is_gtf_record(
+            b"chr1\tHAVANA\texon\t11869\t12227\t.\t+\t.\tID=exon:1;Parent=t1"
+        ));
+        assert!(!is_gtf_record(b"AAAS\t645"));
+    }
+
+    #[test]
+    fn test_is_track_or_browser_line() {
+        assert!(is_track_or_browser_line(b"track"));
+        assert!(is_track_or_browser_line(b"track name=genes"));
+        assert!(is_track_or_browser_line(b"browser hide all"));
+        assert!(!is_track_or_browser_line(b"trackless\tHAVANA"));
+        assert!(!is_track_or_browser_line(b"chr1\tHAVANA"));
+    }
+
+    #[test]
+    fn test_parse_features_with_invalid_position() {
+        let data = b"chr1\tHAVANA\texon\tx\t12227\t.\t+\t.\tgene_id \"ENSG00000223972.5\";\n";
+        assert!(parse_features(data, "exon", "gene_id").is_err());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_split_lines() {
+        let data = b"a\nbb\nccc\ndddd\n";
+
+        let chunks = split_lines(data, 2);
+        assert_eq!(chunks, [&b"a\nbb\nccc\n"[..], &b"dddd\n"[..]]);
+
+        let chunks = split_lines(b"a\nbb", 8);
+        assert_eq!(chunks, [&b"a\n"[..], &b"bb"[..]]);
+
+        assert!(split_lines(b"", 4).is_empty());
+    }
+
+    #[test]
+    fn test_parse_last_exons() {
+        let data = b"\
+chr1\t.\texon\t1\t10\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+chr1\t.\texon\t21\t30\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+chr1\t.\texon\t1\t10\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t2\";
+chr1\t.\texon\t41\t50\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t3\";
+chr1\t.\texon\t31\t35\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t3\";
+";
+
+        let features = parse_last_exons(data, "gene_id").unwrap();
+
+        assert_eq!(
+            &features["g1"],
+            &[Feature::new(21, 30), Feature::new(1, 10)]
+        );
+        assert_eq!(&features["g2"], &[Feature::new(31, 35)]);
+
+        let data = b"chr1\t.\texon\t1\t10\t.\t+\t.\tgene_id \"g1\";\n";
+        assert!(parse_last_exons(data, "gene_id").is_err());
+    }
+
+    #[test]
+    fn test_find_attribute() {
+        let attributes = b"gene_id \"ENSG00000223972.5\"; gene_name \"DDX11L1\"; level 2;";
+
+        assert_eq!(
+            find_attribute(attributes, b"gene_id"),
+            Some(&b"ENSG00000223972.5"[..])
+        );
+        assert_eq!(
+            find_attribute(attributes, b"gene_name"),
+            Some(&b"DDX11L1"[..])
+        );
+        assert_eq!(find_attribute(attributes, b"level"), Some(&b"2"[..]));
+        assert_eq!(find_attribute(attributes, b"gene"), None);
+        assert_eq!(find_attribute(attributes, b"transcript_id"), None);
+
+        let attributes = b"gene_id \"g1\"; note \"a; gene_name b\"; gene_name \"A\";";
+        assert_eq!(
+            find_attribute(attributes, b"note"),
+            Some(&b"a; gene_name b"[..])
+        );
+        assert_eq!(find_attribute(attributes, b"gene_name"), Some(&b"A"[..]));
+    }
+
+    #[test]
+    fn test_split_attributes() {
+        let attributes: Vec<_> = split_attributes(b"a \"1\"; b \"2;3\"; c 4").collect();
+        assert_eq!(
+            attributes,
+            [&b"a \"1\""[..], &b" b \"2;3\""[..], &b" c 4"[..]]
+        );
+
+        assert_eq!(split_attributes(b"").count(), 0);
+    }
+
+    #[test]
+    fn test_parse_annotations() -> io::Result<()> {
+        let data = b"\
+chr1\t.\texon\t1\t10\t.\t+\t.\tgene_id \"g1\"; gene_name \"A\"; transcript_id \"t1\";
+chr2\t.\texon\t21\t30\t.\t-\t.\tgene_id \"g1\"; gene_name \"A\"; transcript_id \"t2\";
+chrM\t.\texon\t1\t10\t.\t-\t.\tgene_id \"g2\"; gene_type \"Mt_rRNA\"; transcript_id \"t3\";
+chr1\t.\tgene\t1\t30\t.\t+\t.\tgene_id \"g3\"; transcript_id \"t4\";
+";
+
+        let annotations = parse_annotations(data, "exon", "gene_id", Some("transcript_id"))?;
+
+        assert_eq!(annotations.reference_sequence_names.len(), 2);
+        assert_eq!(annotations.reference_sequence_names["g1"], "chr1");
+        assert_eq!(annotations.reference_sequence_names["g2"], "chrM");
+
+        assert_eq!(annotations.strands["g1"], Strand::Forward);
+        assert_eq!(annotations.strands["g2"], Strand::Reverse);
+
+        assert_eq!(annotations.biotypes.len(), 1);
+        assert_eq!(annotations.biotypes["g2"], "Mt_rRNA");
+
+        assert_eq!(annotations.aliases.len(), 3);
+        assert_eq!(annotations.aliases["t1"], "g1");
+        assert_eq!(annotations.aliases["t2"], "g1");
+        assert_eq!(annotations.aliases["t3"], "g2");
+
+        let annotations = parse_annotations(data, "exon", "gene_id", None)?;
+        assert!(annotations.aliases.is_empty());
+
+        Ok(())
+    }
+}
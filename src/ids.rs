@@ -0,0 +1,505 @@
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    io::{self, Read},
+    str,
+};
+
+use csv::ByteRecord;
+
+use crate::{
+    counts::Counts,
+    feature_id::{strip_version, FeatureId},
+    gtf::Aliases,
+    lengths::Lengths,
+    HashBuilder,
+};
+
+const FROM_ID_INDEX: usize = 0;
+const TO_ID_INDEX: usize = 1;
+
+pub type IdMap = HashMap<String, FeatureId>;
+
+/// The difference between a feature ID and a suggested feature ID.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mismatch {
+    /// The IDs differ by a version suffix, e.g., `ENSG00000157191` and
+    /// `ENSG00000157191.19`.
+    Version,
+    /// The IDs differ in case, e.g., `Xist` and `XIST`.
+    Case,
+    /// The ID is an alias of the suggested ID, e.g., a gene name of a gene ID.
+    Alias,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Version => f.write_str("version suffix"),
+            Mismatch::Case => f.write_str("case"),
+            Mismatch::Alias => f.write_str("alias"),
+        }
+    }
+}
+
+/// A feature ID in the annotations that nearly matches a missing feature ID.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Suggestion {
+    pub id: FeatureId,
+    pub mismatch: Mismatch,
+}
+
+/// A normalization of feature IDs used to match counts to annotations.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Normalization {
+    /// IDs must match exactly.
+    None,
+    /// IDs are compared case-insensitively.
+    Case,
+    /// IDs are compared case-insensitively and with non-alphanumeric
+    /// characters removed.
+    Alphanumeric,
+}
+
+impl Normalization {
+    /// Normalizes a feature ID.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::ids::Normalization;
+    ///
+    /// assert_eq!(Normalization::None.normalize("Hba-a1"), "Hba-a1");
+    /// assert_eq!(Normalization::Case.normalize("Hba-a1"), "hba-a1");
+    /// assert_eq!(Normalization::Alphanumeric.normalize("Hba-a1"), "hbaa1");
+    /// ```
+    pub fn normalize<'a>(&self, id: &'a str) -> Cow<'a, str> {
+        match self {
+            Normalization::None => Cow::from(id),
+            Normalization::Case => Cow::from(id.to_lowercase()),
+            Normalization::Alphanumeric => Cow::from(
+                id.chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .flat_map(char::to_lowercase)
+                    .collect::<String>(),
+            ),
+        }
+    }
+}
+
+/// Renames counts to the feature IDs in the annotations that they match after
+/// normalization.
+///
+/// Counts with a feature ID in `lengths` are kept as is. Otherwise, the
+/// feature ID is normalized and renamed to the feature ID in `lengths` with
+/// the same normalized form. Counts that match no feature ID, or multiple
+/// feature IDs, are kept as is. Counts renamed to the same feature ID are
+/// summed.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     counts::Counts,
+///     feature_id::FeatureId,
+///     ids::{match_feature_ids, Normalization},
+///     lengths::Lengths,
+/// };
+///
+/// let counts: Counts = [(FeatureId::from("XIST"), 8)].iter().cloned().collect();
+/// let lengths: Lengths = [(FeatureId::from("Xist"), 17946)].iter().cloned().collect();
+///
+/// let counts = match_feature_ids(&counts, &lengths, Normalization::Case);
+///
+/// assert_eq!(counts["Xist"], 8);
+/// ```
+pub fn match_feature_ids(
+    counts: &Counts,
+    lengths: &Lengths,
+    normalization: Normalization,
+) -> Counts {
+    if normalization == Normalization::None {
+        return counts.clone();
+    }
+
+    // A normalized ID maps to `None` when multiple feature IDs share it.
+    let mut normalized_ids: HashMap<Cow<'_, str>, Option<&FeatureId>, HashBuilder> =
+        HashMap::default();
+
+    for id in lengths.keys() {
+        normalized_ids
+            .entry(id.normalize(normalization))
+            .and_modify(|entry| *entry = None)
+            .or_insert(Some(id));
+    }
+
+    let mut matched_counts = Counts::default();
+
+    for (id, &count) in counts {
+        let matched_id = if lengths.contains_key(id) {
+            id
+        } else {
+            normalized_ids
+                .get(&id.normalize(normalization))
+                .and_then(|entry| *entry)
+                .unwrap_or(id)
+        };
+
+        *matched_counts.entry(matched_id.clone()).or_insert(0) += count;
+    }
+
+    matched_counts
+}
+
+/// Reads TSV-formatted data and returns a map of feature ID-feature ID pairs.
+///
+/// The input is TSV-formatted with two columns: the feature ID to translate
+/// from (string), e.g., a RefSeq ID, and the feature ID to translate to
+/// (string), e.g., an Ensembl ID.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::ids::read_id_map;
+///
+/// let data = "\
+/// NM_001145862\tENSG00000157191
+/// NM_015665\tENSG00000094914
+/// ";
+///
+/// let id_map = read_id_map(data.as_bytes()).unwrap();
+///
+/// assert_eq!(id_map.len(), 2);
+/// assert_eq!(&*id_map["NM_015665"], "ENSG00000094914");
+/// ```
+pub fn read_id_map<R>(reader: R) -> io::Result<IdMap>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .from_reader(reader);
+
+    let mut id_map = IdMap::new();
+    let mut record = ByteRecord::new();
+
+    while rdr.read_byte_record(&mut record)? {
+        let from_id = record
+            .get(FROM_ID_INDEX)
+            .and_then(|s| str::from_utf8(s).ok())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| invalid_data(format!("invalid feature ID: {:?}", record)))?;
+
+        let to_id = record
+            .get(TO_ID_INDEX)
+            .and_then(|s| str::from_utf8(s).ok())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| invalid_data(format!("invalid feature ID: {:?}", record)))?;
+
+        match id_map.entry(from_id.into()) {
+            Entry::Vacant(entry) => {
+                entry.insert(to_id.into());
+            }
+            Entry::Occupied(entry) => {
+                return Err(invalid_data(format!(
+                    "duplicate feature ID: {}",
+                    entry.key()
+                )));
+            }
+        }
+    }
+
+    Ok(id_map)
+}
+
+/// Translates the feature IDs of counts using an ID map.
+///
+/// Counts translated to the same feature ID are summed. Counts with a feature
+/// ID that is not in the map are kept as is, and these feature IDs are also
+/// returned, sorted.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{counts::Counts, feature_id::FeatureId, ids::{map_feature_ids, IdMap}};
+///
+/// let counts: Counts = [(FeatureId::from("NM_015665"), 8), (FeatureId::from("AAAS"), 1)]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// let id_map: IdMap = [(String::from("NM_015665"), FeatureId::from("ENSG00000094914"))]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// let (counts, unmapped_ids) = map_feature_ids(&counts, &id_map);
+///
+/// assert_eq!(counts["ENSG00000094914"], 8);
+/// assert_eq!(counts["AAAS"], 1);
+/// assert_eq!(unmapped_ids, [FeatureId::from("AAAS")]);
+/// ```
+pub fn map_feature_ids(counts: &Counts, id_map: &IdMap) -> (Counts, Vec<FeatureId>) {
+    let mut mapped_counts = Counts::default();
+    let mut unmapped_ids = Vec::new();
+
+    for (id, &count) in counts {
+        let mapped_id = match id_map.get(&**id) {
+            Some(mapped_id) => mapped_id,
+            None => {
+                unmapped_ids.push(id.clone());
+                id
+            }
+        };
+
+        *mapped_counts.entry(mapped_id.clone()).or_insert(0) += count;
+    }
+
+    unmapped_ids.sort_unstable();
+
+    (mapped_counts, unmapped_ids)
+}
+
+/// Returns the feature IDs of counts that are not in the annotations, sorted.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     counts::Counts,
+///     feature_id::FeatureId,
+///     ids::find_unmatched_feature_ids,
+///     lengths::Lengths,
+/// };
+///
+/// let counts: Counts = [(FeatureId::from("AAAS"), 8), (FeatureId::from("NECAP2"), 1)]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// let lengths: Lengths = [(FeatureId::from("AAAS"), 2471)].iter().cloned().collect();
+///
+/// assert_eq!(find_unmatched_feature_ids(&counts, &lengths), ["NECAP2"]);
+/// ```
+pub fn find_unmatched_feature_ids<'a>(counts: &'a Counts, lengths: &Lengths) -> Vec<&'a FeatureId> {
+    let mut ids: Vec<_> = counts
+        .keys()
+        .filter(|id| !lengths.contains_key(*id))
+        .collect();
+
+    ids.sort_unstable();
+
+    ids
+}
+
+/// Returns the attribute key of the usual alias of a feature ID attribute.
+///
+/// Gene IDs and gene names are aliases of each other, as are transcript IDs
+/// and transcript names.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::ids::alias_key;
+///
+/// assert_eq!(alias_key("gene_id"), Some("gene_name"));
+/// assert_eq!(alias_key("gene_name"), Some("gene_id"));
+/// assert!(alias_key("exon_id").is_none());
+/// ```
+pub fn alias_key(feature_id: &str) -> Option<&'static str> {
+    match feature_id {
+        "gene_id" => Some("gene_name"),
+        "gene_name" => Some("gene_id"),
+        "transcript_id" => Some("transcript_name"),
+        "transcript_name" => Some("transcript_id"),
+        _ => None,
+    }
+}
+
+/// Suggests a feature ID in the annotations for a feature ID that is missing
+/// from them.
+///
+/// A feature ID is suggested if it differs from the missing ID only by a
+/// version suffix or only in case, or if the missing ID is its alias, e.g., a
+/// gene name, as given by `aliases`. These are checked in that order. If
+/// multiple feature IDs match, the least is suggested.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     feature_id::FeatureId,
+///     ids::{suggest_feature_id, Mismatch},
+///     lengths::Lengths,
+/// };
+///
+/// let lengths: Lengths = [(FeatureId::from("ENSG00000157191.19"), 1362)].iter().cloned().collect();
+///
+/// let suggestion = suggest_feature_id("ENSG00000157191", &lengths, None).unwrap();
+/// assert_eq!(&*suggestion.id, "ENSG00000157191.19");
+/// assert_eq!(suggestion.mismatch, Mismatch::Version);
+///
+/// assert!(suggest_feature_id("NECAP2", &lengths, None).is_none());
+/// ```
+pub fn suggest_feature_id(
+    id: &str,
+    lengths: &Lengths,
+    aliases: Option<&Aliases>,
+) -> Option<Suggestion> {
+    let unversioned_id = strip_version(id);
+
+    let version_match = lengths
+        .keys()
+        .filter(|candidate| candidate.without_version() == unversioned_id)
+        .min();
+
+    if let Some(candidate) = version_match {
+        return Some(Suggestion {
+            id: candidate.clone(),
+            mismatch: Mismatch::Version,
+        });
+    }
+
+    let case_match = lengths
+        .keys()
+        .filter(|candidate| candidate.eq_ignore_ascii_case(id))
+        .min();
+
+    if let Some(candidate) = case_match {
+        return Some(Suggestion {
+            id: candidate.clone(),
+            mismatch: Mismatch::Case,
+        });
+    }
+
+    aliases
+        .and_then(|aliases| aliases.get(id))
+        .and_then(|target| lengths.get_key_value(target.as_str()))
+        .map(|(candidate, _)| Suggestion {
+            id: candidate.clone(),
+            mismatch: Mismatch::Alias,
+        })
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_lengths() -> Lengths {
+        [
+            (FeatureId::from("ENSG00000157191.19"), 1362),
+            (FeatureId::from("ENSG00000223972"), 1735),
+            (FeatureId::from("Xist"), 17946),
+        ]
+        .iter()
+        .cloned()
+        .collect()
+    }
+
+    #[test]
+    fn test_suggest_feature_id() {
+        let lengths = build_lengths();
+
+        let aliases: Aliases = [(
+            FeatureId::from("NECAP2"),
+            String::from("ENSG00000157191.19"),
+        )]
+        .iter()
+        .cloned()
+        .collect();
+
+        let suggestion = suggest_feature_id("ENSG00000223972.5", &lengths, None).unwrap();
+        assert_eq!(&*suggestion.id, "ENSG00000223972");
+        assert_eq!(suggestion.mismatch, Mismatch::Version);
+
+        let suggestion = suggest_feature_id("XIST", &lengths, None).unwrap();
+        assert_eq!(&*suggestion.id, "Xist");
+        assert_eq!(suggestion.mismatch, Mismatch::Case);
+
+        let suggestion = suggest_feature_id("NECAP2", &lengths, Some(&aliases)).unwrap();
+        assert_eq!(&*suggestion.id, "ENSG00000157191.19");
+        assert_eq!(suggestion.mismatch, Mismatch::Alias);
+
+        assert!(suggest_feature_id("NECAP2", &lengths, None).is_none());
+        assert!(suggest_feature_id("DDX11L1", &lengths, Some(&aliases)).is_none());
+    }
+
+    #[test]
+    fn test_match_feature_ids() {
+        let lengths = build_lengths();
+
+        let counts: Counts = [
+            (FeatureId::from("xist"), 3),
+            (FeatureId::from("XIST"), 5),
+            (FeatureId::from("ensg00000223972"), 2),
+            (FeatureId::from("AAAS"), 1),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let matched_counts = match_feature_ids(&counts, &lengths, Normalization::Case);
+        assert_eq!(matched_counts.len(), 3);
+        assert_eq!(matched_counts["Xist"], 8);
+        assert_eq!(matched_counts["ENSG00000223972"], 2);
+        assert_eq!(matched_counts["AAAS"], 1);
+
+        assert_eq!(
+            match_feature_ids(&counts, &lengths, Normalization::None),
+            counts
+        );
+    }
+
+    #[test]
+    fn test_match_feature_ids_with_ambiguous_ids() {
+        let lengths: Lengths = [
+            (FeatureId::from("Hba-a1"), 1),
+            (FeatureId::from("Hbaa1"), 1),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let counts: Counts = [(FeatureId::from("HBA_A1"), 3)].iter().cloned().collect();
+
+        let matched_counts = match_feature_ids(&counts, &lengths, Normalization::Alphanumeric);
+        assert_eq!(matched_counts, counts);
+    }
+
+    #[test]
+    fn test_read_id_map_with_invalid_record() {
+        assert!(read_id_map(&b"NM_015665\n"[..]).is_err());
+        assert!(read_id_map(&b"NM_015665\t\n"[..]).is_err());
+        assert!(read_id_map(&b"NM_015665\ta\nNM_015665\tb\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_map_feature_ids_sums_counts() {
+        let counts: Counts = [
+            (FeatureId::from("NM_001145862"), 3),
+            (FeatureId::from("NM_015665"), 5),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let id_map: IdMap = [
+            (String::from("NM_001145862"), FeatureId::from("AAAS")),
+            (String::from("NM_015665"), FeatureId::from("AAAS")),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let (mapped_counts, unmapped_ids) = map_feature_ids(&counts, &id_map);
+        assert_eq!(mapped_counts.len(), 1);
+        assert_eq!(mapped_counts["AAAS"], 8);
+        assert!(unmapped_ids.is_empty());
+    }
+}
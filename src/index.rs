@@ -0,0 +1,142 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Read, Seek, Write},
+};
+
+use crate::bgzf;
+
+/// The extension appended to the path of an indexed output for its index.
+pub const INDEX_EXTENSION: &str = "fidx";
+
+/// A map of feature ID-virtual position pairs of the rows of a BGZF-compressed
+/// table.
+pub type Index = HashMap<String, u64>;
+
+/// Writes an index as TSV-formatted data.
+///
+/// There are two columns: the feature identifier (string) and the virtual
+/// position (integer) of its row in the BGZF-compressed table.
+pub fn write_index<W>(mut writer: W, entries: &[(String, u64)]) -> io::Result<()>
+where
+    W: Write,
+{
+    for (id, position) in entries {
+        writeln!(writer, "{}\t{}", id, position)?;
+    }
+
+    writer.flush()
+}
+
+/// Reads an index written by [`write_index`].
+///
+/// [`write_index`]: fn.write_index.html
+pub fn read_index<R>(reader: R) -> io::Result<Index>
+where
+    R: BufRead,
+{
+    let mut index = Index::new();
+
+    for result in reader.lines() {
+        let line = result?;
+
+        let mut fields = line.splitn(2, '\t');
+
+        let (id, position) = match (fields.next(), fields.next()) {
+            (Some(id), Some(position)) => (id, position),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid index record: {}", line),
+                ))
+            }
+        };
+
+        let position = position.parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid virtual position: {}", position),
+            )
+        })?;
+
+        index.insert(id.into(), position);
+    }
+
+    Ok(index)
+}
+
+/// Reads the row of a feature from an indexed, BGZF-compressed table.
+///
+/// The row is returned without its line ending. This returns `None` when the
+/// feature is not in the index.
+///
+/// # Example
+///
+/// ```
+/// use std::io::{Cursor, Write};
+///
+/// use noodles_fpkm::{bgzf, index::{query, Index}};
+///
+/// let mut writer = bgzf::Writer::new(Vec::new());
+/// writer.write_all(b"tpm\tsample1\n")?;
+/// let position = writer.virtual_position();
+/// writer.write_all(b"AAAS\t3.5\n")?;
+/// let data = writer.finish()?;
+///
+/// let mut index = Index::new();
+/// index.insert(String::from("AAAS"), position);
+///
+/// let mut reader = bgzf::Reader::new(Cursor::new(data));
+///
+/// assert_eq!(query(&mut reader, &index, "AAAS")?, Some(String::from("AAAS\t3.5")));
+/// assert!(query(&mut reader, &index, "RPL37AP1")?.is_none());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn query<R>(reader: &mut bgzf::Reader<R>, index: &Index, id: &str) -> io::Result<Option<String>>
+where
+    R: Read + Seek,
+{
+    let position = match index.get(id) {
+        Some(&position) => position,
+        None => return Ok(None),
+    };
+
+    reader.seek(position)?;
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    if line.ends_with('\n') {
+        line.pop();
+    }
+
+    Ok(Some(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_read_index() -> io::Result<()> {
+        let entries = [
+            (String::from("AAAS"), 12),
+            (String::from("RPL37AP1"), 327680),
+        ];
+
+        let mut buf = Vec::new();
+        write_index(&mut buf, &entries)?;
+        assert_eq!(buf, b"AAAS\t12\nRPL37AP1\t327680\n");
+
+        let index = read_index(&buf[..])?;
+        assert_eq!(index.len(), 2);
+        assert_eq!(index["RPL37AP1"], 327680);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_index_with_invalid_record() {
+        assert!(read_index(&b"AAAS\n"[..]).is_err());
+        assert!(read_index(&b"AAAS\tx\n"[..]).is_err());
+    }
+}
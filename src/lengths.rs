@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+
+use crate::{
+    abundances::TranscriptAbundances,
+    feature_id::FeatureId,
+    features::{flatten_intervals, merge_intervals_in_place, Feature, Features},
+    gtf::Aliases,
+    HashBuilder,
+};
+
+pub type Lengths = HashMap<FeatureId, u64>;
+
+/// A summary of the distribution of feature lengths.
+///
+/// Quantiles are by the nearest rank.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LengthDistribution {
+    /// The number of features.
+    pub count: usize,
+    /// The shortest length.
+    pub min: u64,
+    /// The first quartile.
+    pub q1: u64,
+    /// The median.
+    pub median: u64,
+    /// The third quartile.
+    pub q3: u64,
+    /// The longest length.
+    pub max: u64,
+    /// The mean length.
+    pub mean: f64,
+}
+
+/// Calculates the length of each feature.
+///
+/// The length of a feature is the sum of the lengths of its merged
+/// (nonoverlapping) intervals. Lengths are computed once here so that they can
+/// be reused across methods and samples.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     feature_id::FeatureId,
+///     features::{Feature, Features},
+///     lengths::calculate_lengths,
+/// };
+///
+/// let features: Features = [
+///     (FeatureId::from("AAAS"), vec![Feature::new(2, 5), Feature::new(3, 7)]),
+///     (FeatureId::from("RPL37AP1"), vec![Feature::new(9, 12)]),
+/// ].iter().cloned().collect();
+///
+/// let lengths = calculate_lengths(&features);
+///
+/// assert_eq!(lengths["AAAS"], 6);
+/// assert_eq!(lengths["RPL37AP1"], 4);
+/// ```
+pub fn calculate_lengths(features: &Features) -> Lengths {
+    let mut lengths = Lengths::with_capacity_and_hasher(features.len(), Default::default());
+    let mut buf = Vec::new();
+
+    for (name, intervals) in features {
+        let len = sum_nonoverlapping_interval_lengths(intervals, &mut buf);
+        lengths.insert(name.clone(), len);
+    }
+
+    lengths
+}
+
+/// Calculates the length of each exonic bin of each feature.
+///
+/// The intervals of each feature are split into disjoint bins (see
+/// [`flatten_intervals`]), which are keyed by the feature ID and the 1-based,
+/// zero-padded bin number, e.g., `AAAS:001`. This is the format of DEXSeq
+/// exon counts, so expression can be calculated per bin rather than per
+/// feature.
+///
+/// [`flatten_intervals`]: ../features/fn.flatten_intervals.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     feature_id::FeatureId,
+///     features::{Feature, Features},
+///     lengths::calculate_exon_bin_lengths,
+/// };
+///
+/// let features: Features = [
+///     (FeatureId::from("AAAS"), vec![Feature::new(2, 7), Feature::new(5, 9)]),
+/// ].iter().cloned().collect();
+///
+/// let lengths = calculate_exon_bin_lengths(&features);
+///
+/// assert_eq!(lengths.len(), 3);
+/// assert_eq!(lengths["AAAS:001"], 3);
+/// assert_eq!(lengths["AAAS:002"], 3);
+/// assert_eq!(lengths["AAAS:003"], 2);
+/// ```
+pub fn calculate_exon_bin_lengths(features: &Features) -> Lengths {
+    let mut lengths = Lengths::default();
+
+    for (name, intervals) in features {
+        for (i, bin) in flatten_intervals(intervals).iter().enumerate() {
+            let bin_name = format!("{}:{:03}", name, i + 1);
+            lengths.insert(bin_name.into(), bin.len());
+        }
+    }
+
+    lengths
+}
+
+/// Calculates the span length of each feature.
+///
+/// The span of a feature is from the least start to the greatest end of its
+/// intervals, e.g., the gene body (exons and introns) when the intervals are
+/// exons. This is for pre-mRNA quantification, e.g., of nascent or total RNA,
+/// where intronic reads are counted.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     feature_id::FeatureId,
+///     features::{Feature, Features},
+///     lengths::calculate_span_lengths,
+/// };
+///
+/// let features: Features = [
+///     (FeatureId::from("AAAS"), vec![Feature::new(2, 5), Feature::new(9, 12)]),
+/// ].iter().cloned().collect();
+///
+/// let lengths = calculate_span_lengths(&features);
+///
+/// assert_eq!(lengths["AAAS"], 11);
+/// ```
+pub fn calculate_span_lengths(features: &Features) -> Lengths {
+    features
+        .iter()
+        .filter_map(|(name, intervals)| {
+            let start = intervals.iter().map(|i| i.start).min()?;
+            let end = intervals.iter().map(|i| i.end).max()?;
+            Some((name.clone(), Feature::new(start, end).len()))
+        })
+        .collect()
+}
+
+/// Calculates the span length of each feature, extended by flanks.
+///
+/// This is the span length (see [`calculate_span_lengths`]) plus the lengths
+/// of an upstream and a downstream flank, e.g., a promoter region, for
+/// region-based counts such as ATAC-seq or ChIP-seq reads over gene bodies.
+/// The sum does not depend on the strand of the feature. Flanks are not
+/// clipped at the ends of the reference sequence.
+///
+/// [`calculate_span_lengths`]: fn.calculate_span_lengths.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     feature_id::FeatureId,
+///     features::{Feature, Features},
+///     lengths::calculate_flanked_span_lengths,
+/// };
+///
+/// let features: Features = [
+///     (FeatureId::from("AAAS"), vec![Feature::new(2, 5), Feature::new(9, 12)]),
+/// ].iter().cloned().collect();
+///
+/// let lengths = calculate_flanked_span_lengths(&features, 2000, 500);
+///
+/// assert_eq!(lengths["AAAS"], 2511);
+/// ```
+pub fn calculate_flanked_span_lengths(
+    features: &Features,
+    upstream: u64,
+    downstream: u64,
+) -> Lengths {
+    calculate_span_lengths(features)
+        .into_iter()
+        .map(|(name, len)| (name, len + upstream + downstream))
+        .collect()
+}
+
+/// Assigns a length of 1 to each feature.
+///
+/// This is for protocols where length normalization does not apply, e.g., 3'
+/// tag sequencing (QuantSeq), where each transcript yields reads from only its
+/// 3' end regardless of its length. Using these lengths, TPM is counts per
+/// million (CPM), and the annotations are still used to validate feature IDs.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     feature_id::FeatureId,
+///     features::{Feature, Features},
+///     lengths::calculate_unit_lengths,
+/// };
+///
+/// let features: Features = [
+///     (FeatureId::from("AAAS"), vec![Feature::new(2, 5), Feature::new(3, 7)]),
+/// ].iter().cloned().collect();
+///
+/// let lengths = calculate_unit_lengths(&features);
+///
+/// assert_eq!(lengths["AAAS"], 1);
+/// ```
+pub fn calculate_unit_lengths(features: &Features) -> Lengths {
+    features.keys().map(|name| (name.clone(), 1)).collect()
+}
+
+/// Calculates the effective length of each gene as the abundance-weighted
+/// mean of the effective lengths of its transcripts.
+///
+/// `transcript_genes` maps transcript IDs to gene IDs, e.g., as read by
+/// [`read_aliases`] with the `transcript_id` alias key. This is the average
+/// transcript length of tximport: unlike the length of the union of a gene's
+/// exons, it reflects which isoforms are expressed in the sample. Genes whose
+/// transcripts have no abundance use the unweighted mean. Lengths are rounded
+/// to the nearest integer and are at least 1.
+///
+/// Transcripts that are not in `transcript_genes` are ignored.
+///
+/// [`read_aliases`]: ../gtf/fn.read_aliases.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     abundances::{TranscriptAbundance, TranscriptAbundances},
+///     feature_id::FeatureId,
+///     gtf::Aliases,
+///     lengths::calculate_gene_effective_lengths,
+/// };
+///
+/// let abundances: TranscriptAbundances = [
+///     (FeatureId::from("t1"), TranscriptAbundance { effective_length: 1000.0, tpm: 3.0 }),
+///     (FeatureId::from("t2"), TranscriptAbundance { effective_length: 200.0, tpm: 1.0 }),
+///     (FeatureId::from("t3"), TranscriptAbundance { effective_length: 500.0, tpm: 0.0 }),
+/// ].iter().cloned().collect();
+///
+/// let transcript_genes: Aliases = [
+///     (FeatureId::from("t1"), String::from("g1")),
+///     (FeatureId::from("t2"), String::from("g1")),
+///     (FeatureId::from("t3"), String::from("g2")),
+/// ].iter().cloned().collect();
+///
+/// let lengths = calculate_gene_effective_lengths(&abundances, &transcript_genes);
+///
+/// assert_eq!(lengths["g1"], 800);
+/// assert_eq!(lengths["g2"], 500);
+/// ```
+pub fn calculate_gene_effective_lengths(
+    abundances: &TranscriptAbundances,
+    transcript_genes: &Aliases,
+) -> Lengths {
+    // Transcripts are visited in order of ID so the sums do not depend on the
+    // iteration order of the map.
+    let mut transcript_ids: Vec<_> = abundances.keys().collect();
+    transcript_ids.sort_unstable();
+
+    let mut sums: HashMap<&str, (f64, f64, f64, u64), HashBuilder> = HashMap::default();
+
+    for id in transcript_ids {
+        let gene_id = match transcript_genes.get(id) {
+            Some(gene_id) => gene_id,
+            None => continue,
+        };
+
+        let abundance = &abundances[id];
+        let sum = sums.entry(gene_id).or_insert((0.0, 0.0, 0.0, 0));
+
+        sum.0 += abundance.tpm * abundance.effective_length;
+        sum.1 += abundance.tpm;
+        sum.2 += abundance.effective_length;
+        sum.3 += 1;
+    }
+
+    sums.into_iter()
+        .map(|(gene_id, (weighted_sum, tpm_sum, sum, n))| {
+            let len = if tpm_sum > 0.0 {
+                weighted_sum / tpm_sum
+            } else {
+                sum / n as f64
+            };
+
+            (gene_id.into(), (len.round() as u64).max(1))
+        })
+        .collect()
+}
+
+/// Summarizes the distribution of lengths.
+///
+/// This is useful to check that `--type` and `--id` select the intended
+/// features, e.g., the median length of genes summed over exons is usually a
+/// few kilobases, but it is much longer when the intervals are gene spans.
+///
+/// This returns `None` when there are no lengths.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::lengths::summarize_lengths;
+///
+/// let distribution = summarize_lengths(vec![100, 4, 30, 20, 8]).unwrap();
+///
+/// assert_eq!(distribution.count, 5);
+/// assert_eq!(distribution.min, 4);
+/// assert_eq!(distribution.q1, 8);
+/// assert_eq!(distribution.median, 20);
+/// assert_eq!(distribution.q3, 30);
+/// assert_eq!(distribution.max, 100);
+/// assert_eq!(distribution.mean, 32.4);
+/// ```
+pub fn summarize_lengths<I>(lengths: I) -> Option<LengthDistribution>
+where
+    I: IntoIterator<Item = u64>,
+{
+    let mut lengths: Vec<u64> = lengths.into_iter().collect();
+
+    if lengths.is_empty() {
+        return None;
+    }
+
+    lengths.sort_unstable();
+
+    let quantile = |q| nearest_rank(&lengths, q);
+
+    let sum: u64 = lengths.iter().sum();
+
+    Some(LengthDistribution {
+        count: lengths.len(),
+        min: lengths[0],
+        q1: quantile(0.25),
+        median: quantile(0.5),
+        q3: quantile(0.75),
+        max: lengths[lengths.len() - 1],
+        mean: sum as f64 / lengths.len() as f64,
+    })
+}
+
+/// Returns the length at a quantile of the lengths.
+///
+/// The quantile uses the nearest-rank method, as in [`summarize_lengths`], so
+/// it is always one of the lengths. This returns `None` when there are no
+/// lengths.
+///
+/// [`summarize_lengths`]: fn.summarize_lengths.html
+///
+/// # Panics
+///
+/// Panics if `q` is not in [0, 1].
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{feature_id::FeatureId, lengths::{length_quantile, Lengths}};
+///
+/// let lengths: Lengths = [
+///     (FeatureId::from("AAAS"), 2541),
+///     (FeatureId::from("AC009952.3"), 1087),
+///     (FeatureId::from("RPL37AP1"), 4275189),
+/// ].iter().cloned().collect();
+///
+/// assert_eq!(length_quantile(&lengths, 0.5), Some(2541));
+/// ```
+pub fn length_quantile(lengths: &Lengths, q: f64) -> Option<u64> {
+    assert!((0.0..=1.0).contains(&q), "invalid quantile: {}", q);
+
+    let mut values: Vec<u64> = lengths.values().copied().collect();
+
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_unstable();
+
+    Some(nearest_rank(&values, q))
+}
+
+/// Caps lengths at a maximum length.
+///
+/// This limits the effect of pathological records on the values of their
+/// features, e.g., multi-megabase readthrough or fusion "genes", whose long
+/// lengths otherwise make their values near 0. The capped features are
+/// returned with their original lengths, sorted by ID.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{feature_id::FeatureId, lengths::{cap_lengths, Lengths}};
+///
+/// let mut lengths: Lengths = [
+///     (FeatureId::from("AAAS"), 2541),
+///     (FeatureId::from("RPL37AP1"), 4275189),
+/// ].iter().cloned().collect();
+///
+/// let capped = cap_lengths(&mut lengths, 100000);
+///
+/// assert_eq!(capped, [(FeatureId::from("RPL37AP1"), 4275189)]);
+/// assert_eq!(lengths["AAAS"], 2541);
+/// assert_eq!(lengths["RPL37AP1"], 100000);
+/// ```
+pub fn cap_lengths(lengths: &mut Lengths, max_length: u64) -> Vec<(FeatureId, u64)> {
+    let mut capped = Vec::new();
+
+    for (id, len) in lengths.iter_mut() {
+        if *len > max_length {
+            capped.push((id.clone(), *len));
+            *len = max_length;
+        }
+    }
+
+    capped.sort_unstable();
+
+    capped
+}
+
+// `values` must be sorted and nonempty.
+fn nearest_rank(values: &[u64], q: f64) -> u64 {
+    let rank = (q * values.len() as f64).ceil() as usize;
+    values[rank.max(1) - 1]
+}
+
+// `buf` is scratch space for the merged intervals, so that it can be reused
+// across features.
+fn sum_nonoverlapping_interval_lengths(intervals: &[Feature], buf: &mut Vec<Feature>) -> u64 {
+    buf.clear();
+    buf.extend_from_slice(intervals);
+    merge_intervals_in_place(buf);
+    buf.iter().map(|i| i.len()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_lengths() {
+        assert!(summarize_lengths(Vec::new()).is_none());
+
+        let distribution = summarize_lengths(vec![7]).unwrap();
+        assert_eq!(distribution.q1, 7);
+        assert_eq!(distribution.median, 7);
+        assert_eq!(distribution.q3, 7);
+    }
+
+    #[test]
+    fn test_cap_lengths() {
+        let mut lengths: Lengths = [
+            (FeatureId::from("AAAS"), 2541),
+            (FeatureId::from("AC009952.3"), 1087),
+            (FeatureId::from("RPL37AP1"), 4275189),
+            (FeatureId::from("XIST"), 32000),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert_eq!(length_quantile(&lengths, 0.75), Some(32000));
+        assert_eq!(length_quantile(&lengths, 1.0), Some(4275189));
+        assert!(length_quantile(&Lengths::default(), 0.5).is_none());
+
+        let capped = cap_lengths(&mut lengths, 2541);
+
+        assert_eq!(
+            capped,
+            [
+                (FeatureId::from("RPL37AP1"), 4275189),
+                (FeatureId::from("XIST"), 32000),
+            ]
+        );
+
+        assert_eq!(lengths["AC009952.3"], 1087);
+        assert_eq!(lengths["XIST"], 2541);
+    }
+
+    #[test]
+    fn test_sum_nonoverlapping_interval_lengths() {
+        let features = [
+            Feature::new(2, 5),
+            Feature::new(3, 4),
+            Feature::new(5, 7),
+            Feature::new(9, 12),
+            Feature::new(10, 15),
+            Feature::new(16, 21),
+        ];
+
+        let len = sum_nonoverlapping_interval_lengths(&features, &mut Vec::new());
+        assert_eq!(len, 19);
+    }
+}
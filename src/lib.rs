@@ -1,73 +1,458 @@
+pub mod abundances;
+pub mod annotation_diff;
+pub mod bgzf;
+pub mod checksum;
+pub mod chunked;
+mod compression;
 pub mod counts;
+pub mod cqn;
+pub mod downsampling;
+#[cfg(feature = "cli")]
+pub mod error_report;
+pub mod expression_set;
+pub mod feature_id;
+pub mod feature_index;
+pub mod feature_sources;
 pub mod features;
-
-use std::collections::{BTreeMap, HashMap};
+pub mod fold_change;
+pub mod fragment_lengths;
+pub mod gene_metadata;
+pub mod group_by;
+pub mod groups;
+pub mod gtf;
+pub mod ids;
+pub mod index;
+pub mod lengths;
+pub mod matrix;
+pub mod method_comparison;
+pub mod mtx;
+pub mod normalization;
+pub mod output_format;
+pub mod prelude;
+pub mod pseudobulk;
+pub mod qc;
+pub mod quantile_mapping;
+pub mod regression;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "cli")]
+pub mod resources;
+pub mod sample_sheet;
+pub mod sequence;
+#[cfg(feature = "cli")]
+pub mod server;
+pub mod signatures;
+pub mod simulation;
+pub mod size_factors;
+pub mod sparse;
+pub mod spike_in_screen;
+pub mod spike_ins;
+pub mod stability;
+pub mod summation;
+pub mod top;
+pub mod transform;
+pub mod unit;
+pub mod validation;
+#[cfg(feature = "cli")]
+pub mod watch;
+#[cfg(feature = "cli")]
+pub mod wildcard;
+
+use std::collections::BTreeMap;
 
 use self::{
     counts::{sum_counts, Counts},
-    features::{merge_intervals, Feature, Features},
+    expression_set::Method,
+    feature_id::FeatureId,
+    lengths::Lengths,
+    sparse::SparseExpressionMatrix,
+    summation::Summation,
 };
 
 #[derive(Debug)]
 pub enum Error {
-    MissingFeature(String),
+    /// A feature is not in the lengths.
+    MissingFeature(FeatureId),
+    /// A feature has a length of 0.
+    ZeroLengthFeature(FeatureId),
+    /// A sum that values are normalized by is 0, e.g., a sample with no counts.
+    ZeroDenominator,
+    /// Expression values calculated by different methods are combined, e.g.,
+    /// FPKM and TPM.
+    MixedMethods(Method, Method),
+    /// A method cannot be calculated from the given inputs, e.g., scaled
+    /// counts without size factors.
+    UnsupportedMethod(Method),
 }
 
-pub type Expressions = BTreeMap<String, f64>;
+pub type Expressions = BTreeMap<FeatureId, f64>;
+
+// The hash builder of maps internal to this crate. This is the standard
+// library's `RandomState` unless the `fast-hash` feature is enabled, in which
+// case the faster, non-cryptographic FxHash is used. Public maps always use
+// `RandomState`, so enabling the feature does not change the API.
+#[cfg(not(feature = "fast-hash"))]
+pub(crate) type HashBuilder = std::collections::hash_map::RandomState;
+#[cfg(feature = "fast-hash")]
+pub(crate) type HashBuilder = std::hash::BuildHasherDefault<rustc_hash::FxHasher>;
 
-pub fn calculate_fpkms(counts: &Counts, features: &Features) -> Result<Expressions, Error> {
+pub fn calculate_fpkms(counts: &Counts, lengths: &Lengths) -> Result<Expressions, Error> {
     let counts_sum = sum_counts(counts);
 
-    counts
-        .iter()
-        .map(|(name, &count)| {
-            features
-                .get(name)
-                .map(|intervals| {
-                    let len = sum_nonoverlapping_interval_lengths(intervals);
-                    let fpkm = calculate_fpkm(count, len, counts_sum);
-                    (name.clone(), fpkm)
-                })
-                .ok_or_else(|| Error::MissingFeature(name.clone()))
+    sorted_counts(counts)
+        .into_iter()
+        .map(|(name, count)| {
+            let len = get_length(lengths, name)?;
+            let fpkm = calculate_fpkm(count, len, counts_sum)?;
+            Ok((name.clone(), fpkm))
         })
         .collect()
 }
 
-fn sum_nonoverlapping_interval_lengths(intervals: &[Feature]) -> u64 {
-    merge_intervals(intervals).iter().map(|i| i.len()).sum()
+fn calculate_fpkm(count: u64, len: u64, counts_sum: u64) -> Result<f64, Error> {
+    if counts_sum == 0 {
+        return Err(Error::ZeroDenominator);
+    }
+
+    Ok((count as f64 * 1e9) / (len as f64 * counts_sum as f64))
+}
+
+pub fn calculate_tpms(counts: &Counts, lengths: &Lengths) -> Result<Expressions, Error> {
+    calculate_tpms_with_summation(counts, lengths, Summation::default())
 }
 
-fn calculate_fpkm(count: u64, len: u64, counts_sum: u64) -> f64 {
-    (count as f64 * 1e9) / (len as f64 * counts_sum as f64)
+/// Calculates TPMs, summing the counts per base with the given summation
+/// algorithm.
+///
+/// The counts per base are summed in order of feature ID.
+pub fn calculate_tpms_with_summation(
+    counts: &Counts,
+    lengths: &Lengths,
+    summation: Summation,
+) -> Result<Expressions, Error> {
+    let cpbs: Vec<(FeatureId, f64)> = sorted_counts(counts)
+        .into_iter()
+        .map(|(name, count)| {
+            let len = get_length(lengths, name)?;
+            let cpb = count as f64 / len as f64;
+            Ok((name.clone(), cpb))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let cpbs_sum = summation.sum(cpbs.iter().map(|(_, cpb)| *cpb));
+
+    cpbs.into_iter()
+        .map(|(name, cpb)| Ok((name, calculate_tpm(cpb, cpbs_sum)?)))
+        .collect()
 }
 
-pub fn calculate_tpms(counts: &Counts, features: &Features) -> Result<Expressions, Error> {
-    let cpbs: HashMap<String, f64> = counts
+fn calculate_tpm(cpb: f64, cpbs_sum: f64) -> Result<f64, Error> {
+    if cpbs_sum == 0.0 {
+        return Err(Error::ZeroDenominator);
+    }
+
+    Ok(cpb * 1e6 / cpbs_sum)
+}
+
+// Returns the length of a feature, which must be positive.
+fn get_length(lengths: &Lengths, name: &FeatureId) -> Result<u64, Error> {
+    match lengths.get(name) {
+        Some(0) => Err(Error::ZeroLengthFeature(name.clone())),
+        Some(&len) => Ok(len),
+        None => Err(Error::MissingFeature(name.clone())),
+    }
+}
+
+// Returns the counts sorted by feature ID.
+//
+// The iteration order of a hash map is arbitrary and differs across runs, so
+// this order is used wherever the order affects the result, e.g., the
+// accumulation order of a floating-point sum or which missing feature is
+// reported.
+fn sorted_counts(counts: &Counts) -> Vec<(&FeatureId, u64)> {
+    let mut entries: Vec<_> = counts.iter().map(|(name, &count)| (name, count)).collect();
+    entries.sort_unstable_by_key(|(name, _)| *name);
+    entries
+}
+
+/// Calculates the FPKM of antisense counts on the scale of the sense counts.
+///
+/// The antisense counts are normalized by the total of the sense counts
+/// rather than their own, so the antisense and sense values of a feature can
+/// be compared directly, e.g., as a QC metric for stranded library
+/// preparation.
+pub fn calculate_antisense_fpkms(
+    sense_counts: &Counts,
+    antisense_counts: &Counts,
+    lengths: &Lengths,
+) -> Result<Expressions, Error> {
+    let counts_sum = sum_counts(sense_counts);
+
+    sorted_counts(antisense_counts)
+        .into_iter()
+        .map(|(name, count)| {
+            let len = get_length(lengths, name)?;
+            Ok((name.clone(), calculate_fpkm(count, len, counts_sum)?))
+        })
+        .collect()
+}
+
+/// Calculates the TPM of antisense counts on the scale of the sense counts.
+///
+/// This is the TPM analog of [`calculate_antisense_fpkms`]: the antisense
+/// counts per base are normalized by the sum of the sense counts per base.
+///
+/// [`calculate_antisense_fpkms`]: fn.calculate_antisense_fpkms.html
+pub fn calculate_antisense_tpms(
+    sense_counts: &Counts,
+    antisense_counts: &Counts,
+    lengths: &Lengths,
+) -> Result<Expressions, Error> {
+    calculate_antisense_tpms_with_summation(
+        sense_counts,
+        antisense_counts,
+        lengths,
+        Summation::default(),
+    )
+}
+
+/// Calculates the TPM of antisense counts on the scale of the sense counts,
+/// summing the sense counts per base with the given summation algorithm.
+pub fn calculate_antisense_tpms_with_summation(
+    sense_counts: &Counts,
+    antisense_counts: &Counts,
+    lengths: &Lengths,
+    summation: Summation,
+) -> Result<Expressions, Error> {
+    let sense_cpbs = sorted_counts(sense_counts)
+        .into_iter()
+        .map(|(name, count)| get_length(lengths, name).map(|len| count as f64 / len as f64))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let cpbs_sum = summation.sum(sense_cpbs);
+
+    sorted_counts(antisense_counts)
+        .into_iter()
+        .map(|(name, count)| {
+            let len = get_length(lengths, name)?;
+            let cpb = count as f64 / len as f64;
+            Ok((name.clone(), calculate_tpm(cpb, cpbs_sum)?))
+        })
+        .collect()
+}
+
+/// Calculates the expected count of each feature from its FPKM.
+///
+/// This is the inverse of [`calculate_fpkms`], given the library size, i.e.,
+/// the total number of counts. The expected counts are not rounded, e.g., for
+/// use as the mean of a distribution when simulating counts.
+///
+/// [`calculate_fpkms`]: fn.calculate_fpkms.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     calculate_expected_counts_from_fpkms, feature_id::FeatureId, lengths::Lengths, Expressions,
+/// };
+///
+/// let fpkms: Expressions = [(FeatureId::from("AAAS"), 250.0)].iter().cloned().collect();
+/// let lengths: Lengths = [(FeatureId::from("AAAS"), 2000)].iter().cloned().collect();
+///
+/// let counts = calculate_expected_counts_from_fpkms(&fpkms, &lengths, 1000000).unwrap();
+///
+/// assert_eq!(counts["AAAS"], 500.0);
+/// ```
+pub fn calculate_expected_counts_from_fpkms(
+    fpkms: &Expressions,
+    lengths: &Lengths,
+    library_size: u64,
+) -> Result<Expressions, Error> {
+    fpkms
         .iter()
-        .map(|(name, &count)| {
-            features
+        .map(|(name, &fpkm)| {
+            lengths
                 .get(name)
-                .map(|intervals| {
-                    let len = sum_nonoverlapping_interval_lengths(intervals);
-                    let cpb = count as f64 / len as f64;
-                    (name.clone(), cpb)
+                .map(|&len| {
+                    let count = fpkm * len as f64 * library_size as f64 / 1e9;
+                    (name.clone(), count)
                 })
                 .ok_or_else(|| Error::MissingFeature(name.clone()))
         })
+        .collect()
+}
+
+/// Calculates the expected count of each feature from its TPM.
+///
+/// This is the inverse of [`calculate_tpms`], given the library size, i.e.,
+/// the total number of counts. The library size is distributed over the
+/// features in proportion to their TPMs times their lengths.
+///
+/// [`calculate_tpms`]: fn.calculate_tpms.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     calculate_expected_counts_from_tpms, feature_id::FeatureId, lengths::Lengths, Expressions,
+/// };
+///
+/// let tpms: Expressions = [(FeatureId::from("AAAS"), 500000.0), (FeatureId::from("RPL37AP1"), 500000.0)]
+///     .iter()
+///     .cloned()
+///     .collect();
+/// let lengths: Lengths = [(FeatureId::from("AAAS"), 1000), (FeatureId::from("RPL37AP1"), 3000)]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// let counts = calculate_expected_counts_from_tpms(&tpms, &lengths, 400).unwrap();
+///
+/// assert_eq!(counts["AAAS"], 100.0);
+/// assert_eq!(counts["RPL37AP1"], 300.0);
+/// ```
+pub fn calculate_expected_counts_from_tpms(
+    tpms: &Expressions,
+    lengths: &Lengths,
+    library_size: u64,
+) -> Result<Expressions, Error> {
+    let weights: Vec<(FeatureId, f64)> = tpms
+        .iter()
+        .map(|(name, &tpm)| {
+            lengths
+                .get(name)
+                .map(|&len| (name.clone(), tpm * len as f64))
+                .ok_or_else(|| Error::MissingFeature(name.clone()))
+        })
         .collect::<Result<_, _>>()?;
 
-    let cpbs_sum = cpbs.values().sum();
+    let weights_sum: f64 = weights.iter().map(|(_, weight)| weight).sum();
 
-    let tpms = cpbs
-        .iter()
-        .map(|(name, &cpb)| (name.clone(), calculate_tpm(cpb, cpbs_sum)))
+    if weights_sum == 0.0 {
+        return Err(Error::ZeroDenominator);
+    }
+
+    let counts = weights
+        .into_iter()
+        .map(|(name, weight)| (name, weight * library_size as f64 / weights_sum))
         .collect();
 
+    Ok(counts)
+}
+
+/// Converts FPKMs to TPMs.
+///
+/// The TPM of a feature is its FPKM over the sum of all FPKMs, scaled to 1e6.
+/// This is, e.g., for expression values from other tools, where the counts are
+/// not available.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{calculate_tpms_from_fpkms, feature_id::FeatureId, Expressions};
+///
+/// let fpkms: Expressions = [(FeatureId::from("AAAS"), 1.0), (FeatureId::from("RPL37AP1"), 3.0)]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// let tpms = calculate_tpms_from_fpkms(&fpkms).unwrap();
+///
+/// assert_eq!(tpms["AAAS"], 250000.0);
+/// assert_eq!(tpms["RPL37AP1"], 750000.0);
+/// ```
+pub fn calculate_tpms_from_fpkms(fpkms: &Expressions) -> Result<Expressions, Error> {
+    calculate_tpms_from_fpkms_with_summation(fpkms, Summation::default())
+}
+
+/// Converts FPKMs to TPMs, summing the FPKMs with the given summation
+/// algorithm.
+pub fn calculate_tpms_from_fpkms_with_summation(
+    fpkms: &Expressions,
+    summation: Summation,
+) -> Result<Expressions, Error> {
+    let fpkms_sum = summation.sum(fpkms.values().copied());
+
+    fpkms
+        .iter()
+        .map(|(name, &fpkm)| Ok((name.clone(), calculate_tpm(fpkm, fpkms_sum)?)))
+        .collect()
+}
+
+/// Calculates the FPKM of each feature in each column of a sparse count matrix.
+///
+/// Each column is normalized independently, e.g., per cell. The rows must be
+/// feature IDs present in `lengths`. Columns with no counts are left
+/// unchanged.
+pub fn calculate_sparse_fpkms(
+    counts: &SparseExpressionMatrix,
+    lengths: &Lengths,
+) -> Result<SparseExpressionMatrix, Error> {
+    let row_lengths = build_row_lengths(counts, lengths)?;
+    let mut fpkms = counts.clone();
+
+    for j in 0..fpkms.shape().1 {
+        let (rows, values) = fpkms.column_mut(j);
+        let counts_sum: f64 = values.iter().sum();
+
+        if counts_sum == 0.0 {
+            continue;
+        }
+
+        for (&i, value) in rows.iter().zip(values.iter_mut()) {
+            *value = (*value * 1e9) / (row_lengths[i] as f64 * counts_sum);
+        }
+    }
+
+    Ok(fpkms)
+}
+
+/// Calculates the TPM of each feature in each column of a sparse count matrix.
+///
+/// Each column is normalized independently, e.g., per cell. The rows must be
+/// feature IDs present in `lengths`. Columns with no counts are left
+/// unchanged.
+pub fn calculate_sparse_tpms(
+    counts: &SparseExpressionMatrix,
+    lengths: &Lengths,
+) -> Result<SparseExpressionMatrix, Error> {
+    let row_lengths = build_row_lengths(counts, lengths)?;
+    let mut tpms = counts.clone();
+
+    for j in 0..tpms.shape().1 {
+        let (rows, values) = tpms.column_mut(j);
+
+        for (&i, value) in rows.iter().zip(values.iter_mut()) {
+            *value /= row_lengths[i] as f64;
+        }
+
+        let cpbs_sum: f64 = values.iter().sum();
+
+        if cpbs_sum == 0.0 {
+            continue;
+        }
+
+        for value in values.iter_mut() {
+            *value = calculate_tpm(*value, cpbs_sum)?;
+        }
+    }
+
     Ok(tpms)
 }
 
-fn calculate_tpm(cpb: f64, cpbs_sum: f64) -> f64 {
-    cpb * 1e6 / cpbs_sum
+fn build_row_lengths(
+    matrix: &SparseExpressionMatrix,
+    lengths: &Lengths,
+) -> Result<Vec<u64>, Error> {
+    matrix
+        .row_names()
+        .iter()
+        .map(|name| match lengths.get(name.as_str()) {
+            Some(0) => Err(Error::ZeroLengthFeature(name.as_str().into())),
+            Some(&len) => Ok(len),
+            None => Err(Error::MissingFeature(name.as_str().into())),
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -77,15 +462,16 @@ mod tests {
     use crate::{
         counts::Counts,
         features::{Feature, Features},
+        lengths::{calculate_lengths, Lengths},
     };
 
     use super::*;
 
     fn build_counts() -> Counts {
         let counts = [
-            (String::from("AAAS"), 645),
-            (String::from("AC009952.3"), 1),
-            (String::from("RPL37AP1"), 5714),
+            (FeatureId::from("AAAS"), 645),
+            (FeatureId::from("AC009952.3"), 1),
+            (FeatureId::from("RPL37AP1"), 5714),
         ];
 
         counts.iter().cloned().collect()
@@ -93,13 +479,16 @@ mod tests {
 
     fn build_features() -> Features {
         let features = [
-            (String::from("AAAS"), vec![Feature::new(53307456, 53324864)]),
             (
-                String::from("AC009952.3"),
+                FeatureId::from("AAAS"),
+                vec![Feature::new(53307456, 53324864)],
+            ),
+            (
+                FeatureId::from("AC009952.3"),
                 vec![Feature::new(9189629, 9204611)],
             ),
             (
-                String::from("RPL37AP1"),
+                FeatureId::from("RPL37AP1"),
                 vec![Feature::new(44466564, 44466842)],
             ),
         ];
@@ -107,12 +496,16 @@ mod tests {
         features.into_iter().cloned().collect()
     }
 
+    fn build_lengths() -> Lengths {
+        calculate_lengths(&build_features())
+    }
+
     #[test]
     fn test_calculate_fpkms() {
         let counts = build_counts();
-        let features = build_features();
+        let lengths = build_lengths();
 
-        let fpkms = calculate_fpkms(&counts, &features).unwrap();
+        let fpkms = calculate_fpkms(&counts, &lengths).unwrap();
 
         assert_eq!(fpkms.len(), 3);
 
@@ -132,10 +525,10 @@ mod tests {
     #[test]
     fn test_calculate_fpkms_is_ordered_by_feature_id() {
         let counts = build_counts();
-        let features = build_features();
+        let lengths = build_lengths();
 
-        let fpkms = calculate_fpkms(&counts, &features).unwrap();
-        let mut ids = fpkms.keys();
+        let fpkms = calculate_fpkms(&counts, &lengths).unwrap();
+        let mut ids = fpkms.keys().map(|id| &**id);
 
         assert_eq!(ids.next().unwrap(), "AAAS");
         assert_eq!(ids.next().unwrap(), "AC009952.3");
@@ -143,50 +536,275 @@ mod tests {
         assert!(ids.next().is_none());
     }
 
+    #[test]
+    fn test_calculate_fpkms_shares_keys_with_counts() {
+        let counts = build_counts();
+        let lengths = build_lengths();
+
+        let fpkms = calculate_fpkms(&counts, &lengths).unwrap();
+
+        for name in fpkms.keys() {
+            let (key, _) = counts.get_key_value(name).unwrap();
+            assert!(FeatureId::ptr_eq(key, name));
+        }
+    }
+
     #[test]
     fn test_calculate_fpkms_with_missing_feature() {
         let counts = build_counts();
 
-        let mut features = build_features();
-        features.remove("AC009952.3");
+        let mut lengths = build_lengths();
+        lengths.remove("AC009952.3");
 
-        assert!(calculate_fpkms(&counts, &features).is_err());
+        assert!(calculate_fpkms(&counts, &lengths).is_err());
     }
 
     #[test]
-    fn test_sum_nonoverlapping_interval_lengths() {
-        let features = [
-            Feature::new(2, 5),
-            Feature::new(3, 4),
-            Feature::new(5, 7),
-            Feature::new(9, 12),
-            Feature::new(10, 15),
-            Feature::new(16, 21),
-        ];
+    fn test_calculate_fpkms_with_missing_features_reports_first_feature_id() {
+        let counts = build_counts();
+        let lengths = Lengths::default();
+
+        match calculate_fpkms(&counts, &lengths) {
+            Err(Error::MissingFeature(name)) => assert_eq!(&*name, "AAAS"),
+            _ => panic!("expected missing feature"),
+        }
+    }
+
+    #[test]
+    fn test_calculate_tpms_with_summation() {
+        let counts = build_counts();
+        let lengths = build_lengths();
+
+        let tpms = calculate_tpms(&counts, &lengths).unwrap();
+        let compensated_tpms =
+            calculate_tpms_with_summation(&counts, &lengths, Summation::Compensated).unwrap();
+
+        for (a, b) in tpms.values().zip(compensated_tpms.values()) {
+            assert!((a - b).abs() <= 4.0 * EPSILON * a);
+        }
+    }
+
+    #[test]
+    fn test_calculate_tpms_is_independent_of_insertion_order() {
+        let entries: Vec<(FeatureId, u64)> = (0..1000)
+            .map(|i| (format!("gene{}", i).into(), i * 7 + 1))
+            .collect();
+
+        let lengths: Lengths = entries
+            .iter()
+            .map(|(name, i)| (name.clone(), 100 + i % 997))
+            .collect();
+
+        let counts: Counts = entries.iter().cloned().collect();
+        let reversed_counts: Counts = entries.iter().rev().cloned().collect();
+
+        let tpms = calculate_tpms(&counts, &lengths).unwrap();
+        let reversed_tpms = calculate_tpms(&reversed_counts, &lengths).unwrap();
+
+        for (a, b) in tpms.values().zip(reversed_tpms.values()) {
+            assert_eq!(a.to_bits(), b.to_bits());
+        }
+    }
+
+    fn build_sparse_counts() -> SparseExpressionMatrix {
+        SparseExpressionMatrix::from_triplets(
+            vec![
+                String::from("AAAS"),
+                String::from("AC009952.3"),
+                String::from("RPL37AP1"),
+            ],
+            vec![String::from("sample1"), String::from("sample2")],
+            vec![(0, 0, 645.0), (1, 0, 1.0), (2, 0, 5714.0), (2, 1, 3.0)],
+        )
+    }
+
+    #[test]
+    fn test_calculate_sparse_fpkms() {
+        let counts = build_sparse_counts();
+        let lengths = build_lengths();
+
+        let fpkms = calculate_sparse_fpkms(&counts, &lengths).unwrap();
+        let expected = calculate_fpkms(&build_counts(), &lengths).unwrap();
+
+        for (i, name) in fpkms.row_names().iter().enumerate() {
+            assert!((fpkms.get(i, 0) - expected[name.as_str()]).abs() < EPSILON);
+        }
+
+        assert_eq!(fpkms.column(1).0, [2]);
+    }
+
+    #[test]
+    fn test_calculate_sparse_tpms() {
+        let counts = build_sparse_counts();
+        let lengths = build_lengths();
+
+        let tpms = calculate_sparse_tpms(&counts, &lengths).unwrap();
+        let expected = calculate_tpms(&build_counts(), &lengths).unwrap();
+
+        for (i, name) in tpms.row_names().iter().enumerate() {
+            assert!((tpms.get(i, 0) - expected[name.as_str()]).abs() < 1e-6);
+        }
+
+        assert_eq!(tpms.column(1), (&[2][..], &[1e6][..]));
+    }
+
+    #[test]
+    fn test_calculate_sparse_fpkms_with_missing_feature() {
+        let counts = build_sparse_counts();
+
+        let mut lengths = build_lengths();
+        lengths.remove("AC009952.3");
+
+        assert!(calculate_sparse_fpkms(&counts, &lengths).is_err());
+    }
+
+    #[test]
+    fn test_calculate_antisense_fpkms() {
+        let counts = build_counts();
+        let lengths = build_lengths();
+
+        let fpkms = calculate_fpkms(&counts, &lengths).unwrap();
+        let antisense_fpkms = calculate_antisense_fpkms(&counts, &counts, &lengths).unwrap();
+        assert_eq!(antisense_fpkms, fpkms);
 
-        let len = sum_nonoverlapping_interval_lengths(&features);
-        assert_eq!(len, 19);
+        let antisense_counts = [(FeatureId::from("AAAS"), 6450)].iter().cloned().collect();
+        let antisense_fpkms =
+            calculate_antisense_fpkms(&counts, &antisense_counts, &lengths).unwrap();
+        assert_eq!(antisense_fpkms.len(), 1);
+        assert!((antisense_fpkms["AAAS"] - fpkms["AAAS"] * 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_calculate_antisense_tpms() {
+        let counts = build_counts();
+        let lengths = build_lengths();
+
+        let tpms = calculate_tpms(&counts, &lengths).unwrap();
+
+        let antisense_counts = [(FeatureId::from("AAAS"), 6450)].iter().cloned().collect();
+        let antisense_tpms =
+            calculate_antisense_tpms(&counts, &antisense_counts, &lengths).unwrap();
+        assert!((antisense_tpms["AAAS"] - tpms["AAAS"] * 10.0).abs() < 1e-6);
+
+        let mut lengths = build_lengths();
+        lengths.remove("RPL37AP1");
+        assert!(calculate_antisense_tpms(&counts, &antisense_counts, &lengths).is_err());
+    }
+
+    #[test]
+    fn test_calculate_expected_counts_from_fpkms() {
+        let counts = build_counts();
+        let lengths = build_lengths();
+        let library_size = sum_counts(&counts);
+
+        let fpkms = calculate_fpkms(&counts, &lengths).unwrap();
+        let expected_counts =
+            calculate_expected_counts_from_fpkms(&fpkms, &lengths, library_size).unwrap();
+
+        assert_eq!(expected_counts.len(), 3);
+
+        for (name, &count) in &counts {
+            assert!((expected_counts[name] - count as f64).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_calculate_expected_counts_from_tpms() {
+        let counts = build_counts();
+        let lengths = build_lengths();
+        let library_size = sum_counts(&counts);
+
+        let tpms = calculate_tpms(&counts, &lengths).unwrap();
+        let expected_counts =
+            calculate_expected_counts_from_tpms(&tpms, &lengths, library_size).unwrap();
+
+        assert_eq!(expected_counts.len(), 3);
+
+        for (name, &count) in &counts {
+            assert!((expected_counts[name] - count as f64).abs() < 1e-6);
+        }
+
+        let mut lengths = build_lengths();
+        lengths.remove("AAAS");
+        assert!(calculate_expected_counts_from_tpms(&tpms, &lengths, library_size).is_err());
+    }
+
+    #[test]
+    fn test_calculate_tpms_from_fpkms() {
+        let counts = build_counts();
+        let lengths = build_lengths();
+
+        let fpkms = calculate_fpkms(&counts, &lengths).unwrap();
+        let tpms = calculate_tpms(&counts, &lengths).unwrap();
+
+        for (name, tpm) in calculate_tpms_from_fpkms(&fpkms).unwrap() {
+            assert!((tpm - tpms[&name]).abs() < 1e-6);
+        }
     }
 
     #[test]
     fn test_calculate_fpkm() {
-        let a = calculate_fpkm(2, 10, 212);
+        let a = calculate_fpkm(2, 10, 212).unwrap();
         let b = 943396.2264150943;
         assert!((a - b).abs() < EPSILON);
 
-        let a = calculate_fpkm(5, 138756, 600081);
+        let a = calculate_fpkm(5, 138756, 600081).unwrap();
         let b = 0.06004935631747696;
         assert!((a - b).abs() < EPSILON);
     }
 
     #[test]
     fn test_calculate_tpm() {
-        let a = calculate_tpm(2.0, 10.0);
+        let a = calculate_tpm(2.0, 10.0).unwrap();
         let b = 200000.0;
         assert!((a - b).abs() < EPSILON);
 
-        let a = dbg!(calculate_tpm(0.0010, 26.65));
+        let a = dbg!(calculate_tpm(0.0010, 26.65)).unwrap();
         let b = 37.5234521575985;
         assert!((a - b).abs() < EPSILON);
+
+        assert!(matches!(
+            calculate_tpm(0.0, 0.0),
+            Err(Error::ZeroDenominator)
+        ));
+    }
+
+    #[test]
+    fn test_calculate_fpkms_with_zero_length_feature() {
+        let counts = build_counts();
+
+        let mut lengths = build_lengths();
+        lengths.insert(FeatureId::from("AC009952.3"), 0);
+
+        assert!(matches!(
+            calculate_fpkms(&counts, &lengths),
+            Err(Error::ZeroLengthFeature(name)) if &*name == "AC009952.3"
+        ));
+
+        assert!(matches!(
+            calculate_tpms(&counts, &lengths),
+            Err(Error::ZeroLengthFeature(_))
+        ));
+    }
+
+    #[test]
+    fn test_calculate_expressions_with_no_counts() {
+        let counts: Counts = [(FeatureId::from("AAAS"), 0)].iter().cloned().collect();
+        let lengths = build_lengths();
+
+        assert!(matches!(
+            calculate_fpkms(&counts, &lengths),
+            Err(Error::ZeroDenominator)
+        ));
+
+        assert!(matches!(
+            calculate_tpms(&counts, &lengths),
+            Err(Error::ZeroDenominator)
+        ));
+
+        assert!(matches!(
+            calculate_tpms_from_fpkms(&Expressions::new()),
+            Ok(ref tpms) if tpms.is_empty()
+        ));
     }
 }
@@ -1,116 +1,1672 @@
+mod cli;
+
 use std::{
-    fs::File,
-    io::{self, Write},
+    collections::HashSet,
+    env,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    iter,
+    net::TcpListener,
+    panic,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::{value_t, values_t, ArgMatches};
+use log::{info, warn, LevelFilter};
+#[cfg(feature = "remote")]
+use noodles_fpkm::remote;
+use noodles_fpkm::{
+    abundances::read_expressions,
+    annotation_diff::{diff_lengths, Change, ChangeKind},
+    bgzf,
+    counts::{detect_count_source, sum_counts, Counts, ReadCountsOptions},
+    error_report::{snake_case, ErrorReport},
+    feature_id::FeatureId,
+    feature_index::{write_feature_index, FeatureIndex},
+    features::{read_merged_features_with_options, ReadFeaturesOptions},
+    gene_metadata::GeneMetadata,
+    gtf::{read_annotations, Aliases},
+    ids::suggest_feature_id,
+    index::{query, read_index, write_index, INDEX_EXTENSION},
+    lengths::{calculate_lengths, Lengths},
+    matrix::{read_matrix, ExpressionMatrix},
+    mtx::{read_10x, read_names},
+    output_format::{FormattedWriter, OutputFormat},
+    sample_sheet::{read_sample_sheet, SampleSheet},
+    server::{serve, Quantifier},
+    simulation::simulate_counts,
+    spike_in_screen::{read_spike_in_counts, read_spike_in_index, MAX_KMER_SIZE},
+    unit::{infer_unit, InferredUnit, Unit},
+    validation::Validate,
+    wildcard::{self, has_wildcards},
+    Error, Expressions,
+};
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+
+use crate::cli::{
+    app::app,
+    quantify::run_quantify,
+    reports::{build_rarefaction_depths, sorted_count_values, write_rarefaction_curves},
 };
 
-use clap::{crate_name, crate_version, App, Arg};
-use log::LevelFilter;
-use noodles_fpkm::{
-    calculate_fpkms, calculate_tpms, counts::read_counts, features::read_features, Expressions,
-};
+const DETECTED_TOTALS_NAME: &str = "__detected";
+const STDIN_SRC: &str = "-";
+
+// Whether errors are written to stderr as JSON objects (--error-format json).
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+// Temporary directories to remove when exiting with an error, which does not
+// run destructors.
+static TEMP_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+const MAX_TEMP_DIR_ATTEMPTS: u32 = 100;
+
+// The arguments whose values are read as paths, which do not need to be valid
+// UTF-8.
+const PATH_ARGS: [&str; 3] = ["counts", "annotations", "output"];
+
+fn open_counts(src: &Path) -> io::Result<Box<dyn Read>> {
+    #[cfg(feature = "remote")]
+    {
+        if let Some(url) = src.to_str().filter(|src| remote::is_url(src)) {
+            return remote::open(url);
+        }
+    }
+
+    File::open(src).map(|f| Box::new(f) as Box<dyn Read>)
+}
+
+// A counts file is read by the count source that detects its format. Samples
+// that are not named by the format are named by the file stem (see
+// `CountSource::read_samples_from_path`).
+fn read_samples(src: &Path, options: &ReadCountsOptions) -> io::Result<Vec<(String, Counts)>> {
+    let mut buf = Vec::new();
+    open_counts(src)?.read_to_end(&mut buf)?;
+
+    let source = detect_count_source(&buf).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown counts format: {}", src.display()),
+        )
+    })?;
+
+    info!("reading {} as {} counts", src.display(), source.name());
+
+    source.read_samples_from_path(src, &buf, options)
+}
+
+// Each change is written with its FPKM shift and whether its magnitude exceeds
+// the threshold. Missing values are written as `NA`.
+fn write_annotation_diff<W>(mut writer: W, changes: &[Change], threshold: f64) -> io::Result<()>
+where
+    W: Write,
+{
+    writeln!(
+        writer,
+        "feature_id\tchange\told_feature_id\told_length\tnew_length\tfpkm_shift\tshifted"
+    )?;
+
+    for change in changes {
+        let old_id = match &change.kind {
+            ChangeKind::Renamed(old_id) => old_id.as_str(),
+            ChangeKind::Removed | ChangeKind::Length => change.id.as_str(),
+            ChangeKind::Added => "NA",
+        };
+
+        let format_length =
+            |length: Option<u64>| length.map_or_else(|| String::from("NA"), |n| n.to_string());
+
+        let shift = change.fpkm_shift();
+
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            change.id,
+            change.kind.as_str(),
+            old_id,
+            format_length(change.old_length),
+            format_length(change.new_length),
+            shift.map_or_else(|| String::from("NA"), |n| n.to_string()),
+            shift.is_some_and(|n| n.abs() > threshold),
+        )?;
+    }
+
+    writer.flush()
+}
+
+// A single sample is written in the htseq-count format, and multiple samples
+// are written as a counts table with a header, as featureCounts does. Every
+// sample must have a count for each ID.
+fn write_counts_table<W>(
+    mut writer: W,
+    ids: &[FeatureId],
+    samples: &[(String, Counts)],
+) -> io::Result<()>
+where
+    W: Write,
+{
+    if let [(_, counts)] = samples {
+        for id in ids {
+            writeln!(writer, "{}\t{}", id, counts[id])?;
+        }
+
+        return writer.flush();
+    }
+
+    write!(writer, "Geneid")?;
+
+    for (name, _) in samples {
+        write!(writer, "\t{}", name)?;
+    }
+
+    writeln!(writer)?;
+
+    for id in ids {
+        write!(writer, "{}", id)?;
+
+        for (_, counts) in samples {
+            write!(writer, "\t{}", counts[id])?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    writer.flush()
+}
+
+// Writes the number of detected features of each sample at each depth. When
+// no depths are given, they are evenly spaced up to the largest library size.
+fn write_saturation<W>(
+    mut writer: W,
+    samples: &[(String, Counts)],
+    depths: Option<Vec<u64>>,
+    seed: u64,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    let samples: Vec<_> = samples
+        .iter()
+        .map(|(name, counts)| (name.as_str(), sorted_count_values(counts)))
+        .collect();
+
+    let depths = depths.unwrap_or_else(|| {
+        let max_depth = samples
+            .iter()
+            .map(|(_, values)| values.iter().sum())
+            .max()
+            .unwrap_or(0);
+
+        build_rarefaction_depths(max_depth)
+    });
+
+    writeln!(writer, "sample\tdepth\tdetected")?;
+
+    let mut rng = Pcg64::seed_from_u64(seed);
+    write_rarefaction_curves(writer, &samples, &depths, &mut rng)
+}
+
+// Returns the file stem of the source, e.g., `sample1` for
+// `counts/sample1.txt`.
+fn sample_name<P>(src: P) -> String
+where
+    P: AsRef<Path>,
+{
+    let src = src.as_ref();
+
+    src.file_stem()
+        .unwrap_or(src.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+// FASTQ extensions are removed, e.g., `sample1.fastq.gz` is `sample1`.
+fn reads_sample_name(src: &str) -> String {
+    let mut name = sample_name(src);
+
+    if src.ends_with(".gz") {
+        name = sample_name(&name);
+    }
+
+    name
+}
+
+fn build_count_matrix(samples: &[(String, Counts)]) -> ExpressionMatrix {
+    let samples: Vec<_> = samples
+        .iter()
+        .map(|(name, counts)| {
+            let values = counts
+                .iter()
+                .map(|(id, &count)| (id.clone(), count as f64))
+                .collect();
+
+            (name.clone(), values)
+        })
+        .collect();
+
+    ExpressionMatrix::from_samples(&samples)
+}
+
+// The first cell of the header is a label for the values, e.g., the unit.
+fn write_matrix<W>(mut writer: W, label: &str, matrix: &ExpressionMatrix) -> io::Result<()>
+where
+    W: Write,
+{
+    write!(writer, "{}", label)?;
+
+    for name in matrix.column_names() {
+        write!(writer, "\t{}", name)?;
+    }
+
+    writeln!(writer)?;
+
+    for (name, row) in matrix.row_names().iter().zip(matrix.data().outer_iter()) {
+        write_matrix_row(&mut writer, name, row.iter())?;
+    }
+
+    writer.flush()
+}
+
+// Metadata columns are written between the row name and the values.
+fn write_matrix_with_metadata<W>(
+    mut writer: W,
+    label: &str,
+    matrix: &ExpressionMatrix,
+    metadata: &GeneMetadata,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    write!(writer, "{}", label)?;
+
+    for name in metadata.column_names().iter().chain(matrix.column_names()) {
+        write!(writer, "\t{}", name)?;
+    }
+
+    writeln!(writer)?;
+
+    for (name, row) in matrix.row_names().iter().zip(matrix.data().outer_iter()) {
+        write!(writer, "{}", name)?;
+        write_metadata_fields(&mut writer, metadata, name)?;
+
+        for value in row.iter() {
+            write!(writer, "\t{}", value)?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    writer.flush()
+}
+
+// Features that are not in the metadata are written as NA.
+fn write_metadata_fields<W>(mut writer: W, metadata: &GeneMetadata, id: &str) -> io::Result<()>
+where
+    W: Write,
+{
+    match metadata.get(id) {
+        Some(values) => {
+            for value in values {
+                write!(writer, "\t{}", value)?;
+            }
+        }
+        None => {
+            for _ in metadata.column_names() {
+                write!(writer, "\tNA")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_batch_adjustment_header<W>(mut writer: W, batches: &[(&str, Vec<usize>)]) -> io::Result<()>
+where
+    W: Write,
+{
+    let batches: Vec<String> = batches
+        .iter()
+        .map(|(name, columns)| format!("{} (n={})", name, columns.len()))
+        .collect();
+
+    writeln!(
+        writer,
+        "{} per-batch mean-centering of log2(x + 1) expression; batches: {}",
+        BATCH_ADJUSTMENT_COMMENT,
+        batches.join(", ")
+    )
+}
+
+const BATCH_ADJUSTMENT_COMMENT: &str = "# batch adjustment:";
+
+// Reads the comment lines that precede the header of a matrix.
+fn read_comment_header<R>(reader: R) -> io::Result<Vec<String>>
+where
+    R: BufRead,
+{
+    let mut comments = Vec::new();
+
+    for result in reader.lines() {
+        let line = result?;
+
+        if !line.starts_with('#') {
+            break;
+        }
+
+        comments.push(line);
+    }
+
+    Ok(comments)
+}
+
+// Exits when a matrix was batch-adjusted, as appending unadjusted samples to
+// it, or its samples to another matrix, would mix the two.
+fn check_not_batch_adjusted(src: &str, comments: &[String]) {
+    if comments
+        .iter()
+        .any(|line| line.starts_with(BATCH_ADJUSTMENT_COMMENT))
+    {
+        exit_with_error(
+            &format!("{}: cannot append to or from a batch-adjusted matrix", src),
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+}
+
+fn write_matrix_row<'a, W, I>(mut writer: W, name: &str, values: I) -> io::Result<()>
+where
+    W: Write,
+    I: Iterator<Item = &'a f64>,
+{
+    write!(writer, "{}", name)?;
+
+    for value in values {
+        write!(writer, "\t{}", value)?;
+    }
+
+    writeln!(writer)
+}
+
+fn write_indexed_matrix(dst: &str, label: &str, matrix: &ExpressionMatrix) -> io::Result<()> {
+    let file = File::create(dst).map(BufWriter::new)?;
+    let mut writer = bgzf::Writer::new(file);
+
+    write!(writer, "{}", label)?;
+
+    for name in matrix.column_names() {
+        write!(writer, "\t{}", name)?;
+    }
+
+    writeln!(writer)?;
+
+    let mut entries = Vec::with_capacity(matrix.row_names().len());
+
+    for (name, row) in matrix.row_names().iter().zip(matrix.data().outer_iter()) {
+        entries.push((name.clone(), writer.virtual_position()));
+        write_matrix_row(&mut writer, name, row.iter())?;
+    }
+
+    writer.finish()?;
+
+    let index_dst = format!("{}.{}", dst, INDEX_EXTENSION);
+    let index_writer = File::create(index_dst).map(BufWriter::new)?;
+    write_index(index_writer, &entries)
+}
+
+fn query_indexed_matrix<W>(mut writer: W, src: &str, ids: &[&str]) -> io::Result<()>
+where
+    W: Write,
+{
+    let index_src = format!("{}.{}", src, INDEX_EXTENSION);
+    let index = File::open(index_src)
+        .map(BufReader::new)
+        .and_then(read_index)?;
+
+    let mut reader = File::open(src).map(bgzf::Reader::new)?;
+
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+    write!(writer, "{}", header)?;
+
+    for id in ids {
+        match query(&mut reader, &index, id)? {
+            Some(row) => writeln!(writer, "{}", row)?,
+            None => warn!("{} is not in the index", id),
+        }
+    }
+
+    writer.flush()
+}
+
+// Appends the columns of each input to the matrix at `dst` and replaces it.
+//
+// Reads an expression matrix or a single-sample output. The unit of a matrix
+// is the first cell of its header. Inputs without a unit label, i.e.,
+// single-sample outputs and third-party tables, have the unit given by
+// `input_unit` or, if it is not given, inferred from their values.
+fn read_matrix_or_expressions(
+    src: &str,
+    input_unit: Option<&str>,
+    target_unit: Unit,
+) -> (Unit, ExpressionMatrix) {
+    let data = read_input(src, |mut reader| {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Ok(buf)
+    });
+
+    // Provenance headers, e.g., of a batch adjustment, are comments that
+    // precede the header.
+    let first_line: Vec<String> = data
+        .split(|&b| b == b'\n')
+        .find(|line| !line.is_empty() && !line.starts_with(b"#"))
+        .map(|line| {
+            String::from_utf8_lossy(line)
+                .trim_end()
+                .split('\t')
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if let Some(unit) = first_line.first().and_then(|name| Unit::from_name(name)) {
+        let (_, matrix) = read_matrix(&data[..]).unwrap_or_else(|e| exit_with_io_error(e, src));
+        return (unit, matrix);
+    }
+
+    // A third-party table has a header whose first cell is not a unit, e.g.,
+    // `gene_id`. A single-sample output has no header.
+    let has_header = first_line
+        .iter()
+        .skip(1)
+        .any(|field| field.parse::<f64>().is_err());
+
+    let matrix = if has_header {
+        let (_, matrix) = read_matrix(&data[..]).unwrap_or_else(|e| exit_with_io_error(e, src));
+        matrix
+    } else {
+        let expressions =
+            read_expressions(&data[..]).unwrap_or_else(|e| exit_with_io_error(e, src));
+        ExpressionMatrix::from_samples(&[(sample_name(src), expressions)])
+    };
+
+    let unit = match input_unit {
+        Some(_) => headerless_input_unit(src, input_unit),
+        None => infer_input_unit(src, &matrix, target_unit),
+    };
+
+    (unit, matrix)
+}
+
+// TPM and CPM cannot be told apart by their values, so values per million are
+// assumed to be in the unit of the target, if it is either.
+fn infer_input_unit(src: &str, matrix: &ExpressionMatrix, target_unit: Unit) -> Unit {
+    let inferred_unit = infer_unit(matrix);
+
+    let unit = match inferred_unit {
+        Some(InferredUnit::PerMillion) => match target_unit {
+            Unit::Tpm | Unit::Cpm => target_unit,
+            _ => Unit::Tpm,
+        },
+        Some(InferredUnit::Fpkm) => Unit::Fpkm,
+        Some(InferredUnit::Counts) => exit_with_error(
+            &format!(
+                "{}: no unit label, and the values look like counts (all integers); use --assume-unit to give their unit",
+                src
+            ),
+            clap::ErrorKind::InvalidValue,
+        ),
+        None => exit_with_error(
+            &format!(
+                "{}: no unit label, and no unit can be inferred from negative or nonfinite values; use --assume-unit to give their unit",
+                src
+            ),
+            clap::ErrorKind::MissingRequiredArgument,
+        ),
+    };
+
+    warn!(
+        "{}: no unit label; inferred {} from the values ({}); use --assume-unit to override",
+        src,
+        unit,
+        inferred_unit.unwrap()
+    );
+
+    unit
+}
+
+// Inputs without a header, i.e., single-sample outputs and 10x directories,
+// have no unit, so it must be given with `--unit`.
+fn headerless_input_unit(src: &str, input_unit: Option<&str>) -> Unit {
+    let name = input_unit.unwrap_or_else(|| {
+        exit_with_error(
+            &format!("{}: missing header; use --unit to give its unit", src),
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+    });
+
+    Unit::from_name(name).unwrap_or_else(|| {
+        exit_with_error(
+            &format!("invalid --unit: {}", name),
+            clap::ErrorKind::InvalidValue,
+        )
+    })
+}
+
+// The unit of each input, from its header or `input_unit`, must match the unit
+// of the matrix, and each input must have the same set of features. Comment
+// lines before the header of the matrix are kept.
+fn append_to_matrix(dst: &str, srcs: &[&str], input_unit: Option<&str>) {
+    let comments = read_input(dst, read_comment_header);
+    check_not_batch_adjusted(dst, &comments);
+
+    let (label, mut matrix) = read_input(dst, read_matrix);
+
+    let unit = Unit::from_name(&label).unwrap_or_else(|| {
+        exit_with_error(
+            &format!("{}: unknown unit: {}", dst, label),
+            clap::ErrorKind::InvalidValue,
+        )
+    });
+
+    for &src in srcs {
+        let (src_unit, input) = if Path::new(src).is_dir() {
+            let input = read_10x(src).unwrap_or_else(|e| exit_with_io_error(e, src));
+            (headerless_input_unit(src, input_unit), input.to_dense())
+        } else {
+            check_not_batch_adjusted(src, &read_input(src, read_comment_header));
+            read_matrix_or_expressions(src, input_unit, unit)
+        };
+
+        if src_unit != unit {
+            exit_with_error(
+                &format!("{}: unit is {}, but {} is {}", src, src_unit, dst, unit),
+                clap::ErrorKind::InvalidValue,
+            );
+        }
+
+        let missing_count = matrix
+            .row_names()
+            .iter()
+            .filter(|name| input.row_index(name).is_none())
+            .count();
+
+        let extra: Vec<&String> = input
+            .row_names()
+            .iter()
+            .filter(|name| matrix.row_index(name).is_none())
+            .collect();
+
+        if missing_count > 0 || !extra.is_empty() {
+            let mut message = format!(
+                "{}: features differ from {}: {} missing, {} extra",
+                src,
+                dst,
+                missing_count,
+                extra.len()
+            );
+
+            if let Some(name) = extra.first() {
+                message.push_str(&format!(" (e.g., {})", name));
+            }
+
+            exit_with_error(&message, clap::ErrorKind::InvalidValue);
+        }
+
+        for name in input.column_names() {
+            if matrix.column_index(name).is_some() {
+                exit_with_error(
+                    &format!("{}: sample {} is already in {}", src, name, dst),
+                    clap::ErrorKind::InvalidValue,
+                );
+            }
+        }
+
+        matrix = matrix.append_columns(&input);
+        info!("appended {} from {}", input.column_names().join(", "), src);
+    }
+
+    // The matrix is replaced atomically, so it is unchanged if writing fails.
+    let tmp = format!("{}.tmp", dst);
+
+    write_output(&tmp, |mut writer| {
+        for line in &comments {
+            writeln!(writer, "{}", line)?;
+        }
+
+        write_matrix(writer, unit.as_str(), &matrix)
+    });
+
+    fs::rename(&tmp, dst).unwrap_or_else(|e| exit_with_io_error(e, dst));
+}
+
+// Exits when a list of names to select has duplicates or names that are not
+// in the matrix.
+fn check_selection<F>(src: &str, names: &[String], contains: F)
+where
+    F: Fn(&str) -> bool,
+{
+    let mut seen = HashSet::new();
+
+    if let Some(name) = names.iter().find(|name| !seen.insert(*name)) {
+        exit_with_error(
+            &format!("{}: duplicate name: {}", src, name),
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    let missing: Vec<&String> = names.iter().filter(|name| !contains(name)).collect();
+
+    if let Some(name) = missing.first() {
+        exit_with_error(
+            &format!(
+                "{}: {} names are not in the matrix (e.g., {})",
+                src,
+                missing.len(),
+                name
+            ),
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+}
+
+fn write_expressions<W>(mut writer: W, expressions: &Expressions) -> io::Result<()>
+where
+    W: Write,
+{
+    for (id, value) in expressions {
+        writeln!(writer, "{}\t{}", id, value)?;
+    }
+
+    Ok(())
+}
+
+// Writes the expressions of the given features, in order. Features without a
+// value are given a value of 0. Metadata columns, if any, are written between
+// the feature ID and the value.
+fn write_expressions_in_order<W>(
+    mut writer: W,
+    expressions: &Expressions,
+    ids: &[String],
+    metadata: Option<&GeneMetadata>,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    for id in ids {
+        write!(writer, "{}", id)?;
+
+        if let Some(metadata) = metadata {
+            write_metadata_fields(&mut writer, metadata, id)?;
+        }
+
+        let value = expressions.get(id.as_str()).copied().unwrap_or(0.0);
+        writeln!(writer, "\t{}", value)?;
+    }
+
+    Ok(())
+}
+
+fn write_stranded_expressions<W>(
+    mut writer: W,
+    sense_expressions: &Expressions,
+    antisense_expressions: &Expressions,
+) -> io::Result<()>
+where
+    W: Write,
+{
+    for (id, value) in sense_expressions {
+        let antisense_value = antisense_expressions.get(id).copied().unwrap_or(0.0);
+        writeln!(writer, "{}\t{}\t{}", id, value, antisense_value)?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = args();
+
+    let matches = app().get_matches_from_safe(&args).unwrap_or_else(|e| {
+        if has_json_error_format(&args) {
+            JSON_ERRORS.store(true, Ordering::Relaxed);
+        }
+
+        exit_with_clap_error(e)
+    });
+
+    check_utf8_args(&args, &matches);
+
+    if matches.is_present("verbose") {
+        env_logger::Builder::from_default_env()
+            .filter(Some("noodles_fpkm"), LevelFilter::Info)
+            .init();
+    } else {
+        env_logger::init();
+    }
+
+    if matches.value_of("error-format") == Some("json") {
+        JSON_ERRORS.store(true, Ordering::Relaxed);
+
+        panic::set_hook(Box::new(|info| {
+            let message = info
+                .payload()
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| info.payload().downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("unknown error"));
+
+            eprintln!("{}", ErrorReport::new("internal", message).to_json());
+        }));
+    }
+
+    match matches.subcommand() {
+        ("saturation", Some(matches)) => run_saturation(matches),
+        ("screen-spike-ins", Some(matches)) => run_screen_spike_ins(matches),
+        ("simulate", Some(matches)) => run_simulate(matches),
+        ("annotation-diff", Some(matches)) => run_annotation_diff(matches),
+        ("export-index", Some(matches)) => run_export_index(matches),
+        ("serve", Some(matches)) => run_serve(matches),
+        ("merge", Some(matches)) => run_merge(matches),
+        ("matrix", Some(matches)) => run_matrix(matches),
+        ("query", Some(matches)) => run_query(matches),
+        _ => run_quantify(&matches),
+    }
+}
+
+fn run_saturation(matches: &ArgMatches<'_>) {
+    let depths = if matches.is_present("depths") {
+        Some(values_t!(matches, "depths", u64).unwrap_or_else(|e| exit_with_clap_error(e)))
+    } else {
+        None
+    };
+
+    let seed = value_t!(matches, "seed", u64).unwrap_or_else(|e| exit_with_clap_error(e));
+
+    let mut samples = Vec::new();
+
+    for src in matches.values_of_os("counts").unwrap().map(Path::new) {
+        samples.extend(
+            read_samples(src, &ReadCountsOptions::default())
+                .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy())),
+        );
+    }
+
+    let stdout = io::stdout();
+    let handle = stdout.lock();
+    write_saturation(handle, &samples, depths, seed).unwrap();
+}
+
+fn run_screen_spike_ins(matches: &ArgMatches<'_>) {
+    let src = matches.value_of("spike-ins").unwrap();
+    let kmer_size =
+        value_t!(matches, "kmer-size", usize).unwrap_or_else(|e| exit_with_clap_error(e));
+
+    if kmer_size == 0 || kmer_size > MAX_KMER_SIZE {
+        exit_with_error(
+            &format!("--kmer-size must be in [1, {}]", MAX_KMER_SIZE),
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    let index = read_spike_in_index(src, kmer_size).unwrap_or_else(|e| exit_with_io_error(e, src));
+
+    let samples: Vec<(String, Counts)> = matches
+        .values_of("reads")
+        .unwrap()
+        .map(|src| {
+            let counts =
+                read_spike_in_counts(src, &index).unwrap_or_else(|e| exit_with_io_error(e, src));
+            info!("{}: {} spike-in reads", src, sum_counts(&counts));
+            (reads_sample_name(src), counts)
+        })
+        .collect();
+
+    let stdout = io::stdout();
+    let handle = stdout.lock();
+    write_counts_table(handle, index.names(), &samples).unwrap();
+}
+
+fn run_simulate(matches: &ArgMatches<'_>) {
+    let src = Path::new(matches.value_of_os("annotations").unwrap());
+    let feature_type = matches.value_of("feature-type").unwrap();
+    let feature_id = matches.value_of("feature-id").unwrap();
+    let mean = value_t!(matches, "mean", f64).unwrap_or_else(|e| exit_with_clap_error(e));
+    let dispersion =
+        value_t!(matches, "dispersion", f64).unwrap_or_else(|e| exit_with_clap_error(e));
+    let sample_count =
+        value_t!(matches, "samples", usize).unwrap_or_else(|e| exit_with_clap_error(e));
+    let seed = value_t!(matches, "seed", u64).unwrap_or_else(|e| exit_with_clap_error(e));
+
+    if !(mean.is_finite() && mean >= 0.0) {
+        exit_with_error(
+            &format!("invalid --mean: {}", mean),
+            clap::ErrorKind::InvalidValue,
+        );
+    } else if !(dispersion.is_finite() && dispersion >= 0.0) {
+        exit_with_error(
+            &format!("invalid --dispersion: {}", dispersion),
+            clap::ErrorKind::InvalidValue,
+        );
+    } else if sample_count == 0 {
+        exit_with_error(
+            "--samples must be at least 1",
+            clap::ErrorKind::InvalidValue,
+        );
+    }
+
+    let features = read_merged_features_with_options(
+        src,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+    .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy()));
+
+    let mut ids: Vec<FeatureId> = features.keys().cloned().collect();
+    ids.sort();
+
+    let mut rng = Pcg64::seed_from_u64(seed);
+
+    let samples: Vec<(String, Counts)> = (1..=sample_count)
+        .map(|i| {
+            let counts = simulate_counts(&ids, mean, dispersion, &mut rng);
+            (format!("sample{}", i), counts)
+        })
+        .collect();
+
+    let stdout = io::stdout();
+    let handle = stdout.lock();
+    write_counts_table(handle, &ids, &samples).unwrap();
+}
+
+fn run_annotation_diff(matches: &ArgMatches<'_>) {
+    let feature_type = matches.value_of("feature-type").unwrap();
+    let feature_id = matches.value_of("feature-id").unwrap();
+    let shift_threshold =
+        value_t!(matches, "shift-threshold", f64).unwrap_or_else(|e| exit_with_clap_error(e));
+
+    let load_lengths = |src| {
+        let features = read_merged_features_with_options(
+            src,
+            feature_type,
+            feature_id,
+            &ReadFeaturesOptions::default(),
+        )
+        .unwrap_or_else(|e| exit_with_io_error(e, src));
+
+        calculate_lengths(&features)
+    };
+
+    let old_lengths = load_lengths(matches.value_of("old").unwrap());
+    let new_lengths = load_lengths(matches.value_of("new").unwrap());
+    let changes = diff_lengths(&old_lengths, &new_lengths);
+
+    let shifted_count = changes
+        .iter()
+        .filter_map(|change| change.fpkm_shift())
+        .filter(|shift| shift.abs() > shift_threshold)
+        .count();
+
+    info!(
+        "{} changed features; the fpkm of {} would shift by more than {}% due to length changes",
+        changes.len(),
+        shifted_count,
+        shift_threshold * 100.0
+    );
+
+    let stdout = io::stdout();
+    let handle = stdout.lock();
+    write_annotation_diff(handle, &changes, shift_threshold).unwrap();
+}
+
+fn run_export_index(matches: &ArgMatches<'_>) {
+    let src = Path::new(matches.value_of_os("annotations").unwrap());
+    let feature_type = matches.value_of("feature-type").unwrap();
+    let feature_id = matches.value_of("feature-id").unwrap();
+    let dst = Path::new(matches.value_of_os("output").unwrap());
+
+    let features = read_merged_features_with_options(
+        src,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+    .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy()));
+
+    let lengths = calculate_lengths(&features);
+    let annotations = read_annotations(src, feature_type, feature_id, None)
+        .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy()));
+
+    let index = FeatureIndex::build(
+        &features,
+        &lengths,
+        &annotations.reference_sequence_names,
+        &annotations.strands,
+    );
+    info!("indexed {} features", index.len());
+
+    write_output(&dst.to_string_lossy(), |writer| {
+        write_feature_index(writer, &index)
+    });
+}
+
+fn run_serve(matches: &ArgMatches<'_>) {
+    let src = Path::new(matches.value_of_os("annotations").unwrap());
+    let feature_type = matches.value_of("feature-type").unwrap();
+    let feature_id = matches.value_of("feature-id").unwrap();
+    let host = matches.value_of("host").unwrap();
+    let port = value_t!(matches, "port", u16).unwrap_or_else(|e| exit_with_clap_error(e));
+
+    let features = read_merged_features_with_options(
+        src,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+    .unwrap_or_else(|e| exit_with_io_error(e, &src.to_string_lossy()));
+
+    let quantifier = Quantifier::new(&features);
+    info!("loaded {} features", quantifier.len());
+
+    let listener = TcpListener::bind((host, port))
+        .unwrap_or_else(|e| exit_with_error(&e.to_string(), clap::ErrorKind::Io));
+
+    serve(&listener, &quantifier).unwrap();
+}
+
+fn run_merge(matches: &ArgMatches<'_>) {
+    let dst = matches.value_of("append").unwrap();
+    let srcs: Vec<&str> = matches.values_of("inputs").unwrap().collect();
+    let input_unit = matches.value_of("unit");
+    append_to_matrix(dst, &srcs, input_unit);
+}
+
+fn run_matrix(matches: &ArgMatches<'_>) {
+    let src = matches.value_of("input").unwrap();
+    let rows_src = matches.value_of("rows");
+    let columns_src = matches.value_of("columns");
+    let transpose = matches.is_present("transpose");
+
+    let (label, mut matrix) = if Path::new(src).is_dir() {
+        let unit = headerless_input_unit(src, matches.value_of("unit"));
+        let matrix = read_10x(src).unwrap_or_else(|e| exit_with_io_error(e, src));
+        (String::from(unit.as_str()), matrix.to_dense())
+    } else {
+        read_input(src, read_matrix)
+    };
+
+    if let Some(rows_src) = rows_src {
+        let names = read_names(rows_src).unwrap_or_else(|e| exit_with_io_error(e, rows_src));
+        check_selection(rows_src, &names, |name| matrix.row_index(name).is_some());
+        matrix = matrix.select_rows(&names);
+    }
+
+    if let Some(columns_src) = columns_src {
+        let names = read_names(columns_src).unwrap_or_else(|e| exit_with_io_error(e, columns_src));
+        check_selection(columns_src, &names, |name| {
+            matrix.column_index(name).is_some()
+        });
+        matrix = matrix.select_columns(&names);
+    }
+
+    if transpose {
+        matrix = matrix.transpose();
+    }
+
+    let stdout = io::stdout();
+    let handle = stdout.lock();
+    write_matrix(BufWriter::new(handle), &label, &matrix).unwrap();
+}
+
+fn run_query(matches: &ArgMatches<'_>) {
+    let src = matches.value_of("input").unwrap();
+    let ids: Vec<&str> = matches.values_of("ids").unwrap().collect();
+
+    let stdout = io::stdout();
+    let handle = stdout.lock();
+    query_indexed_matrix(handle, src, &ids).unwrap();
+}
+
+fn open_sample_sheet(src: &str, names: &[String]) -> SampleSheet {
+    let sample_sheet = read_input(src, read_sample_sheet);
 
-fn write_expressions<W>(mut writer: W, expressions: &Expressions) -> io::Result<()>
+    for name in names {
+        if sample_sheet.condition(name).is_none() {
+            exit_with_error(
+                &format!("sample {} is not in the sample sheet", name),
+                clap::ErrorKind::InvalidValue,
+            );
+        }
+    }
+
+    sample_sheet
+}
+
+fn parse_output_format(matches: &ArgMatches<'_>) -> OutputFormat {
+    let delimiter = match matches.value_of("delimiter").unwrap() {
+        "tab" => b'\t',
+        "comma" => b',',
+        "semicolon" => b';',
+        _ => unreachable!(),
+    };
+
+    let decimal_separator = match matches.value_of("decimal-separator").unwrap() {
+        "period" => b'.',
+        "comma" => b',',
+        _ => unreachable!(),
+    };
+
+    if delimiter == decimal_separator {
+        exit_with_error(
+            "--delimiter and --decimal-separator cannot both be comma",
+            clap::ErrorKind::ArgumentConflict,
+        );
+    }
+
+    let format = OutputFormat {
+        delimiter,
+        decimal_separator,
+    };
+
+    if !format.is_default() {
+        for arg in &["watch", "indexed-output"] {
+            if matches.is_present(arg) {
+                exit_with_error(
+                    &format!(
+                        "--{} cannot be used with --delimiter or --decimal-separator",
+                        arg
+                    ),
+                    clap::ErrorKind::ArgumentConflict,
+                );
+            }
+        }
+    }
+
+    format
+}
+
+// Outputs in the default format are written as is.
+fn formatted_writer<'a, W>(writer: W, format: OutputFormat) -> Box<dyn Write + 'a>
 where
-    W: Write,
+    W: Write + 'a,
 {
-    for (id, value) in expressions {
-        writeln!(writer, "{}\t{}", id, value)?;
+    if format.is_default() {
+        Box::new(writer)
+    } else {
+        Box::new(FormattedWriter::new(writer, format))
     }
-
-    Ok(())
 }
 
-fn main() {
-    let matches = App::new(crate_name!())
-        .version(crate_version!())
-        .arg(
-            Arg::with_name("verbose")
-                .short("v")
-                .long("verbose")
-                .help("Use verbose logging"),
-        )
-        .arg(
-            Arg::with_name("feature-type")
-                .short("t")
-                .long("type")
-                .value_name("str")
-                .help("Feature type to count")
-                .default_value("exon"),
-        )
-        .arg(
-            Arg::with_name("feature-id")
-                .short("i")
-                .long("id")
-                .value_name("str")
-                .help("Feature attribute to use as the feature identity")
-                .default_value("gene_id"),
-        )
-        .arg(
-            Arg::with_name("unit")
-                .short("u")
-                .long("unit")
-                .value_name("str")
-                .help("Unit of expression")
-                .default_value("tpm")
-                .possible_values(&["fpkm", "tpm"]),
-        )
-        .arg(
-            Arg::with_name("annotations")
-                .short("a")
-                .long("annotations")
-                .value_name("file")
-                .help("Input annotations file (GTF/GFFv2)")
-                .required(true),
-        )
-        .arg(
-            Arg::with_name("counts")
-                .help("Input feature counts")
-                .required(true)
-                .index(1),
-        )
-        .get_matches();
+fn check_invariants<T>(stage: &str, value: &T)
+where
+    T: Validate,
+{
+    let report = value.validate();
 
-    if matches.is_present("verbose") {
-        env_logger::Builder::from_default_env()
-            .filter(Some("noodles_fpkm"), LevelFilter::Info)
-            .init();
+    if report.is_valid() {
+        info!("self-check passed: {}", stage);
     } else {
-        env_logger::init();
+        exit_with_error(
+            &format!("self-check failed after {}:\n{}", stage, report),
+            clap::ErrorKind::InvalidValue,
+        );
     }
+}
 
-    let counts_src = matches.value_of("counts").unwrap();
-    let annotations_src = matches.value_of("annotations").unwrap();
-    let feature_type = matches.value_of("feature-type").unwrap();
-    let feature_id = matches.value_of("feature-id").unwrap();
-    let unit = matches.value_of("unit").unwrap();
+fn warn_or_exit(message: &str, strict: bool) {
+    if strict {
+        exit_with_error(message, clap::ErrorKind::InvalidValue);
+    } else {
+        warn!("{}", message);
+    }
+}
+
+// A uniquely named temporary directory that is removed, with its contents,
+// when it is dropped or the process exits with an error.
+struct TempDir {
+    path: PathBuf,
+}
 
-    let features = read_features(annotations_src, feature_type, feature_id).unwrap();
+impl TempDir {
+    // Creates a new directory in the system temporary directory. An existing
+    // directory is never reused.
+    fn new(prefix: &str) -> io::Result<TempDir> {
+        let parent = env::temp_dir();
 
-    let file = File::open(&counts_src).unwrap();
-    let counts = read_counts(file).unwrap();
+        for attempt in 0..MAX_TEMP_DIR_ATTEMPTS {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.subsec_nanos())
+                .unwrap_or_default();
 
-    let fpkms = match unit {
-        "fpkm" => calculate_fpkms(&counts, &features).unwrap(),
-        "tpm" => calculate_tpms(&counts, &features).unwrap(),
-        _ => unreachable!(),
+            let name = format!("{}-{}-{}-{}", prefix, process::id(), nanos, attempt);
+            let path = parent.join(name);
+
+            match fs::create_dir(&path) {
+                Ok(()) => {
+                    if let Ok(mut dirs) = TEMP_DIRS.lock() {
+                        dirs.push(path.clone());
+                    }
+
+                    return Ok(TempDir { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "failed to create a unique temporary directory",
+        ))
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Ok(mut dirs) = TEMP_DIRS.lock() {
+            dirs.retain(|path| *path != self.path);
+        }
+
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn remove_temp_dirs() {
+    if let Ok(mut dirs) = TEMP_DIRS.lock() {
+        for path in dirs.drain(..) {
+            let _ = fs::remove_dir_all(path);
+        }
+    }
+}
+
+fn exit_with_calculation_error(e: Error, lengths: &Lengths, aliases: Option<&Aliases>) -> ! {
+    let mut report = ErrorReport::from(&e);
+
+    if let Error::MissingFeature(id) = &e {
+        if let Some(suggestion) = suggest_feature_id(id, lengths, aliases) {
+            report.message.push_str(&format!(
+                " (did you mean {}? the IDs differ by {})",
+                suggestion.id, suggestion.mismatch
+            ));
+        }
+    }
+
+    exit_with_report(&report, clap::ErrorKind::InvalidValue)
+}
+
+// Exits with an error reading or parsing an input file.
+fn exit_with_io_error(e: io::Error, src: &str) -> ! {
+    let report = ErrorReport {
+        file: Some(src.into()),
+        ..ErrorReport::from_io_error(&e)
     };
 
-    let stdout = io::stdout();
-    let handle = stdout.lock();
-    write_expressions(handle, &fpkms).unwrap();
+    exit_with_report(&report, clap::ErrorKind::Io)
+}
+
+// Reads an input file, exiting with an error naming the file on failure.
+fn read_input<T, F>(src: &str, f: F) -> T
+where
+    F: FnOnce(BufReader<File>) -> io::Result<T>,
+{
+    File::open(src)
+        .map(BufReader::new)
+        .and_then(f)
+        .unwrap_or_else(|e| exit_with_io_error(e, src))
+}
+
+// Writes an output file, exiting with an error naming the file on failure.
+fn write_output<F>(dst: &str, f: F)
+where
+    F: FnOnce(BufWriter<File>) -> io::Result<()>,
+{
+    File::create(dst)
+        .map(BufWriter::new)
+        .and_then(f)
+        .unwrap_or_else(|e| exit_with_io_error(e, dst))
+}
+
+// Windows shells do not expand wildcards, so they are expanded here, as a Unix
+// shell would.
+fn args() -> Vec<OsString> {
+    let args = env::args_os();
+
+    if cfg!(windows) {
+        args.enumerate()
+            .flat_map(|(i, arg)| match arg.to_str() {
+                Some(s) if i > 0 && !s.starts_with('-') && has_wildcards(s) => wildcard::expand(s)
+                    .into_iter()
+                    .map(OsString::from)
+                    .collect(),
+                _ => vec![arg],
+            })
+            .collect()
+    } else {
+        args.collect()
+    }
+}
+
+fn exit_with_error(message: &str, kind: clap::ErrorKind) -> ! {
+    let code = snake_case(&format!("{:?}", kind));
+    exit_with_report(&ErrorReport::new(code, message), kind)
+}
+
+// Exits when an argument is not valid UTF-8, unless it is the value of a path
+// argument. Other values are read as strings, which would panic.
+fn check_utf8_args(args: &[OsString], matches: &ArgMatches<'_>) {
+    let path_values: Vec<String> = iter::once(matches)
+        .chain(matches.subcommand().1)
+        .flat_map(|matches| {
+            PATH_ARGS
+                .iter()
+                .flat_map(move |arg| matches.values_of_os(arg).into_iter().flatten())
+        })
+        .map(|value| value.to_string_lossy().into_owned())
+        .collect();
+
+    for arg in args.iter().skip(1).filter(|arg| arg.to_str().is_none()) {
+        let arg = arg.to_string_lossy();
+        let value = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => value,
+            _ => &arg,
+        };
+
+        if !path_values.iter().any(|path| path == value) {
+            exit_with_error(
+                &format!("invalid UTF-8 in argument: {}", arg),
+                clap::ErrorKind::InvalidUtf8,
+            );
+        }
+    }
+}
+
+// Whether `--error-format json` is given, for errors from parsing the
+// arguments, before the matches are available.
+fn has_json_error_format(args: &[OsString]) -> bool {
+    let mut iter = args.iter().skip(1).map(|arg| arg.to_string_lossy());
+
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            break;
+        } else if arg == "--error-format" {
+            return iter.next().map(|value| value == "json").unwrap_or(false);
+        } else if let Some(value) = arg.strip_prefix("--error-format=") {
+            return value == "json";
+        }
+    }
+
+    env::var("NOODLES_FPKM_ERROR_FORMAT")
+        .map(|value| value == "json")
+        .unwrap_or(false)
+}
+
+// Exits with an error from clap, e.g., an argument value that cannot be
+// parsed. Help and version requests are written as usual.
+fn exit_with_clap_error(e: clap::Error) -> ! {
+    use clap::ErrorKind;
+
+    match e.kind {
+        ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+        kind if JSON_ERRORS.load(Ordering::Relaxed) => {
+            exit_with_error(&clap_error_message(&e.message), kind)
+        }
+        _ => e.exit(),
+    }
+}
+
+// Returns the first paragraph of a clap error message, i.e., without usage and
+// help, on one line and without the `error:` prefix and terminal colors.
+fn clap_error_message(s: &str) -> String {
+    let paragraph = s
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut message = String::new();
+    let mut chars = paragraph.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            message.push(c);
+        }
+    }
+
+    message
+        .strip_prefix("error: ")
+        .unwrap_or(&message)
+        .to_string()
+}
+
+fn exit_with_report(report: &ErrorReport, kind: clap::ErrorKind) -> ! {
+    remove_temp_dirs();
+
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        eprintln!("{}", report.to_json());
+        process::exit(1);
+    }
+
+    let mut message = String::new();
+
+    if let Some(file) = &report.file {
+        message.push_str(&format!("{}: ", file));
+    }
+
+    if let Some(n) = report.line_number {
+        message.push_str(&format!("line {}: ", n));
+    }
+
+    message.push_str(&report.message);
+
+    clap::Error::with_description(&message, kind).exit()
 }
 
 #[cfg(test)]
 mod tests {
+    use noodles_fpkm::gene_metadata::read_gene_metadata;
+
     use super::*;
 
+    #[test]
+    fn test_write_saturation() -> io::Result<()> {
+        let counts: Counts = [
+            (FeatureId::from("AAAS"), 5),
+            (FeatureId::from("RPL37AP1"), 5),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let samples = [(String::from("sample1"), counts)];
+
+        let mut buf = Vec::new();
+        write_saturation(&mut buf, &samples, Some(vec![1, 10, 20]), 0)?;
+
+        let actual = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            actual,
+            "sample\tdepth\tdetected\nsample1\t1\t1\nsample1\t10\t2\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_batch_adjustment_header() -> io::Result<()> {
+        let mut buf = Vec::new();
+        write_batch_adjustment_header(&mut buf, &[("b1", vec![0, 2]), ("b2", vec![1])])?;
+
+        let actual = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            actual,
+            "# batch adjustment: per-batch mean-centering of log2(x + 1) expression; batches: b1 (n=2), b2 (n=1)\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_has_json_error_format() {
+        let args = |args: &[&str]| -> Vec<OsString> { args.iter().map(OsString::from).collect() };
+
+        assert!(has_json_error_format(&args(&[
+            "noodles-fpkm",
+            "--error-format",
+            "json",
+            "s1.tsv"
+        ])));
+        assert!(has_json_error_format(&args(&[
+            "noodles-fpkm",
+            "--error-format=json"
+        ])));
+        assert!(!has_json_error_format(&args(&[
+            "noodles-fpkm",
+            "--error-format",
+            "text"
+        ])));
+        assert!(!has_json_error_format(&args(&[
+            "noodles-fpkm",
+            "--",
+            "--error-format=json"
+        ])));
+    }
+
+    #[test]
+    fn test_clap_error_message() {
+        assert_eq!(
+            clap_error_message(
+                "\u{1b}[1;31merror:\u{1b}[0m Invalid value for '--seed <n>': invalid digit\n\nUSAGE:"
+            ),
+            "Invalid value for '--seed <n>': invalid digit"
+        );
+
+        assert_eq!(
+            clap_error_message(
+                "error: The following required arguments were not provided:\n    --append <file>\n\nUSAGE:"
+            ),
+            "The following required arguments were not provided: --append <file>"
+        );
+    }
+
+    #[test]
+    fn test_read_comment_header() -> io::Result<()> {
+        let data = b"# batch adjustment: batches: b1 (n=2)\ntpm\ts1\n# g1\t1\n";
+        let actual = read_comment_header(&data[..])?;
+        assert_eq!(actual, ["# batch adjustment: batches: b1 (n=2)"]);
+
+        let data = b"tpm\ts1\ng1\t1\n";
+        assert!(read_comment_header(&data[..])?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_temp_dir() -> io::Result<()> {
+        let a = TempDir::new("noodles-fpkm-test")?;
+        let b = TempDir::new("noodles-fpkm-test")?;
+        assert_ne!(a.path(), b.path());
+
+        let path = a.path().to_path_buf();
+        std::fs::write(path.join("chunk-0.tsv"), "tpm\n")?;
+        assert!(TEMP_DIRS.lock().unwrap().contains(&path));
+
+        drop(a);
+        assert!(!path.exists());
+        assert!(!TEMP_DIRS.lock().unwrap().contains(&path));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_samples() -> io::Result<()> {
+        let dir = TempDir::new("noodles-fpkm-test")?;
+
+        let src = dir.path().join("counts.tsv");
+        std::fs::write(&src, "Geneid\tdata/sample1.bam\tsample2\nAAAS\t645\t512\n")?;
+
+        let samples = read_samples(&src, &ReadCountsOptions::default())?;
+        let names: Vec<_> = samples.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["sample1", "sample2"]);
+        assert_eq!(samples[1].1["AAAS"], 512);
+
+        std::fs::write(&src, "AAAS\t645\n")?;
+
+        let samples = read_samples(&src, &ReadCountsOptions::default())?;
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].0, "counts");
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_utf8_args() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStringExt};
+
+        let src = OsString::from_vec(b"sample\xff.txt".to_vec());
+        let args: Vec<OsString> = vec!["noodles-fpkm".into(), "-a".into(), "a.gtf".into(), src];
+
+        let matches = app().get_matches_from(&args);
+        check_utf8_args(&args, &matches);
+
+        let counts_srcs: Vec<&OsStr> = matches.values_of_os("counts").unwrap().collect();
+        assert_eq!(counts_srcs, [args[3].as_os_str()]);
+        assert_eq!(sample_name(counts_srcs[0]), "sample\u{fffd}");
+    }
+
+    #[test]
+    fn test_sample_name() {
+        assert_eq!(sample_name("counts/sample1.txt"), "sample1");
+        assert_eq!(sample_name("sample2"), "sample2");
+    }
+
+    #[test]
+    fn test_write_annotation_diff() -> io::Result<()> {
+        let changes = [
+            Change {
+                id: FeatureId::from("AAAS.2"),
+                kind: ChangeKind::Renamed(FeatureId::from("AAAS.1")),
+                old_length: Some(2000),
+                new_length: Some(2500),
+            },
+            Change {
+                id: FeatureId::from("XIST"),
+                kind: ChangeKind::Added,
+                old_length: None,
+                new_length: Some(3000),
+            },
+        ];
+
+        let mut buf = Vec::new();
+        write_annotation_diff(&mut buf, &changes, 0.1)?;
+
+        let expected = "\
+feature_id\tchange\told_feature_id\told_length\tnew_length\tfpkm_shift\tshifted
+AAAS.2\trenamed\tAAAS.1\t2000\t2500\t-0.19999999999999996\ttrue
+XIST\tadded\tNA\tNA\t3000\tNA\tfalse
+";
+
+        assert_eq!(String::from_utf8(buf).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_counts_table() -> io::Result<()> {
+        let ids = [FeatureId::from("AAAS"), FeatureId::from("RPL37AP1")];
+        let counts = |a, b| -> Counts { ids.iter().cloned().zip(vec![a, b]).collect() };
+
+        let mut buf = Vec::new();
+        write_counts_table(&mut buf, &ids, &[(String::from("sample1"), counts(3, 8))])?;
+        assert_eq!(buf, b"AAAS\t3\nRPL37AP1\t8\n");
+
+        let samples = [
+            (String::from("sample1"), counts(3, 8)),
+            (String::from("sample2"), counts(0, 13)),
+        ];
+
+        let mut buf = Vec::new();
+        write_counts_table(&mut buf, &ids, &samples)?;
+
+        let expected = "\
+Geneid\tsample1\tsample2
+AAAS\t3\t0
+RPL37AP1\t8\t13
+";
+
+        assert_eq!(String::from_utf8(buf).unwrap(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_matrix() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("AAAS"), String::from("RPL37AP1")],
+            vec![String::from("sample1"), String::from("sample2")],
+            ndarray::arr2(&[[1.0, 2.5], [0.0, 4.0]]),
+        );
+
+        let mut buf = Vec::new();
+        write_matrix(&mut buf, "tpm", &matrix).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "\
+tpm\tsample1\tsample2
+AAAS\t1\t2.5
+RPL37AP1\t0\t4
+";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_write_matrix_with_metadata() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("AAAS"), String::from("RPL37AP1")],
+            vec![String::from("sample1")],
+            ndarray::arr2(&[[1.0], [0.0]]),
+        );
+
+        let data = "gene_id\tsymbol\tbiotype\nAAAS\tAAAS\tprotein_coding\n";
+        let metadata = read_gene_metadata(data.as_bytes()).unwrap();
+
+        let mut buf = Vec::new();
+        write_matrix_with_metadata(&mut buf, "tpm", &matrix, &metadata).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "\
+tpm\tsymbol\tbiotype\tsample1
+AAAS\tAAAS\tprotein_coding\t1
+RPL37AP1\tNA\tNA\t0
+";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_build_count_matrix() {
+        let samples = [
+            (
+                String::from("sample1"),
+                [(FeatureId::from("AAAS"), 645)].iter().cloned().collect(),
+            ),
+            (
+                String::from("sample2"),
+                [(FeatureId::from("RPL37AP1"), 5714)]
+                    .iter()
+                    .cloned()
+                    .collect(),
+            ),
+        ];
+
+        let matrix = build_count_matrix(&samples);
+
+        assert_eq!(matrix.row_names(), ["AAAS", "RPL37AP1"]);
+        assert_eq!(matrix.get("AAAS", "sample1"), Some(645.0));
+        assert_eq!(matrix.get("AAAS", "sample2"), Some(0.0));
+    }
+
     #[test]
     fn test_write_expressions() {
         let fpkms = [
-            (String::from("AAAS"), 5825.440538780093),
-            (String::from("AC009952.3"), 10.494073576888187),
-            (String::from("RPL37AP1"), 3220170.8708099453),
-            (String::from("ZNF700"), 0.0),
+            (FeatureId::from("AAAS"), 5825.440538780093),
+            (FeatureId::from("AC009952.3"), 10.494073576888187),
+            (FeatureId::from("RPL37AP1"), 3220170.8708099453),
+            (FeatureId::from("ZNF700"), 0.0),
         ]
         .iter()
         .cloned()
@@ -129,4 +1685,49 @@ ZNF700\t0
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_write_expressions_in_order() {
+        let fpkms = [
+            (FeatureId::from("AAAS"), 5825.5),
+            (FeatureId::from("RPL37AP1"), 10.5),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let ids = [
+            String::from("RPL37AP1"),
+            String::from("ZNF700"),
+            String::from("AAAS"),
+        ];
+
+        let mut buf = Vec::new();
+        write_expressions_in_order(&mut buf, &fpkms, &ids, None).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "RPL37AP1\t10.5\nZNF700\t0\nAAAS\t5825.5\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_write_stranded_expressions() {
+        let sense_fpkms = [
+            (FeatureId::from("AAAS"), 5825.5),
+            (FeatureId::from("ZNF700"), 0.0),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let antisense_fpkms = [(FeatureId::from("AAAS"), 8.25)].iter().cloned().collect();
+
+        let mut buf = Vec::new();
+        write_stranded_expressions(&mut buf, &sense_fpkms, &antisense_fpkms).unwrap();
+
+        let actual = String::from_utf8(buf).unwrap();
+        let expected = "AAAS\t5825.5\t8.25\nZNF700\t0\t0\n";
+
+        assert_eq!(actual, expected);
+    }
 }
@@ -0,0 +1,657 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{self, Read},
+};
+
+use csv::StringRecord;
+use ndarray::{Array2, ArrayView1, Axis};
+
+use crate::{features::with_line_number, Expressions, HashBuilder};
+
+const COMMENT_PREFIX: char = '#';
+static SUMMARY_ROW_PREFIX: &str = "__";
+
+/// A dense matrix of expression values.
+///
+/// Rows are features, and columns are samples. Both axes are named, and names
+/// can be used to look up their indices.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpressionMatrix {
+    row_names: Vec<String>,
+    column_names: Vec<String>,
+    row_indices: HashMap<String, usize, HashBuilder>,
+    column_indices: HashMap<String, usize, HashBuilder>,
+    data: Array2<f64>,
+}
+
+impl ExpressionMatrix {
+    /// Creates a matrix from row names, column names, and a data array.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the shape of `data` does not match the number of row and
+    /// column names or when the names on either axis are not unique.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ndarray::arr2;
+    /// use noodles_fpkm::matrix::ExpressionMatrix;
+    ///
+    /// let matrix = ExpressionMatrix::new(
+    ///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+    ///     vec![String::from("sample1"), String::from("sample2")],
+    ///     arr2(&[[1.0, 2.0], [3.0, 4.0]]),
+    /// );
+    ///
+    /// assert_eq!(matrix.get("RPL37AP1", "sample1"), Some(3.0));
+    /// ```
+    pub fn new(
+        row_names: Vec<String>,
+        column_names: Vec<String>,
+        data: Array2<f64>,
+    ) -> ExpressionMatrix {
+        assert_eq!(data.dim(), (row_names.len(), column_names.len()));
+
+        let row_indices = build_index(&row_names);
+        let column_indices = build_index(&column_names);
+
+        ExpressionMatrix {
+            row_names,
+            column_names,
+            row_indices,
+            column_indices,
+            data,
+        }
+    }
+
+    /// Builds a matrix from a list of named samples.
+    ///
+    /// The rows are the union of all feature IDs in the samples, sorted
+    /// lexicographically. Features absent from a sample are given a value of
+    /// 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics when sample names are not unique.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::{feature_id::FeatureId, matrix::ExpressionMatrix, Expressions};
+    ///
+    /// let a: Expressions = [(FeatureId::from("AAAS"), 1.0)].iter().cloned().collect();
+    /// let b: Expressions = [(FeatureId::from("RPL37AP1"), 2.0)].iter().cloned().collect();
+    ///
+    /// let matrix = ExpressionMatrix::from_samples(&[
+    ///     (String::from("sample1"), a),
+    ///     (String::from("sample2"), b),
+    /// ]);
+    ///
+    /// assert_eq!(matrix.row_names(), ["AAAS", "RPL37AP1"]);
+    /// assert_eq!(matrix.get("AAAS", "sample2"), Some(0.0));
+    /// assert_eq!(matrix.get("RPL37AP1", "sample2"), Some(2.0));
+    /// ```
+    pub fn from_samples(samples: &[(String, Expressions)]) -> ExpressionMatrix {
+        let columns: Vec<_> = samples
+            .iter()
+            .map(|(name, expressions)| (name.as_str(), expressions))
+            .collect();
+
+        ExpressionMatrix::from_columns(&columns)
+    }
+
+    pub(crate) fn from_columns(columns: &[(&str, &Expressions)]) -> ExpressionMatrix {
+        let row_names: Vec<String> = columns
+            .iter()
+            .flat_map(|(_, expressions)| expressions.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        let column_names: Vec<String> = columns.iter().map(|(name, _)| name.to_string()).collect();
+
+        let mut data = Array2::zeros((row_names.len(), column_names.len()));
+
+        for (i, row_name) in row_names.iter().enumerate() {
+            for (j, (_, expressions)) in columns.iter().enumerate() {
+                if let Some(&value) = expressions.get(row_name.as_str()) {
+                    data[[i, j]] = value;
+                }
+            }
+        }
+
+        ExpressionMatrix::new(row_names, column_names, data)
+    }
+
+    /// Returns the feature IDs of the rows.
+    pub fn row_names(&self) -> &[String] {
+        &self.row_names
+    }
+
+    /// Returns the sample names of the columns.
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    /// Returns the index of the row with the given feature ID.
+    pub fn row_index(&self, name: &str) -> Option<usize> {
+        self.row_indices.get(name).cloned()
+    }
+
+    /// Returns the index of the column with the given sample name.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.column_indices.get(name).cloned()
+    }
+
+    /// Returns the underlying data array.
+    pub fn data(&self) -> &Array2<f64> {
+        &self.data
+    }
+
+    /// Returns a mutable reference to the underlying data array.
+    ///
+    /// The shape of the array cannot be changed through this reference.
+    pub fn data_mut(&mut self) -> &mut Array2<f64> {
+        &mut self.data
+    }
+
+    /// Returns the value at the given feature ID and sample name.
+    pub fn get(&self, row_name: &str, column_name: &str) -> Option<f64> {
+        let i = self.row_index(row_name)?;
+        let j = self.column_index(column_name)?;
+        Some(self.data[[i, j]])
+    }
+
+    /// Returns the values of a single sample.
+    pub fn column(&self, name: &str) -> Option<ArrayView1<'_, f64>> {
+        self.column_index(name).map(|j| self.data.column(j))
+    }
+
+    /// Returns the values of a single feature across all samples.
+    pub fn row(&self, name: &str) -> Option<ArrayView1<'_, f64>> {
+        self.row_index(name).map(|i| self.data.row(i))
+    }
+
+    /// Returns a binary matrix of whether each feature is detected in each
+    /// sample.
+    ///
+    /// A feature is detected (1) when its value is at least `threshold`, e.g.,
+    /// a TPM of 1; otherwise, it is not detected (0).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ndarray::arr2;
+    /// use noodles_fpkm::matrix::ExpressionMatrix;
+    ///
+    /// let matrix = ExpressionMatrix::new(
+    ///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+    ///     vec![String::from("sample1"), String::from("sample2")],
+    ///     arr2(&[[0.5, 2.0], [1.0, 0.0]]),
+    /// );
+    ///
+    /// let detected = matrix.detect(1.0);
+    ///
+    /// assert_eq!(detected.get("AAAS", "sample1"), Some(0.0));
+    /// assert_eq!(detected.get("AAAS", "sample2"), Some(1.0));
+    /// assert_eq!(detected.get("RPL37AP1", "sample1"), Some(1.0));
+    /// ```
+    pub fn detect(&self, threshold: f64) -> ExpressionMatrix {
+        let data = self
+            .data
+            .mapv(|value| if value >= threshold { 1.0 } else { 0.0 });
+
+        ExpressionMatrix {
+            row_names: self.row_names.clone(),
+            column_names: self.column_names.clone(),
+            row_indices: self.row_indices.clone(),
+            column_indices: self.column_indices.clone(),
+            data,
+        }
+    }
+
+    /// Returns a matrix of the rows with a value of at least `min_value` in
+    /// at least `min_fraction` of the columns.
+    ///
+    /// The order of the remaining rows is unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ndarray::arr2;
+    /// use noodles_fpkm::matrix::ExpressionMatrix;
+    ///
+    /// let matrix = ExpressionMatrix::new(
+    ///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+    ///     vec![String::from("sample1"), String::from("sample2")],
+    ///     arr2(&[[0.5, 2.0], [0.0, 0.5]]),
+    /// );
+    ///
+    /// let filtered = matrix.filter_by_prevalence(1.0, 0.5);
+    ///
+    /// assert_eq!(filtered.row_names(), ["AAAS"]);
+    /// assert_eq!(filtered.data(), &arr2(&[[0.5, 2.0]]));
+    /// ```
+    pub fn filter_by_prevalence(&self, min_value: f64, min_fraction: f64) -> ExpressionMatrix {
+        let column_count = self.column_names.len() as f64;
+
+        let row_indices: Vec<usize> = self
+            .data
+            .outer_iter()
+            .enumerate()
+            .filter(|(_, row)| {
+                let n = row.iter().filter(|&&value| value >= min_value).count();
+                n as f64 >= min_fraction * column_count
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let row_names = row_indices
+            .iter()
+            .map(|&i| self.row_names[i].clone())
+            .collect();
+
+        let data = self.data.select(Axis(0), &row_indices);
+
+        ExpressionMatrix::new(row_names, self.column_names.clone(), data)
+    }
+
+    /// Returns a matrix with the given rows, in the given order.
+    ///
+    /// Rows not in this matrix are given a value of 0 in every column, and
+    /// rows not given are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the row names are not unique.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ndarray::arr2;
+    /// use noodles_fpkm::matrix::ExpressionMatrix;
+    ///
+    /// let matrix = ExpressionMatrix::new(
+    ///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+    ///     vec![String::from("sample1")],
+    ///     arr2(&[[1.0], [2.0]]),
+    /// );
+    ///
+    /// let matrix = matrix.select_rows(&[String::from("ZZZ3"), String::from("AAAS")]);
+    ///
+    /// assert_eq!(matrix.row_names(), ["ZZZ3", "AAAS"]);
+    /// assert_eq!(matrix.data(), &arr2(&[[0.0], [1.0]]));
+    /// ```
+    pub fn select_rows(&self, row_names: &[String]) -> ExpressionMatrix {
+        let mut data = Array2::zeros((row_names.len(), self.column_names.len()));
+
+        for (i, name) in row_names.iter().enumerate() {
+            if let Some(j) = self.row_index(name) {
+                data.row_mut(i).assign(&self.data.row(j));
+            }
+        }
+
+        ExpressionMatrix::new(row_names.to_vec(), self.column_names.clone(), data)
+    }
+
+    /// Returns a matrix with the given columns, in the given order.
+    ///
+    /// Columns not given are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics when a column name is not in this matrix or when the column
+    /// names are not unique.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ndarray::arr2;
+    /// use noodles_fpkm::matrix::ExpressionMatrix;
+    ///
+    /// let matrix = ExpressionMatrix::new(
+    ///     vec![String::from("AAAS")],
+    ///     vec![String::from("sample1"), String::from("sample2"), String::from("sample3")],
+    ///     arr2(&[[1.0, 2.0, 3.0]]),
+    /// );
+    ///
+    /// let matrix = matrix.select_columns(&[String::from("sample3"), String::from("sample1")]);
+    ///
+    /// assert_eq!(matrix.column_names(), ["sample3", "sample1"]);
+    /// assert_eq!(matrix.data(), &arr2(&[[3.0, 1.0]]));
+    /// ```
+    pub fn select_columns(&self, column_names: &[String]) -> ExpressionMatrix {
+        let column_indices: Vec<usize> = column_names
+            .iter()
+            .map(|name| {
+                self.column_index(name)
+                    .unwrap_or_else(|| panic!("missing column: {}", name))
+            })
+            .collect();
+
+        let data = self.data.select(Axis(1), &column_indices);
+
+        ExpressionMatrix::new(self.row_names.clone(), column_names.to_vec(), data)
+    }
+
+    /// Returns the transpose of this matrix, i.e., rows become columns and
+    /// columns become rows.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ndarray::arr2;
+    /// use noodles_fpkm::matrix::ExpressionMatrix;
+    ///
+    /// let matrix = ExpressionMatrix::new(
+    ///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+    ///     vec![String::from("sample1")],
+    ///     arr2(&[[1.0], [2.0]]),
+    /// );
+    ///
+    /// let transposed = matrix.transpose();
+    ///
+    /// assert_eq!(transposed.row_names(), ["sample1"]);
+    /// assert_eq!(transposed.column_names(), ["AAAS", "RPL37AP1"]);
+    /// assert_eq!(transposed.get("sample1", "RPL37AP1"), Some(2.0));
+    /// ```
+    pub fn transpose(&self) -> ExpressionMatrix {
+        ExpressionMatrix {
+            row_names: self.column_names.clone(),
+            column_names: self.row_names.clone(),
+            row_indices: self.column_indices.clone(),
+            column_indices: self.row_indices.clone(),
+            data: self.data.t().to_owned(),
+        }
+    }
+
+    /// Returns a matrix with the columns of another matrix appended.
+    ///
+    /// The rows of `other` are reordered to match the rows of this matrix.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the matrices do not have the same set of row names or
+    /// when a column name is in both matrices.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ndarray::arr2;
+    /// use noodles_fpkm::matrix::ExpressionMatrix;
+    ///
+    /// let matrix = ExpressionMatrix::new(
+    ///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+    ///     vec![String::from("sample1")],
+    ///     arr2(&[[1.0], [2.0]]),
+    /// );
+    ///
+    /// let other = ExpressionMatrix::new(
+    ///     vec![String::from("RPL37AP1"), String::from("AAAS")],
+    ///     vec![String::from("sample2")],
+    ///     arr2(&[[4.0], [3.0]]),
+    /// );
+    ///
+    /// let matrix = matrix.append_columns(&other);
+    ///
+    /// assert_eq!(matrix.column_names(), ["sample1", "sample2"]);
+    /// assert_eq!(matrix.get("AAAS", "sample2"), Some(3.0));
+    /// ```
+    pub fn append_columns(&self, other: &ExpressionMatrix) -> ExpressionMatrix {
+        assert_eq!(
+            self.row_names.len(),
+            other.row_names.len(),
+            "mismatched row names"
+        );
+
+        let row_indices: Vec<usize> = self
+            .row_names
+            .iter()
+            .map(|name| other.row_index(name).expect("mismatched row names"))
+            .collect();
+
+        let other_data = other.data.select(Axis(0), &row_indices);
+        let data = ndarray::stack(Axis(1), &[self.data.view(), other_data.view()]).unwrap();
+
+        let column_names = self
+            .column_names
+            .iter()
+            .chain(other.column_names.iter())
+            .cloned()
+            .collect();
+
+        ExpressionMatrix::new(self.row_names.clone(), column_names, data)
+    }
+}
+
+/// Reads a TSV-formatted expression matrix.
+///
+/// The first row is a header: a label, e.g., the unit of the values, followed
+/// by the column names. Each subsequent row is a row name followed by a value
+/// per column. The label and matrix are returned.
+///
+/// Comment lines, i.e., lines that start with `#`, are skipped, as are summary
+/// rows, i.e., rows whose names start with `__` (e.g., `__detected`), so
+/// matrices written with provenance headers or totals can be read back.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::matrix::read_matrix;
+///
+/// let data = "\
+/// ## batch adjustment: none
+/// tpm\tsample1\tsample2
+/// AAAS\t1.5\t2
+/// RPL37AP1\t3\t0
+/// __detected\t2\t1
+/// ";
+///
+/// let (label, matrix) = read_matrix(data.as_bytes())?;
+///
+/// assert_eq!(label, "tpm");
+/// assert_eq!(matrix.column_names(), ["sample1", "sample2"]);
+/// assert_eq!(matrix.row_names(), ["AAAS", "RPL37AP1"]);
+/// assert_eq!(matrix.get("AAAS", "sample1"), Some(1.5));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_matrix<R>(reader: R) -> io::Result<(String, ExpressionMatrix)>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_reader(reader);
+
+    let mut record = StringRecord::new();
+
+    // Comments are skipped here rather than by the CSV reader, which does not
+    // count them in record positions.
+    loop {
+        if !rdr.read_record(&mut record)? {
+            return Err(invalid_data(String::from("missing header")));
+        } else if !is_comment(&record) {
+            break;
+        }
+    }
+
+    let header_line_number = record.position().map(|p| p.line()).unwrap_or(1);
+    let label = record.get(0).unwrap_or_default().to_string();
+    let column_names: Vec<String> = record.iter().skip(1).map(String::from).collect();
+
+    if let Some(name) = find_duplicate(&column_names) {
+        let e = invalid_data(format!("duplicate column name: {}", name));
+        return Err(with_line_number(e, header_line_number));
+    }
+
+    let mut row_names = Vec::new();
+    let mut seen_row_names = BTreeSet::new();
+    let mut values = Vec::new();
+
+    while rdr.read_record(&mut record)? {
+        let line_number = record.position().map(|p| p.line()).unwrap_or_default();
+
+        if is_comment(&record) || record[0].starts_with(SUMMARY_ROW_PREFIX) {
+            continue;
+        }
+
+        if record.len() != column_names.len() + 1 {
+            return Err(with_line_number(
+                invalid_data(format!(
+                    "invalid number of fields: expected {}, got {}",
+                    column_names.len() + 1,
+                    record.len()
+                )),
+                line_number,
+            ));
+        }
+
+        let row_name = &record[0];
+
+        if !seen_row_names.insert(row_name.to_string()) {
+            let e = invalid_data(format!("duplicate row name: {}", row_name));
+            return Err(with_line_number(e, line_number));
+        }
+
+        row_names.push(row_name.to_string());
+
+        for field in record.iter().skip(1) {
+            let value = field.parse().map_err(|_| {
+                with_line_number(
+                    invalid_data(format!("invalid value: {}", field)),
+                    line_number,
+                )
+            })?;
+
+            values.push(value);
+        }
+    }
+
+    let data = Array2::from_shape_vec((row_names.len(), column_names.len()), values)
+        .map_err(|e| invalid_data(e.to_string()))?;
+
+    Ok((label, ExpressionMatrix::new(row_names, column_names, data)))
+}
+
+fn is_comment(record: &StringRecord) -> bool {
+    record
+        .get(0)
+        .is_some_and(|field| field.starts_with(COMMENT_PREFIX))
+}
+
+fn find_duplicate(names: &[String]) -> Option<&String> {
+    let mut seen = BTreeSet::new();
+    names.iter().find(|name| !seen.insert(*name))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn build_index(names: &[String]) -> HashMap<String, usize, HashBuilder> {
+    let mut index = HashMap::with_capacity_and_hasher(names.len(), Default::default());
+
+    for (i, name) in names.iter().enumerate() {
+        let previous = index.insert(name.clone(), i);
+        assert!(previous.is_none(), "duplicate name '{}'", name);
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    #[should_panic]
+    fn test_new_with_mismatched_shape() {
+        ExpressionMatrix::new(
+            vec![String::from("AAAS")],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[1.0], [2.0]]),
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_with_duplicate_names() {
+        ExpressionMatrix::new(
+            vec![String::from("AAAS"), String::from("AAAS")],
+            vec![String::from("sample1")],
+            arr2(&[[1.0], [2.0]]),
+        );
+    }
+
+    #[test]
+    fn test_read_matrix_with_invalid_input() {
+        assert!(read_matrix(&b""[..]).is_err());
+
+        let e = read_matrix(&b"tpm\ts1\ts1\n"[..]).unwrap_err();
+        assert_eq!(e.to_string(), "line 1: duplicate column name: s1");
+
+        let e = read_matrix(&b"tpm\ts1\nAAAS\t1\nAAAS\t2\n"[..]).unwrap_err();
+        assert_eq!(e.to_string(), "line 3: duplicate row name: AAAS");
+
+        let e = read_matrix(&b"tpm\ts1\nAAAS\tx\n"[..]).unwrap_err();
+        assert_eq!(e.to_string(), "line 2: invalid value: x");
+
+        let e = read_matrix(&b"# comment\ntpm\ts1\ts1\n"[..]).unwrap_err();
+        assert_eq!(e.to_string(), "line 2: duplicate column name: s1");
+
+        let e = read_matrix(&b"# comment\ntpm\ts1\nAAAS\tx\n"[..]).unwrap_err();
+        assert_eq!(e.to_string(), "line 3: invalid value: x");
+
+        assert!(read_matrix(&b"tpm\ts1\nAAAS\t1\t2\n"[..]).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_append_columns_with_mismatched_rows() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("AAAS")],
+            vec![String::from("sample1")],
+            arr2(&[[1.0]]),
+        );
+
+        let other = ExpressionMatrix::new(
+            vec![String::from("RPL37AP1")],
+            vec![String::from("sample2")],
+            arr2(&[[2.0]]),
+        );
+
+        matrix.append_columns(&other);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_select_columns_with_missing_column() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("AAAS")],
+            vec![String::from("sample1")],
+            arr2(&[[1.0]]),
+        );
+
+        matrix.select_columns(&[String::from("sample2")]);
+    }
+
+    #[test]
+    fn test_column() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("AAAS"), String::from("RPL37AP1")],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[1.0, 2.0], [3.0, 4.0]]),
+        );
+
+        let column = matrix.column("sample2").unwrap();
+        assert_eq!(column.to_vec(), [2.0, 4.0]);
+
+        assert!(matrix.column("sample3").is_none());
+    }
+}
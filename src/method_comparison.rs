@@ -0,0 +1,392 @@
+use std::cmp::Ordering;
+
+use crate::{
+    calculate_fpkms, calculate_tpms,
+    counts::{sum_counts, Counts},
+    feature_id::FeatureId,
+    lengths::Lengths,
+    quantile_mapping::average_ranks,
+    Error, Expressions,
+};
+
+// The fractions trimmed from each end of the log ratios and mean log
+// expressions in TMM, as in edgeR.
+const LOG_RATIO_TRIM: f64 = 0.3;
+const SUM_TRIM: f64 = 0.05;
+
+/// A normalization method compared by [`compare_methods`].
+///
+/// [`compare_methods`]: fn.compare_methods.html
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ComparedMethod {
+    /// Fragments per kilobase per million mapped reads.
+    Fpkm,
+    /// Transcripts per million.
+    Tpm,
+    /// FPKM normalized by the upper quartile count rather than the total
+    /// count, as in the GDC mRNA quantification pipeline.
+    FpkmUq,
+    /// Counts per million of the library size scaled by the TMM normalization
+    /// factor, as in edgeR.
+    TmmCpm,
+}
+
+impl ComparedMethod {
+    /// Returns the name of the method, as used in the header of a report.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ComparedMethod::Fpkm => "fpkm",
+            ComparedMethod::Tpm => "tpm",
+            ComparedMethod::FpkmUq => "fpkm_uq",
+            ComparedMethod::TmmCpm => "tmm_cpm",
+        }
+    }
+}
+
+/// The methods compared by [`compare_methods`], in the order of their values
+/// and ranks.
+///
+/// [`compare_methods`]: fn.compare_methods.html
+pub const COMPARED_METHODS: [ComparedMethod; 4] = [
+    ComparedMethod::Fpkm,
+    ComparedMethod::Tpm,
+    ComparedMethod::FpkmUq,
+    ComparedMethod::TmmCpm,
+];
+
+/// The values and ranks of a feature under each of the [`COMPARED_METHODS`].
+///
+/// [`COMPARED_METHODS`]: constant.COMPARED_METHODS.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodComparison {
+    /// The feature ID.
+    pub id: FeatureId,
+    /// The expression value under each method.
+    pub values: [f64; 4],
+    /// The 1-based rank of the value under each method, where 1 is the most
+    /// expressed. Tied values are given the average of their ranks.
+    pub ranks: [f64; 4],
+}
+
+impl MethodComparison {
+    /// Returns the largest difference between the ranks of the feature under
+    /// two methods.
+    pub fn max_rank_shift(&self) -> f64 {
+        let min = self.ranks.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = self.ranks.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        max - min
+    }
+}
+
+/// Calculates FPKM-UQs.
+///
+/// This is FPKM with the upper quartile (75th percentile, by nearest rank) of
+/// the nonzero counts in place of the total count.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     counts::Counts, feature_id::FeatureId, lengths::Lengths,
+///     method_comparison::calculate_fpkm_uqs,
+/// };
+///
+/// let counts: Counts = [
+///     (FeatureId::from("AAAS"), 100),
+///     (FeatureId::from("AC009952.3"), 0),
+///     (FeatureId::from("RPL37AP1"), 400),
+/// ].iter().cloned().collect();
+///
+/// let lengths: Lengths = [
+///     (FeatureId::from("AAAS"), 1000),
+///     (FeatureId::from("AC009952.3"), 1000),
+///     (FeatureId::from("RPL37AP1"), 2000),
+/// ].iter().cloned().collect();
+///
+/// let fpkm_uqs = calculate_fpkm_uqs(&counts, &lengths).unwrap();
+///
+/// assert_eq!(fpkm_uqs[&FeatureId::from("AAAS")], 250000.0);
+/// assert_eq!(fpkm_uqs[&FeatureId::from("RPL37AP1")], 500000.0);
+/// ```
+pub fn calculate_fpkm_uqs(counts: &Counts, lengths: &Lengths) -> Result<Expressions, Error> {
+    let upper_quartile = upper_quartile(counts).ok_or(Error::ZeroDenominator)?;
+    let total = sum_counts(counts) as f64;
+
+    // FPKM is normalized by the total count, so it is rescaled by the ratio
+    // of the total count to the upper quartile.
+    let scale = total / upper_quartile as f64;
+
+    calculate_fpkms(counts, lengths).map(|fpkms| {
+        fpkms
+            .into_iter()
+            .map(|(id, fpkm)| (id, fpkm * scale))
+            .collect()
+    })
+}
+
+/// Calculates TMM-normalized CPMs.
+///
+/// The library size is the total count scaled by the TMM normalization factor
+/// of the sample, e.g., from [`calculate_tmm_factors`].
+///
+/// [`calculate_tmm_factors`]: fn.calculate_tmm_factors.html
+pub fn calculate_tmm_cpms(counts: &Counts, tmm_factor: f64) -> Result<Expressions, Error> {
+    let library_size = sum_counts(counts) as f64 * tmm_factor;
+
+    if library_size == 0.0 {
+        return Err(Error::ZeroDenominator);
+    }
+
+    Ok(counts
+        .iter()
+        .map(|(id, &count)| (id.clone(), count as f64 * 1e6 / library_size))
+        .collect())
+}
+
+/// Calculates the TMM (trimmed mean of M values) normalization factor of a
+/// sample relative to a reference sample, as in edgeR's `calcNormFactors`.
+///
+/// Features counted in both samples are trimmed by their log ratios (30% from
+/// each end) and mean log expressions (5% from each end), and the factor is
+/// the precision-weighted mean of the remaining log ratios. This returns 1 if
+/// no feature remains.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{counts::Counts, feature_id::FeatureId, method_comparison::calculate_tmm_factor};
+///
+/// let counts: Counts = [
+///     (FeatureId::from("AAAS"), 100),
+///     (FeatureId::from("RPL37AP1"), 400),
+/// ].iter().cloned().collect();
+///
+/// assert_eq!(calculate_tmm_factor(&counts, &counts), 1.0);
+/// ```
+pub fn calculate_tmm_factor(counts: &Counts, reference: &Counts) -> f64 {
+    let observed_size = sum_counts(counts) as f64;
+    let reference_size = sum_counts(reference) as f64;
+
+    let mut log_ratios = Vec::new();
+    let mut abundances = Vec::new();
+    let mut variances = Vec::new();
+
+    for (id, &observed) in counts {
+        let reference = match reference.get(id) {
+            Some(&reference) if reference > 0 && observed > 0 => reference as f64,
+            _ => continue,
+        };
+
+        let observed = observed as f64;
+        let p = observed / observed_size;
+        let q = reference / reference_size;
+
+        log_ratios.push((p / q).log2());
+        abundances.push((p.log2() + q.log2()) / 2.0);
+        variances.push(
+            (observed_size - observed) / observed_size / observed
+                + (reference_size - reference) / reference_size / reference,
+        );
+    }
+
+    if log_ratios.iter().all(|r| r.abs() < 1e-6) {
+        return 1.0;
+    }
+
+    let n = log_ratios.len() as f64;
+    let (lo_l, hi_l) = trim_bounds(n, LOG_RATIO_TRIM);
+    let (lo_s, hi_s) = trim_bounds(n, SUM_TRIM);
+
+    let log_ratio_ranks = average_ranks(&log_ratios);
+    let abundance_ranks = average_ranks(&abundances);
+
+    let mut numerator = 0.0;
+    let mut denominator = 0.0;
+
+    for i in 0..log_ratios.len() {
+        let (l, s) = (log_ratio_ranks[i], abundance_ranks[i]);
+
+        if l >= lo_l && l <= hi_l && s >= lo_s && s <= hi_s {
+            numerator += log_ratios[i] / variances[i];
+            denominator += 1.0 / variances[i];
+        }
+    }
+
+    let f = numerator / denominator;
+
+    if f.is_finite() {
+        f.exp2()
+    } else {
+        1.0
+    }
+}
+
+/// Calculates the TMM normalization factors of samples.
+///
+/// As in edgeR, the reference sample is the sample whose upper quartile count,
+/// relative to its total count, is nearest the mean of the samples, and the
+/// factors are scaled to a geometric mean of 1. A single sample has a factor
+/// of 1.
+pub fn calculate_tmm_factors(samples: &[&Counts]) -> Vec<f64> {
+    let upper_quartile_fractions: Vec<f64> = samples
+        .iter()
+        .map(|counts| {
+            let total = sum_counts(counts) as f64;
+            upper_quartile(counts).map_or(0.0, |q| q as f64 / total)
+        })
+        .collect();
+
+    let mean = upper_quartile_fractions.iter().sum::<f64>() / samples.len() as f64;
+
+    let reference = match upper_quartile_fractions
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            let (a, b) = ((*a - mean).abs(), (*b - mean).abs());
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        }) {
+        Some((i, _)) => samples[i],
+        None => return Vec::new(),
+    };
+
+    let factors: Vec<f64> = samples
+        .iter()
+        .map(|counts| calculate_tmm_factor(counts, reference))
+        .collect();
+
+    let log_mean = factors.iter().map(|f| f.ln()).sum::<f64>() / factors.len() as f64;
+    let geometric_mean = log_mean.exp();
+
+    factors.into_iter().map(|f| f / geometric_mean).collect()
+}
+
+/// Calculates the expression values of a sample under each of the
+/// [`COMPARED_METHODS`] and ranks them.
+///
+/// The comparisons are sorted by feature ID. FPKM, TPM, and FPKM-UQ only
+/// differ by a per-sample scale, so their ranks are the same; the ranks shift
+/// between them and TMM-CPM, which does not normalize by length.
+///
+/// [`COMPARED_METHODS`]: constant.COMPARED_METHODS.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     counts::Counts, feature_id::FeatureId, lengths::Lengths,
+///     method_comparison::compare_methods,
+/// };
+///
+/// let counts: Counts = [
+///     (FeatureId::from("AAAS"), 100),
+///     (FeatureId::from("RPL37AP1"), 400),
+/// ].iter().cloned().collect();
+///
+/// let lengths: Lengths = [
+///     (FeatureId::from("AAAS"), 100),
+///     (FeatureId::from("RPL37AP1"), 8000),
+/// ].iter().cloned().collect();
+///
+/// let comparisons = compare_methods(&counts, &lengths, 1.0).unwrap();
+///
+/// assert_eq!(comparisons[0].id, FeatureId::from("AAAS"));
+/// assert_eq!(comparisons[0].ranks, [1.0, 1.0, 1.0, 2.0]);
+/// assert_eq!(comparisons[0].max_rank_shift(), 1.0);
+/// ```
+pub fn compare_methods(
+    counts: &Counts,
+    lengths: &Lengths,
+    tmm_factor: f64,
+) -> Result<Vec<MethodComparison>, Error> {
+    let fpkms = calculate_fpkms(counts, lengths)?;
+    let tpms = calculate_tpms(counts, lengths)?;
+    let fpkm_uqs = calculate_fpkm_uqs(counts, lengths)?;
+    let tmm_cpms: Expressions = calculate_tmm_cpms(counts, tmm_factor)?;
+
+    let columns: Vec<Vec<f64>> = [&fpkms, &tpms, &fpkm_uqs, &tmm_cpms]
+        .iter()
+        .map(|values| values.values().copied().collect())
+        .collect();
+
+    // Ranks are descending, so the negated values are ranked.
+    let ranks: Vec<Vec<f64>> = columns
+        .iter()
+        .map(|values| {
+            let negated: Vec<f64> = values.iter().map(|v| -v).collect();
+            average_ranks(&negated)
+        })
+        .collect();
+
+    Ok(fpkms
+        .keys()
+        .enumerate()
+        .map(|(i, id)| MethodComparison {
+            id: id.clone(),
+            values: [columns[0][i], columns[1][i], columns[2][i], columns[3][i]],
+            ranks: [ranks[0][i], ranks[1][i], ranks[2][i], ranks[3][i]],
+        })
+        .collect())
+}
+
+// Returns the upper quartile, by nearest rank, of the nonzero counts.
+fn upper_quartile(counts: &Counts) -> Option<u64> {
+    let mut values: Vec<u64> = counts.values().copied().filter(|&n| n > 0).collect();
+
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_unstable();
+
+    let rank = (0.75 * values.len() as f64).ceil() as usize;
+    Some(values[rank.max(1) - 1])
+}
+
+// Returns the 1-based bounds of the ranks kept after trimming a fraction from
+// each end of n values, as in edgeR.
+fn trim_bounds(n: f64, fraction: f64) -> (f64, f64) {
+    let lo = (n * fraction).floor() + 1.0;
+    let hi = n + 1.0 - lo;
+    (lo, hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculate_tmm_factors() {
+        let reference: Counts = (1..=20)
+            .map(|i| (FeatureId::from(format!("g{}", i).as_str()), i * 10))
+            .collect();
+
+        // A sample sequenced twice as deep has the same composition.
+        let deeper: Counts = reference
+            .iter()
+            .map(|(id, &count)| (id.clone(), count * 2))
+            .collect();
+
+        assert_eq!(calculate_tmm_factor(&deeper, &reference), 1.0);
+
+        // A single highly expressed feature takes up half of the library, so
+        // the other features are undersampled by half.
+        let mut composed = reference.clone();
+        let total = sum_counts(&reference);
+        composed.insert(FeatureId::from("g0"), total);
+
+        let factor = calculate_tmm_factor(&composed, &reference);
+        assert!((factor - 0.5).abs() < 1e-9, "factor = {}", factor);
+
+        let factors = calculate_tmm_factors(&[&reference, &composed]);
+        assert!((factors[0] * factors[1] - 1.0).abs() < 1e-9);
+        assert!((factors[1] / factors[0] - 0.5).abs() < 1e-9);
+
+        assert_eq!(calculate_tmm_factors(&[&reference]), [1.0]);
+    }
+
+    #[test]
+    fn test_calculate_tmm_cpms_with_zero_library_size() {
+        let counts: Counts = [(FeatureId::from("AAAS"), 0)].iter().cloned().collect();
+        assert!(calculate_tmm_cpms(&counts, 1.0).is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{compression::open, sparse::SparseExpressionMatrix};
+
+static HEADER: &str = "%%MatrixMarket matrix coordinate real general";
+static HEADER_PREFIX: &str = "%%MatrixMarket matrix coordinate";
+const COMMENT_PREFIX: char = '%';
+
+static MATRIX_FILE_NAMES: &[&str] = &["matrix.mtx.gz", "matrix.mtx"];
+static FEATURES_FILE_NAMES: &[&str] = &["features.tsv.gz", "features.tsv", "genes.tsv"];
+static BARCODES_FILE_NAMES: &[&str] = &["barcodes.tsv.gz", "barcodes.tsv"];
+
+/// Reads a 10x Genomics feature-barcode matrix directory.
+///
+/// The directory must contain a matrix (`matrix.mtx`), features
+/// (`features.tsv` or, for Cell Ranger < 3.0, `genes.tsv`), and barcodes
+/// (`barcodes.tsv`). Each file may be gzip-compressed (`.gz`).
+///
+/// The row names are the first column of the features list, i.e., the feature
+/// IDs, and the column names are the barcodes.
+pub fn read_10x<P>(src: P) -> io::Result<SparseExpressionMatrix>
+where
+    P: AsRef<Path>,
+{
+    let src = src.as_ref();
+    let (row_names, column_names) = read_10x_names(src)?;
+    let reader = find_file(src, MATRIX_FILE_NAMES).and_then(open)?;
+    read_mtx(reader, row_names, column_names)
+}
+
+// Reads the row names (feature IDs) and column names (barcodes) of a 10x
+// Genomics feature-barcode matrix directory.
+pub(crate) fn read_10x_names(src: &Path) -> io::Result<(Vec<String>, Vec<String>)> {
+    let row_names = find_file(src, FEATURES_FILE_NAMES)
+        .and_then(read_names)?
+        .into_iter()
+        .map(|line| match line.find('\t') {
+            Some(i) => line[..i].to_string(),
+            None => line,
+        })
+        .collect();
+
+    let column_names = find_file(src, BARCODES_FILE_NAMES).and_then(read_names)?;
+
+    Ok((row_names, column_names))
+}
+
+/// Returns whether the data starts with a Matrix Market exchange (MTX)
+/// coordinate header.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::mtx::is_mtx;
+///
+/// assert!(is_mtx(b"%%MatrixMarket matrix coordinate integer general\n2 2 2\n"));
+/// assert!(!is_mtx(b"AAAS\t645\n"));
+/// ```
+pub fn is_mtx(buf: &[u8]) -> bool {
+    buf.starts_with(HEADER_PREFIX.as_bytes())
+}
+
+/// Reads a list of names, one per line, e.g., a barcode whitelist.
+///
+/// If the extension of the path is `.gz`, the file is decompressed. Blank lines
+/// are skipped.
+pub fn read_names<P>(src: P) -> io::Result<Vec<String>>
+where
+    P: AsRef<Path>,
+{
+    let mut names = Vec::new();
+
+    for result in open(src)?.lines() {
+        let line = result?;
+        let name = line.trim_end();
+
+        if !name.is_empty() {
+            names.push(name.to_string());
+        }
+    }
+
+    Ok(names)
+}
+
+/// Reads a sparse matrix in the Matrix Market exchange (MTX) format.
+///
+/// Only the coordinate format is supported. The dimensions in the size line
+/// must match the number of row and column names.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::mtx::read_mtx;
+///
+/// let data = "\
+/// %%MatrixMarket matrix coordinate integer general
+/// %metadata_json: {}
+/// 2 2 2
+/// 2 1 3
+/// 1 2 5
+/// ";
+///
+/// let matrix = read_mtx(
+///     data.as_bytes(),
+///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+///     vec![String::from("cell1"), String::from("cell2")],
+/// ).unwrap();
+///
+/// assert_eq!(matrix.nnz(), 2);
+/// assert_eq!(matrix.get(1, 0), 3.0);
+/// ```
+pub fn read_mtx<R>(
+    reader: R,
+    row_names: Vec<String>,
+    column_names: Vec<String>,
+) -> io::Result<SparseExpressionMatrix>
+where
+    R: BufRead,
+{
+    let mut lines = reader.lines();
+
+    match lines.next().transpose()? {
+        Some(ref line) if line.starts_with(HEADER_PREFIX) => {}
+        _ => return Err(invalid_data(String::from("invalid MTX header"))),
+    }
+
+    let mut lines = lines.filter(|result| match result {
+        Ok(line) => !line.starts_with(COMMENT_PREFIX),
+        Err(_) => true,
+    });
+
+    let size = lines
+        .next()
+        .transpose()?
+        .ok_or_else(|| invalid_data(String::from("missing MTX size line")))?;
+
+    let (row_count, column_count, nnz) = match parse_size(&size) {
+        Some(size) => size,
+        None => return Err(invalid_data(format!("invalid MTX size line: {}", size))),
+    };
+
+    if row_count != row_names.len() || column_count != column_names.len() {
+        return Err(invalid_data(format!(
+            "MTX dimensions ({} x {}) do not match names ({} x {})",
+            row_count,
+            column_count,
+            row_names.len(),
+            column_names.len()
+        )));
+    }
+
+    let mut triplets = Vec::with_capacity(nnz);
+
+    for result in lines {
+        let line = result?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = parse_entry(&line, row_count, column_count)
+            .ok_or_else(|| invalid_data(format!("invalid MTX entry: {}", line)))?;
+
+        triplets.push(entry);
+    }
+
+    if triplets.len() != nnz {
+        return Err(invalid_data(format!(
+            "expected {} MTX entries, got {}",
+            nnz,
+            triplets.len()
+        )));
+    }
+
+    Ok(SparseExpressionMatrix::from_triplets(
+        row_names,
+        column_names,
+        triplets,
+    ))
+}
+
+fn parse_size(s: &str) -> Option<(usize, usize, usize)> {
+    let mut fields = s.split_whitespace().map(|f| f.parse().ok());
+
+    match (fields.next(), fields.next(), fields.next(), fields.next()) {
+        (Some(Some(m)), Some(Some(n)), Some(Some(nnz)), None) => Some((m, n, nnz)),
+        _ => None,
+    }
+}
+
+// MTX coordinates are 1-based.
+fn parse_entry(s: &str, row_count: usize, column_count: usize) -> Option<(usize, usize, f64)> {
+    let mut fields = s.split_whitespace();
+
+    let i: usize = fields.next()?.parse().ok()?;
+    let j: usize = fields.next()?.parse().ok()?;
+    let value = fields.next()?.parse().ok()?;
+
+    if fields.next().is_some() || i == 0 || i > row_count || j == 0 || j > column_count {
+        return None;
+    }
+
+    Some((i - 1, j - 1, value))
+}
+
+/// Writes a sparse matrix in the Matrix Market exchange (MTX) format.
+///
+/// Entries are written in coordinate format with 1-based indices, ordered by
+/// column and then row. Only the matrix itself is written; use
+/// [`write_names`] for the row and column names, e.g., as the `features.tsv`
+/// and `barcodes.tsv` files of a 10x Genomics matrix directory.
+///
+/// [`write_names`]: fn.write_names.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{mtx::write_mtx, sparse::SparseExpressionMatrix};
+///
+/// let matrix = SparseExpressionMatrix::from_triplets(
+///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+///     vec![String::from("cell1"), String::from("cell2")],
+///     vec![(1, 0, 3.0), (0, 1, 0.5)],
+/// );
+///
+/// let mut buf = Vec::new();
+/// write_mtx(&mut buf, &matrix).unwrap();
+///
+/// assert_eq!(String::from_utf8(buf).unwrap(), "\
+/// %%MatrixMarket matrix coordinate real general
+/// 2 2 2
+/// 2 1 3
+/// 1 2 0.5
+/// ");
+/// ```
+pub fn write_mtx<W>(mut writer: W, matrix: &SparseExpressionMatrix) -> io::Result<()>
+where
+    W: Write,
+{
+    let (row_count, column_count) = matrix.shape();
+
+    writeln!(writer, "{}", HEADER)?;
+    writeln!(writer, "{} {} {}", row_count, column_count, matrix.nnz())?;
+
+    for (i, j, value) in matrix.iter() {
+        writeln!(writer, "{} {} {}", i + 1, j + 1, value)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a list of names, one per line.
+pub fn write_names<W>(mut writer: W, names: &[String]) -> io::Result<()>
+where
+    W: Write,
+{
+    for name in names {
+        writeln!(writer, "{}", name)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a sparse matrix as an uncompressed 10x Genomics feature-barcode
+/// matrix directory.
+///
+/// The directory is created if it does not exist. The matrix, row names, and
+/// column names are written to `matrix.mtx`, `features.tsv`, and
+/// `barcodes.tsv`, respectively.
+pub fn write_10x<P>(dst: P, matrix: &SparseExpressionMatrix) -> io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let dst = dst.as_ref();
+
+    fs::create_dir_all(dst)?;
+
+    let mut writer = File::create(dst.join("matrix.mtx")).map(io::BufWriter::new)?;
+    write_mtx(&mut writer, matrix)?;
+    writer.flush()?;
+
+    let mut writer = File::create(dst.join("features.tsv")).map(io::BufWriter::new)?;
+    write_names(&mut writer, matrix.row_names())?;
+    writer.flush()?;
+
+    let mut writer = File::create(dst.join("barcodes.tsv")).map(io::BufWriter::new)?;
+    write_names(&mut writer, matrix.column_names())?;
+    writer.flush()
+}
+
+fn find_file(src: &Path, names: &[&str]) -> io::Result<PathBuf> {
+    names
+        .iter()
+        .map(|name| src.join(name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found in {}", names.join(" or "), src.display()),
+            )
+        })
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn build_names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_read_mtx_with_invalid_input() {
+        let rows = || build_names(&["AAAS", "RPL37AP1"]);
+        let columns = || build_names(&["cell1"]);
+
+        let data = "2 1 1\n1 1 1\n";
+        assert!(read_mtx(data.as_bytes(), rows(), columns()).is_err());
+
+        let data = "%%MatrixMarket matrix coordinate integer general\n3 1 1\n1 1 1\n";
+        assert!(read_mtx(data.as_bytes(), rows(), columns()).is_err());
+
+        let data = "%%MatrixMarket matrix coordinate integer general\n2 1 1\n3 1 1\n";
+        assert!(read_mtx(data.as_bytes(), rows(), columns()).is_err());
+
+        let data = "%%MatrixMarket matrix coordinate integer general\n2 1 2\n1 1 1\n";
+        assert!(read_mtx(data.as_bytes(), rows(), columns()).is_err());
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        assert_eq!(parse_entry("2 1 3", 2, 1), Some((1, 0, 3.0)));
+        assert_eq!(parse_entry("0 1 3", 2, 1), None);
+        assert_eq!(parse_entry("1 2 3", 2, 1), None);
+        assert_eq!(parse_entry("1 1", 2, 1), None);
+        assert_eq!(parse_entry("1 1 3 4", 2, 1), None);
+    }
+
+    #[test]
+    fn test_write_names() {
+        let mut buf = Vec::new();
+        write_names(&mut buf, &build_names(&["cell1", "cell2"])).unwrap();
+        assert_eq!(buf, b"cell1\ncell2\n");
+    }
+
+    #[test]
+    fn test_write_10x_and_read_10x() {
+        let dir = env::temp_dir().join(format!("noodles-fpkm-mtx-{}", std::process::id()));
+
+        let matrix = SparseExpressionMatrix::from_triplets(
+            build_names(&["AAAS", "RPL37AP1"]),
+            build_names(&["cell1", "cell2"]),
+            vec![(1, 0, 3.0), (0, 1, 5.0)],
+        );
+
+        write_10x(&dir, &matrix).unwrap();
+        let actual = read_10x(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(actual.unwrap(), matrix);
+    }
+}
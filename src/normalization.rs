@@ -0,0 +1,147 @@
+use crate::sparse::SparseExpressionMatrix;
+
+/// The target library size of counts per 10k (CP10k) normalization.
+pub const COUNTS_PER_10K: f64 = 1e4;
+
+/// Returns the library size, i.e., the sum of values, of each column.
+pub fn library_sizes(matrix: &SparseExpressionMatrix) -> Vec<f64> {
+    (0..matrix.shape().1)
+        .map(|j| matrix.column(j).1.iter().sum())
+        .collect()
+}
+
+/// Scales each column so that its library size is `target`.
+///
+/// This is counts per 10k normalization when `target` is [`COUNTS_PER_10K`].
+/// Empty columns are left unchanged.
+///
+/// [`COUNTS_PER_10K`]: constant.COUNTS_PER_10K.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     normalization::{normalize_total, COUNTS_PER_10K},
+///     sparse::SparseExpressionMatrix,
+/// };
+///
+/// let mut matrix = SparseExpressionMatrix::from_triplets(
+///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+///     vec![String::from("cell1")],
+///     vec![(0, 0, 1.0), (1, 0, 3.0)],
+/// );
+///
+/// normalize_total(&mut matrix, COUNTS_PER_10K);
+///
+/// assert_eq!(matrix.get(0, 0), 2500.0);
+/// assert_eq!(matrix.get(1, 0), 7500.0);
+/// ```
+pub fn normalize_total(matrix: &mut SparseExpressionMatrix, target: f64) {
+    for j in 0..matrix.shape().1 {
+        let (_, values) = matrix.column_mut(j);
+        let library_size: f64 = values.iter().sum();
+
+        if library_size > 0.0 {
+            let scale = target / library_size;
+
+            for value in values {
+                *value *= scale;
+            }
+        }
+    }
+}
+
+/// Scales each column so that its library size is the median library size.
+///
+/// Empty columns are excluded from the median and left unchanged. The median
+/// is returned, or `None` if all columns are empty.
+pub fn normalize_median(matrix: &mut SparseExpressionMatrix) -> Option<f64> {
+    let target = median(library_sizes(matrix))?;
+    normalize_total(matrix, target);
+    Some(target)
+}
+
+/// Applies the natural logarithm of one plus the value, i.e., `ln(1 + x)`, to
+/// each value.
+///
+/// Because `ln(1 + 0) = 0`, the sparsity of the matrix is preserved.
+pub fn log1p(matrix: &mut SparseExpressionMatrix) {
+    for j in 0..matrix.shape().1 {
+        for value in matrix.column_mut(j).1 {
+            *value = value.ln_1p();
+        }
+    }
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    values.retain(|&value| value > 0.0);
+
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_unstable_by(|a, b| a.partial_cmp(b).expect("values cannot be NaN"));
+
+    let mid = values.len() / 2;
+
+    match values.len() % 2 {
+        0 => Some((values[mid - 1] + values[mid]) / 2.0),
+        _ => Some(values[mid]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_matrix() -> SparseExpressionMatrix {
+        SparseExpressionMatrix::from_triplets(
+            vec![String::from("AAAS"), String::from("RPL37AP1")],
+            vec![
+                String::from("cell1"),
+                String::from("cell2"),
+                String::from("cell3"),
+            ],
+            vec![(0, 0, 1.0), (1, 0, 3.0), (1, 1, 8.0)],
+        )
+    }
+
+    #[test]
+    fn test_library_sizes() {
+        assert_eq!(library_sizes(&build_matrix()), [4.0, 8.0, 0.0]);
+    }
+
+    #[test]
+    fn test_normalize_median() {
+        let mut matrix = build_matrix();
+
+        assert_eq!(normalize_median(&mut matrix), Some(6.0));
+        assert_eq!(library_sizes(&matrix), [6.0, 6.0, 0.0]);
+        assert_eq!(matrix.get(0, 0), 1.5);
+
+        let mut matrix = SparseExpressionMatrix::from_triplets(
+            vec![String::from("AAAS")],
+            vec![String::from("cell1")],
+            Vec::new(),
+        );
+
+        assert_eq!(normalize_median(&mut matrix), None);
+    }
+
+    #[test]
+    fn test_log1p() {
+        let mut matrix = build_matrix();
+        log1p(&mut matrix);
+
+        assert_eq!(matrix.nnz(), 3);
+        assert_eq!(matrix.get(1, 0), 4.0f64.ln());
+        assert_eq!(matrix.get(0, 1), 0.0);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(vec![3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(vec![4.0, 1.0, 0.0, 2.0, 3.0]), Some(2.5));
+        assert_eq!(median(vec![0.0]), None);
+    }
+}
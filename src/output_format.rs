@@ -0,0 +1,220 @@
+use std::io::{self, Write};
+
+const TAB: u8 = b'\t';
+const LINE_FEED: u8 = b'\n';
+const QUOTE: u8 = b'"';
+const COMMENT_PREFIX: u8 = b'#';
+const DECIMAL_POINT: u8 = b'.';
+
+/// The field delimiter and decimal separator of tabular outputs.
+///
+/// The default is tab-delimited fields with a `.` decimal separator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OutputFormat {
+    /// The field delimiter.
+    pub delimiter: u8,
+    /// The decimal separator of numbers.
+    pub decimal_separator: u8,
+}
+
+impl OutputFormat {
+    /// Returns whether this is the default format, which needs no conversion.
+    pub fn is_default(&self) -> bool {
+        *self == OutputFormat::default()
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat {
+            delimiter: TAB,
+            decimal_separator: DECIMAL_POINT,
+        }
+    }
+}
+
+/// A writer that converts tab-delimited lines to an output format.
+///
+/// Each line is split on tabs and written with the format's delimiter. The
+/// decimal point of each field that is a number is replaced with the format's
+/// decimal separator; other fields, e.g., feature IDs such as `AC009952.3`,
+/// are written as is. Fields that contain the delimiter or a quote are quoted,
+/// as in CSV. Comment lines (starting with `#`) are written unchanged.
+///
+/// A partial line is held until its end is written or the writer is dropped.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Write;
+///
+/// use noodles_fpkm::output_format::{FormattedWriter, OutputFormat};
+///
+/// let format = OutputFormat {
+///     delimiter: b';',
+///     decimal_separator: b',',
+/// };
+///
+/// let mut buf = Vec::new();
+///
+/// {
+///     let mut writer = FormattedWriter::new(&mut buf, format);
+///     writer.write_all(b"tpm\tsample1\nAC009952.3\t27.5\n")?;
+/// }
+///
+/// assert_eq!(buf, b"tpm;sample1\nAC009952.3;27,5\n");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct FormattedWriter<W>
+where
+    W: Write,
+{
+    inner: W,
+    format: OutputFormat,
+    buf: Vec<u8>,
+}
+
+impl<W> FormattedWriter<W>
+where
+    W: Write,
+{
+    /// Creates a formatted writer.
+    pub fn new(inner: W, format: OutputFormat) -> Self {
+        FormattedWriter {
+            inner,
+            format,
+            buf: Vec::new(),
+        }
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if line.first() == Some(&COMMENT_PREFIX) {
+            return self.inner.write_all(line);
+        }
+
+        for (i, field) in line.split(|&b| b == TAB).enumerate() {
+            if i > 0 {
+                self.inner.write_all(&[self.format.delimiter])?;
+            }
+
+            if is_decimal(field) {
+                let field: Vec<u8> = field
+                    .iter()
+                    .map(|&b| {
+                        if b == DECIMAL_POINT {
+                            self.format.decimal_separator
+                        } else {
+                            b
+                        }
+                    })
+                    .collect();
+
+                self.inner.write_all(&field)?;
+            } else if field.contains(&self.format.delimiter) || field.contains(&QUOTE) {
+                write_quoted(&mut self.inner, field)?;
+            } else {
+                self.inner.write_all(field)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W> Write for FormattedWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+
+        if let Some(end) = self.buf.iter().rposition(|&b| b == LINE_FEED) {
+            let lines: Vec<u8> = self.buf.drain(..=end).collect();
+
+            for line in lines.split_inclusive(|&b| b == LINE_FEED) {
+                self.write_line(&line[..line.len() - 1])?;
+                self.inner.write_all(&[LINE_FEED])?;
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// A final line without a line feed is written when the writer is dropped.
+impl<W> Drop for FormattedWriter<W>
+where
+    W: Write,
+{
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            let line = std::mem::take(&mut self.buf);
+            let _ = self.write_line(&line);
+        }
+
+        let _ = self.inner.flush();
+    }
+}
+
+// Only fields that parse as a number and have a decimal point are converted,
+// so IDs with periods that are not numbers are kept.
+fn is_decimal(field: &[u8]) -> bool {
+    field.contains(&DECIMAL_POINT)
+        && std::str::from_utf8(field)
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .is_some()
+}
+
+fn write_quoted<W>(mut writer: W, field: &[u8]) -> io::Result<()>
+where
+    W: Write,
+{
+    writer.write_all(&[QUOTE])?;
+
+    for &b in field {
+        if b == QUOTE {
+            writer.write_all(&[QUOTE, QUOTE])?;
+        } else {
+            writer.write_all(&[b])?;
+        }
+    }
+
+    writer.write_all(&[QUOTE])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatted_writer() -> io::Result<()> {
+        let format = OutputFormat {
+            delimiter: b',',
+            decimal_separator: b'.',
+        };
+
+        let mut buf = Vec::new();
+
+        {
+            let mut writer = FormattedWriter::new(&mut buf, format);
+            writer.write_all(b"# source: a,b\n")?;
+            writer.write_all(b"tpm\tsample \"1\"\nAAAS, isoform 2\t1.")?;
+            writer.write_all(b"5e-3\nRPL37AP1\tNaN")?;
+        }
+
+        let expected = "\
+# source: a,b
+tpm,\"sample \"\"1\"\"\"
+\"AAAS, isoform 2\",1.5e-3
+RPL37AP1,NaN";
+
+        assert_eq!(String::from_utf8(buf).unwrap(), expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+//! The stable core of the crate: calculators, readers, their options, and the
+//! error type.
+//!
+//! Items exported here only change in a breaking way with a new major
+//! version. Other modules, e.g., parsers and indexes, can change between minor
+//! versions.
+//!
+//! # Example
+//!
+//! ```
+//! use noodles_fpkm::prelude::*;
+//!
+//! let counts = read_counts(&b"AAAS\t645\n"[..])?;
+//! let lengths: Lengths = [(FeatureId::from("AAAS"), 2541)].iter().cloned().collect();
+//!
+//! let fpkms = calculate_fpkms(&counts, &lengths).unwrap();
+//! assert!(fpkms["AAAS"] > 0.0);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+pub use crate::{
+    calculate_fpkms, calculate_tpms, calculate_tpms_from_fpkms,
+    counts::{read_counts, read_counts_table, read_counts_with_options, Counts, ReadCountsOptions},
+    expression_set::Method,
+    feature_id::FeatureId,
+    features::{
+        read_merged_features, read_merged_features_with_options, Features, ReadFeaturesOptions,
+    },
+    lengths::{calculate_lengths, calculate_unit_lengths, Lengths},
+    matrix::{read_matrix, ExpressionMatrix},
+    unit::Unit,
+    Error, Expressions,
+};
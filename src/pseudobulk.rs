@@ -0,0 +1,180 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Read},
+};
+
+use csv::StringRecord;
+
+use crate::{
+    counts::Counts, feature_id::FeatureId, features::with_line_number,
+    sparse::SparseExpressionMatrix,
+};
+
+/// Reads a list of barcode-group assignments, e.g., cells to clusters or
+/// samples.
+///
+/// Each line is a barcode and the name of its group, separated by a tab. Blank
+/// lines are skipped, and barcodes must be unique. The assignments are
+/// returned as barcode-group pairs, in order.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::pseudobulk::read_group_assignments;
+///
+/// let data = "AAACCCAAGAAACACT\tcluster1\nAAACCCAAGAAACCAT\tcluster2\n";
+/// let assignments = read_group_assignments(data.as_bytes())?;
+///
+/// assert_eq!(
+///     assignments,
+///     [
+///         (String::from("AAACCCAAGAAACACT"), String::from("cluster1")),
+///         (String::from("AAACCCAAGAAACCAT"), String::from("cluster2")),
+///     ]
+/// );
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_group_assignments<R>(reader: R) -> io::Result<Vec<(String, String)>>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_reader(reader);
+
+    let mut record = StringRecord::new();
+    let mut barcodes = HashSet::new();
+    let mut assignments = Vec::new();
+
+    while rdr.read_record(&mut record)? {
+        let line_number = record.position().map(|p| p.line()).unwrap_or_default();
+
+        if record.len() == 1 && record[0].is_empty() {
+            continue;
+        }
+
+        if record.len() != 2 {
+            return Err(with_line_number(
+                invalid_data(format!(
+                    "invalid number of fields: expected 2, got {}",
+                    record.len()
+                )),
+                line_number,
+            ));
+        }
+
+        let (barcode, group) = (&record[0], &record[1]);
+
+        if !barcodes.insert(barcode.to_string()) {
+            let e = invalid_data(format!("duplicate barcode: {}", barcode));
+            return Err(with_line_number(e, line_number));
+        }
+
+        assignments.push((barcode.into(), group.into()));
+    }
+
+    Ok(assignments)
+}
+
+/// Sums the counts of the columns of a matrix by group.
+///
+/// Groups are returned in the order they are first assigned, each as a sample
+/// of counts of every row of the matrix. Columns that are not assigned to a
+/// group are skipped, as are assigned barcodes that are not in the matrix.
+/// Sums are rounded to the nearest integer.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{pseudobulk::aggregate, sparse::SparseExpressionMatrix};
+///
+/// let matrix = SparseExpressionMatrix::from_triplets(
+///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+///     vec![String::from("cell1"), String::from("cell2"), String::from("cell3")],
+///     vec![(0, 0, 1.0), (0, 1, 2.0), (1, 2, 3.0)],
+/// );
+///
+/// let assignments = [
+///     (String::from("cell2"), String::from("cluster1")),
+///     (String::from("cell3"), String::from("cluster2")),
+///     (String::from("cell1"), String::from("cluster1")),
+/// ];
+///
+/// let samples = aggregate(&matrix, &assignments);
+///
+/// assert_eq!(samples.len(), 2);
+/// assert_eq!(samples[0].0, "cluster1");
+/// assert_eq!(samples[0].1["AAAS"], 3);
+/// assert_eq!(samples[0].1["RPL37AP1"], 0);
+/// assert_eq!(samples[1].0, "cluster2");
+/// assert_eq!(samples[1].1["RPL37AP1"], 3);
+/// ```
+pub fn aggregate(
+    matrix: &SparseExpressionMatrix,
+    assignments: &[(String, String)],
+) -> Vec<(String, Counts)> {
+    let mut group_names: Vec<&str> = Vec::new();
+    let mut group_indices: HashMap<&str, usize> = HashMap::new();
+    let mut barcode_groups: HashMap<&str, usize> = HashMap::new();
+
+    for (barcode, group) in assignments {
+        let k = *group_indices.entry(group.as_str()).or_insert_with(|| {
+            group_names.push(group);
+            group_names.len() - 1
+        });
+
+        barcode_groups.insert(barcode, k);
+    }
+
+    let row_count = matrix.row_names().len();
+    let mut sums = vec![vec![0.0; row_count]; group_names.len()];
+
+    for (j, barcode) in matrix.column_names().iter().enumerate() {
+        if let Some(&k) = barcode_groups.get(barcode.as_str()) {
+            let (rows, values) = matrix.column(j);
+
+            for (&i, &value) in rows.iter().zip(values) {
+                sums[k][i] += value;
+            }
+        }
+    }
+
+    group_names
+        .into_iter()
+        .zip(sums)
+        .map(|(name, sums)| {
+            let counts = matrix
+                .row_names()
+                .iter()
+                .zip(sums)
+                .map(|(id, sum)| (FeatureId::from(id.as_str()), sum.round() as u64))
+                .collect();
+
+            (name.to_string(), counts)
+        })
+        .collect()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_group_assignments_with_invalid_input() {
+        let e = read_group_assignments(&b"cell1\n"[..]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "line 1: invalid number of fields: expected 2, got 1"
+        );
+
+        let data = b"cell1\tcluster1\ncell1\tcluster2\n";
+        let e = read_group_assignments(&data[..]).unwrap_err();
+        assert_eq!(e.to_string(), "line 2: duplicate barcode: cell1");
+    }
+}
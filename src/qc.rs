@@ -0,0 +1,510 @@
+use std::{collections::BTreeMap, fmt};
+
+use ndarray::Array2;
+
+use crate::{
+    counts::{sum_counts, Counts},
+    gtf::{Biotypes, ReferenceSequenceNames},
+    matrix::ExpressionMatrix,
+    Expressions,
+};
+
+/// The common names of the mitochondrial reference sequence, e.g., `chrM` in
+/// UCSC and GENCODE and `MT` in Ensembl.
+pub const MITOCHONDRIAL_REFERENCE_SEQUENCE_NAMES: &[&str] = &["chrM", "chrMT", "M", "MT"];
+
+/// The biotypes of rRNA genes in Ensembl and GENCODE annotations.
+pub const RRNA_BIOTYPES: &[&str] = &["rRNA", "rRNA_pseudogene", "Mt_rRNA"];
+
+// The prefix of the biotypes of mitochondrial genes, e.g., `Mt_tRNA`.
+static MITOCHONDRIAL_BIOTYPE_PREFIX: &str = "Mt_";
+
+/// Calculates the fraction of counts from features that match a predicate.
+///
+/// This is 0 when there are no counts.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{counts::Counts, feature_id::FeatureId, qc::calculate_count_fraction};
+///
+/// let counts: Counts = [(FeatureId::from("AAAS"), 75), (FeatureId::from("MT-CO1"), 25)]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// let fraction = calculate_count_fraction(&counts, |name| name.starts_with("MT-"));
+///
+/// assert_eq!(fraction, 0.25);
+/// ```
+pub fn calculate_count_fraction<F>(counts: &Counts, mut f: F) -> f64
+where
+    F: FnMut(&str) -> bool,
+{
+    let total = sum_counts(counts);
+
+    if total == 0 {
+        return 0.0;
+    }
+
+    let sum: u64 = counts
+        .iter()
+        .filter(|(name, _)| f(name))
+        .map(|(_, &count)| count)
+        .sum();
+
+    sum as f64 / total as f64
+}
+
+/// Returns whether a feature is mitochondrial.
+///
+/// A feature is mitochondrial when it is on a mitochondrial reference sequence
+/// (see [`MITOCHONDRIAL_REFERENCE_SEQUENCE_NAMES`]) or its biotype is
+/// mitochondrial, e.g., `Mt_rRNA` or `Mt_tRNA`.
+///
+/// [`MITOCHONDRIAL_REFERENCE_SEQUENCE_NAMES`]: constant.MITOCHONDRIAL_REFERENCE_SEQUENCE_NAMES.html
+pub fn is_mitochondrial(
+    name: &str,
+    reference_sequence_names: &ReferenceSequenceNames,
+    biotypes: &Biotypes,
+) -> bool {
+    let is_on_mitochondrial_reference_sequence = reference_sequence_names
+        .get(name)
+        .map(|n| MITOCHONDRIAL_REFERENCE_SEQUENCE_NAMES.contains(&n.as_str()))
+        .unwrap_or(false);
+
+    let has_mitochondrial_biotype = biotypes
+        .get(name)
+        .map(|b| b.starts_with(MITOCHONDRIAL_BIOTYPE_PREFIX))
+        .unwrap_or(false);
+
+    is_on_mitochondrial_reference_sequence || has_mitochondrial_biotype
+}
+
+/// Returns whether a feature is an rRNA gene by its biotype (see
+/// [`RRNA_BIOTYPES`]).
+///
+/// [`RRNA_BIOTYPES`]: constant.RRNA_BIOTYPES.html
+pub fn is_rrna(name: &str, biotypes: &Biotypes) -> bool {
+    biotypes
+        .get(name)
+        .map(|b| RRNA_BIOTYPES.contains(&b.as_str()))
+        .unwrap_or(false)
+}
+
+/// The sum of the TPMs of a sample.
+pub const TPM_SUM: f64 = 1e6;
+
+/// The default relative tolerance of the deviation of a sum of TPMs from
+/// [`TPM_SUM`].
+///
+/// [`TPM_SUM`]: constant.TPM_SUM.html
+pub const DEFAULT_TPM_SUM_TOLERANCE: f64 = 1e-6;
+
+/// Checks that TPMs sum to [`TPM_SUM`] within a relative tolerance.
+///
+/// The sum is returned in either case. It is an error if the sum is not
+/// finite or deviates by more than the tolerance, which indicates, e.g., NaN
+/// or infinite values or dropped features.
+///
+/// [`TPM_SUM`]: constant.TPM_SUM.html
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     feature_id::FeatureId,
+///     qc::{check_tpm_sum, DEFAULT_TPM_SUM_TOLERANCE},
+///     Expressions,
+/// };
+///
+/// let tpms: Expressions = [(FeatureId::from("AAAS"), 250000.0), (FeatureId::from("RPL37AP1"), 750000.0)]
+///     .iter()
+///     .cloned()
+///     .collect();
+///
+/// assert_eq!(check_tpm_sum(&tpms, DEFAULT_TPM_SUM_TOLERANCE), Ok(1e6));
+/// ```
+pub fn check_tpm_sum(tpms: &Expressions, tolerance: f64) -> Result<f64, f64> {
+    let sum: f64 = tpms.values().sum();
+
+    if sum.is_finite() && (sum - TPM_SUM).abs() <= tolerance * TPM_SUM {
+        Ok(sum)
+    } else {
+        Err(sum)
+    }
+}
+
+/// The default genes whose expression indicates a female sample.
+pub const DEFAULT_FEMALE_GENES: &[&str] = &["XIST"];
+
+/// The default genes whose expression indicates a male sample.
+///
+/// These are broadly expressed genes in the male-specific region of the Y
+/// chromosome.
+pub const DEFAULT_MALE_GENES: &[&str] = &["RPS4Y1", "DDX3Y", "KDM5D", "UTY", "EIF1AY"];
+
+/// A sex inferred from expression.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Sex {
+    Female,
+    Male,
+    /// Both or neither sets of marker genes are expressed.
+    Unknown,
+}
+
+impl fmt::Display for Sex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sex::Female => f.write_str("female"),
+            Sex::Male => f.write_str("male"),
+            Sex::Unknown => f.write_str("unknown"),
+        }
+    }
+}
+
+/// The sex of a sample inferred from the expression of marker genes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SexInference {
+    /// The sum of the expression of the female marker genes.
+    pub female_expression: f64,
+    /// The sum of the expression of the male marker genes.
+    pub male_expression: f64,
+    /// The inferred sex.
+    pub sex: Sex,
+}
+
+/// Infers the sex of each sample from the expression of marker genes.
+///
+/// A set of marker genes is expressed when the sum of their values is at
+/// least `threshold`. A sample is female when only the female marker genes
+/// (e.g., XIST) are expressed and male when only the male marker genes (e.g.,
+/// Y chromosome genes) are expressed. Genes not in the matrix are ignored.
+///
+/// The result has one inference per column of the matrix. An inferred sex
+/// that does not match the sample's metadata suggests a sample swap.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{
+///     matrix::ExpressionMatrix,
+///     qc::{infer_sex, Sex, DEFAULT_FEMALE_GENES, DEFAULT_MALE_GENES},
+/// };
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("RPS4Y1"), String::from("XIST")],
+///     vec![String::from("sample1"), String::from("sample2")],
+///     arr2(&[[0.0, 85.0], [42.0, 0.5]]),
+/// );
+///
+/// let inferences = infer_sex(&matrix, DEFAULT_FEMALE_GENES, DEFAULT_MALE_GENES, 1.0);
+///
+/// assert_eq!(inferences[0].sex, Sex::Female);
+/// assert_eq!(inferences[1].sex, Sex::Male);
+/// ```
+pub fn infer_sex<S>(
+    matrix: &ExpressionMatrix,
+    female_genes: &[S],
+    male_genes: &[S],
+    threshold: f64,
+) -> Vec<SexInference>
+where
+    S: AsRef<str>,
+{
+    let female_rows = find_rows(matrix, female_genes);
+    let male_rows = find_rows(matrix, male_genes);
+
+    matrix
+        .data()
+        .gencolumns()
+        .into_iter()
+        .map(|column| {
+            let female_expression: f64 = female_rows.iter().map(|&i| column[i]).sum();
+            let male_expression: f64 = male_rows.iter().map(|&i| column[i]).sum();
+
+            let sex = match (female_expression >= threshold, male_expression >= threshold) {
+                (true, false) => Sex::Female,
+                (false, true) => Sex::Male,
+                _ => Sex::Unknown,
+            };
+
+            SexInference {
+                female_expression,
+                male_expression,
+                sex,
+            }
+        })
+        .collect()
+}
+
+fn find_rows<S>(matrix: &ExpressionMatrix, names: &[S]) -> Vec<usize>
+where
+    S: AsRef<str>,
+{
+    names
+        .iter()
+        .filter_map(|name| matrix.row_index(name.as_ref()))
+        .collect()
+}
+
+/// Summarizes expression by reference sequence, e.g., chromosome.
+///
+/// The result has one row per reference sequence, sorted by name, and one
+/// column per sample. Each value is the fraction of the sample's total
+/// expression from features on that reference sequence. Features without a
+/// reference sequence are included in the total but not in any row.
+///
+/// This is a quick way to spot, e.g., an excess of mitochondrial expression or
+/// a sample swap by the expression of the sex chromosomes.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{
+///     feature_id::FeatureId, gtf::ReferenceSequenceNames, matrix::ExpressionMatrix,
+///     qc::summarize_by_reference_sequence,
+/// };
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS"), String::from("MT-CO1"), String::from("NECAP2")],
+///     vec![String::from("sample1")],
+///     arr2(&[[25.0], [50.0], [25.0]]),
+/// );
+///
+/// let names: ReferenceSequenceNames = [
+///     (FeatureId::from("AAAS"), String::from("chr12")),
+///     (FeatureId::from("MT-CO1"), String::from("chrM")),
+///     (FeatureId::from("NECAP2"), String::from("chr1")),
+/// ].iter().cloned().collect();
+///
+/// let summary = summarize_by_reference_sequence(&matrix, &names);
+///
+/// assert_eq!(summary.row_names(), ["chr1", "chr12", "chrM"]);
+/// assert_eq!(summary.get("chrM", "sample1"), Some(0.5));
+/// ```
+pub fn summarize_by_reference_sequence(
+    matrix: &ExpressionMatrix,
+    reference_sequence_names: &ReferenceSequenceNames,
+) -> ExpressionMatrix {
+    summarize_by_group(matrix, |id| reference_sequence_names.get(id))
+}
+
+/// Summarizes expression by biotype, e.g., `protein_coding` or `lncRNA`.
+///
+/// The result has one row per biotype, sorted by name, and one column per
+/// sample. Each value is the fraction of the sample's total expression from
+/// features of that biotype. Features without a biotype are included in the
+/// total but not in any row.
+///
+/// A high fraction of rRNA or of pseudogenes is a sign of poor library
+/// quality, e.g., failed rRNA depletion.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{
+///     feature_id::FeatureId, gtf::Biotypes, matrix::ExpressionMatrix, qc::summarize_by_biotype,
+/// };
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS"), String::from("RNA5-8SN1"), String::from("NECAP2")],
+///     vec![String::from("sample1")],
+///     arr2(&[[25.0], [50.0], [25.0]]),
+/// );
+///
+/// let biotypes: Biotypes = [
+///     (FeatureId::from("AAAS"), String::from("protein_coding")),
+///     (FeatureId::from("RNA5-8SN1"), String::from("rRNA")),
+///     (FeatureId::from("NECAP2"), String::from("protein_coding")),
+/// ].iter().cloned().collect();
+///
+/// let summary = summarize_by_biotype(&matrix, &biotypes);
+///
+/// assert_eq!(summary.row_names(), ["protein_coding", "rRNA"]);
+/// assert_eq!(summary.get("rRNA", "sample1"), Some(0.5));
+/// ```
+pub fn summarize_by_biotype(matrix: &ExpressionMatrix, biotypes: &Biotypes) -> ExpressionMatrix {
+    summarize_by_group(matrix, |id| biotypes.get(id))
+}
+
+// Sums the fractions of the total expression of each sample by group. Rows
+// are groups, sorted by name.
+fn summarize_by_group<'a, F>(matrix: &ExpressionMatrix, group: F) -> ExpressionMatrix
+where
+    F: Fn(&str) -> Option<&'a String>,
+{
+    let mut groups: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+
+    for (i, name) in matrix.row_names().iter().enumerate() {
+        if let Some(group_name) = group(name) {
+            groups.entry(group_name).or_default().push(i);
+        }
+    }
+
+    let data = matrix.data();
+    let column_count = data.cols();
+    let totals: Vec<f64> = data.gencolumns().into_iter().map(|c| c.sum()).collect();
+
+    let mut summary = Array2::zeros((groups.len(), column_count));
+
+    for (k, rows) in groups.values().enumerate() {
+        for j in 0..column_count {
+            let sum: f64 = rows.iter().map(|&i| data[[i, j]]).sum();
+
+            if totals[j] > 0.0 {
+                summary[[k, j]] = sum / totals[j];
+            }
+        }
+    }
+
+    let row_names = groups.keys().map(|name| name.to_string()).collect();
+
+    ExpressionMatrix::new(row_names, matrix.column_names().to_vec(), summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_id::FeatureId;
+
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_summarize_by_reference_sequence() {
+        let matrix = ExpressionMatrix::new(
+            vec![
+                String::from("AAAS"),
+                String::from("ERCC-00130"),
+                String::from("MT-CO1"),
+                String::from("MT-ND1"),
+            ],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[30.0, 0.0], [10.0, 0.0], [40.0, 0.0], [20.0, 0.0]]),
+        );
+
+        let names: ReferenceSequenceNames = [
+            (FeatureId::from("AAAS"), String::from("chr12")),
+            (FeatureId::from("MT-CO1"), String::from("chrM")),
+            (FeatureId::from("MT-ND1"), String::from("chrM")),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let summary = summarize_by_reference_sequence(&matrix, &names);
+
+        assert_eq!(summary.row_names(), ["chr12", "chrM"]);
+        assert_eq!(summary.get("chr12", "sample1"), Some(0.3));
+        assert_eq!(summary.get("chrM", "sample1"), Some(0.6));
+        assert_eq!(summary.get("chrM", "sample2"), Some(0.0));
+    }
+
+    #[test]
+    fn test_infer_sex() {
+        let matrix = ExpressionMatrix::new(
+            vec![
+                String::from("DDX3Y"),
+                String::from("RPS4Y1"),
+                String::from("XIST"),
+            ],
+            vec![
+                String::from("sample1"),
+                String::from("sample2"),
+                String::from("sample3"),
+                String::from("sample4"),
+            ],
+            arr2(&[
+                [0.0, 0.6, 0.0, 20.0],
+                [0.0, 0.6, 0.0, 40.0],
+                [85.0, 0.0, 0.0, 60.0],
+            ]),
+        );
+
+        let inferences = infer_sex(&matrix, &["XIST"], &["DDX3Y", "RPS4Y1", "UTY"], 1.0);
+
+        assert_eq!(inferences.len(), 4);
+
+        assert_eq!(inferences[0].sex, Sex::Female);
+        assert_eq!(inferences[0].female_expression, 85.0);
+
+        assert_eq!(inferences[1].sex, Sex::Male);
+        assert_eq!(inferences[1].male_expression, 1.2);
+
+        assert_eq!(inferences[2].sex, Sex::Unknown);
+        assert_eq!(inferences[3].sex, Sex::Unknown);
+    }
+
+    #[test]
+    fn test_is_mitochondrial() {
+        let names: ReferenceSequenceNames = [
+            (FeatureId::from("AAAS"), String::from("chr12")),
+            (FeatureId::from("MT-CO1"), String::from("chrM")),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let biotypes: Biotypes = [(FeatureId::from("MT-RNR1"), String::from("Mt_rRNA"))]
+            .iter()
+            .cloned()
+            .collect();
+
+        assert!(is_mitochondrial("MT-CO1", &names, &biotypes));
+        assert!(is_mitochondrial("MT-RNR1", &names, &biotypes));
+        assert!(!is_mitochondrial("AAAS", &names, &biotypes));
+    }
+
+    #[test]
+    fn test_is_rrna() {
+        let biotypes: Biotypes = [
+            (FeatureId::from("AAAS"), String::from("protein_coding")),
+            (FeatureId::from("RNA5-8SN1"), String::from("rRNA")),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert!(is_rrna("RNA5-8SN1", &biotypes));
+        assert!(!is_rrna("AAAS", &biotypes));
+        assert!(!is_rrna("RPL37AP1", &biotypes));
+    }
+
+    #[test]
+    fn test_calculate_count_fraction() {
+        assert_eq!(calculate_count_fraction(&Counts::default(), |_| true), 0.0);
+    }
+
+    #[test]
+    fn test_check_tpm_sum() {
+        let tpms: Expressions = [
+            (FeatureId::from("AAAS"), 250000.0),
+            (FeatureId::from("RPL37AP1"), 749990.0),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert_eq!(check_tpm_sum(&tpms, 1e-6), Err(999990.0));
+        assert_eq!(check_tpm_sum(&tpms, 1e-4), Ok(999990.0));
+
+        let tpms: Expressions = [(FeatureId::from("AAAS"), f64::NAN)]
+            .iter()
+            .cloned()
+            .collect();
+        assert!(check_tpm_sum(&tpms, 1e-6).is_err());
+
+        assert_eq!(check_tpm_sum(&Expressions::new(), 1e-6), Err(0.0));
+    }
+
+    #[test]
+    fn test_fmt_sex() {
+        assert_eq!(Sex::Female.to_string(), "female");
+        assert_eq!(Sex::Male.to_string(), "male");
+        assert_eq!(Sex::Unknown.to_string(), "unknown");
+    }
+}
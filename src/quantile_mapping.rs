@@ -0,0 +1,366 @@
+use std::{
+    cmp::Ordering,
+    io::{self, BufRead},
+};
+
+use crate::matrix::ExpressionMatrix;
+
+const COMMENT_PREFIX: char = '#';
+
+/// The rank offset of Blom's inverse normal transform.
+pub const BLOM_OFFSET: f64 = 3.0 / 8.0;
+
+/// Reads a reference distribution of expression values.
+///
+/// Each line is a value or tab-separated fields whose last field is the value,
+/// e.g., a feature ID and its expression in a reference cohort. Blank lines
+/// and comment lines (starting with `#`) are skipped. The values are returned
+/// sorted in ascending order.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::quantile_mapping::read_reference_distribution;
+///
+/// let data = "AAAS\t8.5\nAC009952.3\t0\nRPL37AP1\t120.25\n";
+/// let reference = read_reference_distribution(data.as_bytes())?;
+///
+/// assert_eq!(reference, [0.0, 8.5, 120.25]);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_reference_distribution<R>(reader: R) -> io::Result<Vec<f64>>
+where
+    R: BufRead,
+{
+    let mut values = Vec::new();
+
+    for result in reader.lines() {
+        let line = result?;
+        let line = line.trim_end();
+
+        if line.is_empty() || line.starts_with(COMMENT_PREFIX) {
+            continue;
+        }
+
+        let field = line.rsplit('\t').next().unwrap_or(line);
+
+        let value: f64 = field
+            .trim()
+            .parse()
+            .ok()
+            .filter(|value: &f64| value.is_finite())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid reference value: {}", field),
+                )
+            })?;
+
+        values.push(value);
+    }
+
+    if values.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "reference distribution is empty",
+        ));
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    Ok(values)
+}
+
+/// Maps the values of each sample onto a reference distribution.
+///
+/// This is quantile normalization against a fixed target rather than the
+/// mean of the samples, so samples can be compared with a cohort quantified
+/// elsewhere. Each value is replaced by the value at the same quantile of the
+/// reference, interpolating linearly between reference values. Tied values
+/// are given the quantile of their average rank, so they map to the same
+/// value.
+///
+/// The reference must be sorted in ascending order and not be empty, e.g., as
+/// read by [`read_reference_distribution`].
+///
+/// [`read_reference_distribution`]: fn.read_reference_distribution.html
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{matrix::ExpressionMatrix, quantile_mapping::map_to_reference};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS"), String::from("AC009952.3"), String::from("RPL37AP1")],
+///     vec![String::from("sample1")],
+///     arr2(&[[5.0], [1.0], [9.0]]),
+/// );
+///
+/// let mapped = map_to_reference(&matrix, &[0.0, 10.0, 100.0]);
+///
+/// assert_eq!(mapped.get("AC009952.3", "sample1"), Some(0.0));
+/// assert_eq!(mapped.get("AAAS", "sample1"), Some(10.0));
+/// assert_eq!(mapped.get("RPL37AP1", "sample1"), Some(100.0));
+/// ```
+pub fn map_to_reference(matrix: &ExpressionMatrix, reference: &[f64]) -> ExpressionMatrix {
+    let mut mapped = matrix.clone();
+    let data = mapped.data_mut();
+
+    for mut column in data.gencolumns_mut() {
+        let values: Vec<f64> = column.iter().copied().collect();
+        let ranks = average_ranks(&values);
+        let n = values.len();
+
+        for (value, rank) in column.iter_mut().zip(ranks) {
+            let quantile = if n > 1 {
+                (rank - 1.0) / (n - 1) as f64
+            } else {
+                0.5
+            };
+
+            *value = interpolate(reference, quantile);
+        }
+    }
+
+    mapped
+}
+
+/// Applies a rank-based inverse normal transform to the values of each
+/// feature across samples.
+///
+/// The values of each row are ranked, and each rank r of n values is replaced
+/// by the quantile of the standard normal distribution at (r - c) / (n - 2c +
+/// 1), where c is the rank offset, e.g., [`BLOM_OFFSET`]. Tied values are given
+/// their average rank, so they map to the same value. This is common in eQTL
+/// analysis to make expression values of each feature normally distributed
+/// across samples.
+///
+/// [`BLOM_OFFSET`]: constant.BLOM_OFFSET.html
+///
+/// # Panics
+///
+/// Panics when the offset is not in [0, 0.5).
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{
+///     matrix::ExpressionMatrix,
+///     quantile_mapping::{inverse_normal_transform, BLOM_OFFSET},
+/// };
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS")],
+///     vec![String::from("sample1"), String::from("sample2"), String::from("sample3")],
+///     arr2(&[[8.0, 1.0, 3.0]]),
+/// );
+///
+/// let transformed = inverse_normal_transform(&matrix, BLOM_OFFSET);
+///
+/// let sample1 = transformed.get("AAAS", "sample1").unwrap();
+/// let sample2 = transformed.get("AAAS", "sample2").unwrap();
+/// let sample3 = transformed.get("AAAS", "sample3").unwrap();
+///
+/// assert!((sample1 - 0.8694).abs() < 1e-4);
+/// assert!((sample2 + 0.8694).abs() < 1e-4);
+/// assert!(sample3.abs() < 1e-9);
+/// ```
+pub fn inverse_normal_transform(matrix: &ExpressionMatrix, offset: f64) -> ExpressionMatrix {
+    assert!(
+        (0.0..0.5).contains(&offset),
+        "rank offset must be in [0, 0.5)"
+    );
+
+    let mut transformed = matrix.clone();
+    let data = transformed.data_mut();
+
+    for mut row in data.genrows_mut() {
+        let values: Vec<f64> = row.iter().copied().collect();
+        let ranks = average_ranks(&values);
+        let n = values.len() as f64;
+
+        for (value, rank) in row.iter_mut().zip(ranks) {
+            let p = (rank - offset) / (n - 2.0 * offset + 1.0);
+            *value = normal_quantile(p);
+        }
+    }
+
+    transformed
+}
+
+// Returns the 1-based rank of each value. Tied values are given the average of
+// their ranks.
+pub(crate) fn average_ranks(values: &[f64]) -> Vec<f64> {
+    let mut indices: Vec<usize> = (0..values.len()).collect();
+    indices.sort_by(|&a, &b| values[a].partial_cmp(&values[b]).unwrap_or(Ordering::Equal));
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+
+    while start < indices.len() {
+        let mut end = start + 1;
+
+        while end < indices.len() && values[indices[end]] == values[indices[start]] {
+            end += 1;
+        }
+
+        // The ranks of positions start..end are start + 1..=end.
+        let rank = (start + 1 + end) as f64 / 2.0;
+
+        for &i in &indices[start..end] {
+            ranks[i] = rank;
+        }
+
+        start = end;
+    }
+
+    ranks
+}
+
+// Returns the value at the given quantile (0 to 1) of sorted values.
+fn interpolate(sorted_values: &[f64], quantile: f64) -> f64 {
+    let position = quantile * (sorted_values.len() - 1) as f64;
+    let i = position.floor() as usize;
+    let fraction = position - i as f64;
+
+    match sorted_values.get(i + 1) {
+        Some(&next) => sorted_values[i] + fraction * (next - sorted_values[i]),
+        None => sorted_values[i],
+    }
+}
+
+// Returns the quantile of the standard normal distribution at p, 0 < p < 1.
+//
+// This uses the rational approximations by Peter J. Acklam, which have a
+// relative error less than 1.15e-9.
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+
+    const P_LOW: f64 = 0.02425;
+
+    if p < P_LOW {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -normal_quantile(1.0 - p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_read_reference_distribution_with_invalid_value() {
+        assert!(read_reference_distribution(&b"AAAS\tx\n"[..]).is_err());
+        assert!(read_reference_distribution(&b"AAAS\tNaN\n"[..]).is_err());
+        assert!(read_reference_distribution(&b"# empty\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_map_to_reference_with_ties() {
+        let matrix = ExpressionMatrix::new(
+            vec![
+                String::from("a"),
+                String::from("b"),
+                String::from("c"),
+                String::from("d"),
+            ],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[0.0, 4.0], [0.0, 3.0], [2.0, 2.0], [8.0, 1.0]]),
+        );
+
+        let reference = [0.0, 3.0, 6.0, 9.0];
+        let mapped = map_to_reference(&matrix, &reference);
+
+        // a and b share ranks 1 and 2, i.e., a quantile of 1/6.
+        assert_eq!(mapped.get("a", "sample1"), Some(1.5));
+        assert_eq!(mapped.get("b", "sample1"), Some(1.5));
+        assert_eq!(mapped.get("c", "sample1"), Some(6.0));
+        assert_eq!(mapped.get("d", "sample1"), Some(9.0));
+
+        assert_eq!(mapped.get("a", "sample2"), Some(9.0));
+        assert_eq!(mapped.get("d", "sample2"), Some(0.0));
+    }
+
+    #[test]
+    fn test_inverse_normal_transform_with_ties() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("a")],
+            vec![
+                String::from("sample1"),
+                String::from("sample2"),
+                String::from("sample3"),
+                String::from("sample4"),
+            ],
+            arr2(&[[0.0, 0.0, 5.0, 2.0]]),
+        );
+
+        let transformed = inverse_normal_transform(&matrix, BLOM_OFFSET);
+
+        let get = |name| transformed.get("a", name).unwrap();
+
+        // sample1 and sample2 share ranks 1 and 2, i.e., (1.5 - 3/8) / (4 + 1/4).
+        assert_eq!(get("sample1"), get("sample2"));
+        assert!((get("sample1") - normal_quantile(1.125 / 4.25)).abs() < 1e-12);
+        assert!(get("sample2") < get("sample4") && get("sample4") < get("sample3"));
+    }
+
+    #[test]
+    fn test_normal_quantile() {
+        assert!(normal_quantile(0.5).abs() < 1e-9);
+        assert!((normal_quantile(0.975) - 1.959964).abs() < 1e-6);
+        assert!((normal_quantile(0.01) + 2.326348).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_average_ranks() {
+        assert_eq!(average_ranks(&[3.0, 1.0, 3.0, 2.0]), [3.5, 1.0, 3.5, 2.0]);
+        assert!(average_ranks(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(interpolate(&[0.0, 10.0], 0.25), 2.5);
+        assert_eq!(interpolate(&[0.0, 10.0], 1.0), 10.0);
+        assert_eq!(interpolate(&[7.0], 0.5), 7.0);
+    }
+}
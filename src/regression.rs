@@ -0,0 +1,211 @@
+use std::fmt;
+
+use crate::matrix::ExpressionMatrix;
+
+/// A difference between a matrix and an expected matrix.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// A row of the expected matrix is missing.
+    MissingRow(String),
+    /// A row is not in the expected matrix.
+    UnexpectedRow(String),
+    /// A column of the expected matrix is missing.
+    MissingColumn(String),
+    /// A column is not in the expected matrix.
+    UnexpectedColumn(String),
+    /// A value differs from its expected value by more than the tolerance.
+    Value {
+        row: String,
+        column: String,
+        actual: f64,
+        expected: f64,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::MissingRow(name) => write!(f, "{}: missing row", name),
+            Difference::UnexpectedRow(name) => write!(f, "{}: unexpected row", name),
+            Difference::MissingColumn(name) => write!(f, "{}: missing column", name),
+            Difference::UnexpectedColumn(name) => write!(f, "{}: unexpected column", name),
+            Difference::Value {
+                row,
+                column,
+                actual,
+                expected,
+            } => write!(
+                f,
+                "{} ({}): expected {}, got {}",
+                row, column, expected, actual
+            ),
+        }
+    }
+}
+
+/// Compares a matrix to an expected matrix, e.g., a golden file of a previous
+/// run.
+///
+/// Rows and columns are matched by name, except that when both matrices have
+/// a single column, the columns are compared regardless of their names, as
+/// single-sample outputs have no sample name. Two values are equal if their
+/// absolute difference is at most `tolerance`, relative to the magnitude of
+/// the expected value when it is greater than 1. Two NaN values are equal.
+///
+/// The differences are returned in the order of the expected rows and
+/// columns, followed by unexpected rows and columns.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{matrix::ExpressionMatrix, regression::{compare_matrices, Difference}};
+///
+/// let expected = ExpressionMatrix::new(
+///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+///     vec![String::from("sample1")],
+///     arr2(&[[3.25], [27.5]]),
+/// );
+///
+/// let actual = ExpressionMatrix::new(
+///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+///     vec![String::from("sample1")],
+///     arr2(&[[3.25], [27.6]]),
+/// );
+///
+/// assert_eq!(
+///     compare_matrices(&actual, &expected, 1e-9),
+///     [Difference::Value {
+///         row: String::from("RPL37AP1"),
+///         column: String::from("sample1"),
+///         actual: 27.6,
+///         expected: 27.5,
+///     }]
+/// );
+///
+/// assert!(compare_matrices(&actual, &expected, 0.01).is_empty());
+/// ```
+pub fn compare_matrices(
+    actual: &ExpressionMatrix,
+    expected: &ExpressionMatrix,
+    tolerance: f64,
+) -> Vec<Difference> {
+    let mut differences = Vec::new();
+
+    let match_columns_by_name =
+        actual.column_names().len() != 1 || expected.column_names().len() != 1;
+
+    let columns: Vec<(usize, usize)> = if !match_columns_by_name {
+        vec![(0, 0)]
+    } else {
+        for name in expected.column_names() {
+            if actual.column_index(name).is_none() {
+                differences.push(Difference::MissingColumn(name.clone()));
+            }
+        }
+
+        expected
+            .column_names()
+            .iter()
+            .enumerate()
+            .filter_map(|(j, name)| actual.column_index(name).map(|k| (k, j)))
+            .collect()
+    };
+
+    for (i, row_name) in expected.row_names().iter().enumerate() {
+        let k = match actual.row_index(row_name) {
+            Some(k) => k,
+            None => {
+                differences.push(Difference::MissingRow(row_name.clone()));
+                continue;
+            }
+        };
+
+        for &(actual_j, expected_j) in &columns {
+            let a = actual.data()[[k, actual_j]];
+            let e = expected.data()[[i, expected_j]];
+
+            if !approx_eq(a, e, tolerance) {
+                differences.push(Difference::Value {
+                    row: row_name.clone(),
+                    column: expected.column_names()[expected_j].clone(),
+                    actual: a,
+                    expected: e,
+                });
+            }
+        }
+    }
+
+    for name in actual.row_names() {
+        if expected.row_index(name).is_none() {
+            differences.push(Difference::UnexpectedRow(name.clone()));
+        }
+    }
+
+    if match_columns_by_name {
+        for name in actual.column_names() {
+            if expected.column_index(name).is_none() {
+                differences.push(Difference::UnexpectedColumn(name.clone()));
+            }
+        }
+    }
+
+    differences
+}
+
+fn approx_eq(a: f64, b: f64, tolerance: f64) -> bool {
+    if a.is_nan() || b.is_nan() {
+        return a.is_nan() && b.is_nan();
+    }
+
+    a == b || (a - b).abs() <= tolerance * b.abs().max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_compare_matrices_with_mismatched_names() {
+        let expected = ExpressionMatrix::new(
+            vec![String::from("AAAS"), String::from("RPL37AP1")],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[1.0, 2.0], [3.0, 4.0]]),
+        );
+
+        let actual = ExpressionMatrix::new(
+            vec![String::from("AAAS"), String::from("AC009952.3")],
+            vec![String::from("sample1"), String::from("sample3")],
+            arr2(&[[1.0, 2.0], [3.0, 4.0]]),
+        );
+
+        assert_eq!(
+            compare_matrices(&actual, &expected, 1e-9),
+            [
+                Difference::MissingColumn(String::from("sample2")),
+                Difference::MissingRow(String::from("RPL37AP1")),
+                Difference::UnexpectedRow(String::from("AC009952.3")),
+                Difference::UnexpectedColumn(String::from("sample3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_matrices_with_single_columns() {
+        let expected = ExpressionMatrix::new(
+            vec![String::from("AAAS")],
+            vec![String::from("expected")],
+            arr2(&[[f64::NAN]]),
+        );
+
+        let actual = ExpressionMatrix::new(
+            vec![String::from("AAAS")],
+            vec![String::from("sample1")],
+            arr2(&[[f64::NAN]]),
+        );
+
+        assert!(compare_matrices(&actual, &expected, 1e-9).is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::{self, Read};
+
+use flate2::read::MultiGzDecoder;
+use noodles::formats::gff;
+
+use crate::features::{self, Features, ReadFeaturesOptions};
+
+static HTTP_PREFIX: &str = "http://";
+static HTTPS_PREFIX: &str = "https://";
+static S3_PREFIX: &str = "s3://";
+
+static GZ_SUFFIX: &str = ".gz";
+
+/// Returns whether the given source is a remote URL.
+///
+/// Supported schemes are `http`, `https`, and `s3`.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::remote::is_url;
+///
+/// assert!(is_url("https://example.com/annotations.gtf"));
+/// assert!(is_url("s3://bucket/annotations.gtf.gz"));
+/// assert!(!is_url("annotations.gtf"));
+/// ```
+pub fn is_url(src: &str) -> bool {
+    src.starts_with(HTTP_PREFIX) || src.starts_with(HTTPS_PREFIX) || src.starts_with(S3_PREFIX)
+}
+
+/// Opens a remote source as a stream.
+///
+/// The body is streamed as it is read rather than downloaded in full. If the
+/// path of the URL ends with `.gz`, the stream is decompressed on the fly.
+///
+/// An `s3://bucket/key` URL is fetched anonymously from the bucket's
+/// virtual-hosted-style endpoint, i.e., `https://bucket.s3.amazonaws.com/key`.
+/// Requests are not signed, so the object must be publicly readable. Use a
+/// presigned `https` URL for private objects.
+pub fn open(url: &str) -> io::Result<Box<dyn Read>> {
+    let http_url = resolve_url(url)?;
+
+    // `io::Error::other` requires Rust 1.74.
+    #[allow(clippy::io_other_error)]
+    let response = ureq::get(&http_url)
+        .call()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    let reader = response.into_reader();
+
+    if is_gzipped(url) {
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Builds a map of feature ID-feature vector pairs from a remote GTF/GFFv2.
+///
+/// This is the remote variant of [`read_features`].
+///
+/// [`read_features`]: ../features/fn.read_features.html
+pub fn read_features(url: &str, feature_type: &str, feature_id: &str) -> io::Result<Features> {
+    read_features_with_options(
+        url,
+        feature_type,
+        feature_id,
+        &ReadFeaturesOptions::default(),
+    )
+}
+
+/// Builds a map of feature ID-feature vector pairs from a remote GTF/GFFv2
+/// with options.
+///
+/// This is the same as [`read_features`], but records that lack the feature
+/// ID attribute can be skipped. See [`ReadFeaturesOptions`].
+///
+/// [`read_features`]: fn.read_features.html
+/// [`ReadFeaturesOptions`]: ../features/struct.ReadFeaturesOptions.html
+pub fn read_features_with_options(
+    url: &str,
+    feature_type: &str,
+    feature_id: &str,
+    options: &ReadFeaturesOptions,
+) -> io::Result<Features> {
+    let reader = open(url).map(gff::Reader::new)?;
+    features::parse_features(reader, feature_type, feature_id, Vec::push, options)
+}
+
+fn resolve_url(url: &str) -> io::Result<String> {
+    if !url.starts_with(S3_PREFIX) {
+        return Ok(url.to_string());
+    }
+
+    let path = &url[S3_PREFIX.len()..];
+    let mut components = path.splitn(2, '/');
+
+    match (components.next(), components.next()) {
+        (Some(bucket), Some(key)) if !bucket.is_empty() && !key.is_empty() => {
+            Ok(format!("https://{}.s3.amazonaws.com/{}", bucket, key))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid S3 URL: {}", url),
+        )),
+    }
+}
+
+fn is_gzipped(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.ends_with(GZ_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_url() {
+        assert_eq!(
+            resolve_url("https://example.com/counts.tsv").unwrap(),
+            "https://example.com/counts.tsv"
+        );
+
+        assert_eq!(
+            resolve_url("s3://bucket/refs/annotations.gtf.gz").unwrap(),
+            "https://bucket.s3.amazonaws.com/refs/annotations.gtf.gz"
+        );
+
+        assert!(resolve_url("s3://bucket").is_err());
+        assert!(resolve_url("s3://bucket/").is_err());
+        assert!(resolve_url("s3:///annotations.gtf").is_err());
+    }
+
+    #[test]
+    fn test_is_gzipped() {
+        assert!(is_gzipped("https://example.com/annotations.gtf.gz"));
+        assert!(is_gzipped(
+            "https://example.com/annotations.gtf.gz?X-Amz-Signature=abc"
+        ));
+        assert!(!is_gzipped("https://example.com/annotations.gtf"));
+    }
+}
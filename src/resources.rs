@@ -0,0 +1,237 @@
+use std::{
+    fs,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+const PROC_STATUS_PATH: &str = "/proc/self/status";
+const PEAK_RSS_KEY: &str = "VmHWM:";
+const MISSING_VALUE: &str = "NA";
+
+/// The resources used by a stage of a run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stage {
+    /// The name of the stage, e.g., `counts`.
+    pub name: &'static str,
+    /// The wall time spent in the stage.
+    pub elapsed: Duration,
+    /// The number of records processed in the stage.
+    pub record_count: u64,
+    /// The peak resident set size (in bytes) of the process at the end of the
+    /// stage, if it is known.
+    pub peak_rss: Option<u64>,
+}
+
+impl Stage {
+    /// Returns the number of records processed per second.
+    ///
+    /// This is `None` when no time was measured.
+    pub fn records_per_second(&self) -> Option<f64> {
+        let secs = self.elapsed.as_secs_f64();
+
+        if secs > 0.0 {
+            Some(self.record_count as f64 / secs)
+        } else {
+            None
+        }
+    }
+}
+
+/// A tally of the wall time, records processed, and peak memory of the stages
+/// of a run.
+///
+/// The wall clock starts when the tally is created.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use noodles_fpkm::resources::ResourceUsage;
+///
+/// let mut usage = ResourceUsage::new();
+/// usage.add("counts", Duration::from_millis(250), 1000);
+/// usage.add("counts", Duration::from_millis(250), 1000);
+///
+/// let stages = usage.stages();
+/// assert_eq!(stages.len(), 1);
+/// assert_eq!(stages[0].elapsed, Duration::from_millis(500));
+/// assert_eq!(stages[0].records_per_second(), Some(4000.0));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ResourceUsage {
+    start: Instant,
+    stages: Vec<Stage>,
+}
+
+impl ResourceUsage {
+    /// Creates a tally and starts its wall clock.
+    pub fn new() -> ResourceUsage {
+        ResourceUsage {
+            start: Instant::now(),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Adds the wall time and records of a stage.
+    ///
+    /// Stages are kept in the order they are first added. Adding to an
+    /// existing stage, e.g., when it runs once per sample, accumulates its
+    /// time and records.
+    pub fn add(&mut self, name: &'static str, elapsed: Duration, record_count: u64) {
+        let peak_rss = peak_rss();
+
+        if let Some(stage) = self.stages.iter_mut().find(|s| s.name == name) {
+            stage.elapsed += elapsed;
+            stage.record_count += record_count;
+            stage.peak_rss = peak_rss;
+        } else {
+            self.stages.push(Stage {
+                name,
+                elapsed,
+                record_count,
+                peak_rss,
+            });
+        }
+    }
+
+    /// Adds the wall time since the start of the tally that is not yet
+    /// attributed to a stage to the given stage.
+    pub fn add_remaining(&mut self, name: &'static str, record_count: u64) {
+        let attributed: Duration = self.stages.iter().map(|s| s.elapsed).sum();
+        let remaining = self.elapsed().checked_sub(attributed).unwrap_or_default();
+        self.add(name, remaining, record_count);
+    }
+
+    /// Returns the stages in the order they were first added.
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// Returns the wall time since the start of the tally.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Default for ResourceUsage {
+    fn default() -> Self {
+        ResourceUsage::new()
+    }
+}
+
+/// Returns the peak resident set size (in bytes) of the process.
+///
+/// This is read from `/proc/self/status` and is `None` where it is not
+/// available, e.g., on platforms other than Linux.
+pub fn peak_rss() -> Option<u64> {
+    fs::read_to_string(PROC_STATUS_PATH)
+        .ok()
+        .and_then(|s| parse_peak_rss(&s))
+}
+
+/// Writes a TSV-formatted report of resource usage.
+///
+/// There is one row per stage, followed by a `total` row of the wall time
+/// since the start of the tally. Values that are not known are `NA`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use noodles_fpkm::resources::{write_resource_usage, ResourceUsage};
+///
+/// let mut usage = ResourceUsage::new();
+/// usage.add("counts", Duration::from_millis(500), 1000);
+///
+/// let mut buf = Vec::new();
+/// write_resource_usage(&mut buf, &usage)?;
+///
+/// let report = String::from_utf8(buf).unwrap();
+/// let mut lines = report.lines();
+///
+/// assert_eq!(
+///     lines.next(),
+///     Some("stage\tseconds\trecords\trecords_per_second\tpeak_rss_bytes")
+/// );
+/// assert!(lines.next().unwrap().starts_with("counts\t0.500\t1000\t2000\t"));
+/// assert!(lines.next().unwrap().starts_with("total\t"));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn write_resource_usage<W>(mut writer: W, usage: &ResourceUsage) -> io::Result<()>
+where
+    W: Write,
+{
+    writeln!(
+        writer,
+        "stage\tseconds\trecords\trecords_per_second\tpeak_rss_bytes"
+    )?;
+
+    for stage in usage.stages() {
+        writeln!(
+            writer,
+            "{}\t{:.3}\t{}\t{}\t{}",
+            stage.name,
+            stage.elapsed.as_secs_f64(),
+            stage.record_count,
+            format_optional(stage.records_per_second().map(|n| n.round())),
+            format_optional(stage.peak_rss),
+        )?;
+    }
+
+    writeln!(
+        writer,
+        "total\t{:.3}\t{}\t{}\t{}",
+        usage.elapsed().as_secs_f64(),
+        MISSING_VALUE,
+        MISSING_VALUE,
+        format_optional(peak_rss()),
+    )?;
+
+    writer.flush()
+}
+
+fn format_optional<T>(value: Option<T>) -> String
+where
+    T: ToString,
+{
+    value.map_or_else(|| MISSING_VALUE.into(), |v| v.to_string())
+}
+
+// Parses the peak resident set size from the contents of `/proc/<pid>/status`,
+// where it is given in kB.
+fn parse_peak_rss(s: &str) -> Option<u64> {
+    s.lines()
+        .find_map(|line| line.strip_prefix(PEAK_RSS_KEY))
+        .and_then(|value| value.trim().strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_peak_rss() {
+        let s =
+            "Name:\tnoodles-fpkm\nVmPeak:\t  20480 kB\nVmHWM:\t    2048 kB\nVmRSS:\t    1024 kB\n";
+        assert_eq!(parse_peak_rss(s), Some(2097152));
+
+        assert!(parse_peak_rss("Name:\tnoodles-fpkm\n").is_none());
+        assert!(parse_peak_rss("VmHWM:\tx kB\n").is_none());
+    }
+
+    #[test]
+    fn test_add_remaining() {
+        let mut usage = ResourceUsage::new();
+        usage.add("counts", Duration::from_secs(3600), 1);
+
+        // More time is attributed than has elapsed, so nothing remains.
+        usage.add_remaining("compute", 2);
+
+        let stage = &usage.stages()[1];
+        assert_eq!(stage.name, "compute");
+        assert_eq!(stage.elapsed, Duration::ZERO);
+        assert!(stage.records_per_second().is_none());
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::HashSet,
+    io::{self, Read},
+    str,
+};
+
+use csv::ByteRecord;
+
+const SAMPLE_COLUMN: &str = "sample";
+const CONDITION_COLUMN: &str = "condition";
+const BATCH_COLUMN: &str = "batch";
+
+/// A sample in a sample sheet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Sample {
+    /// The sample name.
+    pub name: String,
+    /// The condition, i.e., the group, of the sample.
+    pub condition: String,
+    /// The batch of the sample, if the sample sheet has a batch column.
+    pub batch: Option<String>,
+}
+
+/// A list of samples and their conditions, batches, and other metadata.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SampleSheet {
+    samples: Vec<Sample>,
+    // The names of the columns other than `sample`, and the values of each
+    // sample in these columns.
+    metadata_columns: Vec<String>,
+    metadata: Vec<Vec<String>>,
+}
+
+impl SampleSheet {
+    /// Returns the samples in the order they were listed.
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Returns the condition of a sample.
+    pub fn condition(&self, sample_name: &str) -> Option<&str> {
+        self.samples
+            .iter()
+            .find(|sample| sample.name == sample_name)
+            .map(|sample| sample.condition.as_str())
+    }
+
+    /// Groups column names by condition.
+    ///
+    /// Conditions are returned in the order they are first listed, each with
+    /// the indices of the columns of its samples. Conditions with no columns
+    /// are omitted, as are columns that are not in the sample sheet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::sample_sheet::read_sample_sheet;
+    ///
+    /// let data = "\
+    /// sample\tcondition
+    /// sample1\ttreated
+    /// sample2\tcontrol
+    /// sample3\ttreated
+    /// ";
+    ///
+    /// let sample_sheet = read_sample_sheet(data.as_bytes())?;
+    /// let column_names = [String::from("sample3"), String::from("sample2")];
+    ///
+    /// assert_eq!(
+    ///     sample_sheet.groups(&column_names),
+    ///     [("treated", vec![0]), ("control", vec![1])]
+    /// );
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn groups(&self, column_names: &[String]) -> Vec<(&str, Vec<usize>)> {
+        self.group_by(column_names, |i| Some(self.samples[i].condition.as_str()))
+    }
+
+    /// Returns whether the samples have batches.
+    pub fn has_batches(&self) -> bool {
+        self.samples.iter().any(|sample| sample.batch.is_some())
+    }
+
+    /// Groups column names by batch.
+    ///
+    /// This is the same as [`groups`] but by batch rather than condition. If
+    /// the sample sheet has no batch column, no groups are returned.
+    ///
+    /// [`groups`]: #method.groups
+    pub fn batches(&self, column_names: &[String]) -> Vec<(&str, Vec<usize>)> {
+        self.group_by(column_names, |i| self.samples[i].batch.as_deref())
+    }
+
+    /// Returns whether the sample sheet has a column with the given name.
+    pub fn has_column(&self, name: &str) -> bool {
+        self.metadata_columns.iter().any(|column| column == name)
+    }
+
+    /// Groups column names by the values of a sample sheet column, e.g.,
+    /// `tissue`.
+    ///
+    /// This is the same as [`groups`] but by any column other than `sample`.
+    /// Samples with an empty value are omitted. If the sample sheet has no
+    /// such column, no groups are returned.
+    ///
+    /// [`groups`]: #method.groups
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::sample_sheet::read_sample_sheet;
+    ///
+    /// let data = "\
+    /// sample\tcondition\ttissue
+    /// sample1\tcontrol\tliver
+    /// sample2\ttreated\tlung
+    /// sample3\ttreated\tliver
+    /// ";
+    ///
+    /// let sample_sheet = read_sample_sheet(data.as_bytes())?;
+    /// let column_names = [String::from("sample1"), String::from("sample2"), String::from("sample3")];
+    ///
+    /// assert_eq!(
+    ///     sample_sheet.strata(&column_names, "tissue"),
+    ///     [("liver", vec![0, 2]), ("lung", vec![1])]
+    /// );
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn strata(&self, column_names: &[String], column: &str) -> Vec<(&str, Vec<usize>)> {
+        let k = match self.metadata_columns.iter().position(|c| c == column) {
+            Some(k) => k,
+            None => return Vec::new(),
+        };
+
+        self.group_by(column_names, |i| {
+            Some(self.metadata[i][k].as_str()).filter(|value| !value.is_empty())
+        })
+    }
+
+    fn group_by<'a, F>(&'a self, column_names: &[String], key: F) -> Vec<(&'a str, Vec<usize>)>
+    where
+        F: Fn(usize) -> Option<&'a str>,
+    {
+        let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+
+        for i in 0..self.samples.len() {
+            if let Some(k) = key(i) {
+                if !groups.iter().any(|(g, _)| *g == k) {
+                    groups.push((k, Vec::new()));
+                }
+            }
+        }
+
+        for (j, name) in column_names.iter().enumerate() {
+            let k = self
+                .samples
+                .iter()
+                .position(|sample| sample.name == *name)
+                .and_then(&key);
+
+            if let Some(k) = k {
+                if let Some((_, indices)) = groups.iter_mut().find(|(g, _)| *g == k) {
+                    indices.push(j);
+                }
+            }
+        }
+
+        groups.retain(|(_, indices)| !indices.is_empty());
+
+        groups
+    }
+}
+
+/// Reads a TSV-formatted sample sheet.
+///
+/// The first line is a header, which must include the columns `sample` and
+/// `condition` and can include `batch`. Other columns are kept as metadata,
+/// e.g., to stratify samples with [`SampleSheet::strata`]. Sample names must be
+/// unique.
+///
+/// [`SampleSheet::strata`]: struct.SampleSheet.html#method.strata
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::sample_sheet::read_sample_sheet;
+///
+/// let data = "\
+/// sample\tcondition\tdescription
+/// sample1\tcontrol\tday 0
+/// sample2\ttreated\tday 7
+/// ";
+///
+/// let sample_sheet = read_sample_sheet(data.as_bytes())?;
+///
+/// assert_eq!(sample_sheet.samples().len(), 2);
+/// assert_eq!(sample_sheet.condition("sample2"), Some("treated"));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_sample_sheet<R>(reader: R) -> io::Result<SampleSheet>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .from_reader(reader);
+
+    let headers = rdr.byte_headers()?.clone();
+    let sample_index = find_column(&headers, SAMPLE_COLUMN)?;
+    let condition_index = find_column(&headers, CONDITION_COLUMN)?;
+    let batch_index = find_column(&headers, BATCH_COLUMN).ok();
+
+    let metadata_indices: Vec<usize> = (0..headers.len()).filter(|&i| i != sample_index).collect();
+    let metadata_columns = metadata_indices
+        .iter()
+        .map(|&i| parse_header(&headers, i))
+        .collect::<io::Result<_>>()?;
+
+    let mut samples = Vec::new();
+    let mut metadata = Vec::new();
+    let mut names = HashSet::new();
+    let mut record = ByteRecord::new();
+
+    while rdr.read_byte_record(&mut record)? {
+        let name = parse_field(&record, sample_index, SAMPLE_COLUMN)?;
+        let condition = parse_field(&record, condition_index, CONDITION_COLUMN)?;
+        let batch = batch_index
+            .map(|i| parse_field(&record, i, BATCH_COLUMN))
+            .transpose()?;
+
+        if !names.insert(name.clone()) {
+            return Err(invalid_data(format!("duplicate sample name: {}", name)));
+        }
+
+        let values = metadata_indices
+            .iter()
+            .map(|&i| {
+                let value = record.get(i).unwrap_or_default();
+                str::from_utf8(value)
+                    .map(|s| s.to_string())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            })
+            .collect::<io::Result<_>>()?;
+
+        samples.push(Sample {
+            name,
+            condition,
+            batch,
+        });
+
+        metadata.push(values);
+    }
+
+    Ok(SampleSheet {
+        samples,
+        metadata_columns,
+        metadata,
+    })
+}
+
+fn parse_header(headers: &ByteRecord, index: usize) -> io::Result<String> {
+    str::from_utf8(&headers[index])
+        .map(|s| s.to_string())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn find_column(headers: &ByteRecord, name: &str) -> io::Result<usize> {
+    headers
+        .iter()
+        .position(|header| header == name.as_bytes())
+        .ok_or_else(|| invalid_data(format!("missing sample sheet column: {}", name)))
+}
+
+fn parse_field(record: &ByteRecord, index: usize, name: &str) -> io::Result<String> {
+    record
+        .get(index)
+        .and_then(|s| str::from_utf8(s).ok())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .ok_or_else(|| invalid_data(format!("invalid {}: {:?}", name, record)))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches() -> io::Result<()> {
+        let data = "\
+sample\tbatch\tcondition
+sample1\tb1\tcontrol
+sample2\tb2\tcontrol
+sample3\tb1\ttreated
+";
+
+        let sample_sheet = read_sample_sheet(data.as_bytes())?;
+        assert!(sample_sheet.has_batches());
+
+        let column_names = [
+            String::from("sample1"),
+            String::from("sample2"),
+            String::from("sample3"),
+        ];
+
+        assert_eq!(
+            sample_sheet.batches(&column_names),
+            [("b1", vec![0, 2]), ("b2", vec![1])]
+        );
+
+        let data = "sample\tcondition\nsample1\tcontrol\n";
+        let sample_sheet = read_sample_sheet(data.as_bytes())?;
+        assert!(!sample_sheet.has_batches());
+        assert!(sample_sheet.batches(&column_names).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strata() -> io::Result<()> {
+        let data = "\
+sample\tcondition\ttissue
+sample1\tcontrol\tliver
+sample2\tcontrol\t
+sample3\ttreated\tliver
+";
+
+        let sample_sheet = read_sample_sheet(data.as_bytes())?;
+        assert!(sample_sheet.has_column("tissue"));
+        assert!(!sample_sheet.has_column("sample"));
+
+        let column_names = [String::from("sample3"), String::from("sample2")];
+
+        assert_eq!(
+            sample_sheet.strata(&column_names, "tissue"),
+            [("liver", vec![0])]
+        );
+        assert_eq!(
+            sample_sheet.strata(&column_names, "condition"),
+            sample_sheet.groups(&column_names)
+        );
+        assert!(sample_sheet.strata(&column_names, "sex").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_sample_sheet_with_invalid_input() {
+        let data = "sample\tgroup\nsample1\tcontrol\n";
+        assert!(read_sample_sheet(data.as_bytes()).is_err());
+
+        let data = "sample\tcondition\nsample1\tcontrol\nsample1\ttreated\n";
+        assert!(read_sample_sheet(data.as_bytes()).is_err());
+
+        let data = "sample\tcondition\nsample1\t\n";
+        assert!(read_sample_sheet(data.as_bytes()).is_err());
+    }
+}
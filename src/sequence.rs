@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead},
+    path::Path,
+};
+
+use crate::{
+    compression,
+    feature_id::FeatureId,
+    features::{merge_intervals_in_place, Feature, Features},
+    gtf::ReferenceSequenceNames,
+};
+
+pub type GcContents = HashMap<FeatureId, f64>;
+
+const HEADER_PREFIX: char = '>';
+
+/// Calculates the GC content of each feature from a reference genome FASTA.
+///
+/// If the extension of the path is `.gz`, the file is decompressed. See
+/// [`calculate_gc_contents`].
+///
+/// [`calculate_gc_contents`]: fn.calculate_gc_contents.html
+pub fn read_gc_contents<P>(
+    src: P,
+    features: &Features,
+    reference_sequence_names: &ReferenceSequenceNames,
+) -> io::Result<GcContents>
+where
+    P: AsRef<Path>,
+{
+    let reader = compression::open(src)?;
+    calculate_gc_contents(reader, features, reference_sequence_names)
+}
+
+/// Calculates the GC content of each feature from a reference genome FASTA.
+///
+/// The GC content of a feature is the fraction of G and C bases over all
+/// A, C, G, and T bases in the union of its intervals, e.g., the exon union of
+/// a gene. Ambiguous bases (e.g., N) are not counted.
+///
+/// The genome is read one reference sequence at a time, so only a single
+/// sequence is held in memory. Features on reference sequences that are not
+/// in the FASTA, or with no unambiguous bases, are omitted.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     feature_id::FeatureId,
+///     features::{Feature, Features},
+///     gtf::ReferenceSequenceNames,
+///     sequence::calculate_gc_contents,
+/// };
+///
+/// let data = b">chr1 description\nACGT\nGGNN\n>chr2\nATAT\n";
+///
+/// let features: Features = [
+///     (FeatureId::from("AAAS"), vec![Feature::new(1, 2), Feature::new(5, 8)]),
+/// ].iter().cloned().collect();
+///
+/// let names: ReferenceSequenceNames = [
+///     (FeatureId::from("AAAS"), String::from("chr1")),
+/// ].iter().cloned().collect();
+///
+/// let gc_contents = calculate_gc_contents(&data[..], &features, &names).unwrap();
+/// assert_eq!(gc_contents["AAAS"], 0.75);
+/// ```
+pub fn calculate_gc_contents<R>(
+    reader: R,
+    features: &Features,
+    reference_sequence_names: &ReferenceSequenceNames,
+) -> io::Result<GcContents>
+where
+    R: BufRead,
+{
+    let mut ids_by_reference_sequence: HashMap<&str, Vec<&FeatureId>> = HashMap::new();
+
+    for id in features.keys() {
+        if let Some(name) = reference_sequence_names.get(id) {
+            ids_by_reference_sequence
+                .entry(name.as_str())
+                .or_default()
+                .push(id);
+        }
+    }
+
+    let mut gc_contents = GcContents::default();
+    let mut name: Option<String> = None;
+    let mut sequence = Vec::new();
+    let mut buf = Vec::new();
+
+    for result in reader.lines() {
+        let line = result?;
+
+        if let Some(header) = line.strip_prefix(HEADER_PREFIX) {
+            if let Some(name) = name.take() {
+                let ids = ids_by_reference_sequence.get(name.as_str());
+                add_gc_contents(&mut gc_contents, ids, features, &sequence, &mut buf)?;
+            }
+
+            name = Some(parse_name(header));
+            sequence.clear();
+        } else {
+            sequence.extend_from_slice(line.trim_end().as_bytes());
+        }
+    }
+
+    if let Some(name) = name {
+        let ids = ids_by_reference_sequence.get(name.as_str());
+        add_gc_contents(&mut gc_contents, ids, features, &sequence, &mut buf)?;
+    }
+
+    Ok(gc_contents)
+}
+
+// The name is the header up to the first whitespace.
+pub(crate) fn parse_name(header: &str) -> String {
+    header.split_whitespace().next().unwrap_or("").to_string()
+}
+
+fn add_gc_contents(
+    gc_contents: &mut GcContents,
+    ids: Option<&Vec<&FeatureId>>,
+    features: &Features,
+    sequence: &[u8],
+    buf: &mut Vec<Feature>,
+) -> io::Result<()> {
+    for &id in ids.into_iter().flatten() {
+        buf.clear();
+        buf.extend_from_slice(&features[id]);
+        merge_intervals_in_place(buf);
+
+        let (mut gc_count, mut base_count) = (0, 0);
+
+        for interval in buf.iter() {
+            let (start, end) = (interval.start as usize, interval.end as usize);
+
+            if start < 1 || end > sequence.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "feature {} interval [{}, {}] is out of bounds",
+                        id, interval.start, interval.end
+                    ),
+                ));
+            }
+
+            let bases = &sequence[start - 1..end];
+            let (gc, n) = count_gc(bases);
+            gc_count += gc;
+            base_count += n;
+        }
+
+        if base_count > 0 {
+            gc_contents.insert(id.clone(), gc_count as f64 / base_count as f64);
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the number of G and C bases and the number of unambiguous bases.
+fn count_gc(bases: &[u8]) -> (u64, u64) {
+    let mut gc_count = 0;
+    let mut base_count = 0;
+
+    for &b in bases {
+        match b.to_ascii_uppercase() {
+            b'G' | b'C' => {
+                gc_count += 1;
+                base_count += 1;
+            }
+            b'A' | b'T' => base_count += 1,
+            _ => {}
+        }
+    }
+
+    (gc_count, base_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculate_gc_contents() {
+        let data = b">chr1\nacgt\nGGNN\n>chr2\nATAT\nGC\n>chr3\nNNNN\n";
+
+        let features: Features = [
+            (
+                FeatureId::from("a"),
+                vec![Feature::new(1, 4), Feature::new(3, 6)],
+            ),
+            (FeatureId::from("b"), vec![Feature::new(3, 6)]),
+            (FeatureId::from("c"), vec![Feature::new(1, 4)]),
+            (FeatureId::from("d"), vec![Feature::new(1, 4)]),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let names: ReferenceSequenceNames = [
+            (FeatureId::from("a"), String::from("chr1")),
+            (FeatureId::from("b"), String::from("chr2")),
+            (FeatureId::from("c"), String::from("chr3")),
+            (FeatureId::from("d"), String::from("chrM")),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let gc_contents = calculate_gc_contents(&data[..], &features, &names).unwrap();
+
+        assert_eq!(gc_contents.len(), 2);
+        assert_eq!(gc_contents["a"], 4.0 / 6.0);
+        assert_eq!(gc_contents["b"], 0.5);
+    }
+
+    #[test]
+    fn test_calculate_gc_contents_with_out_of_bounds_interval() {
+        let data = b">chr1\nACGT\n";
+
+        let features: Features = [(FeatureId::from("a"), vec![Feature::new(3, 5)])]
+            .iter()
+            .cloned()
+            .collect();
+
+        let names: ReferenceSequenceNames = [(FeatureId::from("a"), String::from("chr1"))]
+            .iter()
+            .cloned()
+            .collect();
+
+        assert!(calculate_gc_contents(&data[..], &features, &names).is_err());
+    }
+
+    #[test]
+    fn test_count_gc() {
+        assert_eq!(count_gc(b"ACGTNacgtn"), (4, 8));
+        assert_eq!(count_gc(b""), (0, 0));
+    }
+}
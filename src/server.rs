@@ -0,0 +1,536 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::{
+    calculate_fpkms, calculate_tpms,
+    counts::{read_counts, Counts},
+    error_report::{push_json_string, ErrorReport},
+    expression_set::Method,
+    features::Features,
+    lengths::{calculate_lengths, calculate_unit_lengths, Lengths},
+    Error, Expressions,
+};
+
+const QUANTIFY_PATH: &str = "/quantify";
+const HEALTH_PATH: &str = "/health";
+const UNIT_PARAM: &str = "unit";
+const CONTENT_LENGTH_HEADER: &str = "content-length";
+
+// The largest request body that is read, in bytes.
+const MAX_BODY_LEN: u64 = 256 * 1024 * 1024;
+
+// The longest request or header line that is read, in bytes.
+const MAX_LINE_LEN: u64 = 8 * 1024;
+
+// The most headers that are read from a request.
+const MAX_HEADER_COUNT: usize = 100;
+
+// How long reading a request or writing a response can block, so that a
+// stalled client does not hold the (only) connection open indefinitely.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A calculator of expression values from the counts of a sample, using
+/// lengths from annotations that are parsed once.
+///
+/// The lengths are shared rather than copied, so a quantifier is cheap to
+/// clone, and it can be shared by threads to quantify many samples
+/// concurrently.
+#[derive(Clone, Debug)]
+pub struct Quantifier {
+    lengths: Arc<Lengths>,
+    unit_lengths: Arc<Lengths>,
+}
+
+impl Quantifier {
+    /// Creates a quantifier from features.
+    pub fn new(features: &Features) -> Quantifier {
+        Quantifier::with_lengths(
+            calculate_lengths(features),
+            calculate_unit_lengths(features),
+        )
+    }
+
+    /// Creates a quantifier from feature lengths, e.g., effective lengths, and
+    /// unit lengths, which are used for CPMs.
+    pub fn with_lengths(lengths: Lengths, unit_lengths: Lengths) -> Quantifier {
+        Quantifier {
+            lengths: Arc::new(lengths),
+            unit_lengths: Arc::new(unit_lengths),
+        }
+    }
+
+    /// Returns the number of features.
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    /// Returns whether there are no features.
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    /// Calculates the expression values of a sample.
+    ///
+    /// This returns `Error::UnsupportedMethod` if the method is
+    /// `Method::Scaled`, which requires size factors rather than lengths.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::thread;
+    ///
+    /// use noodles_fpkm::{
+    ///     counts::Counts, expression_set::Method, feature_id::FeatureId, server::Quantifier,
+    /// };
+    ///
+    /// let lengths = [(FeatureId::from("AAAS"), 1000)].iter().cloned().collect();
+    /// let unit_lengths = [(FeatureId::from("AAAS"), 1)].iter().cloned().collect();
+    /// let quantifier = Quantifier::with_lengths(lengths, unit_lengths);
+    ///
+    /// let handles: Vec<_> = (1..=4)
+    ///     .map(|n| {
+    ///         let quantifier = quantifier.clone();
+    ///
+    ///         thread::spawn(move || {
+    ///             let counts: Counts = [(FeatureId::from("AAAS"), n)].iter().cloned().collect();
+    ///             quantifier.quantify_counts(&counts, Method::Fpkm).unwrap()
+    ///         })
+    ///     })
+    ///     .collect();
+    ///
+    /// for handle in handles {
+    ///     let fpkms = handle.join().unwrap();
+    ///     assert_eq!(fpkms[&FeatureId::from("AAAS")], 1000000.0);
+    /// }
+    /// ```
+    pub fn quantify_counts(&self, counts: &Counts, method: Method) -> Result<Expressions, Error> {
+        match method {
+            Method::Fpkm => calculate_fpkms(counts, &self.lengths),
+            Method::Tpm => calculate_tpms(counts, &self.lengths),
+            Method::Cpm => calculate_tpms(counts, &self.unit_lengths),
+            Method::Scaled => Err(Error::UnsupportedMethod(method)),
+        }
+    }
+
+    fn quantify(&self, data: &[u8], method: Method) -> Result<Expressions, (u16, ErrorReport)> {
+        let counts = read_counts(data).map_err(|e| (400, ErrorReport::from_io_error(&e)))?;
+
+        self.quantify_counts(&counts, method)
+            .map_err(|e| (422, ErrorReport::from(&e)))
+    }
+}
+
+/// Answers quantification requests over HTTP until the listener fails.
+///
+/// Connections are handled one at a time, and reads and writes that block for
+/// longer than 30 seconds fail. See [`handle_connection`].
+///
+/// [`handle_connection`]: fn.handle_connection.html
+pub fn serve(listener: &TcpListener, quantifier: &Quantifier) -> io::Result<()> {
+    if let Ok(addr) = listener.local_addr() {
+        info!("listening on http://{}", addr);
+    }
+
+    for result in listener.incoming() {
+        let stream = result?;
+
+        if let Err(e) = set_timeouts(&stream) {
+            warn!("connection error: {}", e);
+            continue;
+        }
+
+        let reader = BufReader::new(&stream);
+
+        if let Err(e) = handle_connection(reader, &stream, quantifier) {
+            warn!("connection error: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+fn set_timeouts(stream: &TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))
+}
+
+/// Reads one HTTP/1.1 request and writes its response.
+///
+/// The API has two endpoints:
+///
+///   * `GET /health` returns `{"status":"ok","feature_count":<n>}`.
+///   * `POST /quantify?unit=<fpkm|tpm|cpm>` takes htseq-count-formatted counts
+///     of a sample as its body and returns
+///     `{"unit":"<unit>","expressions":{"<feature ID>":<value>,...}}`, with
+///     features sorted by ID. The unit defaults to `tpm`.
+///
+/// Errors are returned as JSON error reports with a 4xx status: 400 for
+/// invalid requests or counts and 422 for counts that cannot be quantified,
+/// e.g., a feature that is not in the annotations. Requests with a line longer
+/// than 8 KiB or more than 100 headers are invalid. The connection is closed
+/// after the response.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{feature_id::FeatureId, server::{handle_connection, Quantifier}};
+///
+/// let lengths = [(FeatureId::from("AAAS"), 1000)].iter().cloned().collect();
+/// let unit_lengths = [(FeatureId::from("AAAS"), 1)].iter().cloned().collect();
+/// let quantifier = Quantifier::with_lengths(lengths, unit_lengths);
+///
+/// let request = "POST /quantify?unit=fpkm HTTP/1.1\r\nContent-Length: 9\r\n\r\nAAAS\t645\n";
+///
+/// let mut buf = Vec::new();
+/// handle_connection(request.as_bytes(), &mut buf, &quantifier)?;
+///
+/// let response = String::from_utf8(buf).unwrap();
+/// assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+/// assert!(response.ends_with(r#"{"unit":"fpkm","expressions":{"AAAS":1000000}}"#));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn handle_connection<R, W>(mut reader: R, writer: W, quantifier: &Quantifier) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+{
+    let request = match read_request(&mut reader) {
+        Ok(request) => request,
+        Err(e) => {
+            let report = ErrorReport::from_io_error(&e);
+            return write_response(writer, 400, &report.to_json());
+        }
+    };
+
+    let (path, query) = match request.target.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (request.target.as_str(), ""),
+    };
+
+    match (request.method.as_str(), path) {
+        ("GET", HEALTH_PATH) => {
+            let body = format!(
+                "{{\"status\":\"ok\",\"feature_count\":{}}}",
+                quantifier.len()
+            );
+
+            write_response(writer, 200, &body)
+        }
+        ("POST", QUANTIFY_PATH) => {
+            let method = match parse_unit(query) {
+                Ok(method) => method,
+                Err(report) => return write_response(writer, 400, &report.to_json()),
+            };
+
+            match quantifier.quantify(&request.body, method) {
+                Ok(expressions) => write_response(writer, 200, &build_body(method, &expressions)),
+                Err((status, report)) => write_response(writer, status, &report.to_json()),
+            }
+        }
+        (_, HEALTH_PATH) | (_, QUANTIFY_PATH) => {
+            let report = ErrorReport::new(
+                "method_not_allowed",
+                format!("method not allowed: {}", request.method),
+            );
+
+            write_response(writer, 405, &report.to_json())
+        }
+        _ => {
+            let report = ErrorReport::new("not_found", format!("not found: {}", path));
+            write_response(writer, 404, &report.to_json())
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    target: String,
+    body: Vec<u8>,
+}
+
+fn read_request<R>(reader: &mut R) -> io::Result<Request>
+where
+    R: BufRead,
+{
+    let mut line = String::new();
+    read_line(reader, &mut line)?;
+
+    let mut fields = line.split_whitespace();
+
+    let (method, target) = match (fields.next(), fields.next(), fields.next()) {
+        (Some(method), Some(target), Some(_)) => (method.to_string(), target.to_string()),
+        _ => {
+            return Err(invalid_data(format!(
+                "invalid request line: {}",
+                line.trim()
+            )))
+        }
+    };
+
+    let mut content_length = 0;
+    let mut header_count = 0;
+
+    loop {
+        line.clear();
+
+        if read_line(reader, &mut line)? == 0 {
+            break;
+        }
+
+        let header = line.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        header_count += 1;
+
+        if header_count > MAX_HEADER_COUNT {
+            return Err(invalid_data(format!(
+                "too many headers (max {})",
+                MAX_HEADER_COUNT
+            )));
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case(CONTENT_LENGTH_HEADER) {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|&n| n <= MAX_BODY_LEN)
+                    .ok_or_else(|| {
+                        invalid_data(format!("invalid content length: {}", value.trim()))
+                    })?;
+            }
+        }
+    }
+
+    let mut body = Vec::new();
+    reader.take(content_length).read_to_end(&mut body)?;
+
+    if (body.len() as u64) < content_length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "incomplete request body",
+        ));
+    }
+
+    Ok(Request {
+        method,
+        target,
+        body,
+    })
+}
+
+// Reads a line of at most `MAX_LINE_LEN` bytes.
+fn read_line<R>(reader: &mut R, line: &mut String) -> io::Result<usize>
+where
+    R: BufRead,
+{
+    let n = reader.take(MAX_LINE_LEN).read_line(line)?;
+
+    if n as u64 == MAX_LINE_LEN && !line.ends_with('\n') {
+        return Err(invalid_data(format!(
+            "line too long (max {} bytes)",
+            MAX_LINE_LEN
+        )));
+    }
+
+    Ok(n)
+}
+
+fn parse_unit(query: &str) -> Result<Method, ErrorReport> {
+    let unit = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == UNIT_PARAM)
+        .map(|(_, value)| value)
+        .unwrap_or("tpm");
+
+    match unit {
+        "fpkm" => Ok(Method::Fpkm),
+        "tpm" => Ok(Method::Tpm),
+        "cpm" => Ok(Method::Cpm),
+        _ => Err(ErrorReport {
+            value: Some(unit.into()),
+            ..ErrorReport::new(
+                "invalid_value",
+                format!("invalid unit (expected fpkm, tpm, or cpm): {}", unit),
+            )
+        }),
+    }
+}
+
+fn build_body(method: Method, expressions: &Expressions) -> String {
+    let mut s = String::new();
+
+    s.push_str("{\"unit\":");
+    push_json_string(&mut s, method.as_str());
+    s.push_str(",\"expressions\":{");
+
+    for (i, (id, value)) in expressions.iter().enumerate() {
+        if i > 0 {
+            s.push(',');
+        }
+
+        push_json_string(&mut s, id);
+        s.push(':');
+
+        // JSON has no representation of infinities or NaN.
+        if value.is_finite() {
+            write!(s, "{}", value).unwrap();
+        } else {
+            s.push_str("null");
+        }
+    }
+
+    s.push_str("}}");
+
+    s
+}
+
+fn write_response<W>(mut writer: W, status: u16, body: &str) -> io::Result<()>
+where
+    W: Write,
+{
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        422 => "Unprocessable Entity",
+        _ => "",
+    };
+
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+
+    writer.flush()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feature_id::FeatureId;
+
+    use super::*;
+
+    fn build_quantifier() -> Quantifier {
+        let lengths = [
+            (FeatureId::from("AAAS"), 1000),
+            (FeatureId::from("RPL37AP1"), 500),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let unit_lengths = [
+            (FeatureId::from("AAAS"), 1),
+            (FeatureId::from("RPL37AP1"), 1),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        Quantifier::with_lengths(lengths, unit_lengths)
+    }
+
+    #[test]
+    fn test_quantifier_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Features>();
+        assert_send_sync::<Lengths>();
+        assert_send_sync::<Quantifier>();
+    }
+
+    #[test]
+    fn test_quantify_counts_with_scaled() {
+        let counts: Counts = [(FeatureId::from("AAAS"), 1)].iter().cloned().collect();
+
+        assert!(matches!(
+            build_quantifier().quantify_counts(&counts, Method::Scaled),
+            Err(Error::UnsupportedMethod(Method::Scaled))
+        ));
+    }
+
+    fn request(data: &str) -> io::Result<String> {
+        let mut buf = Vec::new();
+        handle_connection(data.as_bytes(), &mut buf, &build_quantifier())?;
+        Ok(String::from_utf8(buf).unwrap())
+    }
+
+    #[test]
+    fn test_handle_connection_with_cpm_and_health() -> io::Result<()> {
+        let body = "AAAS\t1\nRPL37AP1\t3\n";
+        let response = request(&format!(
+            "POST /quantify?unit=cpm HTTP/1.1\r\ncontent-length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        ))?;
+        assert!(
+            response.ends_with(r#"{"unit":"cpm","expressions":{"AAAS":250000,"RPL37AP1":750000}}"#)
+        );
+
+        let response = request("GET /health HTTP/1.1\r\n\r\n")?;
+        assert!(response.ends_with(r#"{"status":"ok","feature_count":2}"#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_handle_connection_with_errors() -> io::Result<()> {
+        let response = request("POST /quantify HTTP/1.1\r\nContent-Length: 9\r\n\r\nNOPE\t645\n")?;
+        assert!(response.starts_with("HTTP/1.1 422 "));
+        assert!(response.contains(r#""code":"missing_feature""#));
+
+        let response = request("POST /quantify HTTP/1.1\r\nContent-Length: 7\r\n\r\nAAAS\tx\n")?;
+        assert!(response.starts_with("HTTP/1.1 400 "));
+        assert!(response.contains(r#""line":1,"value":"x""#));
+
+        let response = request("POST /quantify?unit=scaled HTTP/1.1\r\n\r\n")?;
+        assert!(response.starts_with("HTTP/1.1 400 "));
+
+        let response = request("POST /quantify HTTP/1.1\r\nContent-Length: 10\r\n\r\nAAAS")?;
+        assert!(response.starts_with("HTTP/1.1 400 "));
+
+        let response = request("GET /quantify HTTP/1.1\r\n\r\n")?;
+        assert!(response.starts_with("HTTP/1.1 405 "));
+
+        let response = request("GET / HTTP/1.1\r\n\r\n")?;
+        assert!(response.starts_with("HTTP/1.1 404 "));
+
+        let response = request("GET\r\n\r\n")?;
+        assert!(response.starts_with("HTTP/1.1 400 "));
+
+        let response = request(&format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(8192)))?;
+        assert!(response.starts_with("HTTP/1.1 400 "));
+        assert!(response.contains("line too long"));
+
+        let response = request(&format!(
+            "GET /health HTTP/1.1\r\n{}\r\n",
+            "x-a: b\r\n".repeat(101)
+        ))?;
+        assert!(response.starts_with("HTTP/1.1 400 "));
+        assert!(response.contains("too many headers"));
+
+        Ok(())
+    }
+}
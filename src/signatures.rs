@@ -0,0 +1,247 @@
+use std::{
+    collections::HashSet,
+    io::{self, BufRead},
+};
+
+use ndarray::Array2;
+
+use crate::matrix::ExpressionMatrix;
+
+const NAME_INDEX: usize = 0;
+const GENES_START_INDEX: usize = 2;
+
+/// A named list of genes, e.g., an immune or proliferation signature.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Signature {
+    pub name: String,
+    pub genes: Vec<String>,
+}
+
+/// Reads gene signatures from a GMT (Gene Matrix Transposed) file.
+///
+/// Each line is tab-separated: the signature name, a description (which is
+/// ignored), and one or more gene IDs. Blank lines are skipped. Signature names
+/// must be unique.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::signatures::read_signatures;
+///
+/// let data = "\
+/// immune\thttps://example.com/immune\tCD3E\tCD8A\tGZMB
+/// proliferation\t-\tMKI67\tTOP2A
+/// ";
+///
+/// let signatures = read_signatures(data.as_bytes()).unwrap();
+///
+/// assert_eq!(signatures.len(), 2);
+/// assert_eq!(signatures[1].name, "proliferation");
+/// assert_eq!(signatures[1].genes, ["MKI67", "TOP2A"]);
+/// ```
+pub fn read_signatures<R>(reader: R) -> io::Result<Vec<Signature>>
+where
+    R: BufRead,
+{
+    let mut signatures = Vec::new();
+    let mut names = HashSet::new();
+
+    for result in reader.lines() {
+        let line = result?;
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+
+        if fields.len() <= GENES_START_INDEX {
+            return Err(invalid_data(format!(
+                "invalid signature: expected at least {} fields, got {}",
+                GENES_START_INDEX + 1,
+                fields.len()
+            )));
+        }
+
+        let name = fields[NAME_INDEX].to_string();
+
+        if !names.insert(name.clone()) {
+            return Err(invalid_data(format!("duplicate signature: {}", name)));
+        }
+
+        let genes = fields[GENES_START_INDEX..]
+            .iter()
+            .filter(|gene| !gene.is_empty())
+            .map(|gene| gene.to_string())
+            .collect();
+
+        signatures.push(Signature { name, genes });
+    }
+
+    Ok(signatures)
+}
+
+/// Scores each signature in each sample of a normalized expression matrix.
+///
+/// Values are log-transformed (`log2(x + 1)`) and standardized per gene across
+/// samples as z-scores. The score of a signature in a sample is the mean
+/// z-score of its genes minus the mean z-score of all other genes, i.e., the
+/// background.
+///
+/// Signature genes that are not in the matrix, and genes with no variance
+/// across samples, are ignored. A signature with no remaining genes has a
+/// score of NaN in every sample.
+///
+/// The rows of the returned matrix are the signature names, and the columns
+/// are the samples.
+///
+/// # Panics
+///
+/// Panics when signature names are not unique.
+pub fn score_signatures(matrix: &ExpressionMatrix, signatures: &[Signature]) -> ExpressionMatrix {
+    let z_scores = calculate_z_scores(matrix);
+    let sample_count = matrix.column_names().len();
+
+    let mut totals = vec![0.0; sample_count];
+    let mut total_count = 0;
+
+    for row in z_scores.iter().flatten() {
+        for (total, z) in totals.iter_mut().zip(row) {
+            *total += z;
+        }
+
+        total_count += 1;
+    }
+
+    let mut data = Array2::zeros((signatures.len(), sample_count));
+
+    for (i, signature) in signatures.iter().enumerate() {
+        let rows: Vec<&Vec<f64>> = signature
+            .genes
+            .iter()
+            .filter_map(|gene| matrix.row_index(gene))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|k| z_scores[k].as_ref())
+            .collect();
+
+        let n = rows.len();
+        let background_count = total_count - n;
+
+        for j in 0..sample_count {
+            if n == 0 {
+                data[[i, j]] = f64::NAN;
+                continue;
+            }
+
+            let sum: f64 = rows.iter().map(|row| row[j]).sum();
+
+            let background = if background_count > 0 {
+                (totals[j] - sum) / background_count as f64
+            } else {
+                0.0
+            };
+
+            data[[i, j]] = sum / n as f64 - background;
+        }
+    }
+
+    let row_names = signatures.iter().map(|s| s.name.clone()).collect();
+    ExpressionMatrix::new(row_names, matrix.column_names().to_vec(), data)
+}
+
+// Returns the z-scores of the log-transformed values of each row, or `None`
+// for rows with no variance.
+fn calculate_z_scores(matrix: &ExpressionMatrix) -> Vec<Option<Vec<f64>>> {
+    let data = matrix.data();
+    let n = data.cols();
+
+    (0..data.rows())
+        .map(|i| {
+            if n < 2 {
+                return None;
+            }
+
+            let values: Vec<f64> = data.row(i).iter().map(|x| (x + 1.0).log2()).collect();
+
+            let mean = values.iter().sum::<f64>() / n as f64;
+            let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+            let sd = variance.sqrt();
+
+            if sd == 0.0 {
+                return None;
+            }
+
+            Some(values.iter().map(|x| (x - mean) / sd).collect())
+        })
+        .collect()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    fn build_matrix() -> ExpressionMatrix {
+        ExpressionMatrix::new(
+            vec![
+                String::from("CD3E"),
+                String::from("CD8A"),
+                String::from("GAPDH"),
+                String::from("MKI67"),
+            ],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[1.0, 15.0], [3.0, 63.0], [7.0, 7.0], [15.0, 1.0]]),
+        )
+    }
+
+    #[test]
+    fn test_read_signatures_with_invalid_input() {
+        assert!(read_signatures(&b"immune\t-\n"[..]).is_err());
+        assert!(read_signatures(&b"immune\t-\tCD3E\nimmune\t-\tCD8A\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_score_signatures() {
+        let matrix = build_matrix();
+
+        let signatures = [
+            Signature {
+                name: String::from("immune"),
+                genes: vec![String::from("CD3E"), String::from("CD8A")],
+            },
+            Signature {
+                name: String::from("housekeeping"),
+                genes: vec![String::from("GAPDH"), String::from("ACTB")],
+            },
+        ];
+
+        let scores = score_signatures(&matrix, &signatures);
+
+        assert_eq!(scores.row_names(), ["immune", "housekeeping"]);
+        assert_eq!(scores.column_names(), ["sample1", "sample2"]);
+
+        // With two samples, every z-score is ±1/√2.
+        let z = 1.0 / 2.0f64.sqrt();
+        assert!((scores.get("immune", "sample1").unwrap() - (-z - z)).abs() < 1e-12);
+        assert!((scores.get("immune", "sample2").unwrap() - (z + z)).abs() < 1e-12);
+
+        assert!(scores.get("housekeeping", "sample1").unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_calculate_z_scores() {
+        let z_scores = calculate_z_scores(&build_matrix());
+        let z = 1.0 / 2.0f64.sqrt();
+
+        assert_eq!(z_scores[0].as_ref().map(|row| row.len()), Some(2));
+        assert!((z_scores[0].as_ref().unwrap()[0] + z).abs() < 1e-12);
+        assert!(z_scores[2].is_none());
+    }
+}
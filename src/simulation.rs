@@ -0,0 +1,106 @@
+use rand::Rng;
+use rand_distr::{Distribution, Gamma, Poisson};
+
+use crate::{counts::Counts, feature_id::FeatureId};
+
+/// Simulates the counts of a sample from a negative binomial distribution.
+///
+/// The count of each feature is drawn independently with the given mean and
+/// dispersion, i.e., a variance of `mean + dispersion * mean^2`, as in DESeq2
+/// and edgeR. A dispersion of 0 is a Poisson distribution. For a fixed seed,
+/// the result is reproducible, as features are drawn in the given order.
+///
+/// # Panics
+///
+/// Panics if `mean` or `dispersion` is negative or not finite.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{feature_id::FeatureId, simulation::simulate_counts};
+/// use rand::SeedableRng;
+/// use rand_pcg::Pcg64;
+///
+/// let ids = [FeatureId::from("AAAS"), FeatureId::from("RPL37AP1")];
+///
+/// let mut rng = Pcg64::seed_from_u64(0);
+/// let counts = simulate_counts(&ids, 100.0, 0.1, &mut rng);
+///
+/// assert_eq!(counts.len(), 2);
+/// ```
+pub fn simulate_counts<R>(ids: &[FeatureId], mean: f64, dispersion: f64, rng: &mut R) -> Counts
+where
+    R: Rng,
+{
+    assert!(mean.is_finite() && mean >= 0.0, "invalid mean: {}", mean);
+    assert!(
+        dispersion.is_finite() && dispersion >= 0.0,
+        "invalid dispersion: {}",
+        dispersion
+    );
+
+    // The negative binomial is a gamma-Poisson mixture: the Poisson rate of
+    // each feature is drawn from a gamma distribution with the given mean and
+    // a shape of 1 / dispersion.
+    let gamma = if dispersion > 0.0 && mean > 0.0 {
+        Some(Gamma::new(1.0 / dispersion, mean * dispersion).unwrap())
+    } else {
+        None
+    };
+
+    ids.iter()
+        .map(|id| {
+            let rate = match &gamma {
+                Some(gamma) => gamma.sample(rng),
+                None => mean,
+            };
+
+            (id.clone(), sample_poisson(rate, rng))
+        })
+        .collect()
+}
+
+fn sample_poisson<R>(rate: f64, rng: &mut R) -> u64
+where
+    R: Rng,
+{
+    if rate > 0.0 {
+        let count: f64 = Poisson::new(rate).unwrap().sample(rng);
+        count as u64
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+
+    #[test]
+    fn test_simulate_counts() {
+        let ids: Vec<FeatureId> = (0..2000)
+            .map(|i| FeatureId::from(format!("g{}", i).as_str()))
+            .collect();
+
+        let mut rng = Pcg64::seed_from_u64(0);
+        let counts = simulate_counts(&ids, 50.0, 0.2, &mut rng);
+
+        let values: Vec<f64> = ids.iter().map(|id| counts[id] as f64).collect();
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+
+        // The expected variance is 50 + 0.2 * 50^2 = 550.
+        assert!((mean - 50.0).abs() < 2.0, "mean = {}", mean);
+        assert!((variance - 550.0).abs() < 100.0, "variance = {}", variance);
+
+        let mut rng = Pcg64::seed_from_u64(0);
+        assert_eq!(simulate_counts(&ids, 50.0, 0.2, &mut rng), counts);
+
+        let counts = simulate_counts(&ids[..1], 0.0, 0.2, &mut rng);
+        assert_eq!(counts[&ids[0]], 0);
+    }
+}
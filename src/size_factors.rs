@@ -0,0 +1,228 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::{self, Read},
+    str,
+};
+
+use csv::ByteRecord;
+
+use crate::{counts::Counts, matrix::ExpressionMatrix, Expressions};
+
+const NAME_INDEX: usize = 0;
+const SIZE_FACTOR_INDEX: usize = 1;
+
+pub type SizeFactors = HashMap<String, f64>;
+
+/// Reads TSV-formatted data and returns a map of sample name-size factor
+/// pairs.
+///
+/// The input is TSV-formatted with two columns: a sample name (string) and a
+/// size factor (positive float), e.g., as computed by DESeq2's
+/// `estimateSizeFactors`.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::size_factors::read_size_factors;
+///
+/// let data = "\
+/// sample1\t0.8
+/// sample2\t1.25
+/// ";
+///
+/// let size_factors = read_size_factors(data.as_bytes()).unwrap();
+///
+/// assert_eq!(size_factors.len(), 2);
+/// assert_eq!(size_factors["sample1"], 0.8);
+/// assert_eq!(size_factors["sample2"], 1.25);
+/// ```
+pub fn read_size_factors<R>(reader: R) -> io::Result<SizeFactors>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .from_reader(reader);
+
+    let mut size_factors = SizeFactors::new();
+    let mut record = ByteRecord::new();
+
+    while rdr.read_byte_record(&mut record)? {
+        let name = record
+            .get(NAME_INDEX)
+            .and_then(|s| str::from_utf8(s).ok())
+            .ok_or_else(|| invalid_data(format!("invalid sample name: {:?}", record)))?;
+
+        let size_factor = record
+            .get(SIZE_FACTOR_INDEX)
+            .and_then(|s| str::from_utf8(s).ok())
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|&n| n.is_finite() && n > 0.0)
+            .ok_or_else(|| invalid_data(format!("invalid size factor: {:?}", record)))?;
+
+        match size_factors.entry(name.into()) {
+            Entry::Vacant(entry) => {
+                entry.insert(size_factor);
+            }
+            Entry::Occupied(entry) => {
+                return Err(invalid_data(format!(
+                    "duplicate sample name: {}",
+                    entry.key()
+                )));
+            }
+        }
+    }
+
+    Ok(size_factors)
+}
+
+/// Calculates the size factor of each sample using the median-of-ratios
+/// method.
+///
+/// This is the method used by DESeq2. Each count is divided by the geometric
+/// mean of its feature's counts across samples, and the size factor of a
+/// sample is the median of these ratios. Only features counted (> 0) in every
+/// sample are used.
+///
+/// The matrix is expected to hold raw counts, with one column per sample. This
+/// returns `None` when no feature is counted in every sample.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{matrix::ExpressionMatrix, size_factors::calculate_size_factors};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+///     vec![String::from("sample1"), String::from("sample2")],
+///     arr2(&[[10.0, 40.0], [20.0, 80.0]]),
+/// );
+///
+/// let size_factors = calculate_size_factors(&matrix).unwrap();
+///
+/// assert!((size_factors[0] - 0.5).abs() < 1e-9);
+/// assert!((size_factors[1] - 2.0).abs() < 1e-9);
+/// ```
+pub fn calculate_size_factors(matrix: &ExpressionMatrix) -> Option<Vec<f64>> {
+    let data = matrix.data();
+
+    let log_rows: Vec<(usize, f64)> = (0..data.rows())
+        .filter(|&i| data.row(i).iter().all(|&x| x > 0.0))
+        .map(|i| {
+            let row = data.row(i);
+            let log_geometric_mean = row.iter().map(|x| x.ln()).sum::<f64>() / row.len() as f64;
+            (i, log_geometric_mean)
+        })
+        .collect();
+
+    if log_rows.is_empty() {
+        return None;
+    }
+
+    let size_factors = (0..data.cols())
+        .map(|j| {
+            let mut log_ratios: Vec<f64> = log_rows
+                .iter()
+                .map(|&(i, log_geometric_mean)| data[[i, j]].ln() - log_geometric_mean)
+                .collect();
+
+            median(&mut log_ratios).exp()
+        })
+        .collect();
+
+    Some(size_factors)
+}
+
+/// Divides each count by a size factor.
+///
+/// The result is comparable across samples, e.g., DESeq2's normalized counts,
+/// but it is not normalized by feature length.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{
+///     counts::Counts, feature_id::FeatureId, size_factors::calculate_scaled_counts,
+/// };
+///
+/// let counts: Counts = [(FeatureId::from("AAAS"), 645)].iter().cloned().collect();
+/// let scaled_counts = calculate_scaled_counts(&counts, 0.5);
+///
+/// assert_eq!(scaled_counts["AAAS"], 1290.0);
+/// ```
+pub fn calculate_scaled_counts(counts: &Counts, size_factor: f64) -> Expressions {
+    counts
+        .iter()
+        .map(|(name, &count)| (name.clone(), count as f64 / size_factor))
+        .collect()
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).expect("values cannot be NaN"));
+
+    let mid = values.len() / 2;
+
+    match values.len() % 2 {
+        0 => (values[mid - 1] + values[mid]) / 2.0,
+        _ => values[mid],
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_read_size_factors_with_invalid_size_factor() {
+        assert!(read_size_factors(&b"sample1\t0\n"[..]).is_err());
+        assert!(read_size_factors(&b"sample1\t-1.5\n"[..]).is_err());
+        assert!(read_size_factors(&b"sample1\tNaN\n"[..]).is_err());
+        assert!(read_size_factors(&b"sample1\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_size_factors_with_duplicate_name() {
+        let data = b"sample1\t0.8\nsample1\t1.25\n";
+        assert!(read_size_factors(&data[..]).is_err());
+    }
+
+    #[test]
+    fn test_calculate_size_factors() {
+        let matrix = ExpressionMatrix::new(
+            vec![
+                String::from("AAAS"),
+                String::from("AC009952.3"),
+                String::from("RPL37AP1"),
+            ],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[10.0, 40.0], [0.0, 5.0], [20.0, 80.0]]),
+        );
+
+        let size_factors = calculate_size_factors(&matrix).unwrap();
+        assert_eq!(size_factors.len(), 2);
+        assert!((size_factors[0] - 0.5).abs() < 1e-9);
+        assert!((size_factors[1] - 2.0).abs() < 1e-9);
+
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("AAAS")],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[0.0, 40.0]]),
+        );
+
+        assert!(calculate_size_factors(&matrix).is_none());
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), 2.5);
+    }
+}
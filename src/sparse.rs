@@ -0,0 +1,329 @@
+use ndarray::Array2;
+
+use crate::matrix::ExpressionMatrix;
+
+/// A sparse matrix of expression values.
+///
+/// Rows are features, and columns are samples or cells. Values are stored in
+/// compressed sparse column (CSC) format, i.e., only nonzero values are kept,
+/// grouped by column and sorted by row. This is the natural layout for
+/// single-cell data, where most values are 0 and normalization is applied per
+/// cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseExpressionMatrix {
+    row_names: Vec<String>,
+    column_names: Vec<String>,
+    column_offsets: Vec<usize>,
+    row_indices: Vec<usize>,
+    values: Vec<f64>,
+}
+
+impl SparseExpressionMatrix {
+    /// Builds a sparse matrix from a list of (row, column, value) entries.
+    ///
+    /// The entries do not need to be sorted. Duplicate positions are summed,
+    /// and zeros are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics when an entry is out of bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::sparse::SparseExpressionMatrix;
+    ///
+    /// let matrix = SparseExpressionMatrix::from_triplets(
+    ///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+    ///     vec![String::from("AAACCCAAGAAACACT"), String::from("AAACCCAAGAAACCAT")],
+    ///     vec![(1, 0, 3.0), (0, 1, 2.0), (1, 1, 0.0)],
+    /// );
+    ///
+    /// assert_eq!(matrix.nnz(), 2);
+    /// assert_eq!(matrix.get(1, 0), 3.0);
+    /// assert_eq!(matrix.get(1, 1), 0.0);
+    /// ```
+    pub fn from_triplets(
+        row_names: Vec<String>,
+        column_names: Vec<String>,
+        mut triplets: Vec<(usize, usize, f64)>,
+    ) -> SparseExpressionMatrix {
+        let (row_count, column_count) = (row_names.len(), column_names.len());
+
+        for &(i, j, _) in &triplets {
+            assert!(
+                i < row_count && j < column_count,
+                "entry ({}, {}) is out of bounds",
+                i,
+                j
+            );
+        }
+
+        triplets.sort_unstable_by_key(|&(i, j, _)| (j, i));
+
+        let mut column_offsets = vec![0; column_count + 1];
+        let mut row_indices: Vec<usize> = Vec::with_capacity(triplets.len());
+        let mut values: Vec<f64> = Vec::with_capacity(triplets.len());
+        let mut last_position = None;
+
+        for (i, j, value) in triplets {
+            if last_position == Some((i, j)) {
+                *values.last_mut().expect("values cannot be empty") += value;
+                continue;
+            }
+
+            row_indices.push(i);
+            values.push(value);
+            column_offsets[j + 1] += 1;
+            last_position = Some((i, j));
+        }
+
+        for j in 0..column_count {
+            column_offsets[j + 1] += column_offsets[j];
+        }
+
+        let mut matrix = SparseExpressionMatrix {
+            row_names,
+            column_names,
+            column_offsets,
+            row_indices,
+            values,
+        };
+
+        matrix.prune();
+
+        matrix
+    }
+
+    /// Returns the feature IDs of the rows.
+    pub fn row_names(&self) -> &[String] {
+        &self.row_names
+    }
+
+    /// Returns the sample or cell names of the columns.
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    /// Returns the number of rows and columns.
+    pub fn shape(&self) -> (usize, usize) {
+        (self.row_names.len(), self.column_names.len())
+    }
+
+    /// Returns the number of stored (nonzero) values.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the value at the given row and column indices.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the column index is out of bounds.
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        let (rows, values) = self.column(j);
+
+        match rows.binary_search(&i) {
+            Ok(k) => values[k],
+            Err(_) => 0.0,
+        }
+    }
+
+    /// Returns the row indices and values of the nonzero entries in a column.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the column index is out of bounds.
+    pub fn column(&self, j: usize) -> (&[usize], &[f64]) {
+        let (start, end) = (self.column_offsets[j], self.column_offsets[j + 1]);
+        (&self.row_indices[start..end], &self.values[start..end])
+    }
+
+    /// Returns the row indices and a mutable slice of the values of the
+    /// nonzero entries in a column.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the column index is out of bounds.
+    pub fn column_mut(&mut self, j: usize) -> (&[usize], &mut [f64]) {
+        let (start, end) = (self.column_offsets[j], self.column_offsets[j + 1]);
+        (&self.row_indices[start..end], &mut self.values[start..end])
+    }
+
+    /// Returns an iterator over the nonzero entries as (row, column, value)
+    /// triplets, ordered by column and then row.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        (0..self.column_names.len()).flat_map(move |j| {
+            let (rows, values) = self.column(j);
+            rows.iter()
+                .zip(values)
+                .map(move |(&i, &value)| (i, j, value))
+        })
+    }
+
+    /// Converts the sparse matrix to a dense matrix.
+    pub fn to_dense(&self) -> ExpressionMatrix {
+        let mut data = Array2::zeros(self.shape());
+
+        for (i, j, value) in self.iter() {
+            data[[i, j]] = value;
+        }
+
+        ExpressionMatrix::new(self.row_names.clone(), self.column_names.clone(), data)
+    }
+
+    /// Keeps only the columns whose names match the predicate.
+    ///
+    /// The order of the remaining columns is unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::sparse::SparseExpressionMatrix;
+    ///
+    /// let mut matrix = SparseExpressionMatrix::from_triplets(
+    ///     vec![String::from("AAAS")],
+    ///     vec![String::from("cell1"), String::from("cell2"), String::from("cell3")],
+    ///     vec![(0, 0, 1.0), (0, 1, 2.0), (0, 2, 3.0)],
+    /// );
+    ///
+    /// matrix.retain_columns(|name| name != "cell2");
+    ///
+    /// assert_eq!(matrix.column_names(), ["cell1", "cell3"]);
+    /// assert_eq!(matrix.get(0, 1), 3.0);
+    /// ```
+    pub fn retain_columns<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let mut column_names = Vec::new();
+        let mut column_offsets = vec![0];
+        let mut k = 0;
+
+        for (j, name) in self.column_names.drain(..).enumerate() {
+            if !f(&name) {
+                continue;
+            }
+
+            for src in self.column_offsets[j]..self.column_offsets[j + 1] {
+                self.row_indices[k] = self.row_indices[src];
+                self.values[k] = self.values[src];
+                k += 1;
+            }
+
+            column_names.push(name);
+            column_offsets.push(k);
+        }
+
+        self.column_names = column_names;
+        self.column_offsets = column_offsets;
+        self.row_indices.truncate(k);
+        self.values.truncate(k);
+    }
+
+    /// Removes stored values that are 0, e.g., after a transform.
+    pub fn prune(&mut self) {
+        let mut k = 0;
+        let mut start = 0;
+
+        for j in 0..self.column_names.len() {
+            let end = self.column_offsets[j + 1];
+
+            for src in start..end {
+                if self.values[src] != 0.0 {
+                    self.row_indices[k] = self.row_indices[src];
+                    self.values[k] = self.values[src];
+                    k += 1;
+                }
+            }
+
+            start = end;
+            self.column_offsets[j + 1] = k;
+        }
+
+        self.row_indices.truncate(k);
+        self.values.truncate(k);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_matrix() -> SparseExpressionMatrix {
+        SparseExpressionMatrix::from_triplets(
+            vec![
+                String::from("AAAS"),
+                String::from("AC009952.3"),
+                String::from("RPL37AP1"),
+            ],
+            vec![String::from("cell1"), String::from("cell2")],
+            vec![(2, 1, 5.0), (0, 0, 1.0), (2, 0, 3.0), (2, 1, 1.0)],
+        )
+    }
+
+    #[test]
+    fn test_from_triplets() {
+        let matrix = build_matrix();
+
+        assert_eq!(matrix.shape(), (3, 2));
+        assert_eq!(matrix.nnz(), 3);
+
+        assert_eq!(matrix.column(0), (&[0, 2][..], &[1.0, 3.0][..]));
+        assert_eq!(matrix.column(1), (&[2][..], &[6.0][..]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_triplets_with_out_of_bounds_entry() {
+        SparseExpressionMatrix::from_triplets(
+            vec![String::from("AAAS")],
+            vec![String::from("cell1")],
+            vec![(1, 0, 1.0)],
+        );
+    }
+
+    #[test]
+    fn test_iter() {
+        let matrix = build_matrix();
+        let entries: Vec<_> = matrix.iter().collect();
+        assert_eq!(entries, [(0, 0, 1.0), (2, 0, 3.0), (2, 1, 6.0)]);
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut matrix = build_matrix();
+
+        matrix.column_mut(0).1[0] = 0.0;
+        matrix.prune();
+
+        assert_eq!(matrix.nnz(), 2);
+        assert_eq!(matrix.column(0), (&[2][..], &[3.0][..]));
+        assert_eq!(matrix.column(1), (&[2][..], &[6.0][..]));
+    }
+
+    #[test]
+    fn test_retain_columns() {
+        let mut matrix = build_matrix();
+
+        matrix.retain_columns(|name| name == "cell2");
+
+        assert_eq!(matrix.shape(), (3, 1));
+        assert_eq!(matrix.nnz(), 1);
+        assert_eq!(matrix.column(0), (&[2][..], &[6.0][..]));
+
+        matrix.retain_columns(|_| false);
+
+        assert_eq!(matrix.shape(), (3, 0));
+        assert_eq!(matrix.nnz(), 0);
+    }
+
+    #[test]
+    fn test_to_dense() {
+        let matrix = build_matrix().to_dense();
+
+        assert_eq!(matrix.get("AAAS", "cell1"), Some(1.0));
+        assert_eq!(matrix.get("AAAS", "cell2"), Some(0.0));
+        assert_eq!(matrix.get("RPL37AP1", "cell2"), Some(6.0));
+    }
+}
@@ -0,0 +1,386 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::{self, BufRead},
+    path::Path,
+};
+
+use crate::{
+    compression, counts::Counts, feature_id::FeatureId, features::with_line_number, sequence,
+};
+
+/// The largest k-mer size, as k-mers are packed into 64 bits.
+pub const MAX_KMER_SIZE: usize = 32;
+
+const FASTA_HEADER_PREFIX: char = '>';
+const FASTQ_HEADER_PREFIX: char = '@';
+const FASTQ_SEPARATOR_PREFIX: char = '+';
+
+// The number of lines of a FASTQ record.
+const FASTQ_RECORD_LINE_COUNT: u64 = 4;
+
+/// Builds an index of the spike-in sequences of a FASTA file.
+///
+/// If the extension of the path is `.gz`, the file is decompressed. See
+/// [`SpikeInIndex::from_fasta`].
+///
+/// [`SpikeInIndex::from_fasta`]: struct.SpikeInIndex.html#method.from_fasta
+pub fn read_spike_in_index<P>(src: P, kmer_size: usize) -> io::Result<SpikeInIndex>
+where
+    P: AsRef<Path>,
+{
+    let reader = compression::open(src)?;
+    SpikeInIndex::from_fasta(reader, kmer_size)
+}
+
+/// Counts the reads of a FASTQ file that are from each spike-in.
+///
+/// If the extension of the path is `.gz`, the file is decompressed. See
+/// [`screen_fastq`].
+///
+/// [`screen_fastq`]: fn.screen_fastq.html
+pub fn read_spike_in_counts<P>(src: P, index: &SpikeInIndex) -> io::Result<Counts>
+where
+    P: AsRef<Path>,
+{
+    let reader = compression::open(src)?;
+    screen_fastq(reader, index)
+}
+
+/// An index of the k-mers of spike-in sequences.
+///
+/// Each k-mer is stored in its canonical form, the lesser of it and its
+/// reverse complement, so reads from either strand match. K-mers shared by
+/// more than one spike-in are dropped, as they cannot tell the spike-ins
+/// apart, as are k-mers with bases other than A, C, G, or T.
+#[derive(Clone, Debug)]
+pub struct SpikeInIndex {
+    kmer_size: usize,
+    names: Vec<FeatureId>,
+    kmers: HashMap<u64, usize>,
+}
+
+impl SpikeInIndex {
+    /// Builds an index of the spike-in sequences of a FASTA.
+    ///
+    /// The name of each spike-in is the header up to the first whitespace,
+    /// e.g., `ERCC-00130`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `kmer_size` is 0 or greater than [`MAX_KMER_SIZE`].
+    ///
+    /// [`MAX_KMER_SIZE`]: constant.MAX_KMER_SIZE.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::spike_in_screen::SpikeInIndex;
+    ///
+    /// let data = b">ERCC-00002\nACGTTGCA\n>ERCC-00003\nGGGAAA\n";
+    /// let index = SpikeInIndex::from_fasta(&data[..], 5)?;
+    ///
+    /// assert_eq!(index.names().len(), 2);
+    /// assert_eq!(index.classify(b"NACGTTG"), Some(0));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn from_fasta<R>(reader: R, kmer_size: usize) -> io::Result<Self>
+    where
+        R: BufRead,
+    {
+        assert!(
+            (1..=MAX_KMER_SIZE).contains(&kmer_size),
+            "invalid k-mer size: {}",
+            kmer_size
+        );
+
+        let mut index = SpikeInIndex {
+            kmer_size,
+            names: Vec::new(),
+            kmers: HashMap::new(),
+        };
+
+        // K-mers shared by spike-ins are kept as tombstones until the end, so
+        // a third occurrence is not added again.
+        let mut shared = Vec::new();
+        let mut sequence = Vec::new();
+
+        for (i, result) in reader.lines().enumerate() {
+            let line = result?;
+
+            if let Some(header) = line.strip_prefix(FASTA_HEADER_PREFIX) {
+                index.add_sequence(&sequence, &mut shared);
+                sequence.clear();
+
+                let name = FeatureId::from(sequence::parse_name(header).as_str());
+
+                if index.names.contains(&name) {
+                    return Err(with_line_number(
+                        invalid_data(format!("duplicate spike-in name: {}", name)),
+                        i as u64 + 1,
+                    ));
+                }
+
+                index.names.push(name);
+            } else if index.names.is_empty() {
+                if !line.trim().is_empty() {
+                    return Err(with_line_number(
+                        invalid_data(String::from("missing FASTA header")),
+                        i as u64 + 1,
+                    ));
+                }
+            } else {
+                sequence.extend_from_slice(line.trim_end().as_bytes());
+            }
+        }
+
+        index.add_sequence(&sequence, &mut shared);
+
+        for kmer in shared {
+            index.kmers.remove(&kmer);
+        }
+
+        Ok(index)
+    }
+
+    /// Returns the names of the spike-ins, in the order of the FASTA.
+    pub fn names(&self) -> &[FeatureId] {
+        &self.names
+    }
+
+    /// Returns the index of the spike-in a read is from.
+    ///
+    /// A read is from a spike-in if at least one of its k-mers is in the index
+    /// and all of its k-mers that are in the index are of that spike-in. Reads
+    /// with k-mers of multiple spike-ins are ambiguous and not assigned.
+    pub fn classify(&self, read: &[u8]) -> Option<usize> {
+        let mut hit = None;
+
+        for kmer in CanonicalKmers::new(read, self.kmer_size) {
+            if let Some(&i) = self.kmers.get(&kmer) {
+                match hit {
+                    Some(j) if j != i => return None,
+                    _ => hit = Some(i),
+                }
+            }
+        }
+
+        hit
+    }
+
+    fn add_sequence(&mut self, sequence: &[u8], shared: &mut Vec<u64>) {
+        let i = match self.names.len().checked_sub(1) {
+            Some(i) => i,
+            None => return,
+        };
+
+        for kmer in CanonicalKmers::new(sequence, self.kmer_size) {
+            match self.kmers.entry(kmer) {
+                Entry::Occupied(entry) => {
+                    if *entry.get() != i {
+                        shared.push(kmer);
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(i);
+                }
+            }
+        }
+    }
+}
+
+/// Counts the reads of a FASTQ that are from each spike-in.
+///
+/// Each read is assigned with [`SpikeInIndex::classify`], without aligning
+/// it. Every spike-in in the index is counted, including those with no reads.
+///
+/// [`SpikeInIndex::classify`]: struct.SpikeInIndex.html#method.classify
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::spike_in_screen::{screen_fastq, SpikeInIndex};
+///
+/// let fasta = b">ERCC-00002\nACGTTGCA\n>ERCC-00003\nGGGAAA\n";
+/// let index = SpikeInIndex::from_fasta(&fasta[..], 5)?;
+///
+/// // The second read is the reverse complement of the first.
+/// let fastq = b"@r1\nACGTTG\n+\nIIIIII\n@r2\nCAACGT\n+\nIIIIII\n@r3\nTTTTTT\n+\nIIIIII\n";
+/// let counts = screen_fastq(&fastq[..], &index)?;
+///
+/// assert_eq!(counts["ERCC-00002"], 2);
+/// assert_eq!(counts["ERCC-00003"], 0);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn screen_fastq<R>(reader: R, index: &SpikeInIndex) -> io::Result<Counts>
+where
+    R: BufRead,
+{
+    let mut counts: Counts = index.names.iter().map(|name| (name.clone(), 0)).collect();
+    let mut lines = reader.lines();
+    let mut line_number = 0;
+
+    while let Some(result) = lines.next() {
+        let header = result?;
+        line_number += 1;
+
+        if header.is_empty() {
+            continue;
+        }
+
+        if !header.starts_with(FASTQ_HEADER_PREFIX) {
+            return Err(with_line_number(
+                invalid_data(String::from("invalid FASTQ header")),
+                line_number,
+            ));
+        }
+
+        let sequence = next_line(&mut lines, line_number)?;
+        let separator = next_line(&mut lines, line_number + 1)?;
+        next_line(&mut lines, line_number + 2)?;
+
+        if !separator.starts_with(FASTQ_SEPARATOR_PREFIX) {
+            return Err(with_line_number(
+                invalid_data(String::from("invalid FASTQ separator")),
+                line_number + 2,
+            ));
+        }
+
+        line_number += FASTQ_RECORD_LINE_COUNT - 1;
+
+        if let Some(i) = index.classify(sequence.as_bytes()) {
+            *counts.get_mut(&index.names[i]).unwrap() += 1;
+        }
+    }
+
+    Ok(counts)
+}
+
+fn next_line<I>(lines: &mut I, line_number: u64) -> io::Result<String>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    lines.next().unwrap_or_else(|| {
+        Err(with_line_number(
+            invalid_data(String::from("truncated FASTQ record")),
+            line_number,
+        ))
+    })
+}
+
+// An iterator over the canonical 2-bit encoded k-mers of a sequence, skipping
+// k-mers with ambiguous bases.
+struct CanonicalKmers<'a> {
+    sequence: &'a [u8],
+    kmer_size: usize,
+    mask: u64,
+    position: usize,
+    forward: u64,
+    reverse: u64,
+    len: usize,
+}
+
+impl<'a> CanonicalKmers<'a> {
+    fn new(sequence: &'a [u8], kmer_size: usize) -> Self {
+        let mask = if kmer_size == MAX_KMER_SIZE {
+            u64::MAX
+        } else {
+            (1 << (2 * kmer_size)) - 1
+        };
+
+        CanonicalKmers {
+            sequence,
+            kmer_size,
+            mask,
+            position: 0,
+            forward: 0,
+            reverse: 0,
+            len: 0,
+        }
+    }
+}
+
+impl Iterator for CanonicalKmers<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&base) = self.sequence.get(self.position) {
+            self.position += 1;
+
+            let code = match base.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => {
+                    self.len = 0;
+                    continue;
+                }
+            };
+
+            // The reverse complement is built from its other end: the
+            // complement of each base is shifted in at the most significant
+            // position.
+            self.forward = ((self.forward << 2) | code) & self.mask;
+            self.reverse = (self.reverse >> 2) | ((3 - code) << (2 * (self.kmer_size - 1)));
+            self.len += 1;
+
+            if self.len >= self.kmer_size {
+                return Some(self.forward.min(self.reverse));
+            }
+        }
+
+        None
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_fasta_drops_shared_kmers() -> io::Result<()> {
+        // ACGTT is in both spike-ins, so only the k-mers around it tell them
+        // apart.
+        let data = b">ERCC-00002 mix\nAAACGTT\n>ERCC-00003\nACGTTCC\n";
+        let index = SpikeInIndex::from_fasta(&data[..], 5)?;
+
+        assert_eq!(
+            index.names(),
+            [FeatureId::from("ERCC-00002"), FeatureId::from("ERCC-00003")]
+        );
+
+        assert_eq!(index.classify(b"ACGTT"), None);
+        assert_eq!(index.classify(b"AAACG"), Some(0));
+        assert_eq!(index.classify(b"GTTCC"), Some(1));
+        assert_eq!(index.classify(b"AAACGTTCC"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonical_kmers() {
+        // CAACG is the reverse complement of CGTTG.
+        let kmers: Vec<u64> = CanonicalKmers::new(b"ACGTTGNCAACG", 5).collect();
+        assert_eq!(kmers.len(), 3);
+        assert_eq!(kmers[1], kmers[2]);
+
+        let kmers: Vec<u64> = CanonicalKmers::new(&[b'A'; 40], MAX_KMER_SIZE).collect();
+        assert_eq!(kmers, vec![0; 9]);
+    }
+
+    #[test]
+    fn test_screen_fastq_with_invalid_records() {
+        let index = SpikeInIndex::from_fasta(&b">ERCC-00002\nACGTTGCA\n"[..], 5).unwrap();
+
+        let data = b"@r1\nACGTTG\n+\nIIIIII\nr2\nACGTTG\n+\nIIIIII\n";
+        let e = screen_fastq(&data[..], &index).unwrap_err();
+        assert_eq!(e.to_string(), "line 5: invalid FASTQ header");
+
+        let data = b"@r1\nACGTTG\n";
+        assert!(screen_fastq(&data[..], &index).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+};
+
+use crate::matrix::ExpressionMatrix;
+
+static ERCC_ID_HEADER: &str = "ERCC ID";
+static MIX_1_CONCENTRATION_HEADER_PREFIX: &str = "concentration in Mix 1";
+static MIX_2_CONCENTRATION_HEADER_PREFIX: &str = "concentration in Mix 2";
+
+pub type Concentrations = HashMap<String, f64>;
+
+/// An ERCC spike-in mix.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mix {
+    One,
+    Two,
+}
+
+/// The fit of observed expression to expected spike-in concentration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DoseResponse {
+    /// The number of spike-ins used in the fit.
+    pub n: usize,
+    /// The slope of the regression line. This is ideally 1.
+    pub slope: f64,
+    /// The intercept of the regression line.
+    pub intercept: f64,
+    /// The coefficient of determination.
+    pub r_squared: f64,
+}
+
+/// Reads the spike-in concentrations from a standard ERCC mix table.
+///
+/// The table is TSV-formatted with a header, as distributed with the ERCC
+/// RNA Spike-In Mix, e.g., `cms_095046.txt`. The `ERCC ID` column is used as
+/// the spike-in ID, and the `concentration in Mix 1` or `Mix 2` column is
+/// used as the concentration, depending on the given mix.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::spike_ins::{read_ercc_concentrations, Mix};
+///
+/// let data = "\
+/// Re-sort ID\tERCC ID\tsubgroup\tconcentration in Mix 1 (attomoles/ul)\tconcentration in Mix 2 (attomoles/ul)
+/// 1\tERCC-00130\tA\t30000\t7500
+/// 2\tERCC-00004\tA\t7500\t1875
+/// ";
+///
+/// let concentrations = read_ercc_concentrations(data.as_bytes(), Mix::Two).unwrap();
+///
+/// assert_eq!(concentrations.len(), 2);
+/// assert_eq!(concentrations["ERCC-00130"], 7500.0);
+/// ```
+pub fn read_ercc_concentrations<R>(reader: R, mix: Mix) -> io::Result<Concentrations>
+where
+    R: Read,
+{
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .from_reader(reader);
+
+    let concentration_header_prefix = match mix {
+        Mix::One => MIX_1_CONCENTRATION_HEADER_PREFIX,
+        Mix::Two => MIX_2_CONCENTRATION_HEADER_PREFIX,
+    };
+
+    let headers = rdr.headers()?.clone();
+
+    let id_index = headers
+        .iter()
+        .position(|h| h == ERCC_ID_HEADER)
+        .ok_or_else(|| invalid_data(format!("missing column: {}", ERCC_ID_HEADER)))?;
+
+    let concentration_index = headers
+        .iter()
+        .position(|h| h.starts_with(concentration_header_prefix))
+        .ok_or_else(|| invalid_data(format!("missing column: {}", concentration_header_prefix)))?;
+
+    let mut concentrations = Concentrations::new();
+
+    for result in rdr.records() {
+        let record = result?;
+
+        let id = record
+            .get(id_index)
+            .ok_or_else(|| invalid_data(format!("invalid record: {:?}", record)))?;
+
+        let concentration = record
+            .get(concentration_index)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid_data(format!("invalid concentration: {:?}", record)))?;
+
+        concentrations.insert(id.into(), concentration);
+    }
+
+    Ok(concentrations)
+}
+
+/// Fits the observed expression of the spike-ins in each sample to their
+/// expected concentrations.
+///
+/// This is a linear regression of log2 expression on log2 concentration. Only
+/// spike-ins with a positive concentration and a positive expression value in
+/// the sample are used. A sample with fewer than 2 such spike-ins has no fit.
+///
+/// The result has one fit per column of the matrix.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{matrix::ExpressionMatrix, spike_ins::{fit_dose_responses, Concentrations}};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("ERCC-00004"), String::from("ERCC-00130"), String::from("AAAS")],
+///     vec![String::from("sample1")],
+///     arr2(&[[20.0], [80.0], [5.0]]),
+/// );
+///
+/// let concentrations: Concentrations = [
+///     (String::from("ERCC-00004"), 7500.0),
+///     (String::from("ERCC-00130"), 30000.0),
+/// ].iter().cloned().collect();
+///
+/// let fits = fit_dose_responses(&matrix, &concentrations);
+/// let fit = fits[0].as_ref().unwrap();
+///
+/// assert_eq!(fit.n, 2);
+/// assert!((fit.slope - 1.0).abs() < 1e-9);
+/// assert!((fit.r_squared - 1.0).abs() < 1e-9);
+/// ```
+pub fn fit_dose_responses(
+    matrix: &ExpressionMatrix,
+    concentrations: &Concentrations,
+) -> Vec<Option<DoseResponse>> {
+    let spike_ins: Vec<(usize, f64)> = matrix
+        .row_names()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| concentrations.get(name).map(|&c| (i, c)))
+        .filter(|&(_, concentration)| concentration > 0.0)
+        .collect();
+
+    matrix
+        .data()
+        .gencolumns()
+        .into_iter()
+        .map(|column| {
+            let points: Vec<(f64, f64)> = spike_ins
+                .iter()
+                .map(|&(i, concentration)| (concentration, column[i]))
+                .filter(|&(_, value)| value > 0.0)
+                .map(|(concentration, value)| (concentration.log2(), value.log2()))
+                .collect();
+
+            fit_line(&points)
+        })
+        .collect()
+}
+
+fn fit_line(points: &[(f64, f64)]) -> Option<DoseResponse> {
+    let n = points.len();
+
+    if n < 2 {
+        return None;
+    }
+
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n as f64;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n as f64;
+
+    let mut sxx = 0.0;
+    let mut sxy = 0.0;
+    let mut syy = 0.0;
+
+    for &(x, y) in points {
+        sxx += (x - mean_x) * (x - mean_x);
+        sxy += (x - mean_x) * (y - mean_y);
+        syy += (y - mean_y) * (y - mean_y);
+    }
+
+    if sxx == 0.0 {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+
+    let r_squared = if syy == 0.0 {
+        1.0
+    } else {
+        (sxy * sxy) / (sxx * syy)
+    };
+
+    Some(DoseResponse {
+        n,
+        slope,
+        intercept,
+        r_squared,
+    })
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_ercc_concentrations_with_missing_column() {
+        let data = "ERCC ID\tsubgroup\nERCC-00130\tA\n";
+        assert!(read_ercc_concentrations(data.as_bytes(), Mix::One).is_err());
+    }
+
+    #[test]
+    fn test_read_ercc_concentrations_with_invalid_concentration() {
+        let data = "ERCC ID\tconcentration in Mix 1 (attomoles/ul)\nERCC-00130\tn/a\n";
+        assert!(read_ercc_concentrations(data.as_bytes(), Mix::One).is_err());
+    }
+
+    #[test]
+    fn test_fit_line() {
+        let fit = fit_line(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)]).unwrap();
+        assert_eq!(fit.n, 3);
+        assert_eq!(fit.slope, 2.0);
+        assert_eq!(fit.intercept, 1.0);
+        assert_eq!(fit.r_squared, 1.0);
+
+        let fit = fit_line(&[(0.0, 0.0), (1.0, 2.0), (2.0, 1.0)]).unwrap();
+        assert_eq!(fit.slope, 0.5);
+        assert_eq!(fit.r_squared, 0.25);
+
+        assert!(fit_line(&[(0.0, 1.0)]).is_none());
+        assert!(fit_line(&[(1.0, 1.0), (1.0, 2.0)]).is_none());
+    }
+}
@@ -0,0 +1,175 @@
+use std::cmp::Ordering;
+
+use crate::matrix::ExpressionMatrix;
+
+/// The stability of a gene's expression across samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stability {
+    /// The gene ID.
+    pub name: String,
+    /// The mean expression.
+    pub mean: f64,
+    /// The coefficient of variation, i.e., the standard deviation over the
+    /// mean.
+    pub cv: f64,
+    /// The geNorm gene-stability measure M, i.e., the mean pairwise variation
+    /// with all other candidates.
+    pub m: f64,
+}
+
+/// Ranks genes by expression stability to find candidate reference genes.
+///
+/// Only genes expressed (> 0) in every sample are considered. Of these, the
+/// `max_candidates` genes with the lowest coefficient of variation (CV) are
+/// compared pairwise, as in [geNorm]: the pairwise variation of two genes is
+/// the standard deviation of their log2 expression ratios across samples, and
+/// M is the mean pairwise variation of a gene with all other candidates.
+///
+/// Candidates are returned in order of increasing M (most stable first), with
+/// ties broken by CV. At least two samples are required; otherwise, the result
+/// is empty.
+///
+/// [geNorm]: https://doi.org/10.1186/gb-2002-3-7-research0034
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{matrix::ExpressionMatrix, stability::rank_reference_genes};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("ACTB"), String::from("GAPDH"), String::from("MKI67")],
+///     vec![String::from("sample1"), String::from("sample2"), String::from("sample3")],
+///     arr2(&[[10.0, 11.0, 10.0], [20.0, 21.0, 20.0], [1.0, 30.0, 5.0]]),
+/// );
+///
+/// let stabilities = rank_reference_genes(&matrix, 1000);
+///
+/// assert_eq!(stabilities.len(), 3);
+/// assert_eq!(stabilities[2].name, "MKI67");
+/// ```
+pub fn rank_reference_genes(matrix: &ExpressionMatrix, max_candidates: usize) -> Vec<Stability> {
+    let data = matrix.data();
+    let n = data.cols();
+
+    if n < 2 {
+        return Vec::new();
+    }
+
+    let mut candidates: Vec<(usize, f64, f64)> = (0..data.rows())
+        .filter(|&i| data.row(i).iter().all(|&x| x > 0.0))
+        .map(|i| {
+            let values: Vec<f64> = data.row(i).to_vec();
+            let (mean, sd) = mean_and_sd(&values);
+            (i, mean, sd / mean)
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| compare(a.2, b.2));
+    candidates.truncate(max_candidates);
+
+    let log_values: Vec<Vec<f64>> = candidates
+        .iter()
+        .map(|&(i, _, _)| data.row(i).iter().map(|x| x.log2()).collect())
+        .collect();
+
+    let mut variation_sums = vec![0.0; candidates.len()];
+    let mut ratios = vec![0.0; n];
+
+    for a in 0..candidates.len() {
+        for b in (a + 1)..candidates.len() {
+            for (ratio, (x, y)) in ratios
+                .iter_mut()
+                .zip(log_values[a].iter().zip(&log_values[b]))
+            {
+                *ratio = x - y;
+            }
+
+            let (_, v) = mean_and_sd(&ratios);
+            variation_sums[a] += v;
+            variation_sums[b] += v;
+        }
+    }
+
+    let pair_count = candidates.len().saturating_sub(1).max(1) as f64;
+
+    let mut stabilities: Vec<_> = candidates
+        .into_iter()
+        .zip(variation_sums)
+        .map(|((i, mean, cv), sum)| Stability {
+            name: matrix.row_names()[i].clone(),
+            mean,
+            cv,
+            m: sum / pair_count,
+        })
+        .collect();
+
+    stabilities.sort_by(|a, b| compare(a.m, b.m).then_with(|| compare(a.cv, b.cv)));
+
+    stabilities
+}
+
+// Returns the mean and sample standard deviation.
+fn mean_and_sd(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, variance.sqrt())
+}
+
+fn compare(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_rank_reference_genes() {
+        let matrix = ExpressionMatrix::new(
+            vec![
+                String::from("ACTB"),
+                String::from("GAPDH"),
+                String::from("MKI67"),
+                String::from("XIST"),
+            ],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[4.0, 8.0], [2.0, 4.0], [1.0, 16.0], [0.0, 8.0]]),
+        );
+
+        let stabilities = rank_reference_genes(&matrix, 1000);
+
+        assert_eq!(stabilities.len(), 3);
+
+        // ACTB and GAPDH have a constant ratio, so their pairwise variation
+        // is 0.
+        let v = (3.0f64.powi(2) * 2.0 / 4.0).sqrt();
+        assert_eq!(stabilities[0].name, "ACTB");
+        assert!((stabilities[0].m - v / 2.0).abs() < 1e-12);
+        assert_eq!(stabilities[2].name, "MKI67");
+        assert!((stabilities[2].m - v).abs() < 1e-12);
+
+        let stabilities = rank_reference_genes(&matrix, 1);
+        assert_eq!(stabilities.len(), 1);
+        assert_eq!(stabilities[0].m, 0.0);
+    }
+
+    #[test]
+    fn test_rank_reference_genes_with_one_sample() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("ACTB")],
+            vec![String::from("sample1")],
+            arr2(&[[4.0]]),
+        );
+
+        assert!(rank_reference_genes(&matrix, 1000).is_empty());
+    }
+
+    #[test]
+    fn test_mean_and_sd() {
+        assert_eq!(mean_and_sd(&[2.0, 4.0, 6.0]), (4.0, 2.0));
+    }
+}
@@ -0,0 +1,72 @@
+/// A floating-point summation algorithm.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Summation {
+    /// Adds the values in order.
+    #[default]
+    Naive,
+    /// Adds the values in order, carrying the rounding error of each addition
+    /// in a separate compensation term (Kahan–Babuška–Neumaier summation).
+    ///
+    /// The error of the result does not grow with the number of values, so
+    /// the result is much less sensitive to the magnitudes and order of the
+    /// values.
+    Compensated,
+}
+
+impl Summation {
+    /// Sums the given values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::summation::Summation;
+    ///
+    /// let values = [1.0, 1e100, 1.0, -1e100];
+    ///
+    /// assert_eq!(Summation::Naive.sum(values.iter().copied()), 0.0);
+    /// assert_eq!(Summation::Compensated.sum(values.iter().copied()), 2.0);
+    /// ```
+    pub fn sum<I>(self, values: I) -> f64
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        match self {
+            Summation::Naive => values.into_iter().sum(),
+            Summation::Compensated => sum_compensated(values),
+        }
+    }
+}
+
+fn sum_compensated<I>(values: I) -> f64
+where
+    I: IntoIterator<Item = f64>,
+{
+    let mut sum = 0.0;
+    let mut compensation = 0.0;
+
+    for value in values {
+        let t = sum + value;
+
+        if f64::abs(sum) >= f64::abs(value) {
+            compensation += (sum - t) + value;
+        } else {
+            compensation += (value - t) + sum;
+        }
+
+        sum = t;
+    }
+
+    sum + compensation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sum_compensated() {
+        assert_eq!(sum_compensated(Vec::new()), 0.0);
+        assert_eq!(sum_compensated(vec![0.1; 10]), 1.0);
+        assert_ne!(vec![0.1; 10].into_iter().sum::<f64>(), 1.0);
+    }
+}
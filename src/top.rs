@@ -0,0 +1,110 @@
+use std::cmp::Ordering;
+
+use crate::matrix::ExpressionMatrix;
+
+/// A highly expressed feature in a sample.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopFeature {
+    /// The feature ID.
+    pub name: String,
+    /// The expression value.
+    pub value: f64,
+    /// The sum of the values of this and all more highly expressed features
+    /// over the sum of all values in the sample.
+    pub cumulative_fraction: f64,
+}
+
+/// Finds the most highly expressed features in each sample.
+///
+/// Up to `n` features are returned per sample, in order of decreasing
+/// expression. The cumulative fraction shows how much of the library the top
+/// features account for, e.g., to check for contamination or dominance by
+/// globin, rRNA, or mitochondrial genes.
+///
+/// The result has one list per column of the matrix.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{matrix::ExpressionMatrix, top::top_features};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS"), String::from("HBB"), String::from("MT-CO1")],
+///     vec![String::from("sample1")],
+///     arr2(&[[10.0], [60.0], [30.0]]),
+/// );
+///
+/// let top = top_features(&matrix, 2);
+///
+/// assert_eq!(top[0].len(), 2);
+/// assert_eq!(top[0][0].name, "HBB");
+/// assert_eq!(top[0][0].cumulative_fraction, 0.6);
+/// assert_eq!(top[0][1].name, "MT-CO1");
+/// assert_eq!(top[0][1].cumulative_fraction, 0.9);
+/// ```
+pub fn top_features(matrix: &ExpressionMatrix, n: usize) -> Vec<Vec<TopFeature>> {
+    let row_names = matrix.row_names();
+
+    matrix
+        .data()
+        .gencolumns()
+        .into_iter()
+        .map(|column| {
+            let total: f64 = column.sum();
+
+            let mut entries: Vec<(usize, f64)> = column.iter().cloned().enumerate().collect();
+            entries.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+            entries.truncate(n);
+
+            let mut sum = 0.0;
+
+            entries
+                .into_iter()
+                .map(|(i, value)| {
+                    sum += value;
+
+                    TopFeature {
+                        name: row_names[i].clone(),
+                        value,
+                        cumulative_fraction: sum / total,
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_top_features() {
+        let matrix = ExpressionMatrix::new(
+            vec![
+                String::from("AAAS"),
+                String::from("HBB"),
+                String::from("MT-CO1"),
+            ],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[10.0, 3.0], [60.0, 0.0], [30.0, 1.0]]),
+        );
+
+        let top = top_features(&matrix, 5);
+
+        assert_eq!(top.len(), 2);
+
+        let names: Vec<_> = top[0].iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["HBB", "MT-CO1", "AAAS"]);
+        assert_eq!(top[0][2].cumulative_fraction, 1.0);
+
+        let names: Vec<_> = top[1].iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["AAAS", "MT-CO1", "HBB"]);
+        assert_eq!(top[1][0].cumulative_fraction, 0.75);
+
+        assert!(top_features(&matrix, 0).iter().all(|t| t.is_empty()));
+    }
+}
@@ -0,0 +1,182 @@
+use std::cmp::Ordering;
+
+use crate::{matrix::ExpressionMatrix, unit::Unit};
+
+/// A post-normalization transform of the values of each sample.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Transform {
+    /// Subtracts the median value of each sample from its values.
+    MedianCenter,
+    /// Scales each sample so that the total of its values is the target.
+    ///
+    /// When features are given, only their values are totaled, e.g., to scale
+    /// the total TPM of a core gene set to a constant. All values of the
+    /// sample are scaled.
+    ScaleTotal {
+        /// The total of each sample after scaling.
+        target: f64,
+        /// The names of the features to total, or `None` for all features.
+        features: Option<Vec<String>>,
+    },
+}
+
+impl Transform {
+    /// Applies the transform to each column of the matrix.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ndarray::arr2;
+    /// use noodles_fpkm::{matrix::ExpressionMatrix, transform::Transform};
+    ///
+    /// let matrix = ExpressionMatrix::new(
+    ///     vec![String::from("AAAS"), String::from("AC009952.3"), String::from("RPL37AP1")],
+    ///     vec![String::from("sample1")],
+    ///     arr2(&[[1.0], [3.0], [6.0]]),
+    /// );
+    ///
+    /// let centered = Transform::MedianCenter.apply(&matrix);
+    /// assert_eq!(centered.column("sample1").unwrap().to_vec(), [-2.0, 0.0, 3.0]);
+    ///
+    /// let transform = Transform::ScaleTotal {
+    ///     target: 100.0,
+    ///     features: Some(vec![String::from("AAAS"), String::from("AC009952.3")]),
+    /// };
+    ///
+    /// let scaled = transform.apply(&matrix);
+    /// assert_eq!(scaled.column("sample1").unwrap().to_vec(), [25.0, 75.0, 150.0]);
+    /// ```
+    pub fn apply(&self, matrix: &ExpressionMatrix) -> ExpressionMatrix {
+        match self {
+            Transform::MedianCenter => median_center(matrix),
+            Transform::ScaleTotal { target, features } => {
+                scale_total(matrix, *target, features.as_deref())
+            }
+        }
+    }
+
+    /// Returns the unit of values in the given unit after the transform.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::{expression_set::Method, transform::Transform, unit::Unit};
+    ///
+    /// assert_eq!(
+    ///     Transform::MedianCenter.unit(Unit::Tpm),
+    ///     Unit::MedianCentered(Method::Tpm)
+    /// );
+    /// ```
+    pub fn unit(&self, unit: Unit) -> Unit {
+        match self {
+            Transform::MedianCenter => unit.median_centered(),
+            Transform::ScaleTotal { .. } => unit,
+        }
+    }
+}
+
+/// Applies transforms to the matrix in order.
+pub fn apply_transforms(matrix: &ExpressionMatrix, transforms: &[Transform]) -> ExpressionMatrix {
+    transforms
+        .iter()
+        .fold(matrix.clone(), |matrix, transform| transform.apply(&matrix))
+}
+
+fn median_center(matrix: &ExpressionMatrix) -> ExpressionMatrix {
+    let mut centered = matrix.clone();
+
+    for mut column in centered.data_mut().gencolumns_mut() {
+        let values: Vec<f64> = column.iter().copied().collect();
+
+        if let Some(median) = median(values) {
+            for value in column.iter_mut() {
+                *value -= median;
+            }
+        }
+    }
+
+    centered
+}
+
+// Columns with a total of 0 are left unchanged.
+fn scale_total(
+    matrix: &ExpressionMatrix,
+    target: f64,
+    feature_names: Option<&[String]>,
+) -> ExpressionMatrix {
+    let row_indices: Vec<usize> = match feature_names {
+        Some(names) => names
+            .iter()
+            .filter_map(|name| matrix.row_index(name))
+            .collect(),
+        None => (0..matrix.row_names().len()).collect(),
+    };
+
+    let mut scaled = matrix.clone();
+
+    for mut column in scaled.data_mut().gencolumns_mut() {
+        let total: f64 = row_indices.iter().map(|&i| column[i]).sum();
+
+        if total != 0.0 {
+            let scale = target / total;
+
+            for value in column.iter_mut() {
+                *value *= scale;
+            }
+        }
+    }
+
+    scaled
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    let mid = values.len() / 2;
+
+    match values.len() % 2 {
+        0 => Some((values[mid - 1] + values[mid]) / 2.0),
+        _ => Some(values[mid]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use super::*;
+
+    #[test]
+    fn test_apply_transforms() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("a"), String::from("b")],
+            vec![String::from("sample1"), String::from("sample2")],
+            arr2(&[[1.0, 0.0], [3.0, 0.0]]),
+        );
+
+        let transforms = [
+            Transform::ScaleTotal {
+                target: 8.0,
+                features: None,
+            },
+            Transform::MedianCenter,
+        ];
+
+        let transformed = apply_transforms(&matrix, &transforms);
+
+        assert_eq!(transformed.get("a", "sample1"), Some(-2.0));
+        assert_eq!(transformed.get("b", "sample1"), Some(2.0));
+        assert_eq!(transformed.get("a", "sample2"), Some(0.0));
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(vec![3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(vec![4.0, 1.0]), Some(2.5));
+        assert_eq!(median(Vec::new()), None);
+    }
+}
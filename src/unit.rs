@@ -0,0 +1,239 @@
+use std::fmt;
+
+use crate::{expression_set::Method, matrix::ExpressionMatrix};
+
+// The relative tolerance of the column sums of per-million units.
+const PER_MILLION_SUM_TOLERANCE: f64 = 0.01;
+
+/// The unit of expression values.
+///
+/// This starts as the method used to calculate the values and follows the
+/// values through transforms, e.g., TPMs become normal scores after an inverse
+/// normal transform. It labels the first cell of the header of a table, so
+/// values in different units are not mistaken for each other downstream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Unit {
+    /// Fragments per kilobase per million mapped reads.
+    Fpkm,
+    /// Transcripts per million.
+    Tpm,
+    /// Counts per million.
+    Cpm,
+    /// Counts divided by a size factor.
+    Scaled,
+    /// Values in a unit less the median value of their sample.
+    MedianCentered(Method),
+    /// Rank-based inverse normal transformed values.
+    NormalScore,
+    /// Log2 fold changes versus a control.
+    Log2FoldChange,
+}
+
+impl Unit {
+    /// Returns the name of the unit, as used in the header of a table.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::{expression_set::Method, unit::Unit};
+    ///
+    /// assert_eq!(Unit::Tpm.as_str(), "tpm");
+    /// assert_eq!(Unit::MedianCentered(Method::Tpm).as_str(), "tpm_median_centered");
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unit::Fpkm => "fpkm",
+            Unit::Tpm => "tpm",
+            Unit::Cpm => "cpm",
+            Unit::Scaled => "scaled",
+            Unit::MedianCentered(Method::Fpkm) => "fpkm_median_centered",
+            Unit::MedianCentered(Method::Tpm) => "tpm_median_centered",
+            Unit::MedianCentered(Method::Cpm) => "cpm_median_centered",
+            Unit::MedianCentered(Method::Scaled) => "scaled_median_centered",
+            Unit::NormalScore => "normal_score",
+            Unit::Log2FoldChange => "log2_fold_change",
+        }
+    }
+
+    /// Parses a unit from its name, e.g., the first cell of the header of a
+    /// table.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::{expression_set::Method, unit::Unit};
+    ///
+    /// assert_eq!(Unit::from_name("fpkm_median_centered"), Some(Unit::MedianCentered(Method::Fpkm)));
+    /// assert_eq!(Unit::from_name("AAAS"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Unit> {
+        match name {
+            "fpkm" => Some(Unit::Fpkm),
+            "tpm" => Some(Unit::Tpm),
+            "cpm" => Some(Unit::Cpm),
+            "scaled" => Some(Unit::Scaled),
+            "fpkm_median_centered" => Some(Unit::MedianCentered(Method::Fpkm)),
+            "tpm_median_centered" => Some(Unit::MedianCentered(Method::Tpm)),
+            "cpm_median_centered" => Some(Unit::MedianCentered(Method::Cpm)),
+            "scaled_median_centered" => Some(Unit::MedianCentered(Method::Scaled)),
+            "normal_score" => Some(Unit::NormalScore),
+            "log2_fold_change" => Some(Unit::Log2FoldChange),
+            _ => None,
+        }
+    }
+
+    /// Returns the unit of the values after each sample is median-centered.
+    ///
+    /// Only values in the unit of a method are relabeled. Centering, e.g.,
+    /// normal scores, which already have a median of about 0, keeps their
+    /// unit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use noodles_fpkm::{expression_set::Method, unit::Unit};
+    ///
+    /// assert_eq!(Unit::Fpkm.median_centered(), Unit::MedianCentered(Method::Fpkm));
+    /// assert_eq!(Unit::NormalScore.median_centered(), Unit::NormalScore);
+    /// ```
+    pub fn median_centered(self) -> Unit {
+        match self {
+            Unit::Fpkm => Unit::MedianCentered(Method::Fpkm),
+            Unit::Tpm => Unit::MedianCentered(Method::Tpm),
+            Unit::Cpm => Unit::MedianCentered(Method::Cpm),
+            Unit::Scaled => Unit::MedianCentered(Method::Scaled),
+            unit => unit,
+        }
+    }
+}
+
+impl From<Method> for Unit {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Fpkm => Unit::Fpkm,
+            Method::Tpm => Unit::Tpm,
+            Method::Cpm => Unit::Cpm,
+            Method::Scaled => Unit::Scaled,
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A unit inferred from expression values, e.g., of a third-party table with
+/// no unit label.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InferredUnit {
+    /// Every value is an integer, i.e., the values are likely raw counts.
+    Counts,
+    /// The values of each sample sum to 1e6, i.e., TPM or CPM.
+    PerMillion,
+    /// The values are nonnegative but are neither counts nor per million,
+    /// i.e., likely FPKM.
+    Fpkm,
+}
+
+impl fmt::Display for InferredUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InferredUnit::Counts => f.write_str("counts"),
+            InferredUnit::PerMillion => f.write_str("tpm or cpm"),
+            InferredUnit::Fpkm => f.write_str("fpkm"),
+        }
+    }
+}
+
+/// Infers the unit of a matrix from its values.
+///
+/// This is a heuristic: values that are all integers are counts, values whose
+/// samples each sum to 1e6 (within 1%) are per million, and other
+/// nonnegative values are FPKM. Nothing is inferred for an empty matrix or
+/// one with negative or nonfinite values, e.g., transformed values.
+///
+/// # Example
+///
+/// ```
+/// use ndarray::arr2;
+/// use noodles_fpkm::{matrix::ExpressionMatrix, unit::{infer_unit, InferredUnit}};
+///
+/// let matrix = ExpressionMatrix::new(
+///     vec![String::from("AAAS"), String::from("RPL37AP1")],
+///     vec![String::from("sample1")],
+///     arr2(&[[250000.5], [749999.5]]),
+/// );
+///
+/// assert_eq!(infer_unit(&matrix), Some(InferredUnit::PerMillion));
+/// ```
+pub fn infer_unit(matrix: &ExpressionMatrix) -> Option<InferredUnit> {
+    let data = matrix.data();
+
+    if data.is_empty() || data.iter().any(|&n| !n.is_finite() || n < 0.0) {
+        return None;
+    }
+
+    if data.iter().all(|&n| n.fract() == 0.0) {
+        return Some(InferredUnit::Counts);
+    }
+
+    let is_per_million = data.gencolumns().into_iter().all(|column| {
+        let sum = column.sum();
+        ((sum - 1e6) / 1e6).abs() <= PER_MILLION_SUM_TOLERANCE
+    });
+
+    if is_per_million {
+        Some(InferredUnit::PerMillion)
+    } else {
+        Some(InferredUnit::Fpkm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        for &method in &[Method::Fpkm, Method::Tpm, Method::Cpm, Method::Scaled] {
+            for &unit in &[Unit::from(method), Unit::from(method).median_centered()] {
+                assert_eq!(Unit::from_name(unit.as_str()), Some(unit));
+            }
+        }
+
+        for &unit in &[Unit::NormalScore, Unit::Log2FoldChange] {
+            assert_eq!(Unit::from_name(unit.as_str()), Some(unit));
+        }
+    }
+
+    #[test]
+    fn test_infer_unit() {
+        use ndarray::arr2;
+
+        let build = |data| {
+            ExpressionMatrix::new(
+                vec![String::from("AAAS"), String::from("RPL37AP1")],
+                vec![String::from("sample1"), String::from("sample2")],
+                data,
+            )
+        };
+
+        let matrix = build(arr2(&[[645.0, 0.0], [5714.0, 12.0]]));
+        assert_eq!(infer_unit(&matrix), Some(InferredUnit::Counts));
+
+        let matrix = build(arr2(&[[3.5, 0.0], [27.6, 12.0]]));
+        assert_eq!(infer_unit(&matrix), Some(InferredUnit::Fpkm));
+
+        let matrix = build(arr2(&[[1.5, -0.5], [0.0, 2.0]]));
+        assert_eq!(infer_unit(&matrix), None);
+    }
+
+    #[test]
+    fn test_from_method() {
+        for &method in &[Method::Fpkm, Method::Tpm, Method::Cpm, Method::Scaled] {
+            assert_eq!(Unit::from(method).as_str(), method.as_str());
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt;
+
+use crate::{counts::Counts, features::Features, matrix::ExpressionMatrix, Expressions};
+
+/// An invariant that does not hold.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Violation {
+    /// What the invariant is about, e.g., a feature ID or a matrix cell.
+    pub subject: String,
+    /// A description of the violation.
+    pub message: String,
+}
+
+/// The violations of the invariants of a value, sorted by subject.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ValidationReport {
+    violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    /// Returns whether every invariant holds.
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Returns the violations.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    fn push<S, M>(&mut self, subject: S, message: M)
+    where
+        S: Into<String>,
+        M: Into<String>,
+    {
+        self.violations.push(Violation {
+            subject: subject.into(),
+            message: message.into(),
+        });
+    }
+
+    fn finish(mut self) -> Self {
+        self.violations.sort_by(|a, b| a.subject.cmp(&b.subject));
+        self
+    }
+}
+
+// Each violation is written on its own line.
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, violation) in self.violations.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "{}: {}", violation.subject, violation.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A check of the internal invariants of a value.
+///
+/// This is meant for debugging, e.g., after a stage of a pipeline. Values
+/// built by this crate are always valid; a violation is a bug.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::{feature_id::FeatureId, validation::Validate, Expressions};
+///
+/// let expressions: Expressions = [
+///     (FeatureId::from("AAAS"), 5.0),
+///     (FeatureId::from("RPL37AP1"), f64::NAN),
+/// ].iter().cloned().collect();
+///
+/// let report = expressions.validate();
+///
+/// assert!(!report.is_valid());
+/// assert_eq!(report.to_string(), "RPL37AP1: value is not finite: NaN");
+/// ```
+pub trait Validate {
+    /// Checks the invariants of the value and returns their violations.
+    fn validate(&self) -> ValidationReport;
+}
+
+// Features are merged intervals: each list is nonempty and sorted, and
+// intervals are valid and do not overlap.
+impl Validate for Features {
+    fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        for (id, features) in self {
+            if features.is_empty() {
+                report.push(id.as_str(), "no intervals");
+            }
+
+            for feature in features {
+                if feature.start == 0 || feature.start > feature.end {
+                    report.push(
+                        id.as_str(),
+                        format!("invalid interval: [{}, {}]", feature.start, feature.end),
+                    );
+                }
+            }
+
+            for pair in features.windows(2) {
+                let (a, b) = (&pair[0], &pair[1]);
+
+                if b.start <= a.end {
+                    report.push(
+                        id.as_str(),
+                        format!(
+                            "intervals are unsorted or overlap: [{}, {}], [{}, {}]",
+                            a.start, a.end, b.start, b.end
+                        ),
+                    );
+                }
+            }
+        }
+
+        report.finish()
+    }
+}
+
+impl Validate for Counts {
+    fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        if self.keys().any(|id| id.as_str().is_empty()) {
+            report.push("", "empty feature ID");
+        }
+
+        if self
+            .values()
+            .try_fold(0u64, |sum, &count| sum.checked_add(count))
+            .is_none()
+        {
+            report.push("(total)", "sum of counts overflows");
+        }
+
+        report.finish()
+    }
+}
+
+// Expressions are calculated from counts, so they are finite and nonnegative.
+impl Validate for Expressions {
+    fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        for (id, &value) in self {
+            if !value.is_finite() {
+                report.push(id.as_str(), format!("value is not finite: {}", value));
+            } else if value < 0.0 {
+                report.push(id.as_str(), format!("value is negative: {}", value));
+            }
+        }
+
+        report.finish()
+    }
+}
+
+// Matrix values can be negative after transforms, e.g., log fold changes, but
+// they are always finite.
+impl Validate for ExpressionMatrix {
+    fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        let (row_count, column_count) = self.data().dim();
+
+        if row_count != self.row_names().len() || column_count != self.column_names().len() {
+            report.push(
+                "(shape)",
+                format!(
+                    "data is {} x {}, but there are {} row names and {} column names",
+                    row_count,
+                    column_count,
+                    self.row_names().len(),
+                    self.column_names().len()
+                ),
+            );
+
+            return report;
+        }
+
+        for (i, name) in self.row_names().iter().enumerate() {
+            if self.row_index(name) != Some(i) {
+                report.push(name.as_str(), "row index is inconsistent");
+            }
+        }
+
+        for (j, name) in self.column_names().iter().enumerate() {
+            if self.column_index(name) != Some(j) {
+                report.push(name.as_str(), "column index is inconsistent");
+            }
+        }
+
+        for ((i, j), value) in self.data().indexed_iter() {
+            if !value.is_finite() {
+                report.push(
+                    format!("{}/{}", self.row_names()[i], self.column_names()[j]),
+                    format!("value is not finite: {}", value),
+                );
+            }
+        }
+
+        report.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr2;
+
+    use crate::{feature_id::FeatureId, features::Feature};
+
+    use super::*;
+
+    #[test]
+    fn test_validate_features() {
+        let features: Features = [
+            (
+                FeatureId::from("AAAS"),
+                vec![Feature::new(2, 5), Feature::new(6, 9)],
+            ),
+            (
+                FeatureId::from("RPL37AP1"),
+                vec![Feature::new(8, 12), Feature::new(3, 4), Feature::new(7, 6)],
+            ),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let report = features.validate();
+
+        assert_eq!(
+            report.to_string(),
+            "\
+RPL37AP1: invalid interval: [7, 6]
+RPL37AP1: intervals are unsorted or overlap: [8, 12], [3, 4]"
+        );
+    }
+
+    #[test]
+    fn test_validate_expression_matrix() {
+        let matrix = ExpressionMatrix::new(
+            vec![String::from("AAAS"), String::from("RPL37AP1")],
+            vec![String::from("sample1")],
+            arr2(&[[-1.0], [f64::INFINITY]]),
+        );
+
+        let report = matrix.validate();
+
+        assert_eq!(report.violations().len(), 1);
+        assert_eq!(report.violations()[0].subject, "RPL37AP1/sample1");
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const HIDDEN_PREFIX: char = '.';
+
+/// A poller of a directory for new files.
+///
+/// A file is ready once its size is unchanged between two polls, so files
+/// that are still being written, e.g., copied from a sequencer, are not read
+/// early. Each file is returned once. Hidden files (names starting with `.`)
+/// and subdirectories are ignored.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::watch::DirectoryWatcher;
+///
+/// let dir = std::env::temp_dir().join(format!("noodles-fpkm-watch-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir)?;
+///
+/// let mut watcher = DirectoryWatcher::new(&dir);
+/// std::fs::write(dir.join("sample1.txt"), "AAAS\t645\n")?;
+///
+/// assert!(watcher.poll()?.is_empty());
+/// assert_eq!(watcher.poll()?, [dir.join("sample1.txt")]);
+/// assert!(watcher.poll()?.is_empty());
+///
+/// std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct DirectoryWatcher {
+    dir: PathBuf,
+    sizes: HashMap<PathBuf, u64>,
+    seen: HashSet<PathBuf>,
+}
+
+impl DirectoryWatcher {
+    /// Creates a watcher of a directory.
+    pub fn new<P>(dir: P) -> DirectoryWatcher
+    where
+        P: AsRef<Path>,
+    {
+        DirectoryWatcher {
+            dir: dir.as_ref().to_path_buf(),
+            sizes: HashMap::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Returns the files that became ready since the last poll, sorted by
+    /// path.
+    pub fn poll(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut ready = Vec::new();
+        let mut sizes = HashMap::new();
+
+        for result in fs::read_dir(&self.dir)? {
+            let entry = result?;
+            let path = entry.path();
+
+            if self.seen.contains(&path) || is_hidden(&path) {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let len = metadata.len();
+
+            if self.sizes.get(&path) == Some(&len) {
+                self.seen.insert(path.clone());
+                ready.push(path);
+            } else {
+                sizes.insert(path, len);
+            }
+        }
+
+        self.sizes = sizes;
+        ready.sort();
+
+        Ok(ready)
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with(HIDDEN_PREFIX))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poll_with_growing_and_hidden_files() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("noodles-fpkm-watch-{}", std::process::id()));
+        fs::create_dir_all(dir.join("subdir"))?;
+
+        let mut watcher = DirectoryWatcher::new(&dir);
+
+        fs::write(dir.join("sample1.txt"), "AAAS\t645\n")?;
+        fs::write(dir.join(".sample2.txt.partial"), "AAAS\t5\n")?;
+        assert!(watcher.poll()?.is_empty());
+
+        fs::write(dir.join("sample1.txt"), "AAAS\t645\nRPL37AP1\t5714\n")?;
+        assert!(watcher.poll()?.is_empty());
+
+        fs::rename(dir.join(".sample2.txt.partial"), dir.join("sample2.txt"))?;
+        assert_eq!(watcher.poll()?, [dir.join("sample1.txt")]);
+        assert_eq!(watcher.poll()?, [dir.join("sample2.txt")]);
+        assert!(watcher.poll()?.is_empty());
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
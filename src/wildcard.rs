@@ -0,0 +1,152 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const ANY_CHARS: char = '*';
+const ANY_CHAR: char = '?';
+
+/// Returns whether a path has wildcards (`*` or `?`) in its file name.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::wildcard::has_wildcards;
+///
+/// assert!(has_wildcards("counts/*.txt"));
+/// assert!(!has_wildcards("counts/sample1.txt"));
+/// assert!(!has_wildcards("https://example.com/counts.txt?download=1"));
+/// ```
+pub fn has_wildcards(src: &str) -> bool {
+    !src.contains("://")
+        && Path::new(src)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.contains(&[ANY_CHARS, ANY_CHAR][..]))
+            .unwrap_or(false)
+}
+
+/// Returns whether a file name matches a wildcard pattern.
+///
+/// `*` matches any sequence of characters, including none, and `?` matches
+/// exactly one character. As in Unix shells, a leading `.` in the name must be
+/// matched literally, so hidden files are only matched by patterns that start
+/// with `.`.
+///
+/// # Example
+///
+/// ```
+/// use noodles_fpkm::wildcard::matches;
+///
+/// assert!(matches("sample?.txt", "sample1.txt"));
+/// assert!(matches("*.txt", "sample1.txt"));
+/// assert!(!matches("*.txt", "sample1.tsv"));
+/// assert!(!matches("*.txt", ".sample1.txt"));
+/// ```
+pub fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // The position of the last `*` in the pattern and the position in the name
+    // it was tried at, to backtrack to when a later character does not match.
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(&ANY_CHARS) => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == ANY_CHAR || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, start)) => {
+                    p = star + 1;
+                    n = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == ANY_CHARS)
+}
+
+/// Expands wildcards in the file name of a path to the paths that match it.
+///
+/// Only the file name can have wildcards; the directory is literal. Matches
+/// are sorted. If nothing matches, or the directory cannot be read, the path
+/// is returned as is, as in Unix shells, so the error is reported when it is
+/// opened.
+///
+/// This is used on Windows, where the shell does not expand wildcards.
+///
+/// # Example
+///
+/// ```no_run
+/// use noodles_fpkm::wildcard::expand;
+///
+/// let srcs = expand("counts/*.txt");
+/// ```
+pub fn expand(src: &str) -> Vec<PathBuf> {
+    if !has_wildcards(src) {
+        return vec![PathBuf::from(src)];
+    }
+
+    let path = Path::new(src);
+    let pattern = path.file_name().and_then(|name| name.to_str()).unwrap();
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut srcs: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .map(|name| matches(pattern, name))
+                .unwrap_or(false)
+        })
+        .map(|entry| path.with_file_name(entry.file_name()))
+        .collect();
+
+    if srcs.is_empty() {
+        srcs.push(PathBuf::from(src));
+    } else {
+        srcs.sort();
+    }
+
+    srcs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*", "sample1.txt"));
+        assert!(matches("*1*", "sample1.txt"));
+        assert!(matches("s*e*.txt", "sample1.txt"));
+        assert!(matches("*.tar.gz", "counts.tar.gz"));
+        assert!(matches(".*", ".hidden"));
+
+        assert!(!matches("sample?.txt", "sample10.txt"));
+        assert!(!matches("*.txt", "sample1.txt.gz"));
+        assert!(!matches("sample", "sample1"));
+        assert!(!matches("*", ".hidden"));
+    }
+}
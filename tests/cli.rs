@@ -0,0 +1,77 @@
+use std::{
+    env, fs,
+    process::{self, Command},
+};
+
+const BIN: &str = env!("CARGO_BIN_EXE_noodles-fpkm");
+
+#[test]
+fn test_quantify_scaled_without_annotations() {
+    let output = Command::new(BIN)
+        .args(&[
+            "--unit",
+            "scaled",
+            "--size-factors",
+            "test/fixtures/size_factors.tsv",
+            "test/fixtures/counts.txt",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"g1\t100\ng2\t10\ng3\t20\n");
+}
+
+#[test]
+fn test_quantify_scaled_qc_report_without_annotations() {
+    let output = Command::new(BIN)
+        .args(&[
+            "--unit",
+            "scaled",
+            "--size-factors",
+            "test/fixtures/size_factors.tsv",
+            "--qc-report",
+            "qc.txt",
+            "test/fixtures/counts.txt",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(
+        output.stderr,
+        &b"error: --qc-report requires --annotations\n"[..]
+    );
+}
+
+#[test]
+fn test_quantify_single_sample_resource_report() {
+    let dst = env::temp_dir().join(format!("noodles-fpkm-resources-{}.tsv", process::id()));
+
+    let output = Command::new(BIN)
+        .args(&[
+            "--unit",
+            "scaled",
+            "--size-factors",
+            "test/fixtures/size_factors.tsv",
+            "--resource-report",
+        ])
+        .arg(&dst)
+        .arg("test/fixtures/counts.txt")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"g1\t100\ng2\t10\ng3\t20\n");
+
+    let report = fs::read_to_string(&dst).unwrap();
+    fs::remove_file(&dst).unwrap();
+
+    let stages: Vec<_> = report
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split('\t').next())
+        .collect();
+
+    assert_eq!(stages, ["counts", "compute", "write", "total"]);
+}